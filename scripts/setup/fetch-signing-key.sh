@@ -0,0 +1,39 @@
+#!/bin/bash
+##############################################################################
+# Armbian Imager - Image Signing Key
+#
+# Downloads the Armbian signing key into src-tauri/keys/armbian.asc, where
+# it is bundled with the app to verify image signatures, and checks its
+# fingerprint. Release builds fail without it (see src-tauri/keys/README.md).
+#
+# Usage: bash scripts/setup/fetch-signing-key.sh
+##############################################################################
+
+set -e  # Exit on error
+
+KEY_URL="https://apt.armbian.com/armbian.key"
+FINGERPRINT="DF00FAF1C577104B50BF1D0093D6889F9F0E78D5"
+
+SCRIPT_DIR="$( cd "$( dirname "${BASH_SOURCE[0]}" )" && pwd )"
+KEY_FILE="$SCRIPT_DIR/../../src-tauri/keys/armbian.asc"
+
+fingerprints() {
+    gpg --show-keys --with-colons "$1" 2>/dev/null | awk -F: '$1 == "fpr" { print $10 }'
+}
+
+if [ -f "$KEY_FILE" ] && fingerprints "$KEY_FILE" | grep -qx "$FINGERPRINT"; then
+    echo "Signing key already present: $KEY_FILE"
+    exit 0
+fi
+
+TEMP_FILE="$(mktemp)"
+trap 'rm -f "$TEMP_FILE"' EXIT
+
+curl -fsSL --retry 3 "$KEY_URL" -o "$TEMP_FILE"
+if ! fingerprints "$TEMP_FILE" | grep -qx "$FINGERPRINT"; then
+    echo "Signing key from $KEY_URL does not have fingerprint $FINGERPRINT" >&2
+    exit 1
+fi
+
+mv "$TEMP_FILE" "$KEY_FILE"
+echo "Signing key saved to $KEY_FILE"
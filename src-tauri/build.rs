@@ -1,7 +1,11 @@
 fn main() {
     // Extract Tauri version from Cargo.toml and expose it as a compile-time env var
+    println!("cargo:rerun-if-changed=Cargo.toml");
     println!("cargo:rustc-env=TAURI_VERSION={}", tauri_version());
 
+    // Release builds must ship the image signing keys
+    check_signing_keys();
+
     // On Windows, embed the manifest to request admin privileges at startup
     #[cfg(windows)]
     {
@@ -15,6 +19,30 @@ fn main() {
     tauri_build::build();
 }
 
+/// Fail release builds when no signing key is bundled
+///
+/// Without a key every image signature is reported as "unknown key" and
+/// the check silently accepts it.
+fn check_signing_keys() {
+    println!("cargo:rerun-if-changed=keys");
+
+    let has_key = std::fs::read_dir("keys")
+        .map(|entries| {
+            entries
+                .flatten()
+                .any(|e| e.path().extension().is_some_and(|ext| ext == "asc"))
+        })
+        .unwrap_or(false);
+    if has_key {
+        return;
+    }
+
+    if std::env::var("PROFILE").as_deref() == Ok("release") {
+        panic!("No signing key in keys/, run scripts/setup/fetch-signing-key.sh");
+    }
+    println!("cargo:warning=No signing key in keys/, image signatures cannot be verified");
+}
+
 /// Extract Tauri version from Cargo.toml dependencies
 fn tauri_version() -> String {
     let cargo_toml = std::path::PathBuf::from("Cargo.toml");
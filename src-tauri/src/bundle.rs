@@ -0,0 +1,492 @@
+//! Offline bundles
+//!
+//! A bundle is a tar archive carrying what the app needs to browse boards
+//! and flash without a network: the saved catalogs with their validators,
+//! the picture cache (board photos, vendor logos and `meta.json`) and a
+//! selection of cached images with their published SHA and signature
+//! files. Paths inside the bundle mirror the cache directory. The manifest,
+//! written last, lists every file with its SHA256; an import is checked
+//! against it before anything is installed. Images are also checked
+//! against the published SHA and signature files bundled for them, and
+//! indexed as verified when their published checksum matches.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cache::{IndexEntry, Verification};
+use crate::config;
+use crate::download::signature::{self, SignatureStatus};
+use crate::{log_info, log_warn};
+
+const MODULE: &str = "bundle";
+
+/// Current time as a Unix timestamp (seconds)
+fn now_secs() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+/// Top-level cache directories a bundle may contain
+const BUNDLED_DIRS: [&str; 3] = ["assets", "images", config::bundle::CHECKSUMS_DIR];
+
+/// One file of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BundledFile {
+    /// Path relative to the cache directory, with '/' separators
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+/// Bundle contents, written as the last entry
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    /// Unix timestamp (seconds) the bundle was created
+    created_at: u64,
+    files: Vec<BundledFile>,
+}
+
+/// What a bundle export or import covered
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BundleSummary {
+    /// Cached image file names
+    pub images: Vec<String>,
+    /// Number of catalog and picture cache files
+    pub assets: usize,
+    /// Total size of all files in bytes
+    pub total_bytes: u64,
+}
+
+impl BundleSummary {
+    fn count(&mut self, path: &str, size: u64) {
+        match path.strip_prefix("images/") {
+            Some(image) => self.images.push(image.to_string()),
+            None if path.starts_with("assets/") => self.assets += 1,
+            None => {}
+        }
+        self.total_bytes += size;
+    }
+}
+
+/// Reader that hashes everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Check that a cached image name is a plain, complete cache file
+fn validate_image_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!("Invalid image name: {}", name));
+    }
+    if crate::cache::is_partial_download(name) {
+        return Err(format!("{} is an unfinished download", name));
+    }
+    Ok(())
+}
+
+/// Convert a bundle entry path into a safe relative path
+///
+/// Only plain components below one of the bundled directories are allowed.
+fn bundle_path(path: &Path) -> Result<String, String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(
+                part.to_str()
+                    .ok_or_else(|| format!("Invalid path in bundle: {}", path.display()))?,
+            ),
+            _ => return Err(format!("Unsafe path in bundle: {}", path.display())),
+        }
+    }
+    match parts.first() {
+        Some(dir) if parts.len() > 1 && BUNDLED_DIRS.contains(dir) => Ok(parts.join("/")),
+        _ => Err(format!("Unexpected file in bundle: {}", path.display())),
+    }
+}
+
+/// Files of a directory tree, as paths relative to `root`
+fn walk(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<(), String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            walk(root, &path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "tmp") {
+            continue;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push((bundle_path(relative)?, path));
+        }
+    }
+    Ok(())
+}
+
+fn append(
+    builder: &mut tar::Builder<File>,
+    path: &str,
+    size: u64,
+    reader: impl Read,
+) -> Result<BundledFile, String> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(now_secs());
+    let mut reader = HashingReader {
+        inner: reader,
+        hasher: Sha256::new(),
+    };
+    builder
+        .append_data(&mut header, path, &mut reader)
+        .map_err(|e| format!("Failed to add {} to the bundle: {}", path, e))?;
+    Ok(BundledFile {
+        path: path.to_string(),
+        size,
+        sha256: hex::encode(reader.hasher.finalize()),
+    })
+}
+
+/// Write a bundle of the cache at `cache_root` to `output`
+///
+/// Includes the whole `assets` directory, the named cached `images` and
+/// the published SHA and signature files in `checksums` (file name and
+/// contents), which were fetched while exporting.
+pub fn export(
+    cache_root: &Path,
+    output: &Path,
+    images: &[String],
+    checksums: &[(String, Vec<u8>)],
+) -> Result<BundleSummary, String> {
+    let mut files = Vec::new();
+    walk(cache_root, &cache_root.join("assets"), &mut files)?;
+    for image in images {
+        validate_image_name(image)?;
+        let path = cache_root.join("images").join(image);
+        if !path.is_file() {
+            return Err(format!("{} is not in the image cache", image));
+        }
+        files.push((format!("images/{}", image), path));
+    }
+
+    let file = File::create(output)
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let mut builder = tar::Builder::new(file);
+    let result = (|| {
+        let mut manifest = Manifest {
+            version: config::bundle::FORMAT_VERSION,
+            created_at: now_secs(),
+            files: Vec::new(),
+        };
+        let mut summary = BundleSummary::default();
+
+        for (path, source) in &files {
+            let file = File::open(source)
+                .map_err(|e| format!("Failed to open {}: {}", source.display(), e))?;
+            let size = file
+                .metadata()
+                .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?
+                .len();
+            manifest.files.push(append(&mut builder, path, size, file)?);
+            summary.count(path, size);
+        }
+        for (name, data) in checksums {
+            validate_image_name(name)?;
+            let path = format!("{}/{}", config::bundle::CHECKSUMS_DIR, name);
+            manifest
+                .files
+                .push(append(&mut builder, &path, data.len() as u64, &data[..])?);
+            summary.count(&path, data.len() as u64);
+        }
+
+        let data = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| format!("Failed to serialize the bundle manifest: {}", e))?;
+        append(
+            &mut builder,
+            config::bundle::MANIFEST,
+            data.len() as u64,
+            &data[..],
+        )?;
+        builder
+            .into_inner()
+            .and_then(|mut file| file.flush())
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+        Ok(summary)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(output);
+    }
+    result
+}
+
+/// Unpack the bundle at `input` into a staging directory, checking every
+/// file against the manifest
+fn unpack(input: &Path, staging: &Path) -> Result<Manifest, String> {
+    let file =
+        File::open(input).map_err(|e| format!("Failed to open {}: {}", input.display(), e))?;
+    let mut archive = tar::Archive::new(file);
+    let mut unpacked: HashMap<String, (u64, String)> = HashMap::new();
+    let mut manifest: Option<Manifest> = None;
+
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to read the bundle: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read the bundle: {}", e))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .map_err(|e| format!("Invalid path in bundle: {}", e))?
+            .into_owned();
+
+        if path == Path::new(config::bundle::MANIFEST) {
+            let mut data = Vec::new();
+            entry
+                .read_to_end(&mut data)
+                .map_err(|e| format!("Failed to read the bundle manifest: {}", e))?;
+            manifest = Some(
+                serde_json::from_slice(&data)
+                    .map_err(|e| format!("Invalid bundle manifest: {}", e))?,
+            );
+            continue;
+        }
+
+        let relative = bundle_path(&path)?;
+        let target = staging.join(&relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut reader = HashingReader {
+            inner: &mut entry,
+            hasher: Sha256::new(),
+        };
+        let mut output = File::create(&target)
+            .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+        let size = io::copy(&mut reader, &mut output)
+            .map_err(|e| format!("Failed to unpack {}: {}", relative, e))?;
+        unpacked.insert(relative, (size, hex::encode(reader.hasher.finalize())));
+    }
+
+    let manifest = manifest.ok_or("Not an offline bundle: the manifest is missing")?;
+    if manifest.version > config::bundle::FORMAT_VERSION {
+        return Err(format!(
+            "The bundle was made by a newer version (format {})",
+            manifest.version
+        ));
+    }
+    for file in &manifest.files {
+        match unpacked.remove(&file.path) {
+            Some((size, sha256)) if size == file.size && sha256 == file.sha256 => {}
+            Some(_) => return Err(format!("{} is corrupted in the bundle", file.path)),
+            None => return Err(format!("{} is missing from the bundle", file.path)),
+        }
+    }
+    if let Some(path) = unpacked.keys().next() {
+        return Err(format!("{} is not listed in the bundle manifest", path));
+    }
+    Ok(manifest)
+}
+
+/// Published SHA256 bundled for an image, from its `<image>.sha` file
+fn published_sha(staging: &Path, image: &str) -> Result<Option<String>, String> {
+    let path = staging
+        .join(config::bundle::CHECKSUMS_DIR)
+        .join(format!("{}.sha", image));
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    // "hash *filename" or "hash  filename"
+    let hash = content.split_whitespace().next().unwrap_or_default();
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid SHA file for {}", image));
+    }
+    Ok(Some(hash.to_lowercase()))
+}
+
+/// Check the bundled images against their published SHA and signature
+/// files, returning their cache index entries
+///
+/// Images without published files for the exact file (a decompressed
+/// image's checksum covers the `.img.xz`) are only checked against the
+/// manifest, and indexed as unverified.
+fn check_images(manifest: &Manifest, staging: &Path) -> Result<Vec<(String, IndexEntry)>, String> {
+    let now = now_secs();
+    let mut entries = Vec::new();
+    for file in &manifest.files {
+        let Some(image) = file.path.strip_prefix("images/") else {
+            continue;
+        };
+
+        // The manifest hash is the hash of the unpacked file
+        let verification = match published_sha(staging, image)? {
+            Some(expected) if expected == file.sha256 => Verification::Verified,
+            Some(expected) => {
+                return Err(format!(
+                    "{} does not match its published SHA256 (expected {}, got {})",
+                    image, expected, file.sha256
+                ))
+            }
+            None => {
+                log_info!(MODULE, "{} has no published checksum in the bundle", image);
+                Verification::Unverified
+            }
+        };
+
+        let asc = staging
+            .join(config::bundle::CHECKSUMS_DIR)
+            .join(format!("{}.asc", image));
+        if let Ok(data) = fs::read(&asc) {
+            let status = signature::check_file(&data, &staging.join(&file.path))?;
+            if status == SignatureStatus::BadSignature {
+                return Err(format!("{}: the signature does not match", image));
+            }
+            log_info!(MODULE, "{} signature: {}", image, status.as_str());
+        }
+
+        entries.push((
+            image.to_string(),
+            IndexEntry {
+                sha256: Some(file.sha256.clone()),
+                checked_at: Some(now),
+                verification,
+                ..IndexEntry::adopted(file.size, now)
+            },
+        ));
+    }
+    Ok(entries)
+}
+
+/// Install a bundle into the cache at `cache_root`
+///
+/// Nothing is installed unless every file matches the manifest and every
+/// image its published checksum and signature. Installed files replace the
+/// cached ones of the same name. Returns the cache index entries of the
+/// installed images, for the caller to record.
+pub fn import(
+    cache_root: &Path,
+    input: &Path,
+) -> Result<(BundleSummary, Vec<(String, IndexEntry)>), String> {
+    let staging = cache_root.join(config::bundle::STAGING_DIR);
+    let _ = fs::remove_dir_all(&staging);
+
+    let result = unpack(input, &staging).and_then(|manifest| {
+        let entries = check_images(&manifest, &staging)?;
+        let mut summary = BundleSummary::default();
+        for file in &manifest.files {
+            let target = cache_root.join(&file.path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            fs::rename(staging.join(&file.path), &target)
+                .map_err(|e| format!("Failed to install {}: {}", file.path, e))?;
+            summary.count(&file.path, file.size);
+        }
+        Ok((summary, entries))
+    });
+
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        log_warn!(MODULE, "Failed to remove {}: {}", staging.display(), e);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, data: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("armbian-bundle-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let source = dir.join("source");
+        write(&source.join("assets/api-images.json"), b"[]");
+        write(&source.join("assets/boards/rock-5b.png"), b"png");
+        write(&source.join("assets/api-images.json.tmp"), b"partial");
+        write(&source.join("images/Armbian_rock-5b.img"), b"image");
+        write(&source.join("images/other.img"), b"not exported");
+
+        let bundle = dir.join("bundle.tar");
+        let checksums = vec![("Armbian_rock-5b.img.xz.sha".to_string(), b"abc".to_vec())];
+        let images = vec!["Armbian_rock-5b.img".to_string()];
+        let exported = export(&source, &bundle, &images, &checksums).unwrap();
+        assert_eq!(exported.images, images);
+        assert_eq!(exported.assets, 2);
+
+        let target = dir.join("target");
+        let (imported, entries) = import(&target, &bundle).unwrap();
+        assert_eq!(imported.total_bytes, exported.total_bytes);
+        // The checksum covers the compressed image, not the exported one
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, "Armbian_rock-5b.img");
+        assert_eq!(entries[0].1.verification, Verification::Unverified);
+        assert_eq!(entries[0].1.size, 5);
+        assert_eq!(
+            fs::read(target.join("images/Armbian_rock-5b.img")).unwrap(),
+            b"image"
+        );
+        assert!(target.join("assets/boards/rock-5b.png").exists());
+        assert!(target.join("checksums/Armbian_rock-5b.img.xz.sha").exists());
+        assert!(!target.join("images/other.img").exists());
+        assert!(!target.join("assets/api-images.json.tmp").exists());
+        assert!(!target.join(config::bundle::STAGING_DIR).exists());
+
+        // A truncated bundle installs nothing
+        let data = fs::read(&bundle).unwrap();
+        fs::write(&bundle, &data[..1024]).unwrap();
+        let fresh = dir.join("fresh");
+        assert!(import(&fresh, &bundle).is_err());
+        assert!(!fresh.join("assets").exists());
+
+        // An image matching its published checksum is verified, one that
+        // does not match is refused
+        let sha = hex::encode(Sha256::digest(b"image"));
+        let checksums = vec![(
+            "Armbian_rock-5b.img.sha".to_string(),
+            format!("{} *Armbian_rock-5b.img\n", sha).into_bytes(),
+        )];
+        export(&source, &bundle, &images, &checksums).unwrap();
+        let (_, entries) = import(&dir.join("verified"), &bundle).unwrap();
+        assert_eq!(entries[0].1.verification, Verification::Verified);
+        assert_eq!(entries[0].1.sha256.as_deref(), Some(sha.as_str()));
+
+        let checksums = vec![(
+            "Armbian_rock-5b.img.sha".to_string(),
+            format!("{} *Armbian_rock-5b.img\n", "0".repeat(64)).into_bytes(),
+        )];
+        export(&source, &bundle, &images, &checksums).unwrap();
+        let mismatch = dir.join("mismatch");
+        assert!(import(&mismatch, &bundle).is_err());
+        assert!(!mismatch.join("images").exists());
+
+        assert!(bundle_path(Path::new("images/../../etc/passwd")).is_err());
+        assert!(bundle_path(Path::new("settings.json")).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
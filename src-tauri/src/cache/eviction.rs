@@ -0,0 +1,194 @@
+//! Eviction policies
+//!
+//! Decide which unpinned cached images go first when the cache is over its
+//! size limit. Builds of the same board are grouped by the board slug of
+//! the Armbian filename and ranked by their version; other files form a
+//! group of their own.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{parse_armbian_filename, version_parts};
+
+use super::CacheEntry;
+
+/// How cached images are chosen for eviction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Least recently used first
+    #[default]
+    Lru,
+    /// Least often flashed first, least recently used among equals
+    Lfu,
+    /// Builds superseded by newer releases of their board first
+    OldestRelease,
+    /// Only the latest `count` releases of each board are kept; the rest
+    /// are evicted even under the size limit
+    KeepLatest { count: usize },
+}
+
+impl EvictionPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::KeepLatest { count: 0 } => {
+                Err("At least one release per board must be kept".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn file_name(entry: &CacheEntry) -> &str {
+    entry
+        .path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+}
+
+/// Board an image belongs to and its release, as numeric version parts
+fn release(filename: &str) -> (String, Vec<u64>) {
+    match parse_armbian_filename(filename) {
+        Some(info) => (
+            info.board_slug,
+            version_parts(&info.version.unwrap_or_default()),
+        ),
+        None => (filename.to_string(), Vec::new()),
+    }
+}
+
+/// Number of newer releases of the same board cached, for each file
+fn newer_releases(files: &[CacheEntry]) -> Vec<usize> {
+    let releases: Vec<(String, Vec<u64>)> = files
+        .iter()
+        .map(|entry| release(file_name(entry)))
+        .collect();
+
+    let mut by_board: HashMap<&str, Vec<&Vec<u64>>> = HashMap::new();
+    for (board, version) in &releases {
+        let versions = by_board.entry(board.as_str()).or_default();
+        if !versions.contains(&version) {
+            versions.push(version);
+        }
+    }
+
+    releases
+        .iter()
+        .map(|(board, version)| {
+            by_board[board.as_str()]
+                .iter()
+                .filter(|other| **other > version)
+                .count()
+        })
+        .collect()
+}
+
+/// Sort `files` in the order they should be evicted
+pub(super) fn order(files: &mut Vec<CacheEntry>, policy: EvictionPolicy) {
+    match policy {
+        EvictionPolicy::Lru | EvictionPolicy::KeepLatest { .. } => {
+            files.sort_by_key(|entry| entry.modified)
+        }
+        EvictionPolicy::Lfu => files.sort_by_key(|entry| (entry.use_count, entry.modified)),
+        EvictionPolicy::OldestRelease => {
+            let newer = newer_releases(files);
+            let mut ranked: Vec<(usize, CacheEntry)> =
+                newer.into_iter().zip(files.drain(..)).collect();
+            ranked.sort_by_key(|(newer, entry)| (Reverse(*newer), entry.modified));
+            files.extend(ranked.into_iter().map(|(_, entry)| entry));
+        }
+    }
+}
+
+/// Split off the files beyond the latest `count` releases of their board
+///
+/// Returns the surplus, and keeps the rest in `files`.
+pub(super) fn take_surplus(files: &mut Vec<CacheEntry>, count: usize) -> Vec<CacheEntry> {
+    let newer = newer_releases(files);
+    let (surplus, kept): (Vec<_>, Vec<_>) = newer
+        .into_iter()
+        .zip(files.drain(..))
+        .partition(|(newer, _)| *newer >= count);
+    files.extend(kept.into_iter().map(|(_, entry)| entry));
+    surplus.into_iter().map(|(_, entry)| entry).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    fn entry(name: &str, age: u64, use_count: u32) -> CacheEntry {
+        CacheEntry {
+            path: PathBuf::from(name),
+            size: 1,
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(1000 - age),
+            use_count,
+        }
+    }
+
+    fn names(files: &[CacheEntry]) -> Vec<&str> {
+        files.iter().map(file_name).collect()
+    }
+
+    #[test]
+    fn test_eviction_order() {
+        let files = || {
+            vec![
+                entry(
+                    "Armbian_25.11.1_Rock-5b_trixie_current_6.12.8_minimal.img",
+                    30,
+                    0,
+                ),
+                entry(
+                    "Armbian_25.8.1_Rock-5b_trixie_current_6.12.8_minimal.img",
+                    10,
+                    5,
+                ),
+                entry(
+                    "Armbian_25.11.1_Nanopi-m5_trixie_current_6.12.8_minimal.img",
+                    20,
+                    1,
+                ),
+                entry("custom.img", 5, 0),
+            ]
+        };
+
+        let mut lru = files();
+        order(&mut lru, EvictionPolicy::Lru);
+        assert_eq!(
+            names(&lru)[0],
+            "Armbian_25.11.1_Rock-5b_trixie_current_6.12.8_minimal.img"
+        );
+
+        let mut lfu = files();
+        order(&mut lfu, EvictionPolicy::Lfu);
+        assert_eq!(
+            names(&lfu)[..2],
+            [
+                "Armbian_25.11.1_Rock-5b_trixie_current_6.12.8_minimal.img",
+                "custom.img"
+            ]
+        );
+
+        let mut oldest = files();
+        order(&mut oldest, EvictionPolicy::OldestRelease);
+        assert_eq!(
+            names(&oldest)[0],
+            "Armbian_25.8.1_Rock-5b_trixie_current_6.12.8_minimal.img"
+        );
+
+        let mut kept = files();
+        let surplus = take_surplus(&mut kept, 1);
+        assert_eq!(
+            names(&surplus),
+            ["Armbian_25.8.1_Rock-5b_trixie_current_6.12.8_minimal.img"]
+        );
+        assert_eq!(kept.len(), 3);
+        assert!(EvictionPolicy::KeepLatest { count: 0 }.validate().is_err());
+    }
+}
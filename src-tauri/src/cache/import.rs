@@ -0,0 +1,258 @@
+//! Importing local images
+//!
+//! Copies (or moves) an image from outside the cache into it, so it is
+//! listed, evicted and revalidated like a downloaded one and survives
+//! restarts. The image is hashed on the way in, but was never checked
+//! against a published checksum. One import runs at a time, with its own
+//! progress, apart from the interactive download.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config;
+use crate::{log_info, log_warn};
+
+use super::index::{hash_file, now_secs, Index};
+use super::{
+    cache_root, get_images_cache_dir, image_info, is_partial_download, CachedImageInfo, IndexEntry,
+    CACHE_LOCK, MODULE,
+};
+
+/// State of the running (or last) import
+static IMPORT: Lazy<Import> = Lazy::new(Import::default);
+
+#[derive(Default)]
+struct Import {
+    running: AtomicBool,
+    cancelled: AtomicBool,
+    total_bytes: AtomicU64,
+    copied_bytes: AtomicU64,
+}
+
+/// Clears the running flag when the import ends, however it ends
+struct Running;
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        IMPORT.running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Import progress
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportProgress {
+    pub running: bool,
+    pub total_bytes: u64,
+    pub copied_bytes: u64,
+    pub progress_percent: f64,
+}
+
+/// Progress of the running (or last) import
+pub fn progress() -> ImportProgress {
+    let total = IMPORT.total_bytes.load(Ordering::SeqCst);
+    let copied = IMPORT.copied_bytes.load(Ordering::SeqCst);
+    ImportProgress {
+        running: IMPORT.running.load(Ordering::SeqCst),
+        total_bytes: total,
+        copied_bytes: copied,
+        progress_percent: if total > 0 {
+            (copied as f64 / total as f64) * 100.0
+        } else {
+            0.0
+        },
+    }
+}
+
+/// Cancel the running import
+pub fn cancel() {
+    IMPORT.cancelled.store(true, Ordering::SeqCst);
+}
+
+/// Name an imported file gets in the cache
+///
+/// Decompressed custom images carry a `-<timestamp>` suffix that is dropped.
+fn import_filename(source: &Path) -> Result<String, String> {
+    let name = source
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Invalid filename")?;
+    let custom_dir = cache_root().join(config::images::CUSTOM_DECOMPRESS_DIR);
+    let name = if source.parent() == Some(custom_dir.as_path()) {
+        strip_timestamp(name)
+    } else {
+        name
+    };
+    if name.is_empty() || is_partial_download(name) || name == config::cache::INDEX_FILE {
+        return Err(format!("Cannot import {} into the cache", name));
+    }
+    Ok(name.to_string())
+}
+
+fn strip_timestamp(name: &str) -> &str {
+    match name.rsplit_once('-') {
+        Some((base, stamp)) if !stamp.is_empty() && stamp.bytes().all(|b| b.is_ascii_digit()) => {
+            base
+        }
+        _ => name,
+    }
+}
+
+/// Copy `source` to `dest`, returning the SHA256 of the data
+fn copy_hashing(
+    source: &Path,
+    dest: &Path,
+    cancelled: &AtomicBool,
+    copied: &AtomicU64,
+) -> Result<String, String> {
+    let mut input =
+        File::open(source).map_err(|e| format!("Failed to open {}: {}", source.display(), e))?;
+    let mut output =
+        File::create(dest).map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; config::logging::SHA_BUFFER_SIZE];
+    loop {
+        if cancelled.load(Ordering::SeqCst) {
+            return Err("Import cancelled".to_string());
+        }
+        let n = input
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        output
+            .write_all(&buffer[..n])
+            .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+        hasher.update(&buffer[..n]);
+        copied.fetch_add(n as u64, Ordering::SeqCst);
+    }
+    output
+        .sync_all()
+        .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Import a local image into the cache
+///
+/// With `move_file` the source is moved (or copied then removed, across
+/// file systems) instead of copied; a moved image that cannot be hashed is
+/// moved back. Fails if an image of the same name is already cached, or
+/// while another import runs.
+pub fn import_image(
+    source: &Path,
+    label: Option<String>,
+    move_file: bool,
+) -> Result<CachedImageInfo, String> {
+    let metadata = fs::metadata(source)
+        .ok()
+        .filter(|m| m.is_file())
+        .ok_or_else(|| format!("Not a file: {}", source.display()))?;
+    if IMPORT.running.swap(true, Ordering::SeqCst) {
+        return Err("An import is already running".to_string());
+    }
+    let _running = Running;
+    IMPORT.cancelled.store(false, Ordering::SeqCst);
+    IMPORT.copied_bytes.store(0, Ordering::SeqCst);
+    IMPORT.total_bytes.store(metadata.len(), Ordering::SeqCst);
+    let cancelled = &IMPORT.cancelled;
+    let copied = &IMPORT.copied_bytes;
+
+    let cache_dir = get_images_cache_dir();
+    if source.parent() == Some(cache_dir.as_path()) {
+        return Err("The image is already in the cache".to_string());
+    }
+    let filename = import_filename(source)?;
+    let dest = cache_dir.join(&filename);
+    if dest.exists() {
+        return Err(format!("{} is already in the cache", filename));
+    }
+    fs::create_dir_all(&cache_dir)
+        .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+
+    log_info!(
+        MODULE,
+        "Importing {} into the cache as {}",
+        source.display(),
+        filename
+    );
+
+    let moved = move_file && fs::rename(source, &dest).is_ok();
+    let sha256 = if moved {
+        hash_file(&dest, cancelled, copied).inspect_err(|_| {
+            if let Err(e) = fs::rename(&dest, source) {
+                log_warn!(
+                    MODULE,
+                    "Failed to move {} back to {}: {}",
+                    dest.display(),
+                    source.display(),
+                    e
+                );
+            }
+        })?
+    } else {
+        // Copy under a partial name so the half-copied file is never used
+        let temp = cache_dir.join(format!("{}{}", filename, config::images::DOWNLOAD_SUFFIX));
+        let sha256 = copy_hashing(source, &temp, cancelled, copied)
+            .and_then(|sha256| {
+                fs::rename(&temp, &dest)
+                    .map_err(|e| format!("Failed to move image into the cache: {}", e))?;
+                Ok(sha256)
+            })
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&temp);
+            })?;
+        if move_file {
+            if let Err(e) = fs::remove_file(source) {
+                log_warn!(MODULE, "Failed to remove {}: {}", source.display(), e);
+            }
+        }
+        sha256
+    };
+
+    let label = label
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty());
+    let size = fs::metadata(&dest).map(|m| m.len()).unwrap_or(0);
+    let now = now_secs();
+    let entry = IndexEntry {
+        label,
+        sha256: Some(sha256),
+        checked_at: Some(now),
+        ..IndexEntry::adopted(size, now)
+    };
+
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+    let mut index = Index::load();
+    index.entries.insert(filename.clone(), entry.clone());
+    index.save();
+
+    log_info!(MODULE, "Imported {} ({} bytes)", filename, size);
+    Ok(image_info(&dest, filename, size, now, entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_filename() {
+        let custom_dir = cache_root().join(config::images::CUSTOM_DECOMPRESS_DIR);
+        assert_eq!(
+            import_filename(&custom_dir.join("Armbian_25.11.1_Rock-5b.img-1700000000000")).unwrap(),
+            "Armbian_25.11.1_Rock-5b.img"
+        );
+        assert_eq!(
+            import_filename(Path::new("/home/user/my-image-2.img")).unwrap(),
+            "my-image-2.img"
+        );
+        assert!(import_filename(Path::new("/tmp/a.img.downloading")).is_err());
+    }
+}
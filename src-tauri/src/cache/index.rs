@@ -0,0 +1,208 @@
+//! Cache index
+//!
+//! Metadata of every cached image, keyed by file name and persisted in the
+//! cache root: the SHA256 of the cached file and of the download it was
+//! made from, the source URL, when it was downloaded, how it was verified
+//! and how often flashing it failed. The recorded hash identifies the
+//! content, so a cached file that was truncated or corrupted since it was
+//! downloaded is detected instead of flashed. Entries also carry the
+//! user's pin and tags, the label of an imported image, and how often the
+//! image was flashed.
+//!
+//! Callers hold the cache lock while loading and saving the index.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config;
+use crate::log_warn;
+
+use super::MODULE;
+
+pub(super) fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// How the content of a cached image was checked when it was downloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verification {
+    /// Matched the checksum published with the image
+    Verified,
+    /// No published checksum was compared (none available, or the user
+    /// continued without it, or the file predates the index)
+    Unverified,
+}
+
+/// Metadata of a cached image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// SHA256 of the cached file, None until it has been hashed
+    pub sha256: Option<String>,
+    /// SHA256 of the downloaded file, before decompression
+    pub compressed_sha256: Option<String>,
+    pub source_url: Option<String>,
+    /// Unix timestamp (seconds) of the download
+    pub downloaded_at: u64,
+    /// Size of the cached file in bytes
+    pub size: u64,
+    pub verification: Verification,
+    /// Unix timestamp (seconds) of the last time the file matched `sha256`
+    pub checked_at: Option<u64>,
+    /// Consecutive failed flashes of this image
+    #[serde(default)]
+    pub flash_failures: u32,
+    /// Successful flashes of this image
+    #[serde(default)]
+    pub use_count: u32,
+    /// Pinned by the user: never evicted
+    #[serde(default)]
+    pub pinned: bool,
+    /// User tags (e.g. "production-v3")
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Label given to an imported image
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+impl IndexEntry {
+    /// Entry for a cached file found without metadata
+    pub(crate) fn adopted(size: u64, modified: u64) -> Self {
+        Self {
+            sha256: None,
+            compressed_sha256: None,
+            source_url: None,
+            downloaded_at: modified,
+            size,
+            verification: Verification::Unverified,
+            checked_at: None,
+            flash_failures: 0,
+            use_count: 0,
+            pinned: false,
+            tags: Vec::new(),
+            label: None,
+        }
+    }
+
+    /// Whether the file should be hashed again before it is used
+    pub(super) fn revalidation_due(&self, now: u64) -> bool {
+        match (&self.sha256, self.checked_at) {
+            (Some(_), Some(checked_at)) => {
+                now.saturating_sub(checked_at) >= config::cache::REVALIDATE_INTERVAL_SECS
+            }
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct Index {
+    #[serde(default)]
+    pub entries: BTreeMap<String, IndexEntry>,
+}
+
+fn index_path() -> PathBuf {
+    super::cache_root().join(config::cache::INDEX_FILE)
+}
+
+impl Index {
+    pub fn load() -> Self {
+        Self::load_from(&index_path())
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(_) => return Self::default(),
+        };
+        serde_json::from_slice(&data).unwrap_or_else(|e| {
+            log_warn!(MODULE, "Ignoring unreadable cache index: {}", e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = self.save_to(&index_path()) {
+            log_warn!(MODULE, "Failed to save cache index: {}", e);
+        }
+    }
+
+    /// Write to a temp file and rename, so a crash never truncates the index
+    fn save_to(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, json).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp_path, path).map_err(|e| {
+            let _ = std::fs::remove_file(&tmp_path);
+            e.to_string()
+        })
+    }
+}
+
+/// SHA256 of a file, stopping early when `cancelled` is set
+///
+/// `hashed` counts the bytes read.
+pub fn hash_file(
+    path: &Path,
+    cancelled: &AtomicBool,
+    hashed: &AtomicU64,
+) -> Result<String, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; config::logging::SHA_BUFFER_SIZE];
+    loop {
+        if cancelled.load(Ordering::SeqCst) {
+            return Err("SHA256 verification cancelled".to_string());
+        }
+        let n = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        hashed.fetch_add(n as u64, Ordering::SeqCst);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("cache-index-test-{}", std::process::id()));
+        let path = dir.join("index.json");
+
+        let mut index = Index::default();
+        let mut entry = IndexEntry::adopted(4, 100);
+        entry.sha256 = Some("ab".repeat(32));
+        entry.checked_at = Some(100);
+        index.entries.insert("a.img".to_string(), entry.clone());
+        index.save_to(&path).unwrap();
+
+        let loaded = Index::load_from(&path);
+        assert_eq!(loaded.entries.get("a.img"), Some(&entry));
+        assert!(!entry.revalidation_due(100 + 60));
+        assert!(entry.revalidation_due(100 + config::cache::REVALIDATE_INTERVAL_SECS));
+        assert!(IndexEntry::adopted(4, 100).revalidation_due(100));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,361 @@
+//! Cache location
+//!
+//! The cache root holds the image cache, its index, the custom-decompress
+//! area and the asset cache. It defaults to the platform cache directory
+//! and can be moved elsewhere, e.g. to an external drive, where it lives in
+//! a dedicated `armbian-imager` directory of the chosen one. When the
+//! configured root is unavailable (drive unplugged), the default one is
+//! used until it comes back.
+//!
+//! Moving the root migrates its contents: files are renamed when both roots
+//! are on the same file system and copied otherwise, and a failed migration
+//! is rolled back, so the cache is never left split between two roots. The
+//! cache lock is taken for each file, so the cache stays usable meanwhile.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::utils::get_cache_dir;
+use crate::{log_debug, log_info, log_warn};
+
+use super::{CACHE_LOCK, MODULE};
+
+/// Directory chosen for the cache, None for the default root
+static CONFIGURED: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(None));
+
+/// Whether the configured root was found missing (logged once per outage)
+static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// Progress of the running migration
+static MIGRATION: Lazy<Migration> = Lazy::new(Migration::default);
+
+#[derive(Default)]
+struct Migration {
+    running: AtomicBool,
+    total_bytes: AtomicU64,
+    moved_bytes: AtomicU64,
+}
+
+/// Where the cache lives, shown in the settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheLocationStatus {
+    /// Root in use
+    pub path: String,
+    /// Directory chosen by the user, None for the default root
+    pub configured: Option<String>,
+    /// Platform cache directory, used when none is configured
+    pub default_path: String,
+    /// The configured root is unavailable and the default one is used
+    pub fallback: bool,
+    /// A migration is running
+    pub migrating: bool,
+    pub migrated_bytes: u64,
+    pub migration_total_bytes: u64,
+}
+
+/// Platform cache directory of the app
+pub fn default_root() -> PathBuf {
+    get_cache_dir(config::app::NAME)
+}
+
+/// Cache root in the directory chosen by the user
+fn root_in(dir: &Path) -> PathBuf {
+    dir.join(config::app::NAME)
+}
+
+/// Cache root in use: the configured one if it is available, else the default
+pub fn cache_root() -> PathBuf {
+    let configured = CONFIGURED
+        .read()
+        .ok()
+        .and_then(|dir| dir.as_deref().map(root_in));
+    match configured {
+        Some(root) if root.is_dir() => {
+            if UNAVAILABLE.swap(false, Ordering::SeqCst) {
+                log_info!(MODULE, "Cache location {} is back", root.display());
+            }
+            root
+        }
+        Some(root) => {
+            if !UNAVAILABLE.swap(true, Ordering::SeqCst) {
+                log_warn!(
+                    MODULE,
+                    "Cache location {} is unavailable, using the default one",
+                    root.display()
+                );
+            }
+            default_root()
+        }
+        None => default_root(),
+    }
+}
+
+/// Set the cache root from the settings, without migrating anything
+pub fn set_configured_root(root: Option<PathBuf>) {
+    log_debug!(MODULE, "Cache location: {:?}", root);
+    if let Ok(mut configured) = CONFIGURED.write() {
+        *configured = root;
+    }
+    UNAVAILABLE.store(false, Ordering::SeqCst);
+}
+
+pub fn status() -> CacheLocationStatus {
+    let configured = CONFIGURED.read().ok().and_then(|dir| dir.clone());
+    let path = cache_root();
+    CacheLocationStatus {
+        fallback: configured
+            .as_deref()
+            .is_some_and(|dir| root_in(dir) != path),
+        path: path.to_string_lossy().to_string(),
+        configured: configured.map(|root| root.to_string_lossy().to_string()),
+        default_path: default_root().to_string_lossy().to_string(),
+        migrating: MIGRATION.running.load(Ordering::SeqCst),
+        migrated_bytes: MIGRATION.moved_bytes.load(Ordering::SeqCst),
+        migration_total_bytes: MIGRATION.total_bytes.load(Ordering::SeqCst),
+    }
+}
+
+/// Entries of the cache root that move with it
+fn migrated_entries() -> [&'static str; 5] {
+    [
+        "images",
+        config::images::CUSTOM_DECOMPRESS_DIR,
+        "assets",
+        config::bundle::CHECKSUMS_DIR,
+        config::cache::INDEX_FILE,
+    ]
+}
+
+/// Make sure `root` can hold the cache: an existing directory, or a new
+/// one in an existing directory, that can be written to and holds no
+/// cache files yet
+fn prepare_root(root: &Path) -> Result<(), String> {
+    if !root.is_absolute() {
+        return Err(format!("{} is not an absolute path", root.display()));
+    }
+    if !root.is_dir() {
+        if !root.parent().is_some_and(Path::is_dir) {
+            return Err(format!("{} is not available", root.display()));
+        }
+        fs::create_dir(root).map_err(|e| format!("Failed to create {}: {}", root.display(), e))?;
+    }
+    let probe = root.join(".write-test");
+    File::create(&probe)
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| format!("{} is not writable: {}", root.display(), e))?;
+
+    let mut files = Vec::new();
+    for entry in migrated_entries() {
+        collect_files(root, Path::new(entry), &mut files);
+    }
+    if !files.is_empty() {
+        return Err(format!("{} already holds cache files", root.display()));
+    }
+    Ok(())
+}
+
+/// Files under `entry` with their size, relative to `root`
+fn collect_files(root: &Path, entry: &Path, files: &mut Vec<(PathBuf, u64)>) {
+    let path = root.join(entry);
+    if path.is_dir() {
+        for child in fs::read_dir(&path).into_iter().flatten().flatten() {
+            collect_files(root, &entry.join(child.file_name()), files);
+        }
+    } else if let Ok(metadata) = fs::metadata(&path) {
+        files.push((entry.to_path_buf(), metadata.len()));
+    }
+}
+
+fn copy_file(source: &Path, dest: &Path) -> Result<(), String> {
+    let mut input =
+        File::open(source).map_err(|e| format!("Failed to open {}: {}", source.display(), e))?;
+    let mut output =
+        File::create(dest).map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+    let mut buffer = vec![0u8; config::logging::SHA_BUFFER_SIZE];
+    loop {
+        let n = input
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        output
+            .write_all(&buffer[..n])
+            .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+        MIGRATION.moved_bytes.fetch_add(n as u64, Ordering::SeqCst);
+    }
+    output
+        .sync_all()
+        .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))
+}
+
+/// Remove the directories left empty by a migration, deepest first
+fn remove_empty_dirs(path: &Path) {
+    if !path.is_dir() {
+        return;
+    }
+    for child in fs::read_dir(path).into_iter().flatten().flatten() {
+        remove_empty_dirs(&child.path());
+    }
+    let _ = fs::remove_dir(path);
+}
+
+/// How a file reached the new root
+enum Moved {
+    Renamed,
+    Copied,
+}
+
+/// Take the cache lock for one step of a migration
+fn lock() -> Result<std::sync::MutexGuard<'static, ()>, String> {
+    CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))
+}
+
+/// Move the cache contents from `from` to `to`, one file at a time
+fn migrate(from: &Path, to: &Path) -> Result<(), String> {
+    let mut files = Vec::new();
+    for entry in migrated_entries() {
+        collect_files(from, Path::new(entry), &mut files);
+    }
+    let total: u64 = files.iter().map(|(_, size)| size).sum();
+    MIGRATION.total_bytes.store(total, Ordering::SeqCst);
+    MIGRATION.moved_bytes.store(0, Ordering::SeqCst);
+    log_info!(
+        MODULE,
+        "Migrating {} files ({} bytes) from {} to {}",
+        files.len(),
+        total,
+        from.display(),
+        to.display()
+    );
+
+    let mut done: Vec<(&Path, Moved)> = Vec::new();
+    let result = files.iter().try_for_each(|(relative, size)| {
+        let _lock = lock()?;
+        let source = from.join(relative);
+        let dest = to.join(relative);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        if fs::rename(&source, &dest).is_ok() {
+            MIGRATION.moved_bytes.fetch_add(*size, Ordering::SeqCst);
+            done.push((relative, Moved::Renamed));
+            return Ok(());
+        }
+        copy_file(&source, &dest).inspect_err(|_| {
+            let _ = fs::remove_file(&dest);
+        })?;
+        done.push((relative, Moved::Copied));
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => {
+            // Only now are the copied originals redundant
+            for (relative, moved) in &done {
+                if let Moved::Copied = moved {
+                    let _lock = CACHE_LOCK.lock();
+                    if let Err(e) = fs::remove_file(from.join(relative)) {
+                        log_warn!(MODULE, "Failed to remove {}: {}", relative.display(), e);
+                    }
+                }
+            }
+            for entry in migrated_entries() {
+                remove_empty_dirs(&from.join(entry));
+            }
+            Ok(())
+        }
+        Err(e) => {
+            log_warn!(MODULE, "Migration failed, rolling back: {}", e);
+            for (relative, moved) in done.iter().rev() {
+                let _lock = CACHE_LOCK.lock();
+                let undone = match moved {
+                    Moved::Renamed => fs::rename(to.join(relative), from.join(relative)),
+                    Moved::Copied => fs::remove_file(to.join(relative)),
+                };
+                if let Err(e) = undone {
+                    log_warn!(MODULE, "Failed to roll back {}: {}", relative.display(), e);
+                }
+            }
+            for entry in migrated_entries() {
+                remove_empty_dirs(&to.join(entry));
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Move the cache to the `armbian-imager` directory of `dir` (None for the
+/// default location), migrating its contents
+///
+/// The new root is used once all the files are there.
+pub fn relocate(dir: Option<PathBuf>) -> Result<(), String> {
+    if MIGRATION.running.swap(true, Ordering::SeqCst) {
+        return Err("The cache is already being moved".to_string());
+    }
+    let result = relocate_running(dir);
+    MIGRATION.running.store(false, Ordering::SeqCst);
+    result
+}
+
+fn relocate_running(dir: Option<PathBuf>) -> Result<(), String> {
+    let target = dir.as_deref().map(root_in).unwrap_or_else(default_root);
+    let current = cache_root();
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    if canonical(&current) != canonical(&target) {
+        prepare_root(&target)?;
+        let (from, to) = (canonical(&current), canonical(&target));
+        if to.starts_with(&from) || from.starts_with(&to) {
+            return Err(format!(
+                "{} and {} must not be inside each other",
+                current.display(),
+                target.display()
+            ));
+        }
+        migrate(&current, &target)?;
+    }
+
+    let _lock = lock()?;
+    set_configured_root(dir);
+    log_info!(MODULE, "Cache location is now {}", target.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate() {
+        let base = std::env::temp_dir().join(format!("cache-location-test-{}", std::process::id()));
+        let (from, to) = (base.join("from"), base.join("to"));
+        fs::create_dir_all(from.join("images")).unwrap();
+        fs::create_dir_all(from.join("assets").join("boards")).unwrap();
+        fs::write(from.join("images").join("a.img"), b"image").unwrap();
+        fs::write(from.join("assets").join("boards").join("b.png"), b"png").unwrap();
+        fs::write(from.join("other.txt"), b"stays").unwrap();
+        prepare_root(&to).unwrap();
+        assert!(prepare_root(&from).is_err());
+
+        migrate(&from, &to).unwrap();
+        assert_eq!(fs::read(to.join("images").join("a.img")).unwrap(), b"image");
+        assert!(to.join("assets").join("boards").join("b.png").is_file());
+        assert!(!from.join("images").exists());
+        assert!(from.join("other.txt").is_file());
+        assert_eq!(MIGRATION.total_bytes.load(Ordering::SeqCst), 8);
+
+        assert!(prepare_root(&base.join("missing").join("root")).is_err());
+        let _ = fs::remove_dir_all(&base);
+    }
+}
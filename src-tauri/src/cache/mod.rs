@@ -0,0 +1,1012 @@
+//! Image cache management module
+//!
+//! Handles persistent caching of downloaded Armbian images with
+//! configurable size limits and eviction policies (LRU by default).
+//! Every cached image has an entry in the cache index recording its
+//! SHA256, origin and verification, checked before the image is reused
+//! and on demand by a scrub of the whole cache, and the user's pin and tags.
+//!
+//! Thread Safety:
+//! All cache operations are protected by a global Mutex to prevent
+//! race conditions when multiple threads access the cache simultaneously.
+
+mod eviction;
+pub mod import;
+mod index;
+pub mod location;
+pub mod scrub;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use filetime::FileTime;
+use once_cell::sync::Lazy;
+
+use crate::config;
+use crate::utils::{parse_armbian_filename, validate_cache_path, ArmbianFilenameInfo};
+use crate::{log_debug, log_error, log_info, log_warn};
+
+pub use eviction::EvictionPolicy;
+pub use import::import_image;
+pub use index::{IndexEntry, Verification};
+pub use location::cache_root;
+
+use index::{now_secs, Index};
+
+const MODULE: &str = "cache";
+
+/// Re-export default max cache size from config
+pub use crate::config::cache::DEFAULT_MAX_SIZE;
+
+/// Global mutex to ensure thread-safe cache operations
+///
+/// This prevents race conditions when multiple operations try to
+/// read/write cache files simultaneously (e.g., eviction during download).
+static CACHE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Cached images that eviction must keep (current builds of pinned boards)
+static PINNED_IMAGES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Policy choosing which images are evicted first
+static EVICTION_POLICY: Lazy<Mutex<EvictionPolicy>> =
+    Lazy::new(|| Mutex::new(EvictionPolicy::default()));
+
+/// Cache entry with metadata for eviction
+#[derive(Debug)]
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+    /// Successful flashes, from the cache index
+    use_count: u32,
+}
+
+/// Get the image cache directory path
+pub fn get_images_cache_dir() -> PathBuf {
+    cache_root().join("images")
+}
+
+/// Calculate total size of all cached images in bytes
+///
+/// Scans the images cache directory and sums up file sizes.
+/// Returns 0 if directory doesn't exist or on error.
+/// Thread-safe: acquires cache lock during operation.
+pub fn calculate_cache_size() -> Result<u64, String> {
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+
+    calculate_cache_size_internal()
+}
+
+/// Internal implementation of calculate_cache_size without locking
+///
+/// Used by functions that already hold the cache lock.
+fn calculate_cache_size_internal() -> Result<u64, String> {
+    let cache_dir = get_images_cache_dir();
+
+    if !cache_dir.exists() {
+        log_debug!(MODULE, "Cache directory doesn't exist, size is 0");
+        return Ok(0);
+    }
+
+    let entries = fs::read_dir(&cache_dir).map_err(|e| {
+        log_error!(MODULE, "Failed to read cache directory: {}", e);
+        format!("Failed to read cache directory: {}", e)
+    })?;
+
+    let mut total_size: u64 = 0;
+    let mut file_count = 0;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_file() {
+            if let Ok(metadata) = fs::metadata(&path) {
+                total_size += metadata.len();
+                file_count += 1;
+            }
+        }
+    }
+
+    log_debug!(
+        MODULE,
+        "Cache size: {} bytes ({} files)",
+        total_size,
+        file_count
+    );
+
+    Ok(total_size)
+}
+
+/// Get list of cached files sorted by modification time (oldest first)
+///
+/// Returns a vector of CacheEntry structs for LRU eviction.
+/// Note: This function does not acquire the cache lock - caller must ensure thread safety.
+fn get_cached_files_by_age_internal() -> Result<Vec<CacheEntry>, String> {
+    let cache_dir = get_images_cache_dir();
+
+    if !cache_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(&cache_dir).map_err(|e| {
+        log_error!(MODULE, "Failed to read cache directory: {}", e);
+        format!("Failed to read cache directory: {}", e)
+    })?;
+
+    let mut files: Vec<CacheEntry> = Vec::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_file() {
+            if let Ok(metadata) = fs::metadata(&path) {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push(CacheEntry {
+                    path,
+                    size: metadata.len(),
+                    modified,
+                    use_count: 0,
+                });
+            }
+        }
+    }
+
+    // Sort by modification time (oldest first for LRU eviction)
+    files.sort_by(|a, b| a.modified.cmp(&b.modified));
+
+    Ok(files)
+}
+
+/// Replace the set of cached images protected from eviction
+pub fn set_pinned_images(filenames: HashSet<String>) {
+    if let Ok(mut pinned) = PINNED_IMAGES.lock() {
+        log_debug!(MODULE, "Pinned cache images: {:?}", filenames);
+        *pinned = filenames;
+    }
+}
+
+/// Replace the eviction policy, applied from the next eviction
+pub fn set_eviction_policy(policy: EvictionPolicy) {
+    if let Ok(mut current) = EVICTION_POLICY.lock() {
+        log_debug!(MODULE, "Cache eviction policy: {:?}", policy);
+        *current = policy;
+    }
+}
+
+/// Check whether a cached file is protected from eviction, either as the
+/// current build of a pinned board or pinned by the user
+fn is_pinned(path: &std::path::Path, index: &Index) -> bool {
+    let filename = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    index
+        .entries
+        .get(filename)
+        .is_some_and(|entry| entry.pinned)
+        || PINNED_IMAGES
+            .lock()
+            .map(|pinned| pinned.contains(filename))
+            .unwrap_or(false)
+}
+
+/// Remove an evicted file and its index entry, returning whether it is gone
+fn evict_file(entry: &CacheEntry, index: &mut Index) -> bool {
+    log_info!(MODULE, "Evicting cached file: {}", entry.path.display());
+
+    if let Err(e) = fs::remove_file(&entry.path) {
+        log_warn!(MODULE, "Failed to remove cached file: {}", e);
+        return false;
+    }
+    if let Some(name) = entry.path.file_name().and_then(|n| n.to_str()) {
+        index.entries.remove(name);
+    }
+    true
+}
+
+/// Evict files until cache is under the specified limit
+///
+/// Files go in the order of the eviction policy (by default LRU, based on
+/// file modification time); with "keep latest N per board", older releases
+/// beyond the latest N are evicted even under the limit. Pinned images are
+/// never evicted, so the cache may stay above the limit when they alone
+/// exceed it.
+/// Thread-safe: acquires cache lock during operation.
+pub fn evict_to_size(max_size: u64) -> Result<(), String> {
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+
+    evict_to_size_internal(max_size)
+}
+
+/// Internal implementation of evict_to_size without locking
+fn evict_to_size_internal(max_size: u64) -> Result<(), String> {
+    let policy = EVICTION_POLICY.lock().map(|p| *p).unwrap_or_default();
+    let mut index = Index::load();
+
+    // Unfinished downloads and pinned images are never candidates
+    let mut files: Vec<CacheEntry> = get_cached_files_by_age_internal()?
+        .into_iter()
+        .filter(|entry| {
+            let name = entry.path.file_name().and_then(|n| n.to_str());
+            !name.is_some_and(is_partial_download) && !is_pinned(&entry.path, &index)
+        })
+        .collect();
+    for entry in &mut files {
+        let name = entry.path.file_name().and_then(|n| n.to_str());
+        entry.use_count = name
+            .and_then(|name| index.entries.get(name))
+            .map_or(0, |meta| meta.use_count);
+    }
+
+    let mut freed_space: u64 = 0;
+    if let EvictionPolicy::KeepLatest { count } = policy {
+        for entry in eviction::take_surplus(&mut files, count) {
+            if evict_file(&entry, &mut index) {
+                freed_space += entry.size;
+            }
+        }
+    }
+
+    let current_size = calculate_cache_size_internal()?;
+
+    if current_size <= max_size {
+        log_debug!(
+            MODULE,
+            "Cache size {} is within limit {}, no eviction needed",
+            current_size,
+            max_size
+        );
+        if freed_space > 0 {
+            index.save();
+            log_info!(MODULE, "Evicted {} bytes of older releases", freed_space);
+        }
+        return Ok(());
+    }
+
+    log_info!(
+        MODULE,
+        "Cache size {} exceeds limit {}, evicting files ({:?})",
+        current_size,
+        max_size,
+        policy
+    );
+
+    eviction::order(&mut files, policy);
+    let target_free = current_size - max_size;
+    let mut freed_to_size: u64 = 0;
+
+    for entry in files {
+        if freed_to_size >= target_free {
+            break;
+        }
+        if evict_file(&entry, &mut index) {
+            freed_to_size += entry.size;
+        }
+    }
+    freed_space += freed_to_size;
+    index.save();
+
+    log_info!(MODULE, "Evicted {} bytes from cache", freed_space);
+
+    Ok(())
+}
+
+/// Check that a new image of `size` bytes fits within `max_size` next to
+/// the pinned images, without evicting anything
+///
+/// Thread-safe: acquires cache lock during operation.
+pub fn check_space(size: u64, max_size: u64) -> Result<(), String> {
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+    check_space_internal(size, max_size)
+}
+
+/// Make room for a new image of `size` bytes within `max_size`
+///
+/// Evicts unpinned images as needed. Fails without evicting anything if
+/// the pinned images already in the cache leave too little room.
+/// Thread-safe: acquires cache lock during operation.
+pub fn reserve_space(size: u64, max_size: u64) -> Result<(), String> {
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+
+    check_space_internal(size, max_size)?;
+    evict_to_size_internal(max_size - size)
+}
+
+/// Internal implementation of check_space without locking
+fn check_space_internal(size: u64, max_size: u64) -> Result<(), String> {
+    let index = Index::load();
+    let pinned_size: u64 = get_cached_files_by_age_internal()?
+        .iter()
+        .filter(|entry| is_pinned(&entry.path, &index))
+        .map(|entry| entry.size)
+        .sum();
+
+    if pinned_size.saturating_add(size) > max_size {
+        return Err(format!(
+            "Not enough cache space: {} bytes needed, {} of {} bytes used by pinned images",
+            size, pinned_size, max_size
+        ));
+    }
+    Ok(())
+}
+
+/// Clear all cached images
+///
+/// Removes all files from the images cache directory.
+/// Thread-safe: acquires cache lock during operation.
+pub fn clear_cache() -> Result<(), String> {
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+
+    let cache_dir = get_images_cache_dir();
+
+    if !cache_dir.exists() {
+        log_info!(MODULE, "Cache directory doesn't exist, nothing to clear");
+        return Ok(());
+    }
+
+    log_info!(MODULE, "Clearing cache directory: {}", cache_dir.display());
+
+    let entries = fs::read_dir(&cache_dir).map_err(|e| {
+        log_error!(MODULE, "Failed to read cache directory: {}", e);
+        format!("Failed to read cache directory: {}", e)
+    })?;
+
+    let mut removed_count = 0;
+    let mut failed_count = 0;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_file() {
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    removed_count += 1;
+                    log_debug!(MODULE, "Removed: {}", path.display());
+                }
+                Err(e) => {
+                    failed_count += 1;
+                    log_warn!(MODULE, "Failed to remove {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+
+    Index::default().save();
+
+    log_info!(
+        MODULE,
+        "Cache cleared: {} files removed, {} failed",
+        removed_count,
+        failed_count
+    );
+
+    if failed_count > 0 {
+        return Err(format!("Failed to remove {} cached files", failed_count));
+    }
+
+    Ok(())
+}
+
+/// Check if a cached image exists and return its path
+///
+/// Looks for a file with the given filename in the cache directory.
+/// If found, updates the file's modification time to mark it as recently used.
+/// Thread-safe: acquires cache lock during operation.
+pub fn get_cached_image(filename: &str) -> Option<PathBuf> {
+    let _lock = match CACHE_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log_error!(MODULE, "Failed to acquire cache lock: {}", e);
+            return None;
+        }
+    };
+
+    let cache_dir = get_images_cache_dir();
+    let cached_path = cache_dir.join(filename);
+
+    if cached_path.exists() && cached_path.is_file() {
+        log_info!(MODULE, "Found cached image: {}", cached_path.display());
+
+        // A size differing from the recorded one means the file changed
+        // since it was downloaded: drop it rather than flash it
+        let size = fs::metadata(&cached_path).map(|m| m.len()).unwrap_or(0);
+        let mut index = Index::load();
+        match index.entries.get(filename) {
+            Some(entry) if entry.size != size => {
+                log_warn!(
+                    MODULE,
+                    "Cached image {} is {} bytes, {} expected, removing it",
+                    filename,
+                    size,
+                    entry.size
+                );
+                if let Err(e) = fs::remove_file(&cached_path) {
+                    log_warn!(MODULE, "Failed to remove cached file: {}", e);
+                }
+                index.entries.remove(filename);
+                index.save();
+                return None;
+            }
+            Some(_) => {}
+            None => {
+                log_debug!(MODULE, "Adding {} to the cache index", filename);
+                index
+                    .entries
+                    .insert(filename.to_string(), IndexEntry::adopted(size, now_secs()));
+                index.save();
+            }
+        }
+
+        // Touch the file to update modification time (for LRU)
+        if let Err(e) = update_file_mtime(&cached_path) {
+            log_warn!(MODULE, "Failed to update mtime for cached file: {}", e);
+        }
+
+        Some(cached_path)
+    } else {
+        log_debug!(MODULE, "Image not in cache: {}", filename);
+        None
+    }
+}
+
+/// Record the metadata of an image just added to the cache
+///
+/// The pin and tags of an image cached again under the same name are kept.
+/// Thread-safe: acquires cache lock during operation.
+pub fn record_image(filename: &str, mut entry: IndexEntry) {
+    let _lock = match CACHE_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log_error!(MODULE, "Failed to acquire cache lock: {}", e);
+            return;
+        }
+    };
+    let mut index = Index::load();
+    if let Some(previous) = index.entries.remove(filename) {
+        entry.pinned = previous.pinned;
+        entry.tags = previous.tags;
+    }
+    log_debug!(MODULE, "Indexing {}: {:?}", filename, entry);
+    index.entries.insert(filename.to_string(), entry);
+    index.save();
+}
+
+/// Whether a cached image should be hashed again before it is used
+///
+/// True when its content was never hashed or the last check is older than
+/// the revalidation interval.
+pub fn revalidation_due(filename: &str) -> bool {
+    let _lock = match CACHE_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(_) => return false,
+    };
+    Index::load()
+        .entries
+        .get(filename)
+        .is_some_and(|entry| entry.revalidation_due(now_secs()))
+}
+
+/// Hash a cached image and compare it with the recorded SHA256
+///
+/// A file without a recorded hash gets the computed one. A file that no
+/// longer matches is deleted with its entry. Returns whether the image is
+/// intact. The file is hashed without holding the cache lock.
+pub fn verify_cached_image(filename: &str, cancelled: &AtomicBool) -> Result<bool, String> {
+    if filename.contains("..") || filename.contains('/') || filename.contains('\\') {
+        return Err("Invalid filename".to_string());
+    }
+    let path = get_images_cache_dir().join(filename);
+    if !path.is_file() {
+        return Err(format!("File not found in cache: {}", filename));
+    }
+
+    log_info!(MODULE, "Verifying cached image: {}", filename);
+    let actual = index::hash_file(&path, cancelled, &AtomicU64::new(0))?;
+
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+    let mut index = Index::load();
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    let entry = index
+        .entries
+        .entry(filename.to_string())
+        .or_insert_with(|| IndexEntry::adopted(size, now_secs()));
+
+    let intact = match entry.sha256.as_deref() {
+        Some(expected) => expected.eq_ignore_ascii_case(&actual),
+        None => {
+            entry.sha256 = Some(actual.clone());
+            true
+        }
+    };
+
+    if intact {
+        entry.checked_at = Some(now_secs());
+        log_info!(MODULE, "Cached image {} is intact", filename);
+    } else {
+        log_warn!(
+            MODULE,
+            "Cached image {} is corrupted (SHA256 {}), removing it",
+            filename,
+            actual
+        );
+        if let Err(e) = fs::remove_file(&path) {
+            log_warn!(MODULE, "Failed to remove cached file: {}", e);
+        }
+        index.entries.remove(filename);
+    }
+    index.save();
+    Ok(intact)
+}
+
+/// Change the index entry of a cached image, adding one if it has none
+///
+/// Thread-safe: acquires cache lock during operation.
+fn update_entry(filename: &str, update: impl FnOnce(&mut IndexEntry)) -> Result<(), String> {
+    if filename.contains("..") || filename.contains('/') || filename.contains('\\') {
+        return Err("Invalid filename".to_string());
+    }
+    let path = get_images_cache_dir().join(filename);
+    if !path.is_file() || is_partial_download(filename) {
+        return Err(format!("File not found in cache: {}", filename));
+    }
+
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+    let mut index = Index::load();
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    update(
+        index
+            .entries
+            .entry(filename.to_string())
+            .or_insert_with(|| IndexEntry::adopted(size, now_secs())),
+    );
+    index.save();
+    Ok(())
+}
+
+/// Pin or unpin a cached image; pinned images are never evicted
+pub fn set_image_pinned(filename: &str, pinned: bool) -> Result<(), String> {
+    log_info!(
+        MODULE,
+        "{} cached image {}",
+        if pinned { "Pinning" } else { "Unpinning" },
+        filename
+    );
+    update_entry(filename, |entry| entry.pinned = pinned)
+}
+
+/// Replace the tags of a cached image
+///
+/// Tags are trimmed, and empty or repeated ones dropped. Returns the tags
+/// as stored.
+pub fn set_image_tags(filename: &str, tags: Vec<String>) -> Result<Vec<String>, String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !cleaned.iter().any(|t| t == tag) {
+            cleaned.push(tag.to_string());
+        }
+    }
+    log_info!(MODULE, "Tagging cached image {}: {:?}", filename, cleaned);
+    let stored = cleaned.clone();
+    update_entry(filename, |entry| entry.tags = stored)?;
+    Ok(cleaned)
+}
+
+/// Record the outcome of flashing a cached image
+///
+/// A success resets the failure count and counts as a use. After too many consecutive
+/// failures the image is assumed corrupted and deleted. Returns whether
+/// it was deleted. Paths outside the image cache are ignored.
+pub fn record_flash_result(path: &Path, success: bool) -> Result<bool, String> {
+    let filename = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) if path.parent() == Some(get_images_cache_dir().as_path()) => name,
+        _ => return Ok(false),
+    };
+
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+    let mut index = Index::load();
+    let entry = match index.entries.get_mut(filename) {
+        Some(entry) => entry,
+        None => return Ok(false),
+    };
+
+    if success {
+        entry.flash_failures = 0;
+        entry.use_count += 1;
+        index.save();
+        return Ok(false);
+    }
+
+    entry.flash_failures += 1;
+    log_warn!(
+        MODULE,
+        "Flashing {} failed ({} of {})",
+        filename,
+        entry.flash_failures,
+        config::cache::MAX_FLASH_FAILURES
+    );
+    if entry.flash_failures < config::cache::MAX_FLASH_FAILURES {
+        index.save();
+        return Ok(false);
+    }
+
+    log_warn!(
+        MODULE,
+        "Too many failed flashes, removing cached image {}",
+        filename
+    );
+    if let Err(e) = fs::remove_file(path) {
+        if path.exists() {
+            return Err(format!("Failed to delete image: {}", e));
+        }
+    }
+    index.entries.remove(filename);
+    index.save();
+    Ok(true)
+}
+
+/// Update file modification time to current time
+///
+/// Used for LRU tracking - accessed files get their mtime updated.
+/// Uses the filetime crate for reliable cross-platform mtime updates.
+fn update_file_mtime(path: &PathBuf) -> Result<(), String> {
+    let now = FileTime::now();
+    filetime::set_file_mtime(path, now)
+        .map_err(|e| format!("Failed to update file mtime: {}", e))?;
+
+    log_debug!(MODULE, "Updated mtime for: {}", path.display());
+    Ok(())
+}
+
+/// Cached image metadata for frontend display
+///
+/// Contains file information and optional board association
+/// parsed from the Armbian filename convention.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CachedImageInfo {
+    pub filename: String,
+    pub path: String,
+    pub size: u64,
+    /// Unix timestamp (seconds) of last use/modification
+    pub last_used: u64,
+    /// Board slug extracted from filename (e.g., "orangepi-5")
+    pub board_slug: Option<String>,
+    /// Human-readable board name derived from slug
+    pub board_name: Option<String>,
+    /// Release details parsed from an Armbian filename
+    pub armbian: Option<ArmbianFilenameInfo>,
+    /// SHA256 of the file, once hashed
+    pub sha256: Option<String>,
+    pub verification: Verification,
+    /// Unix timestamp (seconds) of the last successful integrity check
+    pub checked_at: Option<u64>,
+    /// Consecutive failed flashes
+    pub flash_failures: u32,
+    /// Successful flashes
+    pub use_count: u32,
+    /// Pinned by the user
+    pub pinned: bool,
+    pub tags: Vec<String>,
+    /// Label given when the image was imported
+    pub label: Option<String>,
+}
+
+/// Frontend metadata of a cached file from its index entry
+fn image_info(
+    path: &Path,
+    filename: String,
+    size: u64,
+    last_used: u64,
+    meta: IndexEntry,
+) -> CachedImageInfo {
+    let armbian = parse_armbian_filename(&filename);
+    let board_slug = armbian.as_ref().map(|info| info.board_slug.clone());
+    let board_name = board_slug.as_deref().map(slug_to_display_name);
+    CachedImageInfo {
+        filename,
+        path: path.to_string_lossy().to_string(),
+        size,
+        last_used,
+        board_slug,
+        board_name,
+        armbian,
+        sha256: meta.sha256,
+        verification: meta.verification,
+        checked_at: meta.checked_at,
+        flash_failures: meta.flash_failures,
+        use_count: meta.use_count,
+        pinned: meta.pinned,
+        tags: meta.tags,
+        label: meta.label,
+    }
+}
+
+/// Convert a board slug to a human-readable name
+///
+/// Replaces hyphens with spaces and capitalizes words.
+/// Example: "nanopi-m5" → "Nanopi M5"
+fn slug_to_display_name(slug: &str) -> String {
+    slug.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Check whether a cache file belongs to an unfinished download
+///
+/// Matches `.downloading` temp files and their resume sidecars.
+pub fn is_partial_download(filename: &str) -> bool {
+    let suffix = config::images::DOWNLOAD_SUFFIX;
+    filename.ends_with(suffix) || filename.contains(&format!("{}.", suffix))
+}
+
+/// List all cached images with metadata
+///
+/// Scans the cache directory and returns information about each cached file,
+/// including board association parsed from the filename.
+/// Thread-safe: acquires cache lock during operation.
+pub fn list_cached_images() -> Result<Vec<CachedImageInfo>, String> {
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+
+    let cache_dir = get_images_cache_dir();
+
+    if !cache_dir.exists() {
+        log_debug!(
+            MODULE,
+            "Cache directory doesn't exist, returning empty list"
+        );
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(&cache_dir).map_err(|e| {
+        log_error!(MODULE, "Failed to read cache directory: {}", e);
+        format!("Failed to read cache directory: {}", e)
+    })?;
+
+    let mut images: Vec<CachedImageInfo> = Vec::new();
+    let mut index = Index::load();
+    let indexed = index.entries.len();
+    let mut listed = HashSet::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let filename = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        // Skip in-progress/interrupted downloads and their resume sidecars
+        if is_partial_download(&filename) {
+            continue;
+        }
+
+        let metadata = match fs::metadata(&path) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+
+        let last_used = metadata
+            .modified()
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let meta = index
+            .entries
+            .get(&filename)
+            .cloned()
+            .unwrap_or_else(|| IndexEntry::adopted(metadata.len(), last_used));
+        listed.insert(filename.clone());
+
+        images.push(image_info(&path, filename, metadata.len(), last_used, meta));
+    }
+
+    // Forget entries of files deleted behind our back
+    index
+        .entries
+        .retain(|filename, _| listed.contains(filename));
+    if index.entries.len() != indexed {
+        index.save();
+    }
+
+    // Sort by board_slug (None last), then by filename
+    images.sort_by(|a, b| match (&a.board_slug, &b.board_slug) {
+        (None, None) => a.filename.cmp(&b.filename),
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (Some(a_slug), Some(b_slug)) => {
+            let slug_cmp = a_slug.cmp(b_slug);
+            if slug_cmp == std::cmp::Ordering::Equal {
+                a.filename.cmp(&b.filename)
+            } else {
+                slug_cmp
+            }
+        }
+    });
+
+    log_info!(MODULE, "Listed {} cached images", images.len());
+    Ok(images)
+}
+
+/// How an image file is stored: the part of its name from `.img` on
+fn image_form(filename: &str) -> Option<&str> {
+    filename.rfind(".img").map(|i| &filename[i..])
+}
+
+/// Find a cached older build of the same image
+///
+/// Matches cached images with the same board, distribution, branch and
+/// desktop (kernel and version may differ), for use as a delta seed. The
+/// seed must be stored like the target (`.img` for `.img`, `.img.xz` for
+/// `.img.xz`), since blocks only match between files of the same form.
+/// Returns the most recently used match. Does not touch the file's mtime.
+pub fn find_previous_build(filename: &str) -> Option<PathBuf> {
+    let target = parse_armbian_filename(filename)?;
+    let form = image_form(filename)?;
+
+    list_cached_images()
+        .ok()?
+        .into_iter()
+        .filter(|image| image.filename != filename && image_form(&image.filename) == Some(form))
+        .filter(|image| {
+            parse_armbian_filename(&image.filename).is_some_and(|info| {
+                info.board_slug == target.board_slug
+                    && info.distro == target.distro
+                    && info.branch == target.branch
+                    && info.desktop == target.desktop
+            })
+        })
+        .max_by_key(|image| image.last_used)
+        .map(|image| PathBuf::from(image.path))
+}
+
+/// Delete a single cached image by filename
+///
+/// Validates that the file is within the cache directory to prevent
+/// path traversal attacks, then removes it.
+/// Returns the new total cache size after deletion.
+/// Thread-safe: acquires cache lock during operation.
+pub fn delete_cached_image(filename: &str) -> Result<u64, String> {
+    let _lock = CACHE_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+
+    let cache_dir = get_images_cache_dir();
+    let file_path = cache_dir.join(filename);
+
+    // Security: validate filename doesn't contain path traversal
+    if filename.contains("..") || filename.contains('/') || filename.contains('\\') {
+        log_error!(
+            MODULE,
+            "Invalid filename (path traversal attempt): {}",
+            filename
+        );
+        return Err("Invalid filename".to_string());
+    }
+
+    // Verify file exists and is within cache directory
+    if !file_path.exists() {
+        return Err(format!("File not found in cache: {}", filename));
+    }
+
+    if !file_path.is_file() {
+        return Err(format!("Not a file: {}", filename));
+    }
+
+    // Additional safety: canonicalize and verify within cache directory
+    if let Err(e) = validate_cache_path(&file_path) {
+        log_error!(
+            MODULE,
+            "Attempted to delete file outside cache: {}: {}",
+            filename,
+            e
+        );
+        return Err(e);
+    }
+
+    // Delete the file
+    fs::remove_file(&file_path).map_err(|e| {
+        log_error!(MODULE, "Failed to delete cached image {}: {}", filename, e);
+        format!("Failed to delete image: {}", e)
+    })?;
+
+    log_info!(MODULE, "Deleted cached image: {}", filename);
+
+    // A partial download goes with its resume sidecar
+    if is_partial_download(filename) {
+        let sidecar = cache_dir.join(format!(
+            "{}{}",
+            filename,
+            config::images::RESUME_SIDECAR_EXT
+        ));
+        if sidecar.is_file() {
+            let _ = fs::remove_file(&sidecar);
+        }
+    }
+
+    let mut index = Index::load();
+    if index.entries.remove(filename).is_some() {
+        index.save();
+    }
+
+    // Return updated cache size
+    calculate_cache_size_internal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculate_cache_size_empty() {
+        // For non-existent directory, should return 0
+        let result = calculate_cache_size();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_get_cached_files_by_age() {
+        // Acquire lock and test internal function
+        let _lock = CACHE_LOCK.lock().unwrap();
+        let result = get_cached_files_by_age_internal();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_is_partial_download() {
+        assert!(is_partial_download(
+            "Armbian_25.02.0_Rock-5b.img.xz.downloading"
+        ));
+        assert!(is_partial_download(
+            "Armbian_25.02.0_Rock-5b.img.xz.downloading.json"
+        ));
+        assert!(!is_partial_download("Armbian_25.02.0_Rock-5b.img"));
+    }
+
+    #[test]
+    fn test_image_form() {
+        assert_eq!(image_form("Armbian_25.02.0_Rock-5b.img"), Some(".img"));
+        assert_eq!(
+            image_form("Armbian_25.02.0_Rock-5b.img.xz"),
+            Some(".img.xz")
+        );
+        assert_eq!(image_form("custom.iso"), None);
+    }
+
+    #[test]
+    fn test_clear_cache_nonexistent() {
+        // Should succeed even if directory doesn't exist
+        let result = clear_cache();
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,455 @@
+//! Cache scrub
+//!
+//! Hashes every cached image again in the background and compares it with
+//! its recorded SHA256, or with the published one for catalog images that
+//! were never hashed. Nothing is removed: the report lists the corrupt
+//! images, those without any checksum to compare with, and the partial
+//! downloads left behind, so the user can delete or download them again.
+//! Index entries of files that are gone are dropped along the way.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::{log_info, log_warn};
+
+use super::index::{hash_file, now_secs, Index};
+use super::{
+    get_images_cache_dir, is_partial_download, update_entry, Verification, CACHE_LOCK, MODULE,
+};
+
+/// State of the running (or last) scrub
+static SCRUB: Lazy<Scrub> = Lazy::new(Scrub::default);
+
+#[derive(Default)]
+struct Scrub {
+    running: AtomicBool,
+    cancelled: AtomicBool,
+    total_bytes: AtomicU64,
+    checked_bytes: AtomicU64,
+    current_file: Mutex<Option<String>>,
+    error: Mutex<Option<String>>,
+    report: Mutex<Option<ScrubReport>>,
+}
+
+/// What is wrong with a file found by a scrub
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrubIssueKind {
+    /// Does not match its recorded or published SHA256
+    Corrupt,
+    /// Has no recorded SHA256 and no published one to compare with
+    Unverifiable,
+    /// Partial download no longer being written
+    Orphaned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrubIssue {
+    pub filename: String,
+    pub kind: ScrubIssueKind,
+    pub size: u64,
+    /// Where the file came from; set when it can be downloaded again
+    pub source_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScrubReport {
+    /// Images checked
+    pub checked: usize,
+    /// Images matching their checksum
+    pub intact: usize,
+    /// Index entries dropped because their file is gone
+    pub pruned_entries: usize,
+    pub issues: Vec<ScrubIssue>,
+    /// Unix timestamp (seconds) of the end of the scrub
+    pub finished_at: u64,
+}
+
+/// Scrub progress, and the report once done
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScrubProgress {
+    pub running: bool,
+    pub total_bytes: u64,
+    pub checked_bytes: u64,
+    pub progress_percent: f64,
+    pub current_file: Option<String>,
+    pub error: Option<String>,
+    pub report: Option<ScrubReport>,
+}
+
+/// Result of checking one image
+#[derive(Debug, PartialEq)]
+enum Check {
+    /// Matches its recorded SHA256
+    Intact,
+    /// Matches the published SHA256 (carried, to be recorded)
+    Matched(String),
+    Corrupt,
+    Unverifiable,
+}
+
+/// Resume sidecar of a partial download, only the part needed here
+#[derive(Deserialize)]
+struct Sidecar {
+    url: String,
+}
+
+fn sidecar_url(path: &Path) -> Option<String> {
+    let sidecar: Sidecar = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    Some(sidecar.url)
+}
+
+fn sidecar_name(filename: &str) -> String {
+    format!("{}{}", filename, config::images::RESUME_SIDECAR_EXT)
+}
+
+/// Hash `path` and compare it with the recorded, else the published, SHA256
+fn check_image(
+    path: &Path,
+    recorded: Option<&str>,
+    published: Option<&str>,
+    cancelled: &AtomicBool,
+    checked: &AtomicU64,
+) -> Result<Check, String> {
+    let expected = match recorded.or(published) {
+        Some(expected) => expected,
+        None => {
+            let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            checked.fetch_add(size, Ordering::SeqCst);
+            return Ok(Check::Unverifiable);
+        }
+    };
+    let actual = hash_file(path, cancelled, checked)?;
+    Ok(if !expected.eq_ignore_ascii_case(&actual) {
+        Check::Corrupt
+    } else if recorded.is_some() {
+        Check::Intact
+    } else {
+        Check::Matched(actual)
+    })
+}
+
+/// Partial downloads in `dir` not written to for a while
+///
+/// A resume sidecar is reported with its temp file, or on its own when the
+/// temp file is gone.
+fn orphaned_partials(dir: &Path, now: SystemTime) -> Vec<ScrubIssue> {
+    let mut issues = Vec::new();
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if !is_partial_download(&filename) {
+            continue;
+        }
+        let metadata = match entry.metadata() {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => continue,
+        };
+        let idle = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age.as_secs() >= config::cache::ORPHAN_AGE_SECS);
+
+        let source_url = if filename.ends_with(config::images::DOWNLOAD_SUFFIX) {
+            if !idle {
+                continue;
+            }
+            sidecar_url(&dir.join(sidecar_name(&filename)))
+        } else {
+            let temp = filename.trim_end_matches(config::images::RESUME_SIDECAR_EXT);
+            if temp == filename || dir.join(temp).exists() {
+                continue;
+            }
+            sidecar_url(&entry.path())
+        };
+        issues.push(ScrubIssue {
+            filename,
+            kind: ScrubIssueKind::Orphaned,
+            size: metadata.len(),
+            source_url,
+        });
+    }
+    issues
+}
+
+/// Names of the cached images without a recorded SHA256
+///
+/// Their published SHA256, where there is one, can be passed to `run`.
+pub fn unhashed_images() -> Vec<String> {
+    let dir = get_images_cache_dir();
+    let index = match CACHE_LOCK.lock() {
+        Ok(_lock) => Index::load(),
+        Err(_) => return Vec::new(),
+    };
+    fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !is_partial_download(name))
+        .filter(|name| {
+            index
+                .entries
+                .get(name)
+                .is_none_or(|entry| entry.sha256.is_none())
+        })
+        .collect()
+}
+
+fn scrub(published: &HashMap<String, String>) -> Result<ScrubReport, String> {
+    let dir = get_images_cache_dir();
+    let mut report = ScrubReport::default();
+
+    let (images, index) = {
+        let _lock = CACHE_LOCK
+            .lock()
+            .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+        let mut index = Index::load();
+        let before = index.entries.len();
+        index
+            .entries
+            .retain(|filename, _| dir.join(filename).is_file());
+        report.pruned_entries = before - index.entries.len();
+        if report.pruned_entries > 0 {
+            log_info!(
+                MODULE,
+                "Dropped {} index entries of missing files",
+                report.pruned_entries
+            );
+            index.save();
+        }
+
+        let images: Vec<(String, u64)> = fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+                let filename = entry.file_name().to_string_lossy().to_string();
+                (!is_partial_download(&filename)).then_some((filename, metadata.len()))
+            })
+            .collect();
+        (images, index)
+    };
+
+    SCRUB
+        .total_bytes
+        .store(images.iter().map(|(_, size)| size).sum(), Ordering::SeqCst);
+    log_info!(MODULE, "Scrubbing {} cached images", images.len());
+
+    for (filename, size) in images {
+        if let Ok(mut current) = SCRUB.current_file.lock() {
+            *current = Some(filename.clone());
+        }
+        let entry = index.entries.get(&filename);
+        let check = check_image(
+            &dir.join(&filename),
+            entry.and_then(|entry| entry.sha256.as_deref()),
+            published.get(&filename).map(String::as_str),
+            &SCRUB.cancelled,
+            &SCRUB.checked_bytes,
+        );
+        let check = match check {
+            Ok(check) => check,
+            Err(_) if SCRUB.cancelled.load(Ordering::SeqCst) => {
+                return Err("Scrub cancelled".to_string());
+            }
+            Err(e) => {
+                // Removed while scrubbing, e.g. evicted
+                log_warn!(MODULE, "Skipping {}: {}", filename, e);
+                continue;
+            }
+        };
+        report.checked += 1;
+
+        let kind = match check {
+            Check::Intact | Check::Matched(_) => {
+                let recorded = update_entry(&filename, |entry| {
+                    if let Check::Matched(sha256) = check {
+                        entry.sha256 = Some(sha256);
+                        entry.verification = Verification::Verified;
+                    }
+                    entry.checked_at = Some(now_secs());
+                });
+                if let Err(e) = recorded {
+                    log_warn!(MODULE, "Failed to record check of {}: {}", filename, e);
+                }
+                report.intact += 1;
+                continue;
+            }
+            Check::Corrupt => ScrubIssueKind::Corrupt,
+            Check::Unverifiable => ScrubIssueKind::Unverifiable,
+        };
+        log_warn!(MODULE, "Cached image {} is {:?}", filename, kind);
+        report.issues.push(ScrubIssue {
+            filename,
+            kind,
+            size,
+            source_url: entry.and_then(|entry| entry.source_url.clone()),
+        });
+    }
+
+    report
+        .issues
+        .extend(orphaned_partials(&dir, SystemTime::now()));
+    report.finished_at = now_secs();
+    log_info!(
+        MODULE,
+        "Scrub done: {} of {} images intact, {} issues",
+        report.intact,
+        report.checked,
+        report.issues.len()
+    );
+    Ok(report)
+}
+
+/// Claim the scrub, resetting the progress of the previous one
+pub fn start() -> Result<(), String> {
+    if SCRUB.running.swap(true, Ordering::SeqCst) {
+        return Err("A cache scrub is already running".to_string());
+    }
+    SCRUB.cancelled.store(false, Ordering::SeqCst);
+    SCRUB.total_bytes.store(0, Ordering::SeqCst);
+    SCRUB.checked_bytes.store(0, Ordering::SeqCst);
+    for slot in [&SCRUB.current_file, &SCRUB.error] {
+        if let Ok(mut slot) = slot.lock() {
+            *slot = None;
+        }
+    }
+    if let Ok(mut report) = SCRUB.report.lock() {
+        *report = None;
+    }
+    Ok(())
+}
+
+/// Run the scrub claimed with `start`
+///
+/// `published` maps images without a recorded SHA256 to their published one.
+/// Blocking: hashes every cached image.
+pub fn run(published: &HashMap<String, String>) {
+    let result = scrub(published);
+    match result {
+        Ok(report) => {
+            if let Ok(mut slot) = SCRUB.report.lock() {
+                *slot = Some(report);
+            }
+        }
+        Err(e) => {
+            log_warn!(MODULE, "Cache scrub failed: {}", e);
+            if let Ok(mut slot) = SCRUB.error.lock() {
+                *slot = Some(e);
+            }
+        }
+    }
+    if let Ok(mut current) = SCRUB.current_file.lock() {
+        *current = None;
+    }
+    SCRUB.running.store(false, Ordering::SeqCst);
+}
+
+pub fn cancel() {
+    if SCRUB.running.load(Ordering::SeqCst) {
+        log_info!(MODULE, "Cancelling cache scrub");
+        SCRUB.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+pub fn progress() -> ScrubProgress {
+    let total = SCRUB.total_bytes.load(Ordering::SeqCst);
+    let checked = SCRUB.checked_bytes.load(Ordering::SeqCst);
+    ScrubProgress {
+        running: SCRUB.running.load(Ordering::SeqCst),
+        total_bytes: total,
+        checked_bytes: checked,
+        progress_percent: if total > 0 {
+            (checked as f64 / total as f64) * 100.0
+        } else {
+            0.0
+        },
+        current_file: SCRUB.current_file.lock().ok().and_then(|c| c.clone()),
+        error: SCRUB.error.lock().ok().and_then(|e| e.clone()),
+        report: SCRUB.report.lock().ok().and_then(|r| r.clone()),
+    }
+}
+
+/// Drop a file from the last report once it was dealt with
+pub fn forget_issue(filename: &str) {
+    if let Ok(mut report) = SCRUB.report.lock() {
+        if let Some(report) = report.as_mut() {
+            report.issues.retain(|issue| issue.filename != filename);
+        }
+    }
+}
+
+/// URL a cached image or partial download came from
+pub fn source_url(filename: &str) -> Option<String> {
+    let dir = get_images_cache_dir();
+    if filename.ends_with(config::images::DOWNLOAD_SUFFIX) {
+        return sidecar_url(&dir.join(sidecar_name(filename)));
+    }
+    if is_partial_download(filename) {
+        return sidecar_url(&dir.join(filename));
+    }
+    let _lock = CACHE_LOCK.lock().ok()?;
+    Index::load().entries.get(filename)?.source_url.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_scrub_checks() {
+        let dir = std::env::temp_dir().join(format!("cache-scrub-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("a.img");
+        fs::write(&image, b"image").unwrap();
+        let sha256 = hash_file(&image, &AtomicBool::new(false), &AtomicU64::new(0)).unwrap();
+        let (cancelled, checked) = (AtomicBool::new(false), AtomicU64::new(0));
+
+        let check = |recorded, published| {
+            check_image(&image, recorded, published, &cancelled, &checked).unwrap()
+        };
+        assert_eq!(check(Some(sha256.as_str()), None), Check::Intact);
+        assert_eq!(
+            check(None, Some(sha256.as_str())),
+            Check::Matched(sha256.clone())
+        );
+        assert_eq!(check(Some("00"), Some(sha256.as_str())), Check::Corrupt);
+        assert_eq!(check(None, None), Check::Unverifiable);
+        assert_eq!(checked.load(Ordering::SeqCst), 20);
+
+        fs::write(dir.join("b.img.downloading"), b"part").unwrap();
+        fs::write(
+            dir.join("b.img.downloading.json"),
+            r#"{"url":"https://example.com/b.img.xz"}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("c.img.downloading.json"), b"{}").unwrap();
+        assert!(orphaned_partials(&dir, SystemTime::now())
+            .iter()
+            .all(|issue| issue.filename == "c.img.downloading.json"));
+
+        let later = SystemTime::now() + Duration::from_secs(config::cache::ORPHAN_AGE_SECS);
+        let mut orphans = orphaned_partials(&dir, later);
+        orphans.sort_by(|a, b| a.filename.cmp(&b.filename));
+        assert_eq!(orphans.len(), 2);
+        assert_eq!(orphans[0].filename, "b.img.downloading");
+        assert_eq!(
+            orphans[0].source_url.as_deref(),
+            Some("https://example.com/b.img.xz")
+        );
+        assert_eq!(orphans[1].source_url, None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
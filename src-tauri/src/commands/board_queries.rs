@@ -3,17 +3,19 @@
 //! Handles fetching and filtering board/image data.
 
 use std::collections::HashSet;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use once_cell::sync::Lazy;
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
 
+use crate::config;
 use crate::devices::{get_block_devices as devices_get_block_devices, BlockDevice};
+use crate::images::diff::{self, CatalogDiff};
+use crate::images::search::{self, SearchFilters, SearchResults};
 use crate::images::{
-    extract_images, fetch_all_images, filter_images_for_board, get_unique_boards, BoardInfo,
-    ImageInfo,
+    fetch_catalog, status, BoardFacets, BoardInfo, Catalog, CatalogStatus, ImageInfo,
 };
-use crate::{log_debug, log_error, log_info};
+use crate::{log_debug, log_error, log_info, log_warn};
 
 use super::state::AppState;
 
@@ -22,27 +24,124 @@ static PREV_DEVICE_PATHS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new
 
 /// Get list of available boards
 #[tauri::command]
-pub async fn get_boards(state: State<'_, AppState>) -> Result<Vec<BoardInfo>, String> {
+pub async fn get_boards(
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Vec<BoardInfo>, String> {
     log_info!("board_queries", "Fetching boards list");
 
-    // Fetch images if not cached
-    let mut json_guard = state.images_json.lock().await;
-    if json_guard.is_none() {
-        log_info!("board_queries", "Cache miss - fetching from API");
-        let json = fetch_all_images().await.map_err(|e| {
-            log_error!("board_queries", "Failed to fetch boards: {}", e);
-            e
-        })?;
-        *json_guard = Some(json);
-    }
-
-    let json = json_guard.as_ref().unwrap();
-    let images = extract_images(json);
-    let boards = get_unique_boards(&images);
+    let catalog = load_catalog(&state, &app).await?;
+    let boards = catalog.boards().to_vec();
     log_info!("board_queries", "Found {} boards", boards.len());
     Ok(boards)
 }
 
+/// Get the catalog, fetching and indexing it on first use
+pub(crate) async fn load_catalog(
+    state: &AppState,
+    app: &AppHandle,
+) -> Result<Arc<Catalog>, String> {
+    // Held across the fetch so concurrent callers do not fetch twice
+    let mut catalog_guard = state.catalog.lock().await;
+    if let Some(catalog) = catalog_guard.as_ref() {
+        return Ok(catalog.clone());
+    }
+
+    log_info!("board_queries", "Cache miss - fetching from API");
+    let sources = super::settings::get_catalog_sources(app.clone());
+    let catalog = fetch_catalog(&sources).await.map_err(|e| {
+        log_error!("board_queries", "Failed to fetch boards: {}", e);
+        e
+    })?;
+    let catalog = Arc::new(catalog);
+    *catalog_guard = Some(catalog.clone());
+    announce_catalog_changes(app);
+    Ok(catalog)
+}
+
+/// Get the catalog if it has been loaded
+async fn loaded_catalog(state: &AppState) -> Result<Arc<Catalog>, String> {
+    state.catalog.lock().await.clone().ok_or_else(|| {
+        log_error!(
+            "board_queries",
+            "Images requested before the catalog was loaded"
+        );
+        "Images not loaded. Call get_boards first.".to_string()
+    })
+}
+
+/// Search boards and images across the whole catalog
+///
+/// Words of `query` are matched fuzzily against board name, slug, vendor,
+/// file name, distribution, branch, desktop and versions; `filters` narrow
+/// the results down to exact facet values.
+#[tauri::command]
+pub async fn search_catalog(
+    query: String,
+    filters: Option<SearchFilters>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<SearchResults, String> {
+    log_info!("board_queries", "Searching catalog: {:?}", query);
+    let catalog = load_catalog(&state, &app).await?;
+    let results = search::search(
+        &catalog,
+        &query,
+        &filters.unwrap_or_default(),
+        config::images::MAX_SEARCH_RESULTS,
+    );
+    log_debug!(
+        "board_queries",
+        "Search found {} images on {} boards",
+        results.total_images,
+        results.boards.len()
+    );
+    Ok(results)
+}
+
+/// Get the distributions, branches, variants and applications of a board
+#[tauri::command]
+pub async fn get_board_facets(
+    board_slug: String,
+    state: State<'_, AppState>,
+) -> Result<BoardFacets, String> {
+    let catalog = loaded_catalog(&state).await?;
+    catalog
+        .facets(&board_slug)
+        .cloned()
+        .ok_or_else(|| format!("Unknown board: {}", board_slug))
+}
+
+/// Get the catalog changes found by the latest fetch that had any
+///
+/// Covers added and removed boards, new images per board and support level
+/// changes (e.g. boards moving to EOS). None until a fetch finds changes.
+#[tauri::command]
+pub fn get_catalog_changes() -> Option<CatalogDiff> {
+    diff::latest()
+}
+
+/// Get where each catalog source in use came from and how old it is
+///
+/// Source is "live" (downloaded), "cached" (saved copy confirmed current by
+/// the server), "stale-offline" (saved copy, server unreachable), "peer"
+/// (newer copy of a LAN peer, server unreachable) or "local" (read from a
+/// file). Empty until the catalog has been loaded; sources
+/// that could not be loaded at all are left out.
+#[tauri::command]
+pub fn get_catalog_status() -> Vec<CatalogStatus> {
+    status::current()
+}
+
+/// Emit catalog changes that the frontend has not been told about yet
+pub(crate) fn announce_catalog_changes(app: &AppHandle) {
+    if let Some(changes) = diff::take_pending() {
+        if let Err(e) = app.emit(config::images::CATALOG_CHANGED_EVENT, changes) {
+            log_warn!("board_queries", "Failed to emit catalog changes: {}", e);
+        }
+    }
+}
+
 /// Get images available for a specific board
 #[tauri::command]
 pub async fn get_images_for_board(
@@ -67,20 +166,13 @@ pub async fn get_images_for_board(
         variant_filter
     );
 
-    let json_guard = state.images_json.lock().await;
-    let json = json_guard.as_ref().ok_or_else(|| {
-        log_error!(
-            "board_queries",
-            "Images not loaded when requesting board: {}",
-            board_slug
-        );
-        "Images not loaded. Call get_boards first.".to_string()
-    })?;
-
-    let images = extract_images(json);
-    log_debug!("board_queries", "Total images available: {}", images.len());
-    let filtered = filter_images_for_board(
-        &images,
+    let catalog = loaded_catalog(&state).await?;
+    log_debug!(
+        "board_queries",
+        "Total images available: {}",
+        catalog.image_count()
+    );
+    let filtered = catalog.images_for_board(
         &board_slug,
         preapp_filter.as_deref(),
         kernel_filter.as_deref(),
@@ -0,0 +1,151 @@
+//! Offline bundle commands
+//!
+//! Export the catalog, picture cache and selected cached images into one
+//! file, and import it on a machine without network access.
+
+use tauri::State;
+
+use crate::bundle::{self, BundleSummary};
+use crate::images::Catalog;
+use crate::{log_info, log_warn};
+
+use super::state::AppState;
+
+/// Fetch a small file, such as a SHA or signature file
+async fn fetch_file(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    let response = crate::http::retry::send("Checksum fetch", || client.get(url))
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to fetch {}: HTTP {}",
+            url,
+            response.status()
+        ));
+    }
+    response
+        .bytes()
+        .await
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| format!("Failed to read {}: {}", url, e))
+}
+
+/// Fetch the published SHA and signature files of cached images
+///
+/// Best effort: images the catalog does not list, images cached under
+/// another name than published (decompressed, which the files do not
+/// cover), and files that cannot be fetched, are exported without them.
+async fn fetch_checksums(catalog: Option<&Catalog>, images: &[String]) -> Vec<(String, Vec<u8>)> {
+    let (catalog, client) = match (catalog, crate::http::client(crate::http::Timeout::Request)) {
+        (Some(catalog), Ok(client)) => (catalog, client),
+        _ => {
+            log_warn!(
+                "bundle",
+                "Catalog or network unavailable, exporting without SHA and signature files"
+            );
+            return Vec::new();
+        }
+    };
+
+    let mut checksums = Vec::new();
+    for filename in images {
+        let image = match catalog.image_for_cached_file(filename) {
+            Some(image) => image,
+            None => {
+                log_warn!("bundle", "{} is not in the catalog", filename);
+                continue;
+            }
+        };
+        if image.file_url.rsplit('/').next() != Some(filename.as_str()) {
+            log_info!(
+                "bundle",
+                "{} is cached decompressed, its published checksum does not apply",
+                filename
+            );
+            continue;
+        }
+        let urls = image
+            .file_url_sha
+            .into_iter()
+            .chain([format!("{}.asc", image.file_url)]);
+        for url in urls {
+            let name = url.rsplit('/').next().unwrap_or_default().to_string();
+            match fetch_file(&client, &url).await {
+                Ok(data) => checksums.push((name, data)),
+                Err(e) => log_warn!("bundle", "{}", e),
+            }
+        }
+    }
+    checksums
+}
+
+/// Export an offline bundle to `path`
+///
+/// The bundle holds the saved catalogs, the picture cache and the cached
+/// `images` (file names from the cache list) with their published SHA and
+/// signature files.
+#[tauri::command]
+pub async fn export_offline_bundle(
+    path: String,
+    images: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<BundleSummary, String> {
+    log_info!(
+        "bundle",
+        "Exporting offline bundle to {} with {} images",
+        path,
+        images.len()
+    );
+    let catalog = state.catalog.lock().await.clone();
+    let checksums = fetch_checksums(catalog.as_deref(), &images).await;
+
+    let cache_root = crate::cache::cache_root();
+    let summary = tokio::task::spawn_blocking(move || {
+        bundle::export(&cache_root, path.as_ref(), &images, &checksums)
+    })
+    .await
+    .map_err(|e| format!("Bundle export failed: {}", e))??;
+
+    log_info!(
+        "bundle",
+        "Exported {} images and {} assets ({} bytes)",
+        summary.images.len(),
+        summary.assets,
+        summary.total_bytes
+    );
+    Ok(summary)
+}
+
+/// Import an offline bundle from `path` into the caches
+///
+/// The catalog is reloaded from the imported copy on next use, so boards
+/// can be browsed and the imported images flashed without a network.
+/// Imported images are indexed, as verified when they matched their
+/// published checksum.
+#[tauri::command]
+pub async fn import_offline_bundle(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<BundleSummary, String> {
+    log_info!("bundle", "Importing offline bundle from {}", path);
+
+    let cache_root = crate::cache::cache_root();
+    let (summary, entries) =
+        tokio::task::spawn_blocking(move || bundle::import(&cache_root, path.as_ref()))
+            .await
+            .map_err(|e| format!("Bundle import failed: {}", e))??;
+    for (filename, entry) in entries {
+        crate::cache::record_image(&filename, entry);
+    }
+
+    log_info!(
+        "bundle",
+        "Imported {} images and {} assets ({} bytes)",
+        summary.images.len(),
+        summary.assets,
+        summary.total_bytes
+    );
+    crate::picture_cache::reload_meta().await;
+    *state.catalog.lock().await = None;
+    Ok(summary)
+}
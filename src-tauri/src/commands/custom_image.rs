@@ -6,11 +6,13 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tauri::State;
 
+use crate::cache::import::{self, ImportProgress};
+use crate::cache::CachedImageInfo;
 use crate::config;
 use crate::decompress::{decompress_local_file, needs_decompression};
-use crate::images::{extract_images, fetch_all_images, get_unique_boards, BoardInfo};
+use crate::images::BoardInfo;
 use crate::qdl::extract::open_tar_reader;
-use crate::utils::{get_cache_dir, normalize_slug, parse_armbian_filename};
+use crate::utils::{normalize_slug, parse_armbian_filename};
 use crate::{log_error, log_info};
 
 use super::state::AppState;
@@ -46,6 +48,7 @@ pub async fn decompress_custom_image(
 ) -> Result<String, String> {
     log_info!("custom_image", "Starting decompression: {}", image_path);
     let path = PathBuf::from(&image_path);
+    let _active = state.begin_operation();
     let download_state = state.download_state.clone();
 
     // Reset state for progress tracking
@@ -75,6 +78,60 @@ pub async fn decompress_custom_image(
     result.map(|p| p.to_string_lossy().to_string())
 }
 
+/// Import a custom image into the image cache
+///
+/// The image is copied, hashed and indexed with its optional `label`, so it
+/// is listed with the cached images and survives restarts. Decompressed
+/// custom images are moved rather than copied, and `move_file` moves any
+/// other image. Progress is reported by `get_import_progress`.
+#[tauri::command]
+pub async fn import_custom_image(
+    image_path: String,
+    label: Option<String>,
+    move_file: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<CachedImageInfo, String> {
+    log_info!("custom_image", "Importing into the cache: {}", image_path);
+    if !super::settings::get_cache_enabled(app.clone()) {
+        return Err("The image cache is disabled".to_string());
+    }
+
+    let path = PathBuf::from(&image_path);
+    let size = std::fs::metadata(&path)
+        .map_err(|e| format!("Failed to read file info: {}", e))?
+        .len();
+    let custom_decompress_dir =
+        crate::cache::cache_root().join(config::images::CUSTOM_DECOMPRESS_DIR);
+    let move_file = move_file.unwrap_or_else(|| path.starts_with(&custom_decompress_dir));
+
+    crate::cache::reserve_space(size, super::settings::get_cache_max_size(app))?;
+
+    let result =
+        tokio::task::spawn_blocking(move || crate::cache::import_image(&path, label, move_file))
+            .await
+            .map_err(|e| {
+                log_error!("custom_image", "Import task failed: {}", e);
+                format!("Task failed: {}", e)
+            })?;
+
+    if let Err(e) = &result {
+        log_error!("custom_image", "Import failed: {}", e);
+    }
+    result
+}
+
+/// Get the progress of the running (or last) image import
+#[tauri::command]
+pub fn get_import_progress() -> ImportProgress {
+    import::progress()
+}
+
+/// Cancel the running image import
+#[tauri::command]
+pub fn cancel_import() {
+    import::cancel();
+}
+
 /// Select a custom image file using native file picker
 #[tauri::command]
 pub async fn select_custom_image(window: tauri::Window) -> Result<Option<CustomImageInfo>, String> {
@@ -146,7 +203,8 @@ pub async fn delete_decompressed_custom_image(image_path: String) -> Result<(),
     let path = PathBuf::from(&image_path);
 
     // Safety check: only delete files in our custom-decompress directory
-    let custom_decompress_dir = get_cache_dir(config::app::NAME).join("custom-decompress");
+    let custom_decompress_dir =
+        crate::cache::cache_root().join(config::images::CUSTOM_DECOMPRESS_DIR);
 
     if !path.starts_with(&custom_decompress_dir) {
         log_error!(
@@ -326,6 +384,7 @@ fn quick_check_qdl_structure<R: std::io::Read>(reader: R) -> bool {
 pub async fn detect_board_from_filename(
     filename: String,
     state: State<'_, AppState>,
+    app: tauri::AppHandle,
 ) -> Result<Option<BoardInfo>, String> {
     log_info!(
         "custom_image",
@@ -364,63 +423,21 @@ pub async fn detect_board_from_filename(
     log_info!("custom_image", "Normalized board slug: {}", normalized_slug);
 
     // 7. Ensure board data is loaded (auto-load if not cached)
-    // Use compare-and-swap pattern to prevent race conditions
     log_info!("custom_image", "Checking if board data is cached...");
-    {
-        let needs_loading = {
-            let json_guard = state.images_json.lock().await;
-            json_guard.is_none()
-        };
-
-        if needs_loading {
-            log_info!(
-                "custom_image",
-                "Board data not cached, fetching from API..."
-            );
-            let json = fetch_all_images().await.map_err(|e| {
-                log_error!("custom_image", "Failed to fetch board data: {}", e);
-                format!("Failed to fetch board data: {}", e)
-            })?;
-
-            // Cache the fetched data
-            let mut json_guard = state.images_json.lock().await;
-            // Double-check: another thread might have loaded it while we were fetching
-            if json_guard.is_none() {
-                *json_guard = Some(json);
-                log_info!("custom_image", "Board data cached successfully");
-            } else {
-                log_info!(
-                    "custom_image",
-                    "Board data was already cached by another thread"
-                );
-            }
-        }
-    }
+    let catalog = super::board_queries::load_catalog(&state, &app)
+        .await
+        .map_err(|e| {
+            log_error!("custom_image", "Failed to fetch board data: {}", e);
+            format!("Failed to fetch board data: {}", e)
+        })?;
+    log_info!(
+        "custom_image",
+        "Found {} unique boards in database",
+        catalog.boards().len()
+    );
 
-    // 8. Get cached boards data (now guaranteed to be loaded)
-    // Extract boards in a scoped block to release lock early
-    let matching_board = {
-        log_info!("custom_image", "Accessing cached board data...");
-        let json_guard = state.images_json.lock().await;
-        let json = json_guard.as_ref().ok_or("Images not loaded")?;
-
-        log_info!("custom_image", "Loaded images JSON, extracting boards...");
-        let images = extract_images(json);
-        log_info!("custom_image", "Extracted {} images", images.len());
-        let boards = get_unique_boards(&images);
-        log_info!(
-            "custom_image",
-            "Found {} unique boards in database",
-            boards.len()
-        );
-        // Lock released here
-
-        // 9. Find matching board by slug
-        boards
-            .iter()
-            .find(|board| board.slug == normalized_slug)
-            .cloned()
-    }; // matching_board is now owned, lock is released
+    // 8. Find matching board by slug
+    let matching_board = catalog.board(&normalized_slug).cloned();
 
     if let Some(ref board) = matching_board {
         log_info!(
@@ -3,11 +3,14 @@
 //! Tauri command handlers organized by responsibility.
 
 pub mod board_queries;
+pub mod bundle;
 pub mod custom_image;
 pub mod operations;
 pub mod progress;
 pub mod qdl_operations;
+pub mod queue;
 pub mod scraping;
+pub mod scrub;
 pub mod settings;
 mod state;
 pub mod system;
@@ -3,13 +3,16 @@
 //! Handles download and flash operations.
 
 use std::path::PathBuf;
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Manager, State};
 use tauri_plugin_store::StoreExt;
 
-use crate::config;
-use crate::download::download_image as do_download;
+use crate::cache::{cache_root, get_images_cache_dir};
+use crate::download::{
+    download_and_flash as do_download_and_flash, download_image as do_download, Backend,
+    DownloadOptions, Trust,
+};
 use crate::flash::{flash_image as do_flash, request_authorization};
-use crate::utils::{get_cache_dir, validate_cache_path};
+use crate::utils::validate_cache_path;
 use crate::{log_debug, log_error, log_info};
 
 use super::state::AppState;
@@ -50,18 +53,74 @@ pub async fn request_write_authorization(device_path: String) -> Result<bool, St
     result
 }
 
+/// Verification required by the catalog sources listing an image
+///
+/// The catalog is loaded first if needed, so the requirements of its
+/// sources always apply; fails if it cannot be loaded. Images the catalog
+/// does not list have no extra requirements.
+pub(crate) async fn source_trust(app: &AppHandle, file_url: &str) -> Result<Trust, String> {
+    let state = app.state::<AppState>();
+    let catalog = super::board_queries::load_catalog(&state, app)
+        .await
+        .map_err(|e| format!("Cannot check what the image source requires: {}", e))?;
+    Ok(catalog.trust(file_url))
+}
+
+/// Whether downloaded images are cached compressed
+///
+/// Also when sharing the cache: peers are only asked for the published
+/// (compressed) file.
+fn keep_compressed(app: &AppHandle) -> bool {
+    super::settings::get_cache_compressed(app.clone())
+        || super::settings::get_lan_share_cache(app.clone())
+}
+
+/// Build download options from the settings and the image's catalog source
+///
+/// `backend` overrides the download_backend setting; `scheduled` downloads
+/// wait for the download window.
+pub(crate) async fn download_options(
+    app: &AppHandle,
+    file_url: &str,
+    backend: Option<String>,
+    scheduled: bool,
+) -> Result<DownloadOptions, String> {
+    Ok(DownloadOptions {
+        backend: Backend::from_setting(
+            &backend.unwrap_or_else(|| super::settings::get_download_backend(app.clone())),
+        ),
+        delta: super::settings::get_delta_download(app.clone()),
+        window: if scheduled {
+            super::settings::get_download_window(app.clone())
+        } else {
+            None
+        },
+        trust: source_trust(app, file_url).await?,
+        lan_peers: super::settings::get_lan_use_peers(app.clone()),
+        keep_compressed: keep_compressed(app),
+        reserve: None,
+    })
+}
+
 /// Start downloading an image
+///
+/// `backend` ("http" or "torrent") overrides the download_backend setting
+/// for this download. A `scheduled` download waits for the configured
+/// download window before starting.
 #[tauri::command]
 pub async fn download_image(
     file_url: String,
     file_url_sha: Option<String>,
+    backend: Option<String>,
+    scheduled: Option<bool>,
     state: State<'_, AppState>,
+    app: AppHandle,
 ) -> Result<String, String> {
     log_info!("operations", "Starting download: {}", file_url);
     log_debug!(
         "operations",
         "Download directory: {:?}",
-        get_cache_dir(config::app::NAME).join("images")
+        get_images_cache_dir()
     );
     if let Some(ref sha) = file_url_sha {
         log_info!("operations", "SHA URL: {}", sha);
@@ -69,13 +128,16 @@ pub async fn download_image(
         log_info!("operations", "No SHA URL provided");
         log_debug!("operations", "SHA verification will be skipped");
     }
-    let download_dir = get_cache_dir(config::app::NAME).join("images");
+    let download_dir = get_images_cache_dir();
+    let options = download_options(&app, &file_url, backend, scheduled.unwrap_or(false)).await?;
 
+    let _active = state.begin_operation();
     let download_state = state.download_state.clone();
     let result = do_download(
         &file_url,
         file_url_sha.as_deref(),
         &download_dir,
+        options,
         download_state,
     )
     .await;
@@ -123,6 +185,7 @@ pub async fn flash_image(
     let path = PathBuf::from(&image_path);
     let flash_state = state.flash_state.clone();
 
+    let _active = state.begin_operation();
     let result = do_flash(&path, &device_path, flash_state, verify).await;
 
     match &result {
@@ -137,43 +200,65 @@ pub async fn flash_image(
     result
 }
 
-/// Force delete a cached image regardless of cache settings
+/// Download an image and flash it in a single streaming pass
 ///
-/// Used when an image repeatedly fails to flash, suggesting the cached
-/// file may be corrupted. Bypasses the cache_enabled check.
+/// The compressed download is decompressed on the fly and written straight
+/// to the device without an intermediate file. A SHA256 mismatch detected
+/// when the download completes fails the flash. When the SHA is
+/// unavailable the flash fails with `[SHA_UNAVAILABLE]` before anything is
+/// written; call again with `allow_unverified` once the user accepted it.
 #[tauri::command]
-pub async fn force_delete_cached_image(image_path: String) -> Result<(), String> {
-    log_info!("operations", "Force delete cached image: {}", image_path);
+pub async fn download_and_flash_image(
+    file_url: String,
+    file_url_sha: Option<String>,
+    device_path: String,
+    verify: bool,
+    allow_unverified: Option<bool>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    log_info!(
+        "operations",
+        "Starting streaming flash: {} -> {} (verify: {})",
+        file_url,
+        device_path,
+        verify
+    );
 
-    let path = PathBuf::from(&image_path);
+    let _active = state.begin_operation();
+    let result = do_download_and_flash(
+        &file_url,
+        file_url_sha.as_deref(),
+        &device_path,
+        state.download_state.clone(),
+        state.flash_state.clone(),
+        verify,
+        allow_unverified.unwrap_or(false),
+        source_trust(&app, &file_url).await?,
+    )
+    .await;
 
-    // Safety check: only delete files in our cache directory
-    if let Err(e) = validate_cache_path(&path) {
-        log_error!(
-            "operations",
-            "Attempted to force delete file outside cache: {}: {}",
-            image_path,
-            e
-        );
-        return Err(e);
+    match &result {
+        Ok(_) => {
+            log_info!("operations", "Streaming flash completed successfully");
+        }
+        Err(e) => {
+            log_error!("operations", "Streaming flash failed: {}", e);
+        }
     }
 
-    if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| {
-            log_error!(
-                "operations",
-                "Failed to force delete image {}: {}",
-                image_path,
-                e
-            );
-            format!("Failed to delete image: {}", e)
-        })?;
-        log_info!("operations", "Force deleted cached image: {}", image_path);
-    } else {
-        log_debug!("operations", "Image already deleted: {}", image_path);
-    }
+    result
+}
 
-    Ok(())
+/// Record whether flashing an image succeeded
+///
+/// Failures are counted per cached image; after
+/// `config::cache::MAX_FLASH_FAILURES` consecutive failures the image is
+/// assumed corrupted and deleted. Returns whether it was deleted. Images
+/// outside the cache are ignored.
+#[tauri::command]
+pub async fn record_flash_result(image_path: String, success: bool) -> Result<bool, String> {
+    crate::cache::record_flash_result(&PathBuf::from(image_path), success)
 }
 
 /// Delete a downloaded image file
@@ -205,7 +290,7 @@ pub async fn delete_downloaded_image(image_path: String, app: AppHandle) -> Resu
         Ok(p) => p,
         Err(e) => {
             // If path or cache dir doesn't exist, skip silently
-            if !path.exists() || !get_cache_dir(config::app::NAME).exists() {
+            if !path.exists() || !cache_root().exists() {
                 log_debug!(
                     "operations",
                     "Path or cache directory doesn't exist, skipping delete: {}",
@@ -237,13 +322,19 @@ pub async fn delete_downloaded_image(image_path: String, app: AppHandle) -> Resu
 /// Continue a download that failed due to SHA unavailable
 /// Uses the already downloaded file without re-downloading
 #[tauri::command]
-pub async fn continue_download_without_sha(state: State<'_, AppState>) -> Result<String, String> {
+pub async fn continue_download_without_sha(
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<String, String> {
     log_info!("operations", "Continuing download without SHA verification");
 
-    let download_dir = get_cache_dir(config::app::NAME).join("images");
+    let download_dir = get_images_cache_dir();
     let download_state = state.download_state.clone();
+    let keep_compressed = keep_compressed(&app);
 
-    let result = crate::download::continue_without_sha(download_state, &download_dir).await;
+    let _active = state.begin_operation();
+    let result =
+        crate::download::continue_without_sha(download_state, &download_dir, keep_compressed).await;
 
     match &result {
         Ok(path) => {
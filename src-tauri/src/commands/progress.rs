@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use tauri::State;
 
 use super::state::AppState;
+use crate::download::signature::SignatureStatus;
 
 /// Download progress information
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,6 +17,10 @@ pub struct DownloadProgress {
     pub is_decompressing: bool,
     pub progress_percent: f64,
     pub error: Option<String>,
+    /// SHA256 of the compressed download, computed while streaming
+    pub computed_sha: Option<String>,
+    /// OpenPGP signature check result, once the download is complete
+    pub signature_status: Option<SignatureStatus>,
 }
 
 /// Flash progress information
@@ -60,6 +65,8 @@ pub async fn get_download_progress(state: State<'_, AppState>) -> Result<Downloa
     };
 
     let error = ds.error.lock().await.clone();
+    let computed_sha = ds.computed_sha.lock().await.clone();
+    let signature_status = *ds.signature_status.lock().await;
 
     Ok(DownloadProgress {
         total_bytes: total,
@@ -68,6 +75,8 @@ pub async fn get_download_progress(state: State<'_, AppState>) -> Result<Downloa
         is_decompressing,
         progress_percent: progress,
         error,
+        computed_sha,
+        signature_status,
     })
 }
 
@@ -36,6 +36,7 @@ pub async fn flash_qdl_image(
 ) -> Result<(), String> {
     log_info!("qdl_operations", "Starting QDL flash: {}", tar_path);
 
+    let _active = state.begin_operation();
     let flash_state = state.flash_state.clone();
     flash_state.reset();
 
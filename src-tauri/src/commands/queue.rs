@@ -0,0 +1,93 @@
+//! Download queue commands
+//!
+//! Background download jobs with per-job progress, and prefetching of
+//! catalog images into the cache while something else is being flashed.
+
+use tauri::{AppHandle, State};
+
+use crate::download::queue::{JobId, JobInfo, JobKind};
+use crate::log_info;
+
+use super::operations::download_options;
+use super::state::AppState;
+
+/// Queue an image download, returning its job ID
+///
+/// `backend` ("http" or "torrent") overrides the download_backend setting.
+#[tauri::command]
+pub async fn queue_download(
+    file_url: String,
+    file_url_sha: Option<String>,
+    backend: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<JobId, String> {
+    log_info!("queue", "Queue download requested: {}", file_url);
+    let options = download_options(&app, &file_url, backend, false).await?;
+    Ok(state.download_queue.enqueue(
+        &file_url,
+        file_url_sha.as_deref(),
+        JobKind::Download,
+        options,
+    ))
+}
+
+/// Prefetch an image into the cache, returning its job ID
+///
+/// Prefetches run after queued downloads and only during the download window.
+#[tauri::command]
+pub async fn prefetch_image(
+    file_url: String,
+    file_url_sha: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<JobId, String> {
+    if !super::settings::get_cache_enabled(app.clone()) {
+        return Err("Cannot prefetch images while the image cache is disabled".to_string());
+    }
+
+    log_info!("queue", "Prefetch requested: {}", file_url);
+    let options = download_options(&app, &file_url, None, true).await?;
+    Ok(state.download_queue.enqueue(
+        &file_url,
+        file_url_sha.as_deref(),
+        JobKind::Prefetch,
+        options,
+    ))
+}
+
+/// List all download jobs with their progress
+#[tauri::command]
+pub fn list_download_jobs(state: State<'_, AppState>) -> Vec<JobInfo> {
+    state.download_queue.list()
+}
+
+/// Get one download job with its progress
+#[tauri::command]
+pub fn get_download_job(job_id: JobId, state: State<'_, AppState>) -> Result<JobInfo, String> {
+    state.download_queue.get(job_id)
+}
+
+/// Pause a queued or running download job
+#[tauri::command]
+pub fn pause_download_job(job_id: JobId, state: State<'_, AppState>) -> Result<(), String> {
+    state.download_queue.pause(job_id)
+}
+
+/// Resume a paused download job
+#[tauri::command]
+pub async fn resume_download_job(job_id: JobId, state: State<'_, AppState>) -> Result<(), String> {
+    state.download_queue.resume(job_id)
+}
+
+/// Cancel a download job
+#[tauri::command]
+pub fn cancel_download_job(job_id: JobId, state: State<'_, AppState>) -> Result<(), String> {
+    state.download_queue.cancel(job_id)
+}
+
+/// Remove completed, failed and cancelled jobs from the list
+#[tauri::command]
+pub fn clear_finished_download_jobs(state: State<'_, AppState>) {
+    state.download_queue.clear_finished();
+}
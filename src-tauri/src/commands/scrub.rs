@@ -0,0 +1,120 @@
+//! Cache scrub commands
+//!
+//! Check the whole image cache in the background, and deal with what the
+//! scrub found: damaged images and abandoned partial downloads can be
+//! deleted (`delete_cached_image`) or downloaded again.
+
+use std::collections::HashMap;
+
+use tauri::{AppHandle, State};
+
+use crate::cache::scrub::{self, ScrubProgress};
+use crate::download::queue::{JobId, JobKind};
+use crate::images::Catalog;
+use crate::{log_info, log_warn};
+
+use super::operations::download_options;
+use super::state::AppState;
+
+/// Published SHA256 of the cached images that were never hashed
+///
+/// Only images cached as published (not decompressed) can be compared
+/// with it. Best effort: images the catalog does not list, and SHA files
+/// that cannot be fetched, are left out.
+async fn published_checksums(catalog: Option<&Catalog>) -> HashMap<String, String> {
+    let mut checksums = HashMap::new();
+    let images = scrub::unhashed_images();
+    if images.is_empty() {
+        return checksums;
+    }
+    let (catalog, client) = match (catalog, crate::http::client(crate::http::Timeout::Request)) {
+        (Some(catalog), Ok(client)) => (catalog, client),
+        _ => {
+            log_warn!(
+                "scrub",
+                "Catalog or network unavailable, scrubbing without published checksums"
+            );
+            return checksums;
+        }
+    };
+
+    for filename in images {
+        let sha_url = catalog
+            .image_for_cached_file(&filename)
+            .filter(|image| image.file_url.rsplit('/').next() == Some(filename.as_str()))
+            .and_then(|image| image.file_url_sha);
+        if let Some(sha_url) = sha_url {
+            match crate::download::fetch_expected_sha(&client, &sha_url).await {
+                Ok(sha) => {
+                    checksums.insert(filename, sha);
+                }
+                Err(e) => log_warn!("scrub", "{}: {}", filename, e),
+            }
+        }
+    }
+    checksums
+}
+
+/// Start a scrub of the image cache in the background
+///
+/// Every cached image is hashed again and compared with its recorded
+/// SHA256, or with the published one for catalog images never hashed.
+/// Progress and the report are polled with `get_cache_scrub_progress`.
+#[tauri::command]
+pub async fn start_cache_scrub(state: State<'_, AppState>) -> Result<(), String> {
+    scrub::start()?;
+    log_info!("scrub", "Starting cache scrub");
+
+    let catalog = state.catalog.lock().await.clone();
+    tauri::async_runtime::spawn(async move {
+        let published = published_checksums(catalog.as_deref()).await;
+        if let Err(e) = tokio::task::spawn_blocking(move || scrub::run(&published)).await {
+            log_warn!("scrub", "Cache scrub failed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+/// Get the progress of the cache scrub, with its report once done
+#[tauri::command]
+pub fn get_cache_scrub_progress() -> ScrubProgress {
+    scrub::progress()
+}
+
+/// Cancel the running cache scrub
+#[tauri::command]
+pub fn cancel_cache_scrub() {
+    scrub::cancel();
+}
+
+/// Download a cached image again from where it came from, returning the
+/// job ID
+///
+/// The cached file is deleted first; an abandoned partial download is
+/// resumed where possible.
+#[tauri::command]
+pub async fn redownload_cached_image(
+    filename: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<JobId, String> {
+    let url = scrub::source_url(&filename)
+        .ok_or_else(|| format!("The source of {} is unknown", filename))?;
+    log_info!("scrub", "Downloading {} again from {}", filename, url);
+
+    if !crate::cache::is_partial_download(&filename) {
+        crate::cache::delete_cached_image(&filename)?;
+    }
+    scrub::forget_issue(&filename);
+
+    let catalog = state.catalog.lock().await.clone();
+    let sha_url = crate::download::cached_filename(&url)
+        .ok()
+        .and_then(|name| catalog?.image_for_cached_file(name))
+        .and_then(|image| image.file_url_sha);
+
+    let options = download_options(&app, &url, None, false).await?;
+    Ok(state
+        .download_queue
+        .enqueue(&url, sha_url.as_deref(), JobKind::Download, options))
+}
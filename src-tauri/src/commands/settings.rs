@@ -2,6 +2,11 @@
 //!
 //! Manages user preferences like theme and language using the Tauri Store plugin.
 
+use crate::cache::EvictionPolicy;
+use crate::download::schedule::DownloadWindow;
+use crate::http::NetworkSettings;
+use crate::images::sources::{self, Source};
+use crate::pinned::PinnedBoard;
 use crate::{log_info, log_warn};
 use tauri_plugin_store::StoreExt;
 
@@ -43,10 +48,38 @@ fn default_cache_max_size() -> u64 {
     crate::cache::DEFAULT_MAX_SIZE
 }
 
+fn default_cache_compressed() -> bool {
+    false
+}
+
 fn default_armbian_board_detection() -> String {
     "modal".to_string()
 }
 
+fn default_download_backend() -> String {
+    "http".to_string()
+}
+
+fn default_delta_download() -> bool {
+    false
+}
+
+fn default_download_rate_limit() -> u64 {
+    0
+}
+
+fn default_download_concurrency() -> usize {
+    crate::config::download::DEFAULT_CONCURRENCY
+}
+
+fn default_lan_share_cache() -> bool {
+    false
+}
+
+fn default_lan_use_peers() -> bool {
+    false
+}
+
 /// Get the current theme preference
 #[tauri::command]
 pub fn get_theme(app: tauri::AppHandle) -> String {
@@ -462,6 +495,174 @@ pub fn set_cache_max_size(size: u64, app: tauri::AppHandle) -> Result<(), String
     }
 }
 
+/// Get the compressed cache preference
+#[tauri::command]
+pub fn get_cache_compressed(app: tauri::AppHandle) -> bool {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => match store.get("cache_compressed") {
+            Some(value) => value.as_bool().unwrap_or_else(default_cache_compressed),
+            None => {
+                log_info!(MODULE, "cache_compressed not found in store, using default");
+                default_cache_compressed()
+            }
+        },
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default cache_compressed: {}",
+                e
+            );
+            default_cache_compressed()
+        }
+    }
+}
+
+/// Set the compressed cache preference
+///
+/// When enabled, new downloads are cached as downloaded and decompressed on
+/// the fly while flashing. Images already cached are left as they are.
+#[tauri::command]
+pub fn set_cache_compressed(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting cache_compressed to: {}", enabled);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            store.set("cache_compressed", enabled);
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+/// Get the cache eviction policy
+#[tauri::command]
+pub fn get_cache_eviction_policy(app: tauri::AppHandle) -> EvictionPolicy {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => store
+            .get("cache_eviction_policy")
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default(),
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default cache_eviction_policy: {}",
+                e
+            );
+            EvictionPolicy::default()
+        }
+    }
+}
+
+/// Set the cache eviction policy
+///
+/// Applied immediately: the cache is trimmed to its size limit (and to the
+/// latest releases per board, with that policy) under the new policy.
+#[tauri::command]
+pub fn set_cache_eviction_policy(
+    policy: EvictionPolicy,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    policy.validate()?;
+
+    log_info!(MODULE, "Setting cache_eviction_policy to: {:?}", policy);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            store.set(
+                "cache_eviction_policy",
+                serde_json::to_value(policy)
+                    .map_err(|e| format!("Failed to serialize eviction policy: {}", e))?,
+            );
+            crate::cache::set_eviction_policy(policy);
+
+            if let Err(e) = crate::cache::evict_to_size(get_cache_max_size(app.clone())) {
+                log_info!(MODULE, "Failed to evict cache after policy change: {}", e);
+            }
+
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+/// Get the configured cache location, None for the default one
+#[tauri::command]
+pub fn get_cache_location(app: tauri::AppHandle) -> Option<String> {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => store
+            .get("cache_location")
+            .and_then(|value| value.as_str().map(str::to_string)),
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default cache_location: {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Move the cache to `path`, or back to the default location with None
+///
+/// The cache lives in an `armbian-imager` directory of `path`. The cached
+/// images, their index and the asset cache are migrated to it first; the
+/// setting only changes once that succeeded. Refused while downloads,
+/// flashes, a scrub or an import are running.
+#[tauri::command]
+pub async fn set_cache_location(
+    path: Option<String>,
+    state: tauri::State<'_, super::state::AppState>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let path = path
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty());
+
+    if state
+        .download_queue
+        .list()
+        .iter()
+        .any(|job| !job.status.is_finished())
+    {
+        return Err("Cannot move the cache while downloads are queued".to_string());
+    }
+    if state.operation_active() {
+        return Err("Cannot move the cache while downloading or flashing".to_string());
+    }
+    if crate::cache::scrub::progress().running || crate::cache::import::progress().running {
+        return Err(
+            "Cannot move the cache while it is being scrubbed or imported into".to_string(),
+        );
+    }
+
+    log_info!(MODULE, "Setting cache_location to: {:?}", path);
+
+    let root = path.clone().map(std::path::PathBuf::from);
+    tokio::task::spawn_blocking(move || crate::cache::location::relocate(root))
+        .await
+        .map_err(|e| format!("Cache migration failed: {}", e))??;
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            match path {
+                Some(path) => store.set("cache_location", path),
+                None => {
+                    store.delete("cache_location");
+                }
+            }
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+/// Get where the cache currently lives and the progress of a migration
+#[tauri::command]
+pub fn get_cache_location_status() -> crate::cache::location::CacheLocationStatus {
+    crate::cache::location::status()
+}
+
 /// Get the current cache size in bytes
 ///
 /// Calculates and returns the total size of all cached images.
@@ -497,7 +698,33 @@ pub fn list_cached_images() -> Result<Vec<crate::cache::CachedImageInfo>, String
 /// and returns the updated total cache size in bytes.
 #[tauri::command]
 pub fn delete_cached_image(filename: String) -> Result<u64, String> {
-    crate::cache::delete_cached_image(&filename)
+    let size = crate::cache::delete_cached_image(&filename)?;
+    crate::cache::scrub::forget_issue(&filename);
+    Ok(size)
+}
+
+/// Check a cached image against its recorded SHA256
+///
+/// A corrupted image is deleted. Returns whether the image is intact.
+#[tauri::command]
+pub async fn verify_cached_image(filename: String) -> Result<bool, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::cache::verify_cached_image(&filename, &std::sync::atomic::AtomicBool::new(false))
+    })
+    .await
+    .map_err(|e| format!("Cache verification failed: {}", e))?
+}
+
+/// Pin or unpin a cached image; pinned images are never evicted
+#[tauri::command]
+pub fn set_cached_image_pinned(filename: String, pinned: bool) -> Result<(), String> {
+    crate::cache::set_image_pinned(&filename, pinned)
+}
+
+/// Replace the tags of a cached image, returning them as stored
+#[tauri::command]
+pub fn set_cached_image_tags(filename: String, tags: Vec<String>) -> Result<Vec<String>, String> {
+    crate::cache::set_image_tags(&filename, tags)
 }
 
 // ============================================================================
@@ -560,3 +787,494 @@ pub fn set_armbian_board_detection(mode: String, app: tauri::AppHandle) -> Resul
         Err(e) => Err(format!("Failed to access store: {}", e)),
     }
 }
+
+// ============================================================================
+// Download Backend Settings
+// ============================================================================
+
+/// Get the download backend ("http" or "torrent")
+#[tauri::command]
+pub fn get_download_backend(app: tauri::AppHandle) -> String {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => match store.get("download_backend") {
+            Some(value) => value
+                .as_str()
+                .map(|s| s.to_string())
+                .unwrap_or_else(default_download_backend),
+            None => {
+                log_info!(MODULE, "download_backend not found in store, using default");
+                default_download_backend()
+            }
+        },
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default download_backend: {}",
+                e
+            );
+            default_download_backend()
+        }
+    }
+}
+
+/// Set the download backend
+///
+/// Valid values:
+/// - "http": Download from the HTTP mirrors
+/// - "torrent": Download over BitTorrent, using the mirrors as web seeds
+#[tauri::command]
+pub fn set_download_backend(backend: String, app: tauri::AppHandle) -> Result<(), String> {
+    if !matches!(backend.as_str(), "http" | "torrent") {
+        return Err(format!(
+            "Invalid download_backend: {}. Must be 'http' or 'torrent'",
+            backend
+        ));
+    }
+
+    log_info!(MODULE, "Setting download_backend to: {}", backend);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            store.set("download_backend", backend);
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+/// Get the delta download preference
+#[tauri::command]
+pub fn get_delta_download(app: tauri::AppHandle) -> bool {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => match store.get("delta_download") {
+            Some(value) => value.as_bool().unwrap_or_else(default_delta_download),
+            None => {
+                log_info!(MODULE, "delta_download not found in store, using default");
+                default_delta_download()
+            }
+        },
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default delta_download: {}",
+                e
+            );
+            default_delta_download()
+        }
+    }
+}
+
+/// Set the delta download preference
+///
+/// When enabled, a new build of a cached image is rebuilt from the cached
+/// build plus the changed ranges, if the server publishes a zsync control file.
+/// Only images kept as published (`.img.xz` needs the cache_compressed
+/// setting) with a SHA256 qualify, since the rebuilt file must match the
+/// published checksum; the older build must be cached in the same form.
+#[tauri::command]
+pub fn set_delta_download(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting delta_download to: {}", enabled);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            store.set("delta_download", enabled);
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+// ============================================================================
+// Bandwidth Settings
+// ============================================================================
+
+/// Get the download rate limit in bytes per second (0 = unlimited)
+#[tauri::command]
+pub fn get_download_rate_limit(app: tauri::AppHandle) -> u64 {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => match store.get("download_rate_limit") {
+            Some(value) => value.as_u64().unwrap_or_else(default_download_rate_limit),
+            None => {
+                log_info!(
+                    MODULE,
+                    "download_rate_limit not found in store, using default"
+                );
+                default_download_rate_limit()
+            }
+        },
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default download_rate_limit: {}",
+                e
+            );
+            default_download_rate_limit()
+        }
+    }
+}
+
+/// Set the download rate limit in bytes per second (0 = unlimited)
+///
+/// Applies immediately, including to a download already in progress.
+#[tauri::command]
+pub fn set_download_rate_limit(limit: u64, app: tauri::AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting download_rate_limit to: {} bytes/s", limit);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            store.set("download_rate_limit", limit);
+            crate::download::throttle::set_rate_limit(limit);
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+/// Get how many queued downloads may run at the same time
+#[tauri::command]
+pub fn get_download_concurrency(app: tauri::AppHandle) -> usize {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => match store.get("download_concurrency") {
+            Some(value) => value
+                .as_u64()
+                .map(|v| v as usize)
+                .unwrap_or_else(default_download_concurrency),
+            None => {
+                log_info!(
+                    MODULE,
+                    "download_concurrency not found in store, using default"
+                );
+                default_download_concurrency()
+            }
+        },
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default download_concurrency: {}",
+                e
+            );
+            default_download_concurrency()
+        }
+    }
+}
+
+/// Set how many queued downloads may run at the same time (1-8)
+#[tauri::command]
+pub async fn set_download_concurrency(
+    limit: usize,
+    state: tauri::State<'_, super::state::AppState>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    use crate::config::download::MAX_CONCURRENCY;
+
+    if !(1..=MAX_CONCURRENCY).contains(&limit) {
+        return Err(format!(
+            "Invalid download_concurrency: {} (must be between 1 and {})",
+            limit, MAX_CONCURRENCY
+        ));
+    }
+
+    log_info!(MODULE, "Setting download_concurrency to: {}", limit);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            store.set("download_concurrency", limit);
+            state.download_queue.set_concurrency(limit);
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+/// Get the hours during which queued downloads may run (None = any time)
+#[tauri::command]
+pub fn get_download_window(app: tauri::AppHandle) -> Option<DownloadWindow> {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => store
+            .get("download_window")
+            .and_then(|value| serde_json::from_value(value).ok()),
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using no download_window: {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Set the download window, or clear it with None
+///
+/// Hours are in local time; a window whose end is before its start wraps
+/// past midnight (e.g. 22-6).
+#[tauri::command]
+pub fn set_download_window(
+    window: Option<DownloadWindow>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    if let Some(window) = &window {
+        window.validate()?;
+    }
+
+    log_info!(MODULE, "Setting download_window to: {:?}", window);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            match window {
+                Some(window) => store.set(
+                    "download_window",
+                    serde_json::to_value(window)
+                        .map_err(|e| format!("Failed to serialize download window: {}", e))?,
+                ),
+                None => {
+                    store.delete("download_window");
+                }
+            }
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+// ============================================================================
+// Network Settings
+// ============================================================================
+
+/// Get the proxy and CA bundle settings
+#[tauri::command]
+pub fn get_network_settings(app: tauri::AppHandle) -> NetworkSettings {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => store
+            .get("network")
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default(),
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default network settings: {}",
+                e
+            );
+            NetworkSettings::default()
+        }
+    }
+}
+
+/// Set the proxy and CA bundle settings
+///
+/// The settings are rejected if no HTTP client can be built from them
+/// (invalid proxy URL, unreadable CA bundle). They apply to all requests
+/// started afterwards.
+#[tauri::command]
+pub fn set_network_settings(
+    settings: NetworkSettings,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    crate::http::validate(&settings)?;
+
+    log_info!(MODULE, "Setting network settings to: {:?}", settings);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            store.set(
+                "network",
+                serde_json::to_value(&settings)
+                    .map_err(|e| format!("Failed to serialize network settings: {}", e))?,
+            );
+            crate::http::configure(settings);
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+// ============================================================================
+// Pinned Boards
+// ============================================================================
+
+/// Get the pinned boards whose newest stable image is kept cached
+#[tauri::command]
+pub fn get_pinned_boards(app: tauri::AppHandle) -> Vec<PinnedBoard> {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => store
+            .get("pinned_boards")
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default(),
+        Err(e) => {
+            log_warn!(MODULE, "Error loading store, using no pinned_boards: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Set the pinned boards
+///
+/// Starts a check right away, so newly pinned boards are prefetched
+/// without waiting for the next periodic check.
+#[tauri::command]
+pub fn set_pinned_boards(pins: Vec<PinnedBoard>, app: tauri::AppHandle) -> Result<(), String> {
+    for pin in &pins {
+        pin.validate()?;
+    }
+
+    log_info!(MODULE, "Setting pinned_boards to: {:?}", pins);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            store.set(
+                "pinned_boards",
+                serde_json::to_value(&pins)
+                    .map_err(|e| format!("Failed to serialize pinned boards: {}", e))?,
+            );
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = crate::pinned::check(&app).await {
+                    log_warn!(MODULE, "Pinned board check failed: {}", e);
+                }
+            });
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+// ============================================================================
+// Catalog Sources
+// ============================================================================
+
+/// Get the catalog sources, including the built-in Armbian catalog
+#[tauri::command]
+pub fn get_catalog_sources(app: tauri::AppHandle) -> Vec<Source> {
+    let stored = match app.store(SETTINGS_STORE) {
+        Ok(store) => store
+            .get("catalog_sources")
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default(),
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default catalog_sources: {}",
+                e
+            );
+            Vec::new()
+        }
+    };
+    sources::normalize(stored).unwrap_or_else(|e| {
+        log_warn!(MODULE, "Ignoring invalid catalog_sources: {}", e);
+        vec![Source::builtin()]
+    })
+}
+
+/// Set the catalog sources
+///
+/// The built-in Armbian catalog is added back if missing (disable it
+/// instead). The catalog is fetched again from the new sources on next use.
+#[tauri::command]
+pub async fn set_catalog_sources(
+    sources: Vec<Source>,
+    state: tauri::State<'_, super::state::AppState>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let sources = sources::normalize(sources)?;
+
+    log_info!(MODULE, "Setting catalog_sources to: {:?}", sources);
+
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => {
+            store.set(
+                "catalog_sources",
+                serde_json::to_value(&sources)
+                    .map_err(|e| format!("Failed to serialize catalog sources: {}", e))?,
+            );
+            *state.catalog.lock().await = None;
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to access store: {}", e)),
+    }
+}
+
+// ============================================================================
+// LAN Sharing
+// ============================================================================
+
+/// Get whether the image cache is shared with the local network
+#[tauri::command]
+pub fn get_lan_share_cache(app: tauri::AppHandle) -> bool {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => match store.get("lan_share_cache") {
+            Some(value) => value.as_bool().unwrap_or_else(default_lan_share_cache),
+            None => {
+                log_info!(MODULE, "lan_share_cache not found in store, using default");
+                default_lan_share_cache()
+            }
+        },
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default lan_share_cache: {}",
+                e
+            );
+            default_lan_share_cache()
+        }
+    }
+}
+
+/// Set whether the image cache is shared with the local network
+///
+/// Starts or stops the cache server and its mDNS announcement. While
+/// sharing, new downloads are cached compressed, as published, so peers
+/// can check them against the published SHA.
+#[tauri::command]
+pub async fn set_lan_share_cache(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting lan_share_cache to: {}", enabled);
+
+    let store = app
+        .store(SETTINGS_STORE)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    crate::lan::set_sharing(enabled).await?;
+    store.set("lan_share_cache", enabled);
+    Ok(())
+}
+
+/// Get whether images are fetched from LAN peers when available
+#[tauri::command]
+pub fn get_lan_use_peers(app: tauri::AppHandle) -> bool {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => match store.get("lan_use_peers") {
+            Some(value) => value.as_bool().unwrap_or_else(default_lan_use_peers),
+            None => {
+                log_info!(MODULE, "lan_use_peers not found in store, using default");
+                default_lan_use_peers()
+            }
+        },
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Error loading store, using default lan_use_peers: {}",
+                e
+            );
+            default_lan_use_peers()
+        }
+    }
+}
+
+/// Set whether images are fetched from LAN peers when available
+///
+/// Starts or stops looking for peers. Images from peers are verified like
+/// any other download.
+#[tauri::command]
+pub fn set_lan_use_peers(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting lan_use_peers to: {}", enabled);
+
+    let store = app
+        .store(SETTINGS_STORE)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    crate::lan::set_browsing(enabled)?;
+    store.set("lan_use_peers", enabled);
+    Ok(())
+}
+
+/// Get the LAN sharing state and the peers found
+#[tauri::command]
+pub fn get_lan_status() -> crate::lan::LanStatus {
+    crate::lan::status()
+}
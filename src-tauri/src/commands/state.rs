@@ -2,25 +2,58 @@
 //!
 //! Defines the shared application state used across commands.
 
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use crate::cache::get_images_cache_dir;
+use crate::download::queue::JobManager;
 use crate::download::DownloadState;
 use crate::flash::FlashState;
+use crate::images::Catalog;
 
 /// Application state shared across all commands
 pub struct AppState {
-    pub images_json: Mutex<Option<serde_json::Value>>,
+    /// Catalog index, built once per fetch
+    pub catalog: Mutex<Option<Arc<Catalog>>>,
     pub download_state: Arc<DownloadState>,
     pub flash_state: Arc<FlashState>,
+    /// Background download jobs (queue and prefetch)
+    pub download_queue: Arc<JobManager>,
+    /// Interactive downloads, flashes and decompressions in progress
+    active_operations: Arc<AtomicUsize>,
+}
+
+/// An interactive operation in progress, counted until dropped
+pub struct ActiveOperation(Arc<AtomicUsize>);
+
+impl Drop for ActiveOperation {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl AppState {
+    /// Count an interactive operation as running until the guard is dropped
+    pub fn begin_operation(&self) -> ActiveOperation {
+        self.active_operations.fetch_add(1, Ordering::SeqCst);
+        ActiveOperation(self.active_operations.clone())
+    }
+
+    /// Whether an interactive download, flash or decompression is running
+    pub fn operation_active(&self) -> bool {
+        self.active_operations.load(Ordering::SeqCst) > 0
+    }
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
-            images_json: Mutex::new(None),
+            catalog: Mutex::new(None),
             download_state: Arc::new(DownloadState::new()),
             flash_state: Arc::new(FlashState::new()),
+            download_queue: Arc::new(JobManager::new(get_images_cache_dir)),
+            active_operations: Arc::new(AtomicUsize::new(0)),
         }
     }
 }
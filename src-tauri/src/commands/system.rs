@@ -8,15 +8,6 @@ use sys_locale::get_locale;
 
 const MODULE: &str = "commands::system";
 
-/// Shared HTTP client for connectivity checks (5s timeout)
-static CONNECTIVITY_CLIENT: once_cell::sync::Lazy<reqwest::Client> =
-    once_cell::sync::Lazy::new(|| {
-        reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(5))
-            .build()
-            .expect("Failed to build connectivity HTTP client")
-    });
-
 /// Log a message from the frontend (INFO level)
 #[tauri::command]
 pub fn log_from_frontend(module: String, message: String) {
@@ -213,11 +204,15 @@ fn open_url_windows(url: &str) -> Result<(), String> {
 /// Returns true if reachable, false if offline or any error occurs.
 #[tauri::command]
 pub async fn check_connectivity() -> bool {
-    match CONNECTIVITY_CLIENT
-        .head(crate::config::urls::ALL_IMAGES)
-        .send()
-        .await
-    {
+    let client = match crate::http::client(crate::http::Timeout::Connectivity) {
+        Ok(c) => c,
+        Err(e) => {
+            log_warn!(MODULE, "Connectivity check: no HTTP client ({})", e);
+            return false;
+        }
+    };
+
+    match client.head(crate::config::urls::ALL_IMAGES).send().await {
         Ok(response) => {
             let online = response.status().is_success() || response.status().is_redirection();
             log_debug!(
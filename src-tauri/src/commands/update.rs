@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use tauri::command;
 
+use crate::http::retry;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GitHubRelease {
     pub tag_name: String,
@@ -26,10 +28,7 @@ pub async fn get_github_release(version: String) -> Result<GitHubRelease, String
         return Err("Version cannot be empty".to_string());
     }
 
-    let client = reqwest::Client::builder()
-        .user_agent("Armbian-Imager")
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let client = crate::http::client(crate::http::Timeout::Request)?;
 
     // Ensure version has 'v' prefix (GitHub releases use v1.1.9 format)
     let version_tag = if version.starts_with('v') {
@@ -43,12 +42,13 @@ pub async fn get_github_release(version: String) -> Result<GitHubRelease, String
         version_tag
     );
 
-    let response = client
-        .get(&url)
-        .header("Accept", "application/vnd.github.v3+json")
-        .send()
-        .await
-        .map_err(|e| format!("Failed to fetch release: {}", e))?;
+    let response = retry::send("Release fetch", || {
+        client
+            .get(&url)
+            .header("Accept", "application/vnd.github.v3+json")
+    })
+    .await
+    .map_err(|e| format!("Failed to fetch release: {}", e))?;
 
     if !response.status().is_success() {
         return Err(format!("GitHub API returned error: {}", response.status()));
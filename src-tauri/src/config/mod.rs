@@ -39,6 +39,54 @@ pub mod download {
 
     /// Chunk size for streaming writes (4 MB)
     pub const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+    /// How often the resume sidecar is checkpointed during a download (16 MB)
+    pub const RESUME_CHECKPOINT_BYTES: u64 = 16 * 1024 * 1024;
+
+    /// Byte range fetched per request in segmented downloads (16 MB)
+    pub const SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+
+    /// Maximum number of mirrors used in parallel for one download
+    pub const MAX_MIRRORS: usize = 4;
+
+    /// Failed segments after which a mirror is dropped
+    pub const MAX_MIRROR_FAILURES: u32 = 3;
+
+    /// A mirror slower than this fraction of the fastest one is dropped
+    pub const SLOW_MIRROR_RATIO: f64 = 0.2;
+
+    /// Seconds without data before a segment request is considered stalled
+    pub const SEGMENT_IDLE_TIMEOUT_SECS: u64 = 30;
+
+    /// Network chunks buffered between download and flash when streaming
+    pub const STREAM_BUFFER_CHUNKS: usize = 256;
+
+    /// Block size requested from BitTorrent peers (16 KB, the de facto maximum)
+    pub const TORRENT_BLOCK_SIZE: u32 = 16 * 1024;
+
+    /// Block requests kept in flight per peer
+    pub const TORRENT_PIPELINE_DEPTH: usize = 8;
+
+    /// Maximum number of peers connected at once
+    pub const TORRENT_MAX_PEERS: usize = 30;
+
+    /// Seconds without a response before a peer or web seed is abandoned
+    pub const TORRENT_PEER_TIMEOUT_SECS: u64 = 30;
+
+    /// Port announced to trackers (incoming connections are not accepted)
+    pub const TORRENT_LISTEN_PORT: u16 = 6881;
+
+    /// Tracker announce rounds before a torrent download gives up
+    pub const TORRENT_ANNOUNCE_ROUNDS: u32 = 3;
+
+    /// Missing delta ranges closer than this are fetched in one request (1 MB)
+    pub const DELTA_MERGE_GAP: u64 = 1024 * 1024;
+
+    /// Default number of queued downloads running at the same time
+    pub const DEFAULT_CONCURRENCY: usize = 2;
+
+    /// Upper bound for the download queue concurrency setting
+    pub const MAX_CONCURRENCY: usize = 8;
 }
 
 /// Flash operation settings
@@ -117,6 +165,21 @@ pub mod http {
 
     /// Short timeout for quick requests like board info (10 seconds)
     pub const SHORT_TIMEOUT_SECS: u64 = 10;
+
+    /// Timeout for the connectivity check (5 seconds)
+    pub const CONNECTIVITY_TIMEOUT_SECS: u64 = 5;
+
+    /// Default attempts for network operations, including the first one
+    pub const RETRY_ATTEMPTS: u32 = 5;
+
+    /// Upper bound for the configurable number of attempts
+    pub const MAX_RETRY_ATTEMPTS: u32 = 20;
+
+    /// Delay before the first retry, doubled for each further one (ms)
+    pub const RETRY_BASE_DELAY_MS: u64 = 500;
+
+    /// Longest delay between two attempts
+    pub const RETRY_MAX_DELAY_SECS: u64 = 30;
 }
 
 /// Image filtering constants
@@ -129,6 +192,27 @@ pub mod images {
 
     /// Temporary download file suffix
     pub const DOWNLOAD_SUFFIX: &str = ".downloading";
+
+    /// Cache subdirectory holding decompressed custom images for one session
+    pub const CUSTOM_DECOMPRESS_DIR: &str = "custom-decompress";
+
+    /// Extension appended to the temp file for its resume sidecar
+    pub const RESUME_SIDECAR_EXT: &str = ".json";
+
+    /// Event emitted with the catalog diff when a fetch finds changes
+    pub const CATALOG_CHANGED_EVENT: &str = "catalog-changed";
+
+    /// Maximum boards and images returned by a catalog search
+    pub const MAX_SEARCH_RESULTS: usize = 100;
+
+    /// Id of the built-in Armbian catalog source
+    pub const BUILTIN_SOURCE_ID: &str = "armbian";
+
+    /// Label of the built-in Armbian catalog source
+    pub const BUILTIN_SOURCE_LABEL: &str = "Armbian";
+
+    /// Directory (under the cache assets) holding third-party catalog copies
+    pub const SOURCES_DIR: &str = "catalogs";
 }
 
 /// Cache management settings
@@ -144,4 +228,61 @@ pub mod cache {
 
     /// Maximum consecutive flash failures before auto-deleting cached image
     pub const MAX_FLASH_FAILURES: u32 = 3;
+
+    /// Metadata index of the cached images, in the cache root
+    pub const INDEX_FILE: &str = "image-index.json";
+
+    /// A cached image is hashed again on use when its last check is older (7 days)
+    pub const REVALIDATE_INTERVAL_SECS: u64 = 7 * 24 * 60 * 60;
+
+    /// How often pinned boards are checked for new releases (6 hours)
+    pub const PINNED_CHECK_INTERVAL_SECS: u64 = 6 * 60 * 60;
+
+    /// Upper bound of the size of a decompressed image relative to its
+    /// `.xz` download, for reserving cache space before downloading
+    pub const DECOMPRESSED_SIZE_RATIO: u64 = 8;
+
+    /// How often a pinned prefetch is polled for completion
+    pub const PINNED_POLL_INTERVAL_SECS: u64 = 10;
+
+    /// A partial download untouched for longer is reported as orphaned by a scrub (1 hour)
+    pub const ORPHAN_AGE_SECS: u64 = 60 * 60;
+}
+
+/// LAN cache sharing settings
+pub mod lan {
+    /// mDNS service type advertised by instances sharing their cache
+    pub const SERVICE_TYPE: &str = "_armbian-imager._tcp.local.";
+
+    /// Connections served at the same time
+    pub const MAX_CONNECTIONS: usize = 8;
+
+    /// Longest accepted request head (request line and headers)
+    pub const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+    /// Connect timeout for peers (seconds)
+    pub const CONNECT_TIMEOUT_SECS: u64 = 2;
+
+    /// Timeout for asking a peer whether it has an image (seconds)
+    pub const PROBE_TIMEOUT_SECS: u64 = 3;
+
+    /// Response header carrying when a peer's catalog was last confirmed
+    /// by the server (Unix timestamp, seconds)
+    pub const CATALOG_FETCHED_AT_HEADER: &str = "X-Catalog-Fetched-At";
+}
+
+/// Offline bundle settings
+pub mod bundle {
+    /// Bundle format version, bumped on incompatible layout changes
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Name of the manifest inside the bundle
+    pub const MANIFEST: &str = "manifest.json";
+
+    /// Cache directory holding the published SHA and signature files of
+    /// imported images
+    pub const CHECKSUMS_DIR: &str = "checksums";
+
+    /// Cache directory a bundle is unpacked into before it is installed
+    pub const STAGING_DIR: &str = ".bundle-import";
 }
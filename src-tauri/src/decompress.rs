@@ -12,6 +12,7 @@ use std::sync::Arc;
 use bzip2::read::BzDecoder;
 use flate2::read::GzDecoder;
 use lzma_rust2::XzReaderMt;
+use sha2::{Digest, Sha256};
 use xz2::read::XzDecoder;
 use zstd::stream::read::Decoder as ZstdDecoder;
 
@@ -28,13 +29,14 @@ pub fn needs_decompression(path: &Path) -> bool {
     matches!(ext.to_lowercase().as_str(), "xz" | "gz" | "bz2" | "zst")
 }
 
-/// Decompress XZ files. Uses multi-threaded lzma-rust2 for single-stream files,
+/// Decompress XZ files, returning the SHA256 of the decompressed data.
+/// Uses multi-threaded lzma-rust2 for single-stream files,
 /// falls back to xz2 (liblzma) for multi-stream files (e.g., Khadas OOWOW).
 pub fn decompress_with_rust_xz(
     input_path: &Path,
     output_path: &Path,
     state: &Arc<DownloadState>,
-) -> Result<(), String> {
+) -> Result<String, String> {
     // Try multi-threaded decoder first (faster, but doesn't support multi-stream XZ)
     let threads = get_recommended_threads();
     let input_file =
@@ -71,7 +73,7 @@ pub fn decompress_with_gz(
     input_path: &Path,
     output_path: &Path,
     state: &Arc<DownloadState>,
-) -> Result<(), String> {
+) -> Result<String, String> {
     let input_file =
         File::open(input_path).map_err(|e| format!("Failed to open input file: {}", e))?;
     let buf_reader = BufReader::with_capacity(config::download::DECOMPRESS_BUFFER_SIZE, input_file);
@@ -84,7 +86,7 @@ pub fn decompress_with_bz2(
     input_path: &Path,
     output_path: &Path,
     state: &Arc<DownloadState>,
-) -> Result<(), String> {
+) -> Result<String, String> {
     let input_file =
         File::open(input_path).map_err(|e| format!("Failed to open input file: {}", e))?;
     let buf_reader = BufReader::with_capacity(config::download::DECOMPRESS_BUFFER_SIZE, input_file);
@@ -97,7 +99,7 @@ pub fn decompress_with_zstd(
     input_path: &Path,
     output_path: &Path,
     state: &Arc<DownloadState>,
-) -> Result<(), String> {
+) -> Result<String, String> {
     let input_file =
         File::open(input_path).map_err(|e| format!("Failed to open input file: {}", e))?;
     let buf_reader = BufReader::with_capacity(config::download::DECOMPRESS_BUFFER_SIZE, input_file);
@@ -106,19 +108,80 @@ pub fn decompress_with_zstd(
     decompress_with_reader_mt(decoder, output_path, state, "zstd")
 }
 
+/// Wrap a compressed byte stream in the matching streaming decoder
+///
+/// The format is chosen from the file extension; uncompressed images are
+/// passed through unchanged. XZ uses the single-threaded multi-stream
+/// liblzma decoder since the multi-threaded one needs a seekable input.
+pub fn streaming_decoder<R: Read + Send + 'static>(
+    reader: R,
+    filename: &str,
+) -> Result<Box<dyn Read + Send>, String> {
+    let ext = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let buf_reader = BufReader::with_capacity(config::download::DECOMPRESS_BUFFER_SIZE, reader);
+
+    Ok(match ext.as_str() {
+        "xz" => Box::new(XzDecoder::new_multi_decoder(buf_reader)),
+        "gz" => Box::new(GzDecoder::new(buf_reader)),
+        "bz2" => Box::new(BzDecoder::new(buf_reader)),
+        "zst" => Box::new(
+            ZstdDecoder::with_buffer(buf_reader)
+                .map_err(|e| format!("Failed to create zstd decoder: {}", e))?,
+        ),
+        _ => Box::new(buf_reader),
+    })
+}
+
+/// Open a compressed image file as a stream of decompressed data
+///
+/// Like `streaming_decoder`, but XZ files use the multi-threaded decoder
+/// when possible since a file can be seeked. `file` wraps the opened file,
+/// e.g. to count the bytes consumed.
+pub fn file_decoder<F, W>(path: &Path, wrap: W) -> Result<Box<dyn Read + Send>, String>
+where
+    F: Read + std::io::Seek + Send + 'static,
+    W: Fn(File) -> F,
+{
+    let open = || File::open(path).map_err(|e| format!("Failed to open image: {}", e));
+    let filename = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Invalid filename")?;
+
+    if filename.to_lowercase().ends_with(".xz") {
+        let threads = get_recommended_threads();
+        match XzReaderMt::new(wrap(open()?), true, threads as u32) {
+            Ok(decoder) => return Ok(Box::new(decoder)),
+            Err(e) => log_info!(
+                MODULE,
+                "Multi-threaded decoder failed ({}), using liblzma multi-stream decoder",
+                e
+            ),
+        }
+    }
+    streaming_decoder(wrap(open()?), filename)
+}
+
 /// Generic decompression using any Read implementation (mut reference for multithreaded decoders)
+///
+/// Returns the SHA256 of the decompressed data, hashed while it is written.
 fn decompress_with_reader_mt<R: Read>(
     mut decoder: R,
     output_path: &Path,
     state: &Arc<DownloadState>,
     format_name: &str,
-) -> Result<(), String> {
+) -> Result<String, String> {
     let output_file =
         File::create(output_path).map_err(|e| format!("Failed to create output file: {}", e))?;
 
     let mut buf_writer =
         BufWriter::with_capacity(config::download::DECOMPRESS_BUFFER_SIZE, output_file);
     let mut buffer = vec![0u8; config::download::CHUNK_SIZE];
+    let mut hasher = Sha256::new();
 
     // Progress tracking - we don't know the decompressed size (0), so track output bytes
     // Use config interval for consistent logging
@@ -148,6 +211,7 @@ fn decompress_with_reader_mt<R: Read>(
         buf_writer
             .write_all(&buffer[..bytes_read])
             .map_err(|e| format!("Failed to write decompressed data: {}", e))?;
+        hasher.update(&buffer[..bytes_read]);
 
         // ProgressTracker handles logging automatically
         tracker.update(bytes_read as u64);
@@ -160,7 +224,7 @@ fn decompress_with_reader_mt<R: Read>(
     // Log final summary
     tracker.finish();
 
-    Ok(())
+    Ok(hex::encode(hasher.finalize()))
 }
 
 /// Decompress a local file (for custom images)
@@ -187,7 +251,7 @@ pub fn decompress_local_file(
     let output_filename = format!("{}-{}", base_filename, timestamp);
 
     // Output to cache directory instead of user's directory
-    let custom_cache_dir = crate::utils::get_cache_dir(config::app::NAME).join("custom-decompress");
+    let custom_cache_dir = crate::cache::cache_root().join(config::images::CUSTOM_DECOMPRESS_DIR);
 
     std::fs::create_dir_all(&custom_cache_dir)
         .map_err(|e| format!("Failed to create cache directory: {}", e))?;
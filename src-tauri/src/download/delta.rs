@@ -0,0 +1,697 @@
+//! zsync delta downloads
+//!
+//! Rebuilds a new image from an older cached build of the same image plus
+//! the ranges that changed. The server publishes a zsync control file
+//! (`<image>.zsync`) listing a rolling checksum and an MD4 checksum for
+//! every block of the new image; blocks found anywhere in the old image are
+//! copied locally and only the remaining ranges are fetched over HTTP.
+//!
+//! The control file is not trusted on its own: the result must match both
+//! the SHA-1 of the control file and the published SHA256 of the image, so
+//! delta downloads only apply to images published uncompressed with a
+//! checksum (the SHA256 of an `.img.xz` does not cover the rebuilt `.img`).
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use md4::Md4;
+use reqwest::header::RANGE;
+use reqwest::{Client, StatusCode};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::config;
+use crate::http::retry;
+use crate::utils::bytes_to_mb;
+use crate::{log_debug, log_info, log_warn};
+
+use super::signature;
+use super::{record_signature_status, resume, signature_url, verify_sha256, DownloadState, Trust};
+
+const MODULE: &str = "download::delta";
+
+/// Checksums of one block of the target file
+struct BlockSum {
+    /// Rolling checksum, truncated to `rsum_bytes`
+    rsum: u32,
+    /// MD4 checksum, truncated to `checksum_bytes`
+    checksum: Vec<u8>,
+}
+
+/// Parsed zsync control file
+struct ControlFile {
+    filename: String,
+    length: u64,
+    blocksize: usize,
+    /// Consecutive blocks that must match (1 or 2)
+    seq_matches: usize,
+    rsum_bytes: usize,
+    /// Target file URL, relative to the control file
+    url: Option<String>,
+    sha1: String,
+    blocks: Vec<BlockSum>,
+}
+
+impl ControlFile {
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        let header_end = data
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .ok_or("Control file has no header terminator")?;
+        let header = std::str::from_utf8(&data[..header_end])
+            .map_err(|_| "Control file header is not text".to_string())?;
+
+        let mut fields = HashMap::new();
+        for line in header.lines() {
+            if let Some((key, value)) = line.split_once(':') {
+                fields.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .ok_or_else(|| format!("Control file has no {} header", name))
+        };
+
+        let length: u64 = field("length")?
+            .parse()
+            .map_err(|_| "Invalid Length header".to_string())?;
+        let blocksize: usize = field("blocksize")?
+            .parse()
+            .map_err(|_| "Invalid Blocksize header".to_string())?;
+        if !blocksize.is_power_of_two() || blocksize < 64 {
+            return Err(format!("Unsupported block size {}", blocksize));
+        }
+
+        let lengths: Vec<usize> = field("hash-lengths")?
+            .split(',')
+            .filter_map(|n| n.trim().parse().ok())
+            .collect();
+        let [seq_matches, rsum_bytes, checksum_bytes] = lengths[..] else {
+            return Err("Invalid Hash-Lengths header".to_string());
+        };
+        if !(1..=2).contains(&seq_matches)
+            || !(1..=4).contains(&rsum_bytes)
+            || !(3..=16).contains(&checksum_bytes)
+        {
+            return Err("Unsupported Hash-Lengths header".to_string());
+        }
+
+        let block_count = length.div_ceil(blocksize as u64) as usize;
+        let sums = &data[header_end + 2..];
+        let entry = rsum_bytes + checksum_bytes;
+        if sums.len() < block_count * entry {
+            return Err("Control file block checksums are truncated".to_string());
+        }
+
+        let blocks = sums
+            .chunks_exact(entry)
+            .take(block_count)
+            .map(|e| BlockSum {
+                rsum: e[..rsum_bytes]
+                    .iter()
+                    .fold(0u32, |acc, b| (acc << 8) | *b as u32),
+                checksum: e[rsum_bytes..].to_vec(),
+            })
+            .collect();
+
+        Ok(Self {
+            filename: field("filename")?.clone(),
+            length,
+            blocksize,
+            seq_matches,
+            rsum_bytes,
+            url: fields.get("url").cloned(),
+            sha1: field("sha-1")?.to_ascii_lowercase(),
+            blocks,
+        })
+    }
+
+    /// Mask applied to rolling checksums before comparing them
+    fn rsum_mask(&self) -> u32 {
+        match self.rsum_bytes {
+            4 => u32::MAX,
+            n => (1u32 << (8 * n)) - 1,
+        }
+    }
+}
+
+/// zsync rolling checksum of a block: `(a << 16) | b`
+fn rsum(block: &[u8]) -> (u16, u16) {
+    let mut a: u16 = 0;
+    let mut b: u16 = 0;
+    let len = block.len();
+    for (i, &c) in block.iter().enumerate() {
+        a = a.wrapping_add(c as u16);
+        b = b.wrapping_add(((len - i) as u16).wrapping_mul(c as u16));
+    }
+    (a, b)
+}
+
+/// Slide the rolling checksum one byte forward
+fn roll(a: &mut u16, b: &mut u16, old: u8, new: u8, shift: u32) {
+    *a = a.wrapping_add(new as u16).wrapping_sub(old as u16);
+    *b = b
+        .wrapping_add(*a)
+        .wrapping_sub(((old as u32) << shift) as u16);
+}
+
+fn md4_prefix(block: &[u8], len: usize) -> Vec<u8> {
+    Md4::digest(block)[..len].to_vec()
+}
+
+/// Locate target blocks in the seed file
+///
+/// Returns, for every block of the target, the seed offset it can be copied
+/// from, if any.
+fn match_seed(
+    control: &ControlFile,
+    seed_path: &Path,
+    state: &DownloadState,
+) -> Result<Vec<Option<u64>>, String> {
+    let bs = control.blocksize;
+    let shift = bs.trailing_zeros();
+    let mask = control.rsum_mask();
+    let checksum_len = control.blocks.first().map_or(0, |b| b.checksum.len());
+
+    let mut table: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, block) in control.blocks.iter().enumerate() {
+        table.entry(block.rsum & mask).or_default().push(i);
+    }
+
+    let mut found: Vec<Option<u64>> = vec![None; control.blocks.len()];
+    let mut seed = File::open(seed_path).map_err(|e| format!("Failed to open seed: {}", e))?;
+
+    // Sliding buffer over the seed: `buf[0]` is at seed offset `buf_start`
+    let chunk = std::cmp::max(config::download::BUFFER_SIZE * 8, bs * 4);
+    let mut buf: Vec<u8> = Vec::with_capacity(chunk + 2 * bs);
+    let mut buf_start: u64 = 0;
+    let mut eof = false;
+    let mut pos: usize = 0;
+    let mut sums: Option<(u16, u16)> = None;
+
+    loop {
+        // Keep two blocks ahead of the window in memory
+        if pos + 2 * bs > buf.len() && !eof {
+            if state.is_cancelled.load(Ordering::SeqCst) {
+                return Err("Download cancelled".to_string());
+            }
+            buf.drain(..pos);
+            buf_start += pos as u64;
+            pos = 0;
+
+            let filled = buf.len();
+            buf.resize(filled + chunk, 0);
+            let mut read = 0;
+            while filled + read < buf.len() {
+                match seed.read(&mut buf[filled + read..]) {
+                    Ok(0) => {
+                        eof = true;
+                        break;
+                    }
+                    Ok(n) => read += n,
+                    Err(e) => return Err(format!("Failed to read seed: {}", e)),
+                }
+            }
+            buf.truncate(filled + read);
+        }
+        if pos + bs > buf.len() {
+            break;
+        }
+
+        let (a, b) = *sums.get_or_insert_with(|| rsum(&buf[pos..pos + bs]));
+        let mut matched = false;
+
+        if let Some(candidates) = table.get(&(((a as u32) << 16 | b as u32) & mask)) {
+            let checksum = md4_prefix(&buf[pos..pos + bs], checksum_len);
+            for &i in candidates {
+                if found[i].is_some() || control.blocks[i].checksum != checksum {
+                    continue;
+                }
+                // Short checksums rely on the next block matching as well
+                if control.seq_matches > 1 && i + 1 < control.blocks.len() {
+                    let next = match buf.get(pos + bs..pos + 2 * bs) {
+                        Some(next) => next,
+                        None => continue,
+                    };
+                    if md4_prefix(next, checksum_len) != control.blocks[i + 1].checksum {
+                        continue;
+                    }
+                }
+                found[i] = Some(buf_start + pos as u64);
+                matched = true;
+            }
+        }
+
+        if matched {
+            pos += bs;
+            sums = None;
+        } else if pos + bs < buf.len() {
+            let (mut a, mut b) = (a, b);
+            roll(&mut a, &mut b, buf[pos], buf[pos + bs], shift);
+            sums = Some((a, b));
+            pos += 1;
+        } else {
+            pos += 1;
+            sums = None;
+        }
+    }
+
+    Ok(found)
+}
+
+/// Byte ranges `[start, end)` of the target still to fetch
+///
+/// Missing blocks closer than `merge_gap` bytes are fetched in one request.
+fn missing_ranges(
+    found: &[Option<u64>],
+    blocksize: u64,
+    length: u64,
+    merge_gap: u64,
+) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for (i, _) in found.iter().enumerate().filter(|(_, f)| f.is_none()) {
+        let start = i as u64 * blocksize;
+        let end = std::cmp::min(start + blocksize, length);
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 + merge_gap => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+/// Control file URL for an image URL (`.img` → `.img.zsync`)
+fn control_url(url: &str) -> String {
+    format!("{}.zsync", url)
+}
+
+/// Rebuild the image at `url` into `output_path` from an older build
+///
+/// Returns an error without touching `output_path` when delta download is
+/// not possible (no control file, bad signature, verification failure), so
+/// the caller can fall back to a full download. The result must match
+/// `expected_sha`, the published SHA256 of the image. The control file
+/// signature is checked as the image's would be under `trust`.
+pub async fn download(
+    client: &Client,
+    url: &str,
+    expected_sha: &str,
+    seed_path: &Path,
+    output_path: &Path,
+    trust: &Trust,
+    state: &Arc<DownloadState>,
+) -> Result<(), String> {
+    let control_url = control_url(url);
+    log_info!(MODULE, "Trying delta download from {}", control_url);
+
+    let response = retry::send("Control file fetch", || client.get(&control_url))
+        .await
+        .map_err(|e| format!("Failed to fetch control file: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("No control file ({})", response.status()));
+    }
+    let data = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read control file: {}", e))?;
+
+    // The control file carries the SHA-1 of the result: authenticate it
+    let signing_key = trust.signing_key.clone();
+    let status = match signature::fetch(client, &signature_url(&control_url), signing_key).await {
+        Ok(mut pending) => {
+            pending.update(&data);
+            pending.finish()
+        }
+        Err(status) => status,
+    };
+    record_signature_status(state, status, trust.require_signature).await?;
+
+    let control = ControlFile::parse(&data)?;
+    let expected_name = output_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    if control.filename != expected_name {
+        return Err(format!(
+            "Control file describes {}, expected {}",
+            control.filename, expected_name
+        ));
+    }
+
+    let target_url = match &control.url {
+        Some(relative) => reqwest::Url::parse(&control_url)
+            .and_then(|base| base.join(relative))
+            .map_err(|e| format!("Invalid target URL in control file: {}", e))?
+            .to_string(),
+        None => control_url.trim_end_matches(".zsync").to_string(),
+    };
+
+    log_info!(
+        MODULE,
+        "Scanning seed {} for {} blocks of {} bytes",
+        seed_path.display(),
+        control.blocks.len(),
+        control.blocksize
+    );
+    let seed = seed_path.to_path_buf();
+    let scan_state = state.clone();
+    let (control, found) = tokio::task::spawn_blocking(move || {
+        let found = match_seed(&control, &seed, &scan_state);
+        (control, found)
+    })
+    .await
+    .map_err(|e| format!("Seed scan failed: {}", e))?;
+    let found = found?;
+
+    let blocksize = control.blocksize as u64;
+    let ranges = missing_ranges(
+        &found,
+        blocksize,
+        control.length,
+        config::download::DELTA_MERGE_GAP,
+    );
+    let to_fetch: u64 = ranges.iter().map(|(s, e)| e - s).sum();
+    log_info!(
+        MODULE,
+        "Reusing {:.1} MB from seed, fetching {:.1} MB in {} ranges",
+        bytes_to_mb(control.length.saturating_sub(to_fetch)),
+        bytes_to_mb(to_fetch),
+        ranges.len()
+    );
+
+    let temp_path = output_path.with_file_name(format!(
+        "{}{}",
+        control.filename,
+        config::images::DOWNLOAD_SUFFIX
+    ));
+    let result = assemble(
+        client,
+        &control,
+        &found,
+        &ranges,
+        &target_url,
+        expected_sha,
+        seed_path,
+        &temp_path,
+        state,
+    )
+    .await;
+    if let Err(e) = result {
+        resume::discard(&temp_path);
+        return Err(e);
+    }
+
+    std::fs::rename(&temp_path, output_path).map_err(|e| {
+        resume::discard(&temp_path);
+        format!("Failed to move file: {}", e)
+    })?;
+    log_info!(MODULE, "Delta download complete: {}", output_path.display());
+    Ok(())
+}
+
+/// Write the target file: seed blocks, then fetched ranges, then verify
+/// against the control file SHA-1 and the published SHA256
+#[allow(clippy::too_many_arguments)]
+async fn assemble(
+    client: &Client,
+    control: &ControlFile,
+    found: &[Option<u64>],
+    ranges: &[(u64, u64)],
+    target_url: &str,
+    expected_sha: &str,
+    seed_path: &Path,
+    temp_path: &Path,
+    state: &Arc<DownloadState>,
+) -> Result<(), String> {
+    let blocksize = control.blocksize as u64;
+    let mut out = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(temp_path)
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    out.set_len(control.length)
+        .map_err(|e| format!("Failed to preallocate temp file: {}", e))?;
+
+    let mut seed = File::open(seed_path).map_err(|e| format!("Failed to open seed: {}", e))?;
+    let mut block = vec![0u8; control.blocksize];
+    for (i, offset) in found.iter().enumerate() {
+        let Some(offset) = offset else { continue };
+        let start = i as u64 * blocksize;
+        let len = std::cmp::min(blocksize, control.length - start) as usize;
+        seed.seek(SeekFrom::Start(*offset))
+            .and_then(|_| seed.read_exact(&mut block[..len]))
+            .and_then(|_| out.seek(SeekFrom::Start(start)))
+            .and_then(|_| out.write_all(&block[..len]))
+            .map_err(|e| format!("Failed to copy seed block: {}", e))?;
+    }
+
+    let to_fetch: u64 = ranges.iter().map(|(s, e)| e - s).sum();
+    state.total_bytes.store(to_fetch, Ordering::SeqCst);
+    state.downloaded_bytes.store(0, Ordering::SeqCst);
+
+    for &(start, end) in ranges {
+        let response = retry::send("Range request", || {
+            client
+                .get(target_url)
+                .header(RANGE, format!("bytes={}-{}", start, end - 1))
+        })
+        .await
+        .map_err(|e| format!("Range request failed: {}", e))?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(format!(
+                "Server does not serve ranges ({})",
+                response.status()
+            ));
+        }
+
+        out.seek(SeekFrom::Start(start))
+            .map_err(|e| format!("Failed to seek temp file: {}", e))?;
+        let mut received = 0u64;
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            if state.is_cancelled.load(Ordering::SeqCst) {
+                return Err("Download cancelled".to_string());
+            }
+            let chunk = chunk.map_err(|e| format!("Download error: {}", e))?;
+            if received + chunk.len() as u64 > end - start {
+                return Err("Server sent more data than requested".to_string());
+            }
+            out.write_all(&chunk)
+                .map_err(|e| format!("Failed to write chunk: {}", e))?;
+            received += chunk.len() as u64;
+            state
+                .downloaded_bytes
+                .fetch_add(chunk.len() as u64, Ordering::SeqCst);
+            super::throttle::consume(chunk.len() as u64).await;
+            state.wait_while_paused().await;
+        }
+        if received != end - start {
+            return Err(format!(
+                "Range {}-{} ended after {} bytes",
+                start, end, received
+            ));
+        }
+    }
+
+    state.is_verifying_sha.store(true, Ordering::SeqCst);
+    out.seek(SeekFrom::Start(0))
+        .map_err(|e| format!("Failed to seek temp file: {}", e))?;
+    let mut hasher = Sha1::new();
+    let mut sha256 = Sha256::new();
+    let mut buffer = vec![0u8; config::download::BUFFER_SIZE];
+    loop {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Download cancelled".to_string());
+        }
+        let n = out
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read temp file: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        sha256.update(&buffer[..n]);
+    }
+    state.is_verifying_sha.store(false, Ordering::SeqCst);
+
+    let actual = hex::encode(hasher.finalize());
+    if actual != control.sha1 {
+        log_warn!(
+            MODULE,
+            "Delta result SHA-1 mismatch: expected {}, got {}",
+            control.sha1,
+            actual
+        );
+        return Err("Delta result failed verification".to_string());
+    }
+    log_debug!(MODULE, "Delta result SHA-1 verified: {}", actual);
+
+    verify_sha256(expected_sha, &hex::encode(sha256.finalize()))
+        .map_err(|e| format!("Delta result failed verification: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a control file the way zsyncmake does
+    fn make_control(target: &[u8], blocksize: usize, seq_matches: usize) -> Vec<u8> {
+        let (rsum_bytes, checksum_bytes) = if seq_matches > 1 { (3, 5) } else { (4, 16) };
+        let mut data = format!(
+            "zsync: 0.6.2\nFilename: new.img\nBlocksize: {}\nLength: {}\nHash-Lengths: {},{},{}\nSHA-1: {}\n\n",
+            blocksize,
+            target.len(),
+            seq_matches,
+            rsum_bytes,
+            checksum_bytes,
+            hex::encode(Sha1::digest(target))
+        )
+        .into_bytes();
+        for chunk in target.chunks(blocksize) {
+            let mut block = chunk.to_vec();
+            block.resize(blocksize, 0);
+            let (a, b) = rsum(&block);
+            let sum = [(a >> 8) as u8, a as u8, (b >> 8) as u8, b as u8];
+            data.extend_from_slice(&sum[4 - rsum_bytes..]);
+            data.extend_from_slice(&Md4::digest(&block)[..checksum_bytes]);
+        }
+        data
+    }
+
+    fn sample(len: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (x >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rolling_checksum_matches_direct() {
+        let data = sample(600, 1);
+        let (mut a, mut b) = rsum(&data[..256]);
+        for pos in 0..300 {
+            roll(&mut a, &mut b, data[pos], data[pos + 256], 8);
+            assert_eq!((a, b), rsum(&data[pos + 1..pos + 257]));
+        }
+    }
+
+    #[test]
+    fn test_match_seed_finds_shifted_blocks() {
+        let blocksize = 256;
+        let target = sample(20 * blocksize + 100, 7);
+
+        // Seed: bytes inserted at the front, one block changed, tail dropped
+        let mut seed = sample(37, 99);
+        seed.extend_from_slice(&target[..5 * blocksize]);
+        seed.extend_from_slice(&sample(blocksize, 3));
+        seed.extend_from_slice(&target[6 * blocksize..18 * blocksize]);
+
+        let path = std::env::temp_dir().join(format!("armbian-delta-seed-{}", std::process::id()));
+        std::fs::write(&path, &seed).unwrap();
+        let state = DownloadState::new();
+
+        for seq_matches in [1, 2] {
+            let control =
+                ControlFile::parse(&make_control(&target, blocksize, seq_matches)).unwrap();
+            let found = match_seed(&control, &path, &state).unwrap();
+
+            for (i, offset) in found.iter().enumerate() {
+                let reused = match i {
+                    // With pairs, a block needs its successor to match too
+                    4 | 17 => seq_matches == 1,
+                    0..=16 => i != 5,
+                    _ => false,
+                };
+                let expected = reused.then(|| 37 + (i * blocksize) as u64);
+                assert_eq!(*offset, expected, "block {} (seq {})", i, seq_matches);
+            }
+
+            let ranges = missing_ranges(&found, blocksize as u64, target.len() as u64, 0);
+            assert!(ranges
+                .iter()
+                .any(|&(start, end)| start <= 5 * 256 && end >= 6 * 256));
+            assert_eq!(ranges.last().unwrap().1, target.len() as u64);
+        }
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Compress like Armbian's multithreaded xz: independent blocks, so an
+    /// unchanged part of the image compresses to the same bytes
+    fn xz(data: &[u8]) -> Vec<u8> {
+        let stream = xz2::stream::MtStreamBuilder::new()
+            .preset(6)
+            .threads(2)
+            .block_size(64 * 1024)
+            .encoder()
+            .unwrap();
+        let mut encoder = xz2::write::XzEncoder::new_stream(Vec::new(), stream);
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_rebuild_xz_image_from_previous_build() {
+        let blocksize = 2048;
+        // Compressible image: repeated pseudo-random runs
+        let chunk = sample(4096, 5);
+        let old: Vec<u8> = (0..256u32)
+            .flat_map(|i| chunk.iter().map(move |b| b.wrapping_add(i as u8)))
+            .collect();
+        // New build: a few blocks rewritten, like an updated kernel
+        let mut new = old.clone();
+        new[300_000..310_000].copy_from_slice(&sample(10_000, 11));
+        let (old_xz, new_xz) = (xz(&old), xz(&new));
+        assert_ne!(old_xz, new_xz);
+
+        let path = std::env::temp_dir().join(format!(
+            "armbian-delta-xz-seed-{}.img.xz",
+            std::process::id()
+        ));
+        std::fs::write(&path, &old_xz).unwrap();
+        let control = ControlFile::parse(&make_control(&new_xz, blocksize, 2)).unwrap();
+        let found = match_seed(&control, &path, &DownloadState::new()).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // Most blocks come from the previous build
+        let reused = found.iter().filter(|f| f.is_some()).count();
+        assert!(reused * 2 > found.len(), "{} of {}", reused, found.len());
+
+        // Seed blocks plus the fetched ranges give back the new image
+        let mut rebuilt = vec![0u8; new_xz.len()];
+        for (i, offset) in found.iter().enumerate() {
+            if let Some(offset) = offset {
+                let start = i * blocksize;
+                let end = std::cmp::min(start + blocksize, new_xz.len());
+                let offset = *offset as usize;
+                rebuilt[start..end].copy_from_slice(&old_xz[offset..offset + end - start]);
+            }
+        }
+        for (start, end) in missing_ranges(&found, blocksize as u64, new_xz.len() as u64, 0) {
+            let (start, end) = (start as usize, end as usize);
+            rebuilt[start..end].copy_from_slice(&new_xz[start..end]);
+        }
+        assert_eq!(Sha1::digest(&rebuilt), Sha1::digest(&new_xz));
+    }
+
+    #[test]
+    fn test_missing_ranges_merge() {
+        let found = [Some(0), None, None, Some(0), None, Some(0), Some(0), None];
+        assert_eq!(
+            missing_ranges(&found, 10, 75, 0),
+            vec![(10, 30), (40, 50), (70, 75)]
+        );
+        assert_eq!(missing_ranges(&found, 10, 75, 10), vec![(10, 50), (70, 75)]);
+    }
+}
@@ -0,0 +1,1454 @@
+//! Download module
+//!
+//! Handles downloading Armbian images from the web.
+
+mod delta;
+mod pipeline;
+pub mod queue;
+mod resume;
+pub mod schedule;
+mod segmented;
+pub mod signature;
+pub mod throttle;
+mod torrent;
+
+use futures_util::StreamExt;
+use reqwest::header::{CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::{Client, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::cache::{IndexEntry, Verification};
+use crate::config;
+use crate::decompress::decompress_with_rust_xz;
+use crate::http::retry::{self, RetryPolicy};
+use crate::utils::{bytes_to_mb, validate_cache_path, ProgressTracker};
+use crate::{log_debug, log_error, log_info, log_warn};
+
+pub use pipeline::download_and_flash;
+use resume::PartialDownload;
+use signature::{Keyring, PendingSignature, SignatureStatus};
+
+const MODULE: &str = "download";
+
+/// Download progress state
+pub struct DownloadState {
+    pub total_bytes: AtomicU64,
+    pub downloaded_bytes: AtomicU64,
+    pub is_verifying_sha: AtomicBool,
+    pub is_decompressing: AtomicBool,
+    pub is_cancelled: AtomicBool,
+    /// Transfers stop reading while set (download queue jobs)
+    pub is_paused: AtomicBool,
+    pub error: Mutex<Option<String>>,
+    pub output_path: Mutex<Option<PathBuf>>,
+    /// Temp file path for SHA unavailable retry (file kept for user decision)
+    pub temp_path: Mutex<Option<PathBuf>>,
+    /// SHA256 of the compressed download, computed while streaming
+    pub computed_sha: Mutex<Option<String>>,
+    /// Result of the OpenPGP signature check of the download
+    pub signature_status: Mutex<Option<SignatureStatus>>,
+}
+
+impl DownloadState {
+    pub fn new() -> Self {
+        Self {
+            total_bytes: AtomicU64::new(0),
+            downloaded_bytes: AtomicU64::new(0),
+            is_verifying_sha: AtomicBool::new(false),
+            is_decompressing: AtomicBool::new(false),
+            is_cancelled: AtomicBool::new(false),
+            is_paused: AtomicBool::new(false),
+            error: Mutex::new(None),
+            output_path: Mutex::new(None),
+            temp_path: Mutex::new(None),
+            computed_sha: Mutex::new(None),
+            signature_status: Mutex::new(None),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.total_bytes.store(0, Ordering::SeqCst);
+        self.downloaded_bytes.store(0, Ordering::SeqCst);
+        self.is_verifying_sha.store(false, Ordering::SeqCst);
+        self.is_decompressing.store(false, Ordering::SeqCst);
+        self.is_cancelled.store(false, Ordering::SeqCst);
+    }
+
+    /// Wait while the download is paused (returns at once when cancelled)
+    pub async fn wait_while_paused(&self) {
+        while self.is_paused.load(Ordering::SeqCst) && !self.is_cancelled.load(Ordering::SeqCst) {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        }
+    }
+}
+
+impl Default for DownloadState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Transport used to download an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// HTTP from the Armbian mirrors (segmented when possible)
+    Http,
+    /// BitTorrent, with the HTTP mirrors as web seeds
+    Torrent,
+}
+
+impl Backend {
+    /// Parse the `download_backend` setting value
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "torrent" => Self::Torrent,
+            _ => Self::Http,
+        }
+    }
+}
+
+/// Verification the catalog source of an image requires
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trust {
+    /// The image must match a published SHA256 (no continuing without it)
+    pub require_sha: bool,
+    /// The image must carry a valid signature
+    pub require_signature: bool,
+    /// Keys of the catalog source, checked instead of the bundled keyring
+    pub signing_key: Option<Arc<Keyring>>,
+}
+
+/// Options for a single image download
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub backend: Backend,
+    /// Rebuild the image from an older cached build when possible
+    pub delta: bool,
+    /// Hold the download until this window opens (queued downloads only)
+    pub window: Option<schedule::DownloadWindow>,
+    pub trust: Trust,
+    /// Fetch the image from a LAN peer sharing it, when one is found
+    pub lan_peers: bool,
+    /// Cache the image as downloaded, decompressing it when flashing
+    pub keep_compressed: bool,
+    /// Cache space to make room for once the download starts (prefetches)
+    pub reserve: Option<Reservation>,
+}
+
+/// Cache space a download needs, reserved by evicting unpinned images
+#[derive(Debug, Clone, Copy)]
+pub struct Reservation {
+    /// Expected size of the cached image
+    pub size: u64,
+    /// Cache size limit
+    pub max_size: u64,
+}
+
+/// Extract filename from URL
+fn extract_filename(url: &str) -> Result<&str, String> {
+    log_debug!(MODULE, "Extracting filename from URL: {}", url);
+    let url_path = url.split('?').next().unwrap_or(url);
+    let filename = url_path
+        .split('/')
+        .next_back()
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| "Invalid URL: no filename".to_string())?;
+    log_debug!(MODULE, "Extracted filename: {}", filename);
+    Ok(filename)
+}
+
+/// Name of the cached image for a download URL (compressed .xz images are
+/// stored decompressed)
+pub fn cached_filename(url: &str) -> Result<&str, String> {
+    Ok(extract_filename(url)?.trim_end_matches(".xz"))
+}
+
+/// Names the image for a download URL may be cached under: decompressed,
+/// or as downloaded when compressed images are kept
+pub fn cached_filenames(url: &str) -> Result<Vec<&str>, String> {
+    let mut names = vec![cached_filename(url)?];
+    let filename = extract_filename(url)?;
+    if !names.contains(&filename) {
+        names.push(filename);
+    }
+    Ok(names)
+}
+
+/// Fetch expected SHA256 from URL
+/// Errors are prefixed with [SHA_UNAVAILABLE] to distinguish from SHA mismatch
+pub async fn fetch_expected_sha(client: &Client, sha_url: &str) -> Result<String, String> {
+    log_debug!(MODULE, "Fetching SHA256 from: {}", sha_url);
+
+    let response = retry::send("SHA fetch", || client.get(sha_url))
+        .await
+        .map_err(|e| format!("[SHA_UNAVAILABLE] Failed to fetch SHA: {}", e))?;
+
+    // Log the final URL after redirect (shows which mirror serves the SHA)
+    let final_sha_url = response.url().to_string();
+    if final_sha_url != sha_url {
+        log_debug!(MODULE, "SHA redirected to mirror: {}", final_sha_url);
+    }
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "[SHA_UNAVAILABLE] SHA fetch failed with status: {}",
+            response.status()
+        ));
+    }
+
+    let content = response
+        .text()
+        .await
+        .map_err(|e| format!("[SHA_UNAVAILABLE] Failed to read SHA response: {}", e))?;
+
+    // Parse SHA file format: "hash *filename" or "hash  filename"
+    let hash = content
+        .split_whitespace()
+        .next()
+        .ok_or("[SHA_UNAVAILABLE] Invalid SHA file format")?
+        .to_lowercase();
+
+    // Validate it looks like a SHA256 hash (64 hex chars)
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "[SHA_UNAVAILABLE] Invalid SHA256 hash format: {}",
+            hash
+        ));
+    }
+
+    log_debug!(MODULE, "Expected SHA256: {}", hash);
+    Ok(hash)
+}
+
+/// Digests fed with the downloaded data as it is received
+struct Digests {
+    sha256: Sha256,
+    /// Signature check, if a signature was fetched for the image
+    signature: Option<PendingSignature>,
+}
+
+impl Digests {
+    fn new(signature: Option<PendingSignature>) -> Self {
+        Self {
+            sha256: Sha256::new(),
+            signature,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        if let Some(signature) = &mut self.signature {
+            signature.update(data);
+        }
+    }
+
+    /// Discard the data hashed so far
+    fn reset(&mut self) {
+        Digest::reset(&mut self.sha256);
+        if let Some(signature) = &mut self.signature {
+            signature.reset();
+        }
+    }
+
+    /// Returns the hex SHA256 and the signature status, if one was checked
+    fn finish(self) -> (String, Option<SignatureStatus>) {
+        let hash = format!("{:x}", self.sha256.finalize());
+        log_debug!(MODULE, "Calculated SHA256: {}", hash);
+        (hash, self.signature.map(PendingSignature::finish))
+    }
+}
+
+/// Feed the first `len` bytes of a file into `digests`
+fn hash_file_prefix(
+    path: &Path,
+    len: u64,
+    digests: &mut Digests,
+    state: &Arc<DownloadState>,
+) -> Result<(), String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open file for SHA: {}", e))?;
+    let mut buffer = vec![0u8; config::logging::SHA_BUFFER_SIZE];
+    let mut tracker =
+        ProgressTracker::new("SHA256", MODULE, len, config::logging::SHA_LOG_INTERVAL_MB);
+    let mut remaining = len;
+
+    while remaining > 0 {
+        // Check for cancellation
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            log_info!(MODULE, "SHA256 calculation cancelled by user");
+            return Err("SHA256 verification cancelled".to_string());
+        }
+
+        let to_read = std::cmp::min(buffer.len() as u64, remaining) as usize;
+        let bytes_read = file
+            .read(&mut buffer[..to_read])
+            .map_err(|e| format!("Failed to read file for SHA: {}", e))?;
+        if bytes_read == 0 {
+            return Err(format!(
+                "File ended {} bytes early while hashing: {}",
+                remaining,
+                path.display()
+            ));
+        }
+        digests.update(&buffer[..bytes_read]);
+        remaining -= bytes_read as u64;
+        tracker.update(bytes_read as u64);
+    }
+
+    tracker.finish();
+    Ok(())
+}
+
+/// Feed a whole file into `digests`
+///
+/// Only needed when the data did not arrive in order (segmented downloads);
+/// single-stream downloads are hashed while they are received.
+fn hash_file(path: &Path, digests: &mut Digests, state: &Arc<DownloadState>) -> Result<(), String> {
+    log_debug!(MODULE, "Calculating SHA256 of: {}", path.display());
+    let len = path
+        .metadata()
+        .map_err(|e| format!("Failed to open file for SHA: {}", e))?
+        .len();
+
+    hash_file_prefix(path, len, digests, state)
+}
+
+/// Compare two byte strings in constant time
+///
+/// The comparison always inspects every byte, so its duration does not
+/// reveal how long the matching prefix is.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Verify a computed SHA256 against the expected value
+fn verify_sha256(expected: &str, actual: &str) -> Result<(), String> {
+    if constant_time_eq(
+        expected.to_lowercase().as_bytes(),
+        actual.to_lowercase().as_bytes(),
+    ) {
+        log_info!(MODULE, "SHA256 verification PASSED");
+        Ok(())
+    } else {
+        log_error!(
+            MODULE,
+            "SHA256 verification FAILED! Expected: {}, Got: {}",
+            expected,
+            actual
+        );
+        Err(format!(
+            "SHA256 mismatch: expected {}, got {}",
+            expected, actual
+        ))
+    }
+}
+
+/// Failure of one single-stream transfer attempt
+enum TransferError {
+    /// Network failure that may go away when the transfer is resumed
+    Transient(String),
+    /// Cancellation, local I/O errors and permanent HTTP errors
+    Fatal(String),
+    /// Paused: the connection was dropped and the kept data is resumed later
+    Paused,
+}
+
+impl TransferError {
+    /// Classify a failed request or response body
+    fn from_request(error: &reqwest::Error, message: String) -> Self {
+        if retry::is_retryable_error(error) {
+            Self::Transient(message)
+        } else {
+            Self::Fatal(message)
+        }
+    }
+}
+
+/// Read a response header as an owned string
+fn header_string(response: &Response, name: reqwest::header::HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+}
+
+/// Send the download request and open the temp file
+///
+/// If a partial download for the same URL exists, requests only the missing
+/// bytes with `Range` guarded by `If-Range`, and appends to the temp file.
+/// Falls back to a full download when the server ignores the range (200),
+/// answers with an unexpected range, or rejects it (416).
+async fn start_transfer(
+    client: &Client,
+    url: &str,
+    temp_path: &Path,
+) -> Result<(Response, File, PartialDownload), TransferError> {
+    let mut resume_from = resume::load(temp_path, url);
+
+    loop {
+        let mut request = client.get(url);
+        if let Some(ref partial) = resume_from {
+            log_info!(MODULE, "Resuming download from byte {}", partial.offset);
+            request = request.header(RANGE, format!("bytes={}-", partial.offset));
+            if let Some(validator) = partial.if_range_validator() {
+                request = request.header(IF_RANGE, validator);
+            }
+        }
+
+        let response = request.send().await.map_err(|e| {
+            log_error!(MODULE, "Failed to start download: {}", e);
+            TransferError::from_request(&e, format!("Failed to start download: {}", e))
+        })?;
+
+        // Log the final URL after redirect (shows which mirror is being used)
+        let final_url = response.url().to_string();
+        if final_url != url {
+            log_debug!(MODULE, "Redirected to mirror: {}", final_url);
+        }
+
+        let status = response.status();
+        if let Some(partial) = resume_from.take() {
+            if status == StatusCode::PARTIAL_CONTENT {
+                let range = response
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(resume::parse_content_range);
+
+                if let Some((start, _, total)) = range.filter(|r| r.0 == partial.offset) {
+                    let file = OpenOptions::new()
+                        .append(true)
+                        .open(temp_path)
+                        .map_err(|e| {
+                            TransferError::Fatal(format!("Failed to open temp file: {}", e))
+                        })?;
+                    let total_size = total.unwrap_or_else(|| {
+                        response
+                            .content_length()
+                            .map(|len| start + len)
+                            .unwrap_or(partial.total_size)
+                    });
+                    return Ok((
+                        response,
+                        file,
+                        PartialDownload {
+                            total_size,
+                            ..partial
+                        },
+                    ));
+                }
+
+                log_warn!(
+                    MODULE,
+                    "Server returned an unexpected range ({:?}), restarting download",
+                    range
+                );
+                resume::discard(temp_path);
+                continue;
+            }
+
+            if status == StatusCode::RANGE_NOT_SATISFIABLE {
+                log_warn!(MODULE, "Server rejected resume range, restarting download");
+                resume::discard(temp_path);
+                continue;
+            }
+
+            if status.is_success() {
+                log_info!(
+                    MODULE,
+                    "Server ignored range request or file changed, restarting download"
+                );
+            }
+        }
+
+        if !status.is_success() {
+            log_error!(MODULE, "Download failed with status: {}", status);
+            let message = format!("Download failed with status: {}", status);
+            return Err(if retry::is_retryable_status(status) {
+                TransferError::Transient(message)
+            } else {
+                TransferError::Fatal(message)
+            });
+        }
+
+        let file = File::create(temp_path)
+            .map_err(|e| TransferError::Fatal(format!("Failed to create temp file: {}", e)))?;
+        let partial = PartialDownload {
+            url: url.to_string(),
+            etag: header_string(&response, ETAG),
+            last_modified: header_string(&response, LAST_MODIFIED),
+            offset: 0,
+            total_size: response.content_length().unwrap_or(0),
+        };
+        return Ok((response, file, partial));
+    }
+}
+
+/// Flush the temp file and record the confirmed offset in the resume sidecar
+///
+/// Returns false if the download cannot be resumed later (no validator
+/// from the server) or the checkpoint could not be written.
+fn save_checkpoint(
+    file: &File,
+    temp_path: &Path,
+    partial: &mut PartialDownload,
+    offset: u64,
+) -> bool {
+    if partial.if_range_validator().is_none() {
+        return false;
+    }
+    if let Err(e) = file.sync_data() {
+        log_warn!(MODULE, "Failed to flush partial download: {}", e);
+        return false;
+    }
+
+    partial.offset = offset;
+    match resume::save(temp_path, partial) {
+        Ok(()) => true,
+        Err(e) => {
+            log_warn!(MODULE, "{}", e);
+            false
+        }
+    }
+}
+
+/// Download `url` into `temp_path`
+///
+/// With the torrent backend the image is fetched through its `.torrent`
+/// file. Otherwise a segmented multi-mirror transfer is used when the
+/// redirector exposes several mirrors. Either falls back to a single
+/// resumable stream on failure, and a partial single-stream download always
+/// takes precedence so it can resume. With `lan_peers`, peers sharing
+/// their cache are asked first.
+///
+/// The downloaded data is fed into `digests`. Returns whether it came from
+/// a LAN peer.
+async fn transfer_to_temp(
+    client: &Client,
+    url: &str,
+    temp_path: &Path,
+    backend: Backend,
+    lan_peers: bool,
+    digests: &mut Digests,
+    state: &Arc<DownloadState>,
+) -> Result<bool, String> {
+    if !resume::sidecar_path(temp_path).exists() {
+        if lan_peers {
+            if let Some(peer_url) = crate::lan::find_mirror(extract_filename(url)?).await {
+                log_info!(MODULE, "Downloading from LAN peer: {}", peer_url);
+                // The peer is not trusted: hash the file it sent, then let the
+                // usual SHA and signature checks decide
+                let peer = match crate::http::client(crate::http::Timeout::Lan) {
+                    Ok(lan_client) => {
+                        download_with_retry(
+                            &lan_client,
+                            &peer_url,
+                            temp_path,
+                            &mut Digests::new(None),
+                            state,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                };
+                match peer {
+                    Ok(()) => return hash_file(temp_path, digests, state).map(|()| true),
+                    Err(e) => {
+                        if state.is_cancelled.load(Ordering::SeqCst) {
+                            log_info!(MODULE, "Download cancelled by user");
+                            resume::discard(temp_path);
+                            return Err("Download cancelled".to_string());
+                        }
+                        log_warn!(
+                            MODULE,
+                            "LAN peer download failed, falling back to the internet: {}",
+                            e
+                        );
+                        resume::discard(temp_path);
+                    }
+                }
+            }
+        }
+
+        let mirrors = segmented::resolve_mirrors(client, url).await;
+        if backend == Backend::Torrent {
+            match torrent::download(client, url, &mirrors, temp_path, state).await {
+                // Pieces arrive out of order, so hash the assembled file
+                Ok(()) => return hash_file(temp_path, digests, state).map(|()| false),
+                Err(e) => {
+                    if state.is_cancelled.load(Ordering::SeqCst) {
+                        log_info!(MODULE, "Download cancelled by user");
+                        resume::discard(temp_path);
+                        return Err("Download cancelled".to_string());
+                    }
+                    log_warn!(
+                        MODULE,
+                        "Torrent download failed, falling back to HTTP: {}",
+                        e
+                    );
+                    resume::discard(temp_path);
+                }
+            }
+        } else if mirrors.len() >= 2 {
+            match segmented::download(client, &mirrors, temp_path, state).await {
+                // Segments arrive out of order, so hash the assembled file
+                Ok(()) => return hash_file(temp_path, digests, state).map(|()| false),
+                Err(e) => {
+                    if state.is_cancelled.load(Ordering::SeqCst) {
+                        log_info!(MODULE, "Download cancelled by user");
+                        resume::discard(temp_path);
+                        return Err("Download cancelled".to_string());
+                    }
+                    log_warn!(
+                        MODULE,
+                        "Segmented download failed, falling back to a single stream: {}",
+                        e
+                    );
+                    resume::discard(temp_path);
+                }
+            }
+        }
+    }
+
+    download_with_retry(client, url, temp_path, digests, state)
+        .await
+        .map(|()| false)
+}
+
+/// Single-stream download that survives dropped connections
+///
+/// Transient network failures are retried with backoff, resuming from the
+/// data kept on disk. A paused download closes its connection and resumes
+/// the same way once unpaused.
+async fn download_with_retry(
+    client: &Client,
+    url: &str,
+    temp_path: &Path,
+    digests: &mut Digests,
+    state: &Arc<DownloadState>,
+) -> Result<(), String> {
+    let policy = RetryPolicy::current();
+    let mut attempt = 1;
+    loop {
+        match download_single(client, url, temp_path, digests, state).await {
+            Ok(()) => return Ok(()),
+            Err(TransferError::Transient(e))
+                if attempt < policy.attempts && !state.is_cancelled.load(Ordering::SeqCst) =>
+            {
+                let delay = policy.backoff(attempt);
+                log_warn!(
+                    MODULE,
+                    "{}, resuming in {:.1}s ({}/{})",
+                    e,
+                    delay.as_secs_f64(),
+                    attempt,
+                    policy.attempts - 1
+                );
+                tokio::time::sleep(delay).await;
+                // The resumed transfer hashes the kept data again
+                digests.reset();
+                attempt += 1;
+            }
+            Err(TransferError::Paused) => {
+                state.wait_while_paused().await;
+                if state.is_cancelled.load(Ordering::SeqCst) {
+                    resume::discard(temp_path);
+                    return Err("Download cancelled".to_string());
+                }
+                log_info!(MODULE, "Download unpaused, resuming");
+                digests.reset();
+            }
+            Err(TransferError::Transient(e)) => {
+                return Err(format!("{} (gave up after {} attempts)", e, attempt))
+            }
+            Err(TransferError::Fatal(e)) => return Err(e),
+        }
+    }
+}
+
+/// Download `url` into `temp_path` over a single (resumable) stream
+///
+/// The data is hashed as it arrives; when resuming, the bytes already on
+/// disk are hashed first.
+async fn download_single(
+    client: &Client,
+    url: &str,
+    temp_path: &Path,
+    digests: &mut Digests,
+    state: &Arc<DownloadState>,
+) -> Result<(), TransferError> {
+    // Start download (resumes a previous partial download when possible)
+    log_info!(MODULE, "Starting download...");
+    let (response, mut temp_file, mut partial) = start_transfer(client, url, temp_path).await?;
+
+    let total_size = partial.total_size;
+    state.total_bytes.store(total_size, Ordering::SeqCst);
+    state
+        .downloaded_bytes
+        .store(partial.offset, Ordering::SeqCst);
+
+    log_info!(
+        MODULE,
+        "Download size: {} bytes ({:.2} MB)",
+        total_size,
+        bytes_to_mb(total_size)
+    );
+
+    if partial.offset > 0 {
+        if let Err(e) = hash_file_prefix(temp_path, partial.offset, digests, state) {
+            drop(temp_file);
+            resume::discard(temp_path);
+            return Err(TransferError::Fatal(e));
+        }
+    }
+
+    // Download with progress tracking
+    let mut stream = response.bytes_stream();
+    let mut downloaded: u64 = partial.offset;
+    let mut since_checkpoint: u64 = 0;
+    let mut tracker = ProgressTracker::new(
+        "Download",
+        MODULE,
+        total_size.saturating_sub(partial.offset),
+        config::logging::DOWNLOAD_LOG_INTERVAL_MB,
+    );
+
+    while let Some(chunk) = stream.next().await {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            log_info!(MODULE, "Download cancelled by user");
+            drop(temp_file);
+            resume::discard(temp_path);
+            return Err(TransferError::Fatal("Download cancelled".to_string()));
+        }
+
+        let chunk = match chunk {
+            Ok(c) => c,
+            Err(e) => {
+                if save_checkpoint(&temp_file, temp_path, &mut partial, downloaded) {
+                    log_warn!(
+                        MODULE,
+                        "Download interrupted at {} bytes, keeping partial file for resume",
+                        downloaded
+                    );
+                } else {
+                    drop(temp_file);
+                    resume::discard(temp_path);
+                }
+                return Err(TransferError::from_request(
+                    &e,
+                    format!("Download error: {}", e),
+                ));
+            }
+        };
+        if let Err(e) = temp_file.write_all(&chunk) {
+            drop(temp_file);
+            resume::discard(temp_path);
+            return Err(TransferError::Fatal(format!(
+                "Failed to write chunk: {}",
+                e
+            )));
+        }
+        digests.update(&chunk);
+
+        downloaded += chunk.len() as u64;
+        since_checkpoint += chunk.len() as u64;
+        state.downloaded_bytes.store(downloaded, Ordering::SeqCst);
+        tracker.update(chunk.len() as u64);
+
+        if since_checkpoint >= config::download::RESUME_CHECKPOINT_BYTES {
+            save_checkpoint(&temp_file, temp_path, &mut partial, downloaded);
+            since_checkpoint = 0;
+        }
+
+        throttle::consume(chunk.len() as u64).await;
+        if state.is_paused.load(Ordering::SeqCst) {
+            // Without a resume checkpoint the connection has to stay open
+            if save_checkpoint(&temp_file, temp_path, &mut partial, downloaded) {
+                log_info!(MODULE, "Download paused at {} bytes", downloaded);
+                return Err(TransferError::Paused);
+            }
+            state.wait_while_paused().await;
+        }
+    }
+
+    drop(temp_file);
+    // Transfer complete: the temp file no longer needs resume state
+    resume::remove(temp_path);
+    tracker.finish();
+    Ok(())
+}
+
+/// URL of the detached OpenPGP signature published next to an image
+fn signature_url(url: &str) -> String {
+    format!("{}.asc", url)
+}
+
+/// Store and log the signature check result
+///
+/// Only a bad signature is fatal, unless the catalog source `required` a
+/// valid one: unsigned images and images signed by a key outside the
+/// bundled keyring are otherwise still accepted, with a warning.
+async fn record_signature_status(
+    state: &DownloadState,
+    status: SignatureStatus,
+    required: bool,
+) -> Result<(), String> {
+    *state.signature_status.lock().await = Some(status);
+    match status {
+        SignatureStatus::Valid => {
+            log_info!(MODULE, "Signature verification: {}", status.as_str());
+            Ok(())
+        }
+        SignatureStatus::UnknownKey | SignatureStatus::Missing if required => {
+            log_error!(MODULE, "Signature verification: {}", status.as_str());
+            Err(format!(
+                "Signature verification failed: {}, but the catalog source requires a valid signature",
+                status.as_str()
+            ))
+        }
+        SignatureStatus::UnknownKey | SignatureStatus::Missing => {
+            log_warn!(MODULE, "Signature verification: {}", status.as_str());
+            Ok(())
+        }
+        SignatureStatus::BadSignature => {
+            log_error!(MODULE, "Signature verification: {}", status.as_str());
+            Err("Signature verification failed: the image is not authentic".to_string())
+        }
+    }
+}
+
+/// Return a cached image if it is still intact
+///
+/// The image is hashed again when its revalidation is due; a corrupted one
+/// is removed so it gets downloaded again.
+async fn cached_image(
+    filename: &str,
+    state: &Arc<DownloadState>,
+) -> Result<Option<PathBuf>, String> {
+    let cached_path = match crate::cache::get_cached_image(filename) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    if crate::cache::revalidation_due(filename) {
+        state.is_verifying_sha.store(true, Ordering::SeqCst);
+        let name = filename.to_string();
+        let task_state = Arc::clone(state);
+        let result = tokio::task::spawn_blocking(move || {
+            crate::cache::verify_cached_image(&name, &task_state.is_cancelled)
+        })
+        .await
+        .map_err(|e| format!("Cache verification failed: {}", e));
+        state.is_verifying_sha.store(false, Ordering::SeqCst);
+        if !result?? {
+            return Ok(None);
+        }
+    }
+
+    log_info!(MODULE, "Using cached image: {}", cached_path.display());
+    *state.output_path.lock().await = Some(cached_path.clone());
+    Ok(Some(cached_path))
+}
+
+/// Return the cached image for a download URL, under either of its names
+async fn cached_download(url: &str, state: &Arc<DownloadState>) -> Result<Option<PathBuf>, String> {
+    for filename in cached_filenames(url)? {
+        if let Some(cached_path) = cached_image(filename, state).await? {
+            return Ok(Some(cached_path));
+        }
+    }
+    Ok(None)
+}
+
+/// Rebuild an image from an older cached build, checked against the
+/// published SHA256
+///
+/// Returns `None` when the full image must be downloaded instead.
+async fn delta_download(
+    client: &Client,
+    url: &str,
+    sha_url: &str,
+    seed: &Path,
+    output_path: &Path,
+    options: &DownloadOptions,
+    state: &Arc<DownloadState>,
+) -> Result<Option<PathBuf>, String> {
+    let result = match fetch_expected_sha(client, sha_url).await {
+        Ok(expected) => delta::download(
+            client,
+            url,
+            &expected,
+            seed,
+            output_path,
+            &options.trust,
+            state,
+        )
+        .await
+        .map(|()| expected),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(sha) => {
+            index_image(
+                output_path,
+                Some(url),
+                Some(sha.clone()),
+                None,
+                Verification::Verified,
+            );
+            *state.computed_sha.lock().await = Some(sha);
+            *state.output_path.lock().await = Some(output_path.to_path_buf());
+            Ok(Some(output_path.to_path_buf()))
+        }
+        Err(e) => {
+            if state.is_cancelled.load(Ordering::SeqCst) {
+                return Err("Download cancelled".to_string());
+            }
+            log_warn!(
+                MODULE,
+                "Delta download not possible, downloading the full image: {}",
+                e
+            );
+            *state.signature_status.lock().await = None;
+            state.is_verifying_sha.store(false, Ordering::SeqCst);
+            Ok(None)
+        }
+    }
+}
+
+/// Add a finished download to the cache index
+fn index_image(
+    output_path: &Path,
+    source_url: Option<&str>,
+    sha256: Option<String>,
+    compressed_sha256: Option<String>,
+    verification: Verification,
+) {
+    let filename = match output_path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => return,
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let checked_at = sha256.is_some().then_some(now);
+    crate::cache::record_image(
+        filename,
+        IndexEntry {
+            sha256,
+            compressed_sha256,
+            source_url: source_url.map(str::to_string),
+            downloaded_at: now,
+            size: std::fs::metadata(output_path).map(|m| m.len()).unwrap_or(0),
+            verification,
+            checked_at,
+            flash_failures: 0,
+            use_count: 0,
+            pinned: false,
+            tags: Vec::new(),
+            label: None,
+        },
+    );
+}
+
+/// Download and decompress an Armbian image
+/// If sha_url is provided, verifies the downloaded compressed file before decompression
+pub async fn download_image(
+    url: &str,
+    sha_url: Option<&str>,
+    output_dir: &PathBuf,
+    options: DownloadOptions,
+    state: Arc<DownloadState>,
+) -> Result<PathBuf, String> {
+    state.reset();
+    // Clear any stale temp_path from previous failed downloads
+    *state.temp_path.lock().await = None;
+    *state.computed_sha.lock().await = None;
+    *state.signature_status.lock().await = None;
+
+    if options.trust.require_sha && sha_url.is_none() {
+        return Err(
+            "The catalog source requires SHA256 verification, but the image has no checksum"
+                .to_string(),
+        );
+    }
+
+    let filename = extract_filename(url)?;
+    let image_filename = cached_filename(url)?;
+    let decompress = filename.ends_with(".xz") && !options.keep_compressed;
+
+    // Determine output filename (remove .xz if the image is decompressed)
+    let output_filename = if decompress { image_filename } else { filename };
+    let output_path = output_dir.join(output_filename);
+
+    log_info!(MODULE, "Download requested: {} ({:?})", url, options);
+    log_debug!(MODULE, "Output path: {}", output_path.display());
+
+    // Wait for any queued job writing the same image, so a half-written
+    // image is never taken for a cached one
+    let mut claim = queue::claim_image(image_filename, &state).await?;
+
+    // Check if image is already in cache (also updates mtime for LRU)
+    if let Some(cached_path) = cached_download(url, &state).await? {
+        return Ok(cached_path);
+    }
+
+    if let Some(window) = options.window {
+        // Do not hold up other downloads of this image while waiting
+        drop(claim);
+        schedule::wait_for_window(window, &state).await?;
+        claim = queue::claim_image(image_filename, &state).await?;
+        if let Some(cached_path) = cached_download(url, &state).await? {
+            return Ok(cached_path);
+        }
+    }
+    let _claim = claim;
+
+    if let Some(reserve) = options.reserve {
+        crate::cache::reserve_space(reserve.size, reserve.max_size)?;
+    }
+
+    // Create output directory if needed
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create output directory: {}", e))?;
+
+    let client = crate::http::client(crate::http::Timeout::Transfer)?;
+
+    // Rebuild from an older cached build of the same image when possible.
+    // The rebuilt file must match the published SHA256, so this only
+    // applies to images kept as published (compressed ones with
+    // keep_compressed) with a checksum, from a seed stored the same way.
+    if let (true, false, Some(sha_url)) = (options.delta, decompress, sha_url) {
+        if let Some(seed) = crate::cache::find_previous_build(output_filename) {
+            if let Some(path) =
+                delta_download(&client, url, sha_url, &seed, &output_path, &options, &state).await?
+            {
+                return Ok(path);
+            }
+        } else {
+            log_debug!(MODULE, "No previous build cached, delta download skipped");
+        }
+    }
+
+    // Temp file for compressed data, kept on network errors so it can be resumed
+    let temp_path = output_dir.join(format!("{}{}", filename, config::images::DOWNLOAD_SUFFIX));
+
+    // Fetch the expected SHA256 while the image downloads
+    let expected_sha = sha_url.map(|sha_url| {
+        let client = client.clone();
+        let sha_url = sha_url.to_string();
+        tokio::spawn(async move { fetch_expected_sha(&client, &sha_url).await })
+    });
+
+    // The signature is small: fetch it first so it is checked while downloading
+    let signing_key = options.trust.signing_key.clone();
+    let (signature, fetch_status) =
+        match signature::fetch(&client, &signature_url(url), signing_key).await {
+            Ok(pending) => (Some(pending), None),
+            Err(status) => (None, Some(status)),
+        };
+    let mut digests = Digests::new(signature);
+
+    // Peer data can only be trusted once checked against the published SHA
+    let lan_peers = options.lan_peers && sha_url.is_some();
+    let from_peer = match transfer_to_temp(
+        &client,
+        url,
+        &temp_path,
+        options.backend,
+        lan_peers,
+        &mut digests,
+        &state,
+    )
+    .await
+    {
+        Ok(from_peer) => from_peer,
+        Err(e) => {
+            if let Some(task) = expected_sha {
+                task.abort();
+            }
+            return Err(e);
+        }
+    };
+    let (actual_sha, checked) = digests.finish();
+    *state.computed_sha.lock().await = Some(actual_sha.clone());
+
+    let status = checked.or(fetch_status).unwrap_or(SignatureStatus::Missing);
+    if let Err(e) = record_signature_status(&state, status, options.trust.require_signature).await {
+        if let Some(task) = expected_sha {
+            task.abort();
+        }
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    // Verify SHA256 if URL provided
+    let verification = if expected_sha.is_some() {
+        Verification::Verified
+    } else {
+        Verification::Unverified
+    };
+    if let Some(task) = expected_sha {
+        state.is_verifying_sha.store(true, Ordering::SeqCst);
+        log_info!(MODULE, "Verifying SHA256...");
+        let result = match task.await {
+            Ok(Ok(expected)) => verify_sha256(&expected, &actual_sha),
+            Ok(Err(e)) => Err(e),
+            Err(e) => Err(format!("[SHA_UNAVAILABLE] SHA fetch task failed: {}", e)),
+        };
+        match result {
+            Ok(()) => {
+                log_info!(MODULE, "SHA256 verification successful");
+            }
+            Err(e) => {
+                log_error!(MODULE, "SHA256 verification failed: {}", e);
+                state.is_verifying_sha.store(false, Ordering::SeqCst);
+
+                // Check if it was a cancellation
+                if state.is_cancelled.load(Ordering::SeqCst) {
+                    let _ = std::fs::remove_file(&temp_path);
+                    return Err("Download cancelled".to_string());
+                }
+
+                // Data from a LAN peer is never used unchecked
+                if e.contains("[SHA_UNAVAILABLE]") && from_peer {
+                    let _ = std::fs::remove_file(&temp_path);
+                    return Err(format!(
+                        "SHA256 verification failed: {}, and the image came from a LAN peer",
+                        e.trim_start_matches("[SHA_UNAVAILABLE] ")
+                    ));
+                }
+
+                // If SHA is unavailable (fetch failed), keep the file for user
+                // decision, unless the catalog source requires the check
+                if e.contains("[SHA_UNAVAILABLE]") && options.trust.require_sha {
+                    let _ = std::fs::remove_file(&temp_path);
+                    return Err(format!(
+                        "SHA256 verification failed: {}, and the catalog source requires it",
+                        e.trim_start_matches("[SHA_UNAVAILABLE] ")
+                    ));
+                }
+                if e.contains("[SHA_UNAVAILABLE]") {
+                    log_info!(
+                        MODULE,
+                        "SHA unavailable, keeping temp file for user decision: {}",
+                        temp_path.display()
+                    );
+                    *state.temp_path.lock().await = Some(temp_path.clone());
+                    return Err(format!("SHA256 verification failed: {}", e));
+                }
+
+                // SHA mismatch (hash different) → delete file (corrupted image)
+                let _ = std::fs::remove_file(&temp_path);
+                return Err(format!("SHA256 verification failed: {}", e));
+            }
+        }
+        state.is_verifying_sha.store(false, Ordering::SeqCst);
+    } else {
+        log_warn!(MODULE, "No SHA URL provided, skipping verification");
+    }
+
+    // Decompress if needed
+    if decompress {
+        state.is_decompressing.store(true, Ordering::SeqCst);
+        log_info!(
+            MODULE,
+            "Starting decompression with Rust lzma-rust2 (multi-threaded)..."
+        );
+
+        // Use Rust lzma-rust2 library (multi-threaded) on all platforms
+        let image_sha = match decompress_with_rust_xz(&temp_path, &output_path, &state) {
+            Ok(sha) => sha,
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp_path);
+                let _ = std::fs::remove_file(&output_path);
+                return Err(e);
+            }
+        };
+        log_info!(MODULE, "Decompression complete");
+
+        // Clean up compressed temp file
+        let _ = std::fs::remove_file(&temp_path);
+        index_image(
+            &output_path,
+            Some(url),
+            Some(image_sha),
+            Some(actual_sha),
+            verification,
+        );
+    } else {
+        // No decompression needed, just rename
+        if let Err(e) = std::fs::rename(&temp_path, &output_path) {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(format!("Failed to move file: {}", e));
+        }
+        index_image(
+            &output_path,
+            Some(url),
+            Some(actual_sha.clone()),
+            Some(actual_sha),
+            verification,
+        );
+    }
+
+    log_info!(MODULE, "Image ready: {}", output_path.display());
+    *state.output_path.lock().await = Some(output_path.clone());
+    Ok(output_path)
+}
+
+/// Continue download without SHA verification (uses already downloaded file)
+/// Called when user confirms to proceed after SHA unavailable error
+pub async fn continue_without_sha(
+    state: Arc<DownloadState>,
+    output_dir: &Path,
+    keep_compressed: bool,
+) -> Result<PathBuf, String> {
+    let temp_path = state
+        .temp_path
+        .lock()
+        .await
+        .take()
+        .ok_or("No pending download to continue")?;
+
+    // Defense in depth: verify temp_path is within cache directory
+    if let Err(e) = validate_cache_path(&temp_path) {
+        log_error!(
+            MODULE,
+            "Security: temp_path {} is outside cache directory: {}",
+            temp_path.display(),
+            e
+        );
+        return Err("Invalid temp file location".to_string());
+    }
+
+    log_info!(
+        MODULE,
+        "Continuing without SHA verification: {}",
+        temp_path.display()
+    );
+    // Hash computed during the download, kept so it can be checked by hand
+    let computed_sha = state.computed_sha.lock().await.clone();
+    match computed_sha.as_deref() {
+        Some(hash) => log_info!(MODULE, "Unverified SHA256 of download: {}", hash),
+        None => log_warn!(MODULE, "No SHA256 was computed for this download"),
+    }
+
+    let filename = temp_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Invalid temp path")?;
+
+    // temp_path is "filename.xz.downloading" or "filename.img.downloading"
+    // Remove .downloading to get the original filename
+    let original_filename = filename.trim_end_matches(".downloading");
+    let decompress = original_filename.ends_with(".xz") && !keep_compressed;
+    // Output without .xz extension, unless the image is kept compressed
+    let output_filename = if decompress {
+        original_filename.trim_end_matches(".xz")
+    } else {
+        original_filename
+    };
+    let output_path = output_dir.join(output_filename);
+
+    log_info!(MODULE, "Output path: {}", output_path.display());
+
+    // Decompress if needed
+    if decompress {
+        state.is_decompressing.store(true, Ordering::SeqCst);
+        log_info!(
+            MODULE,
+            "Starting decompression with Rust lzma-rust2 (multi-threaded)..."
+        );
+
+        let image_sha = match decompress_with_rust_xz(&temp_path, &output_path, &state) {
+            Ok(sha) => sha,
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp_path);
+                let _ = std::fs::remove_file(&output_path);
+                return Err(e);
+            }
+        };
+
+        state.is_decompressing.store(false, Ordering::SeqCst);
+        log_info!(MODULE, "Decompression complete");
+
+        // Clean up compressed temp file
+        let _ = std::fs::remove_file(&temp_path);
+        index_image(
+            &output_path,
+            None,
+            Some(image_sha),
+            computed_sha,
+            Verification::Unverified,
+        );
+    } else {
+        // No decompression needed, just rename
+        if let Err(e) = std::fs::rename(&temp_path, &output_path) {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(format!("Failed to move file: {}", e));
+        }
+        index_image(
+            &output_path,
+            None,
+            computed_sha.clone(),
+            computed_sha,
+            Verification::Unverified,
+        );
+    }
+
+    log_info!(MODULE, "Image ready: {}", output_path.display());
+    *state.output_path.lock().await = Some(output_path.clone());
+    Ok(output_path)
+}
+
+/// Clean up temp file from a failed download
+/// Called when user cancels after SHA unavailable error
+pub async fn cleanup_pending_download(state: Arc<DownloadState>) {
+    if let Some(temp_path) = state.temp_path.lock().await.take() {
+        log_info!(
+            MODULE,
+            "Cleaning up pending download: {}",
+            temp_path.display()
+        );
+        resume::discard(&temp_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dropped_connection_resumes() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/image.img.xz", listener.local_addr().unwrap());
+
+        let served = data.clone();
+        let server = tokio::spawn(async move {
+            let mut ranges = Vec::new();
+            for first in [true, false] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let start = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("range: bytes="))
+                    .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok())
+                    .unwrap_or(0);
+                ranges.push(start);
+
+                let header = if start == 0 {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nETag: \"v1\"\r\n\r\n",
+                        served.len()
+                    )
+                } else {
+                    format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nETag: \"v1\"\r\n\r\n",
+                        served.len() - start,
+                        start,
+                        served.len() - 1,
+                        served.len()
+                    )
+                };
+                socket.write_all(header.as_bytes()).await.unwrap();
+                // The first response is cut off halfway through the body
+                let end = if first {
+                    served.len() / 2
+                } else {
+                    served.len()
+                };
+                socket.write_all(&served[start..end]).await.unwrap();
+                socket.flush().await.unwrap();
+            }
+            ranges
+        });
+
+        let dir = std::env::temp_dir().join(format!("retry-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let temp_path = dir.join("image.img.xz.downloading");
+        let client = Client::builder().no_proxy().build().unwrap();
+        let state = Arc::new(DownloadState::new());
+        let mut digests = Digests::new(None);
+
+        download_with_retry(&client, &url, &temp_path, &mut digests, &state)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&temp_path).unwrap(), data);
+        assert_eq!(digests.finish().0, hex::encode(Sha256::digest(&data)));
+        let ranges = server.await.unwrap();
+        assert_eq!(ranges[0], 0);
+        assert!(ranges[1] > 0, "second request did not resume");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cached_filenames() {
+        let url = "https://dl.armbian.com/rock-5b/Armbian_25.02.0_Rock-5b.img.xz?x=1";
+        assert_eq!(
+            cached_filenames(url).unwrap(),
+            vec![
+                "Armbian_25.02.0_Rock-5b.img",
+                "Armbian_25.02.0_Rock-5b.img.xz"
+            ]
+        );
+        assert_eq!(
+            cached_filenames("https://example.com/a.img").unwrap(),
+            vec!["a.img"]
+        );
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn test_verify_sha256_ignores_case() {
+        let hash = "a".repeat(64);
+        assert!(verify_sha256(&hash, &hash.to_uppercase()).is_ok());
+        assert!(verify_sha256(&hash, &"b".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_hash_file_prefix_matches_incremental_hash() {
+        let path = std::env::temp_dir().join(format!("armbian-sha-test-{}", std::process::id()));
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let state = Arc::new(DownloadState::new());
+
+        // Prefix from disk + remaining bytes in memory, as when resuming
+        let mut digests = Digests::new(None);
+        hash_file_prefix(&path, 20_000, &mut digests, &state).unwrap();
+        digests.update(&data[20_000..]);
+        let (resumed, _) = digests.finish();
+
+        assert_eq!(resumed, format!("{:x}", Sha256::digest(&data)));
+        let mut whole = Digests::new(None);
+        hash_file(&path, &mut whole, &state).unwrap();
+        assert_eq!(whole.finish().0, resumed);
+        assert!(hash_file_prefix(&path, 60_000, &mut Digests::new(None), &state).is_err());
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,394 @@
+//! Streaming download → decompress → flash pipeline
+//!
+//! Flashes an image straight from the network: the HTTP body is hashed and
+//! fed to a decompressor whose output is written directly to the block
+//! device. Neither the compressed nor the decompressed image touches the
+//! disk, so the free space needed is independent of the image size.
+//!
+//! The published SHA-256 is fetched before anything is written: when it is
+//! unavailable the flash is refused with `[SHA_UNAVAILABLE]` unless the
+//! user chose to continue without it, as for file downloads. The SHA-256
+//! and OpenPGP signature of the compressed stream are only checked once the
+//! last byte has arrived; a mismatch or bad signature at that point fails
+//! the whole flash.
+
+use std::io::{self, Read};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use reqwest::{Client, Response};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use crate::config;
+use crate::decompress::streaming_decoder;
+use crate::flash::{flash_source, FlashState, ImageSource};
+use crate::http::retry;
+use crate::utils::{bytes_to_mb, ProgressTracker};
+use crate::{log_debug, log_error, log_info, log_warn};
+
+use super::signature;
+use super::{
+    extract_filename, fetch_expected_sha, record_signature_status, signature_url, verify_sha256,
+    Digests, DownloadState, Trust,
+};
+
+const MODULE: &str = "download::pipeline";
+
+/// Data handed from the download task to the decompressor
+type Chunk = Result<Vec<u8>, String>;
+
+/// Blocking reader over the chunks received from the download task
+struct ChannelReader {
+    rx: Receiver<Chunk>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    fn new(rx: Receiver<Chunk>) -> Self {
+        Self {
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.chunk.len() {
+            match self.rx.blocking_recv() {
+                Some(Ok(chunk)) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Some(Err(e)) => return Err(io::Error::other(e)),
+                // Sender dropped: the download finished
+                None => return Ok(0),
+            }
+        }
+
+        let n = std::cmp::min(buf.len(), self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Reader that estimates the decompressed image size for flash progress
+///
+/// The decompressed size of a stream is unknown until it ends, so it is
+/// extrapolated from the compression ratio observed so far.
+struct EstimatedSize<R> {
+    inner: R,
+    produced: u64,
+    download_state: Arc<DownloadState>,
+    flash_state: Arc<FlashState>,
+}
+
+impl<R: Read> Read for EstimatedSize<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.produced += n as u64;
+
+        let estimate = if n == 0 {
+            self.produced
+        } else {
+            let downloaded = self.download_state.downloaded_bytes.load(Ordering::SeqCst);
+            let total = self.download_state.total_bytes.load(Ordering::SeqCst);
+            if downloaded == 0 || total == 0 {
+                return Ok(n);
+            }
+            let scaled = self.produced as u128 * total as u128 / downloaded as u128;
+            std::cmp::max(scaled as u64, self.produced)
+        };
+        self.flash_state
+            .total_bytes
+            .store(estimate, Ordering::SeqCst);
+        Ok(n)
+    }
+}
+
+/// Download an image and flash it to a device in a single pass
+///
+/// Download progress is reported through `download_state` and flash
+/// progress through `flash_state`, as for the separate operations.
+/// `allow_unverified` is the user's choice to flash an image whose SHA is
+/// unavailable; it never overrides a source requiring SHA verification.
+#[allow(clippy::too_many_arguments)]
+pub async fn download_and_flash(
+    url: &str,
+    sha_url: Option<&str>,
+    device_path: &str,
+    download_state: Arc<DownloadState>,
+    flash_state: Arc<FlashState>,
+    verify: bool,
+    allow_unverified: bool,
+    trust: Trust,
+) -> Result<(), String> {
+    download_state.reset();
+    *download_state.temp_path.lock().await = None;
+    *download_state.computed_sha.lock().await = None;
+    *download_state.signature_status.lock().await = None;
+
+    if trust.require_sha && sha_url.is_none() {
+        return Err(
+            "The catalog source requires SHA256 verification, but the image has no checksum"
+                .to_string(),
+        );
+    }
+
+    let filename = extract_filename(url)?.to_string();
+    log_info!(
+        MODULE,
+        "Streaming flash requested: {} -> {}",
+        url,
+        device_path
+    );
+
+    let client = crate::http::client(crate::http::Timeout::Transfer)?;
+
+    // Known before the device is touched, so the user can still decide
+    let expected_sha = match sha_url {
+        Some(sha_url) => match fetch_expected_sha(&client, sha_url).await {
+            Ok(expected) => Some(expected),
+            Err(e) if trust.require_sha => {
+                return Err(format!(
+                    "{}, and the catalog source requires SHA256 verification",
+                    e.trim_start_matches("[SHA_UNAVAILABLE] ")
+                ));
+            }
+            Err(e) if allow_unverified => {
+                log_warn!(MODULE, "Continuing without SHA verification: {}", e);
+                None
+            }
+            Err(e) => return Err(e),
+        },
+        None if allow_unverified => {
+            log_warn!(
+                MODULE,
+                "No SHA URL provided, continuing without verification"
+            );
+            None
+        }
+        None => {
+            return Err("[SHA_UNAVAILABLE] The image has no published checksum".to_string());
+        }
+    };
+
+    let response = retry::send("Download", || client.get(url))
+        .await
+        .map_err(|e| {
+            log_error!(MODULE, "Failed to start download: {}", e);
+            format!("Failed to start download: {}", e)
+        })?;
+
+    let final_url = response.url().to_string();
+    if final_url != url {
+        log_debug!(MODULE, "Redirected to mirror: {}", final_url);
+    }
+    if !response.status().is_success() {
+        log_error!(MODULE, "Download failed with status: {}", response.status());
+        return Err(format!(
+            "Download failed with status: {}",
+            response.status()
+        ));
+    }
+
+    let total_size = response.content_length().unwrap_or(0);
+    download_state
+        .total_bytes
+        .store(total_size, Ordering::SeqCst);
+    log_info!(
+        MODULE,
+        "Download size: {} bytes ({:.2} MB)",
+        total_size,
+        bytes_to_mb(total_size)
+    );
+
+    let signing_key = trust.signing_key.clone();
+    let (signature, fetch_status) =
+        match signature::fetch(&client, &signature_url(url), signing_key).await {
+            Ok(pending) => (Some(pending), None),
+            Err(status) => (None, Some(status)),
+        };
+
+    let (tx, rx) = mpsc::channel(config::download::STREAM_BUFFER_CHUNKS);
+    let feed = tokio::spawn(feed_stream(
+        response,
+        tx,
+        Digests::new(signature),
+        download_state.clone(),
+    ));
+
+    let decoder = streaming_decoder(ChannelReader::new(rx), &filename)?;
+    let source = ImageSource::stream(
+        EstimatedSize {
+            inner: decoder,
+            produced: 0,
+            download_state: download_state.clone(),
+            flash_state: flash_state.clone(),
+        },
+        0,
+    );
+
+    // Run the flash on a blocking thread: it blocks on the channel while
+    // the download task fills it from the runtime
+    let handle = tokio::runtime::Handle::current();
+    let device = device_path.to_string();
+    let flash_task_state = flash_state.clone();
+    let flash_result = tokio::task::spawn_blocking(move || {
+        handle.block_on(flash_source(source, &device, flash_task_state, verify))
+    })
+    .await
+    .map_err(|e| format!("Flash task failed: {}", e))?;
+
+    if flash_result.is_err() {
+        // Stop downloading data nobody will write
+        download_state.is_cancelled.store(true, Ordering::SeqCst);
+    }
+    let digests = feed
+        .await
+        .map_err(|e| format!("Download task failed: {}", e))?;
+    flash_result?;
+    let (actual_sha, checked) = digests?.finish();
+    *download_state.computed_sha.lock().await = Some(actual_sha.clone());
+
+    let status = checked
+        .or(fetch_status)
+        .unwrap_or(signature::SignatureStatus::Missing);
+    if let Err(e) = record_signature_status(&download_state, status, trust.require_signature).await
+    {
+        return Err(format!(
+            "Flash failed: {}, the written image must not be used",
+            e
+        ));
+    }
+
+    if let Some(expected) = expected_sha {
+        if let Err(e) = verify_sha256(&expected, &actual_sha) {
+            return Err(format!(
+                "Flash failed: {}, the written image is corrupted",
+                e
+            ));
+        }
+    }
+
+    log_info!(MODULE, "Streaming flash complete");
+    Ok(())
+}
+
+/// Stream the response body into the channel, hashing it on the way
+///
+/// Returns the digests of the whole body. If the decompressor stops
+/// reading before the body ends, the rest is still downloaded and hashed
+/// so the result covers the complete file.
+async fn feed_stream(
+    response: Response,
+    tx: Sender<Chunk>,
+    mut digests: Digests,
+    state: Arc<DownloadState>,
+) -> Result<Digests, String> {
+    let total_size = state.total_bytes.load(Ordering::SeqCst);
+    let mut stream = response.bytes_stream();
+    let mut downloaded: u64 = 0;
+    let mut tx = Some(tx);
+    let mut tracker = ProgressTracker::new(
+        "Download",
+        MODULE,
+        total_size,
+        config::logging::DOWNLOAD_LOG_INTERVAL_MB,
+    );
+
+    while let Some(chunk) = stream.next().await {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            log_info!(MODULE, "Download cancelled");
+            return fail(tx, "Download cancelled".to_string()).await;
+        }
+
+        let chunk = match chunk {
+            Ok(c) => c,
+            Err(e) => {
+                log_error!(MODULE, "Download error: {}", e);
+                return fail(tx, format!("Download error: {}", e)).await;
+            }
+        };
+
+        digests.update(&chunk);
+        downloaded += chunk.len() as u64;
+        state.downloaded_bytes.store(downloaded, Ordering::SeqCst);
+        tracker.update(chunk.len() as u64);
+        super::throttle::consume(chunk.len() as u64).await;
+
+        if let Some(sender) = &tx {
+            if sender.send(Ok(chunk.to_vec())).await.is_err() {
+                log_debug!(MODULE, "Image stream ended early, hashing remaining data");
+                tx = None;
+            }
+        }
+    }
+
+    tracker.finish();
+    Ok(digests)
+}
+
+/// Report a download failure to the flash side as a read error
+async fn fail(tx: Option<Sender<Chunk>>, msg: String) -> Result<Digests, String> {
+    if let Some(tx) = tx {
+        let _ = tx.send(Err(msg.clone())).await;
+    }
+    Err(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_reader_spans_chunks() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.blocking_send(Ok(vec![1, 2, 3])).unwrap();
+        tx.blocking_send(Ok(vec![])).unwrap();
+        tx.blocking_send(Ok(vec![4, 5])).unwrap();
+        drop(tx);
+
+        let mut data = Vec::new();
+        ChannelReader::new(rx).read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_channel_reader_propagates_errors() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.blocking_send(Ok(vec![1])).unwrap();
+        tx.blocking_send(Err("Download error: reset".to_string()))
+            .unwrap();
+
+        let mut data = Vec::new();
+        let err = ChannelReader::new(rx).read_to_end(&mut data).unwrap_err();
+        assert!(err.to_string().contains("reset"));
+    }
+
+    #[test]
+    fn test_estimated_size_tracks_ratio() {
+        let download_state = Arc::new(DownloadState::new());
+        let flash_state = Arc::new(FlashState::new());
+        download_state.total_bytes.store(100, Ordering::SeqCst);
+        download_state.downloaded_bytes.store(25, Ordering::SeqCst);
+
+        let mut reader = EstimatedSize {
+            inner: io::Cursor::new(vec![0u8; 50]),
+            produced: 0,
+            download_state,
+            flash_state: flash_state.clone(),
+        };
+        let mut buf = [0u8; 50];
+        assert_eq!(reader.read(&mut buf).unwrap(), 50);
+        assert_eq!(flash_state.total_bytes.load(Ordering::SeqCst), 200);
+
+        // At the end the estimate becomes the real size
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(flash_state.total_bytes.load(Ordering::SeqCst), 50);
+    }
+}
@@ -0,0 +1,538 @@
+//! Download queue
+//!
+//! Runs image downloads as background jobs, each with its own progress
+//! state, next to the interactive download and while flashing. Jobs are
+//! started in order, user-queued downloads before prefetches, up to the
+//! configured concurrency limit. Finished images land in the image cache.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OwnedMutexGuard;
+
+use super::{cleanup_pending_download, download_image, DownloadOptions, DownloadState};
+use crate::config;
+use crate::{log_info, log_warn};
+
+const MODULE: &str = "download::queue";
+
+/// How often a download waiting for the same image checks for cancellation
+const IN_FLIGHT_POLL: Duration = Duration::from_millis(200);
+
+pub type JobId = u64;
+
+/// Why a job was queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Queued explicitly by the user
+    Download,
+    /// Fetched ahead of time into the cache; runs after user downloads
+    Prefetch,
+}
+
+/// Lifecycle of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// Snapshot of a job for the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub url: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub total_bytes: u64,
+    pub downloaded_bytes: u64,
+    pub progress_percent: f64,
+    pub error: Option<String>,
+    pub output_path: Option<String>,
+}
+
+struct Job {
+    id: JobId,
+    url: String,
+    sha_url: Option<String>,
+    kind: JobKind,
+    options: DownloadOptions,
+    state: Arc<DownloadState>,
+    status: Mutex<JobStatus>,
+    /// Set once the download task has been spawned
+    started: AtomicBool,
+    error: Mutex<Option<String>>,
+    output_path: Mutex<Option<PathBuf>>,
+}
+
+impl Job {
+    fn status(&self) -> JobStatus {
+        *self.status.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn set_status(&self, status: JobStatus) {
+        *self.status.lock().unwrap_or_else(|p| p.into_inner()) = status;
+    }
+
+    fn info(&self) -> JobInfo {
+        let total_bytes = self.state.total_bytes.load(Ordering::SeqCst);
+        let downloaded_bytes = self.state.downloaded_bytes.load(Ordering::SeqCst);
+        let status = self.status();
+        let progress_percent = if status == JobStatus::Completed {
+            100.0
+        } else if total_bytes > 0 {
+            (downloaded_bytes as f64 / total_bytes as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        JobInfo {
+            id: self.id,
+            url: self.url.clone(),
+            kind: self.kind,
+            status,
+            total_bytes,
+            downloaded_bytes,
+            progress_percent,
+            error: self.error.lock().unwrap_or_else(|p| p.into_inner()).clone(),
+            output_path: self
+                .output_path
+                .lock()
+                .unwrap_or_else(|p| p.into_inner())
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string()),
+        }
+    }
+}
+
+/// Background download jobs
+pub struct JobManager {
+    jobs: Mutex<Vec<Arc<Job>>>,
+    next_id: AtomicU64,
+    concurrency: AtomicUsize,
+    /// Looked up for each job, as the cache can move between jobs
+    output_dir: fn() -> PathBuf,
+}
+
+impl JobManager {
+    pub fn new(output_dir: fn() -> PathBuf) -> Self {
+        Self {
+            jobs: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+            concurrency: AtomicUsize::new(config::download::DEFAULT_CONCURRENCY),
+            output_dir,
+        }
+    }
+
+    fn jobs(&self) -> std::sync::MutexGuard<'_, Vec<Arc<Job>>> {
+        self.jobs.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn find(&self, id: JobId) -> Result<Arc<Job>, String> {
+        self.jobs()
+            .iter()
+            .find(|j| j.id == id)
+            .cloned()
+            .ok_or_else(|| format!("Download job {} not found", id))
+    }
+
+    /// Set how many jobs may download at the same time
+    pub fn set_concurrency(self: &Arc<Self>, limit: usize) {
+        self.concurrency.store(limit, Ordering::SeqCst);
+        self.pump();
+    }
+
+    /// Queue a download, returning its job ID
+    ///
+    /// An unfinished job for the same URL is reused; a prefetch queued again
+    /// by the user becomes a regular download.
+    pub fn enqueue(
+        self: &Arc<Self>,
+        url: &str,
+        sha_url: Option<&str>,
+        kind: JobKind,
+        options: DownloadOptions,
+    ) -> JobId {
+        let id = {
+            let mut jobs = self.jobs();
+            if let Some(pos) = jobs
+                .iter()
+                .position(|j| j.url == url && !j.status().is_finished())
+            {
+                let existing = jobs[pos].clone();
+                if kind == JobKind::Download
+                    && existing.kind == JobKind::Prefetch
+                    && !existing.started.load(Ordering::SeqCst)
+                {
+                    log_info!(MODULE, "Job {} promoted from prefetch", existing.id);
+                    jobs[pos] = Arc::new(self.new_job(existing.id, url, sha_url, kind, options));
+                }
+                return existing.id;
+            }
+
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            jobs.push(Arc::new(self.new_job(id, url, sha_url, kind, options)));
+            id
+        };
+
+        log_info!(MODULE, "Queued job {} ({:?}): {}", id, kind, url);
+        self.pump();
+        id
+    }
+
+    fn new_job(
+        &self,
+        id: JobId,
+        url: &str,
+        sha_url: Option<&str>,
+        kind: JobKind,
+        options: DownloadOptions,
+    ) -> Job {
+        Job {
+            id,
+            url: url.to_string(),
+            sha_url: sha_url.map(str::to_string),
+            kind,
+            options,
+            state: Arc::new(DownloadState::new()),
+            status: Mutex::new(JobStatus::Queued),
+            started: AtomicBool::new(false),
+            error: Mutex::new(None),
+            output_path: Mutex::new(None),
+        }
+    }
+
+    /// Snapshot of all jobs, in queue order
+    pub fn list(&self) -> Vec<JobInfo> {
+        self.jobs().iter().map(|j| j.info()).collect()
+    }
+
+    /// Snapshot of one job
+    pub fn get(&self, id: JobId) -> Result<JobInfo, String> {
+        self.find(id).map(|j| j.info())
+    }
+
+    /// Pause a queued or running job
+    ///
+    /// A running job keeps its partial data and its concurrency slot; HTTP
+    /// transfers close their connection and resume by range when unpaused.
+    pub fn pause(&self, id: JobId) -> Result<(), String> {
+        let job = self.find(id)?;
+        match job.status() {
+            JobStatus::Queued | JobStatus::Running => {
+                job.state.is_paused.store(true, Ordering::SeqCst);
+                job.set_status(JobStatus::Paused);
+                log_info!(MODULE, "Paused job {}", id);
+                Ok(())
+            }
+            JobStatus::Paused => Ok(()),
+            status => Err(format!("Download job {} is already {:?}", id, status)),
+        }
+    }
+
+    /// Resume a paused job
+    pub fn resume(self: &Arc<Self>, id: JobId) -> Result<(), String> {
+        let job = self.find(id)?;
+        if job.status() != JobStatus::Paused {
+            return Ok(());
+        }
+        job.state.is_paused.store(false, Ordering::SeqCst);
+        job.set_status(if job.started.load(Ordering::SeqCst) {
+            JobStatus::Running
+        } else {
+            JobStatus::Queued
+        });
+        log_info!(MODULE, "Resumed job {}", id);
+        self.pump();
+        Ok(())
+    }
+
+    /// Cancel a job; a running download is stopped and its data removed
+    pub fn cancel(&self, id: JobId) -> Result<(), String> {
+        let job = self.find(id)?;
+        if job.status().is_finished() {
+            return Ok(());
+        }
+        job.state.is_cancelled.store(true, Ordering::SeqCst);
+        job.state.is_paused.store(false, Ordering::SeqCst);
+        if !job.started.load(Ordering::SeqCst) {
+            job.set_status(JobStatus::Cancelled);
+        }
+        log_info!(MODULE, "Cancelled job {}", id);
+        Ok(())
+    }
+
+    /// Drop completed, failed and cancelled jobs from the list
+    pub fn clear_finished(&self) {
+        self.jobs().retain(|j| !j.status().is_finished());
+    }
+
+    /// Next job to start: user downloads first, then prefetches
+    fn next_queued(&self) -> Option<Arc<Job>> {
+        let jobs = self.jobs();
+        let running = jobs
+            .iter()
+            .filter(|j| j.started.load(Ordering::SeqCst) && !j.status().is_finished())
+            .count();
+        if running >= self.concurrency.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        let queued = |kind| {
+            jobs.iter()
+                .find(|j| j.kind == kind && j.status() == JobStatus::Queued)
+                .cloned()
+        };
+        let job = queued(JobKind::Download).or_else(|| queued(JobKind::Prefetch))?;
+        job.started.store(true, Ordering::SeqCst);
+        job.set_status(JobStatus::Running);
+        Some(job)
+    }
+
+    /// Start queued jobs while below the concurrency limit
+    fn pump(self: &Arc<Self>) {
+        while let Some(job) = self.next_queued() {
+            let manager = Arc::clone(self);
+            tauri::async_runtime::spawn(async move {
+                manager.run(job).await;
+                manager.pump();
+            });
+        }
+    }
+
+    async fn run(&self, job: Arc<Job>) {
+        log_info!(MODULE, "Starting job {}: {}", job.id, job.url);
+        let result = download_image(
+            &job.url,
+            job.sha_url.as_deref(),
+            &(self.output_dir)(),
+            job.options.clone(),
+            job.state.clone(),
+        )
+        .await;
+
+        match result {
+            Ok(path) => {
+                log_info!(MODULE, "Job {} completed: {}", job.id, path.display());
+                *job.output_path.lock().unwrap_or_else(|p| p.into_inner()) = Some(path);
+                job.set_status(JobStatus::Completed);
+            }
+            Err(e) => {
+                // Nobody is there to decide about an image without SHA
+                cleanup_pending_download(job.state.clone()).await;
+                if job.state.is_cancelled.load(Ordering::SeqCst) {
+                    job.set_status(JobStatus::Cancelled);
+                } else {
+                    log_warn!(MODULE, "Job {} failed: {}", job.id, e);
+                    *job.error.lock().unwrap_or_else(|p| p.into_inner()) = Some(e);
+                    job.set_status(JobStatus::Failed);
+                }
+            }
+        }
+    }
+}
+
+/// Locks held by the downloads in progress, by image filename
+static IN_FLIGHT: Lazy<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    Lazy::new(Default::default);
+
+/// Claim on an image filename, released when dropped
+pub(super) struct ImageClaim {
+    filename: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for ImageClaim {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        release_image(&self.filename);
+    }
+}
+
+/// Forget the lock of `filename` unless another download holds or waits for it
+fn release_image(filename: &str) {
+    let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|p| p.into_inner());
+    if in_flight
+        .get(filename)
+        .is_some_and(|lock| Arc::strong_count(lock) == 1)
+    {
+        in_flight.remove(filename);
+    }
+}
+
+/// Wait until no other download of `filename` is in progress
+///
+/// Two downloads of the same image would write the same temp file, so the
+/// second waits for the first and then usually finds the image cached.
+pub(super) async fn claim_image(
+    filename: &str,
+    state: &DownloadState,
+) -> Result<ImageClaim, String> {
+    let lock = IN_FLIGHT
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .entry(filename.to_string())
+        .or_default()
+        .clone();
+
+    let mut logged = false;
+    loop {
+        if let Ok(guard) = lock.clone().try_lock_owned() {
+            return Ok(ImageClaim {
+                filename: filename.to_string(),
+                guard: Some(guard),
+            });
+        }
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            drop(lock);
+            release_image(filename);
+            return Err("Download cancelled".to_string());
+        }
+        if !logged {
+            log_info!(
+                MODULE,
+                "{} is already being downloaded, waiting for it",
+                filename
+            );
+            logged = true;
+        }
+        tokio::time::sleep(IN_FLIGHT_POLL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::{Backend, Trust};
+
+    fn options() -> DownloadOptions {
+        DownloadOptions {
+            backend: Backend::Http,
+            delta: false,
+            window: None,
+            trust: Trust::default(),
+            lan_peers: false,
+            reserve: None,
+            keep_compressed: false,
+        }
+    }
+
+    /// Manager that never starts jobs, to inspect the queue
+    fn stopped_manager() -> Arc<JobManager> {
+        let manager = Arc::new(JobManager::new(std::env::temp_dir));
+        manager.concurrency.store(0, Ordering::SeqCst);
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_image_claims_are_released() {
+        let state = DownloadState::new();
+        let claim = claim_image("claim-test.img", &state).await.unwrap();
+        assert!(IN_FLIGHT.lock().unwrap().contains_key("claim-test.img"));
+        drop(claim);
+        assert!(!IN_FLIGHT.lock().unwrap().contains_key("claim-test.img"));
+
+        let first = claim_image("claim-test.img", &state).await.unwrap();
+        state.is_cancelled.store(true, Ordering::SeqCst);
+        assert!(claim_image("claim-test.img", &state).await.is_err());
+        drop(first);
+        assert!(!IN_FLIGHT.lock().unwrap().contains_key("claim-test.img"));
+    }
+
+    #[test]
+    fn test_enqueue_reuses_unfinished_jobs() {
+        let manager = stopped_manager();
+        let a = manager.enqueue(
+            "https://example.com/a.img.xz",
+            None,
+            JobKind::Prefetch,
+            options(),
+        );
+        let b = manager.enqueue(
+            "https://example.com/b.img.xz",
+            None,
+            JobKind::Download,
+            options(),
+        );
+        assert_ne!(a, b);
+
+        // Queuing a prefetched image again promotes it to a user download
+        let again = manager.enqueue(
+            "https://example.com/a.img.xz",
+            None,
+            JobKind::Download,
+            options(),
+        );
+        assert_eq!(again, a);
+        assert_eq!(manager.get(a).unwrap().kind, JobKind::Download);
+        assert_eq!(manager.list().len(), 2);
+    }
+
+    #[test]
+    fn test_user_downloads_start_before_prefetches() {
+        let manager = stopped_manager();
+        let prefetch = manager.enqueue(
+            "https://example.com/p.img.xz",
+            None,
+            JobKind::Prefetch,
+            options(),
+        );
+        let download = manager.enqueue(
+            "https://example.com/d.img.xz",
+            None,
+            JobKind::Download,
+            options(),
+        );
+
+        manager.concurrency.store(1, Ordering::SeqCst);
+        assert_eq!(manager.next_queued().unwrap().id, download);
+        // The limit is reached while the first job runs
+        assert!(manager.next_queued().is_none());
+
+        manager.concurrency.store(2, Ordering::SeqCst);
+        assert_eq!(manager.next_queued().unwrap().id, prefetch);
+    }
+
+    #[test]
+    fn test_pause_resume_cancel_queued_job() {
+        let manager = stopped_manager();
+        let id = manager.enqueue(
+            "https://example.com/a.img.xz",
+            None,
+            JobKind::Download,
+            options(),
+        );
+
+        manager.pause(id).unwrap();
+        assert_eq!(manager.get(id).unwrap().status, JobStatus::Paused);
+        manager.concurrency.store(1, Ordering::SeqCst);
+        assert!(manager.next_queued().is_none());
+        manager.concurrency.store(0, Ordering::SeqCst);
+
+        manager.resume(id).unwrap();
+        assert_eq!(manager.get(id).unwrap().status, JobStatus::Queued);
+
+        manager.cancel(id).unwrap();
+        assert_eq!(manager.get(id).unwrap().status, JobStatus::Cancelled);
+        assert!(manager.pause(id).is_err());
+
+        manager.clear_finished();
+        assert!(manager.get(id).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+//! Resumable download support
+//!
+//! Persists partial download state in a JSON sidecar next to the
+//! `.downloading` temp file, so an interrupted transfer can continue with
+//! an HTTP `Range` request instead of starting from scratch.
+
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::{log_debug, log_info, log_warn};
+
+const MODULE: &str = "download::resume";
+
+/// Sidecar metadata describing a partial download
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialDownload {
+    /// Original download URL (before any mirror redirect)
+    pub url: String,
+    /// ETag reported by the server when the transfer started
+    pub etag: Option<String>,
+    /// Last-Modified reported by the server when the transfer started
+    pub last_modified: Option<String>,
+    /// Number of bytes durably written to the temp file
+    pub offset: u64,
+    /// Total size of the remote file (0 if unknown)
+    pub total_size: u64,
+}
+
+impl PartialDownload {
+    /// Validator to send in `If-Range`
+    ///
+    /// Only strong ETags are allowed in `If-Range` (RFC 9110), so weak
+    /// ETags fall back to the Last-Modified date.
+    pub fn if_range_validator(&self) -> Option<&str> {
+        match self.etag.as_deref() {
+            Some(etag) if !etag.starts_with("W/") => Some(etag),
+            _ => self.last_modified.as_deref(),
+        }
+    }
+}
+
+/// Get the sidecar path for a temp download file
+pub fn sidecar_path(temp_path: &Path) -> PathBuf {
+    let mut name = temp_path.as_os_str().to_owned();
+    name.push(config::images::RESUME_SIDECAR_EXT);
+    PathBuf::from(name)
+}
+
+/// Load a resumable partial download for the given URL
+///
+/// Returns `None` when there is nothing to resume: no sidecar, a sidecar for
+/// a different URL, no validator to guard the resume, or a temp file shorter
+/// than the recorded offset. Stale state is discarded in those cases.
+///
+/// Bytes past the recorded offset were never confirmed as flushed, so the
+/// temp file is truncated back to the offset before resuming.
+pub fn load(temp_path: &Path, url: &str) -> Option<PartialDownload> {
+    let sidecar = sidecar_path(temp_path);
+    if !sidecar.exists() {
+        return None;
+    }
+
+    let partial: PartialDownload = match std::fs::read_to_string(&sidecar)
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+    {
+        Ok(p) => p,
+        Err(e) => {
+            log_warn!(MODULE, "Corrupted resume sidecar, discarding: {}", e);
+            discard(temp_path);
+            return None;
+        }
+    };
+
+    if partial.url != url {
+        log_info!(
+            MODULE,
+            "Partial download belongs to a different URL, discarding: {}",
+            partial.url
+        );
+        discard(temp_path);
+        return None;
+    }
+
+    if partial.if_range_validator().is_none() {
+        log_info!(
+            MODULE,
+            "Partial download has no ETag/Last-Modified to validate against, discarding"
+        );
+        discard(temp_path);
+        return None;
+    }
+
+    let file_len = std::fs::metadata(temp_path).map(|m| m.len()).unwrap_or(0);
+    if partial.offset == 0 || file_len < partial.offset {
+        log_info!(
+            MODULE,
+            "Partial download is shorter than recorded offset ({} < {}), discarding",
+            file_len,
+            partial.offset
+        );
+        discard(temp_path);
+        return None;
+    }
+
+    if file_len > partial.offset {
+        log_debug!(
+            MODULE,
+            "Truncating unconfirmed tail: {} -> {} bytes",
+            file_len,
+            partial.offset
+        );
+        let truncated = OpenOptions::new()
+            .write(true)
+            .open(temp_path)
+            .and_then(|f| f.set_len(partial.offset));
+        if let Err(e) = truncated {
+            log_warn!(MODULE, "Failed to truncate partial download: {}", e);
+            discard(temp_path);
+            return None;
+        }
+    }
+
+    log_info!(
+        MODULE,
+        "Found partial download at {} of {} bytes",
+        partial.offset,
+        partial.total_size
+    );
+    Some(partial)
+}
+
+/// Persist the sidecar for a partial download
+///
+/// Writes to a temp file and renames it so a crash never leaves a
+/// half-written sidecar behind.
+pub fn save(temp_path: &Path, partial: &PartialDownload) -> Result<(), String> {
+    let sidecar = sidecar_path(temp_path);
+    let json = serde_json::to_string(partial)
+        .map_err(|e| format!("Failed to serialize resume state: {}", e))?;
+
+    let mut tmp_name = sidecar.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    std::fs::write(&tmp_path, json).map_err(|e| format!("Failed to write resume state: {}", e))?;
+    std::fs::rename(&tmp_path, &sidecar).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        format!("Failed to save resume state: {}", e)
+    })
+}
+
+/// Remove the sidecar, keeping the temp file
+pub fn remove(temp_path: &Path) {
+    let _ = std::fs::remove_file(sidecar_path(temp_path));
+}
+
+/// Remove both the temp file and its sidecar
+pub fn discard(temp_path: &Path) {
+    let _ = std::fs::remove_file(temp_path);
+    remove(temp_path);
+}
+
+/// Parse a `Content-Range: bytes start-end/total` header
+///
+/// Returns `(start, end, total)` where `total` is `None` for `*`.
+pub fn parse_content_range(value: &str) -> Option<(u64, u64, Option<u64>)> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (span, total) = range.split_once('/')?;
+    let (start, end) = span.split_once('-')?;
+
+    let start = start.trim().parse().ok()?;
+    let end = end.trim().parse().ok()?;
+    let total = match total.trim() {
+        "*" => None,
+        t => Some(t.parse().ok()?),
+    };
+
+    if end < start {
+        return None;
+    }
+    Some((start, end, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(etag: Option<&str>, last_modified: Option<&str>) -> PartialDownload {
+        PartialDownload {
+            url: "https://dl.armbian.com/test.img.xz".to_string(),
+            etag: etag.map(String::from),
+            last_modified: last_modified.map(String::from),
+            offset: 1024,
+            total_size: 4096,
+        }
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 100-199/1000"),
+            Some((100, 199, Some(1000)))
+        );
+        assert_eq!(parse_content_range("bytes 0-9/*"), Some((0, 9, None)));
+        assert_eq!(parse_content_range("bytes 10-5/100"), None);
+        assert_eq!(parse_content_range("items 0-9/10"), None);
+        assert_eq!(parse_content_range("bytes */1000"), None);
+    }
+
+    #[test]
+    fn test_if_range_prefers_strong_etag() {
+        let p = partial(Some("\"abc\""), Some("Tue, 01 Jan 2026 00:00:00 GMT"));
+        assert_eq!(p.if_range_validator(), Some("\"abc\""));
+
+        let p = partial(Some("W/\"abc\""), Some("Tue, 01 Jan 2026 00:00:00 GMT"));
+        assert_eq!(
+            p.if_range_validator(),
+            Some("Tue, 01 Jan 2026 00:00:00 GMT")
+        );
+
+        let p = partial(Some("W/\"abc\""), None);
+        assert_eq!(p.if_range_validator(), None);
+    }
+
+    #[test]
+    fn test_sidecar_roundtrip_and_truncate() {
+        let dir = std::env::temp_dir().join(format!("armbian-resume-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let temp_path = dir.join("test.img.xz.downloading");
+        std::fs::write(&temp_path, vec![0u8; 2048]).unwrap();
+
+        let p = partial(Some("\"abc\""), None);
+        save(&temp_path, &p).unwrap();
+
+        // Different URL: discarded
+        assert!(load(&temp_path, "https://example.com/other.img.xz").is_none());
+        assert!(!temp_path.exists());
+
+        std::fs::write(&temp_path, vec![0u8; 2048]).unwrap();
+        save(&temp_path, &p).unwrap();
+        assert_eq!(load(&temp_path, &p.url), Some(p.clone()));
+        assert_eq!(std::fs::metadata(&temp_path).unwrap().len(), p.offset);
+
+        discard(&temp_path);
+        assert!(!sidecar_path(&temp_path).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,126 @@
+//! Download window
+//!
+//! Queued downloads can be restricted to a range of hours in local time,
+//! e.g. overnight, so they do not compete with daytime traffic. A download
+//! that started inside the window runs to completion.
+
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::DownloadState;
+use crate::log_info;
+
+const MODULE: &str = "download::schedule";
+
+/// How often a waiting download checks the clock and cancellation
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Hours during which queued downloads may run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadWindow {
+    /// First hour of the window (0-23)
+    pub start_hour: u8,
+    /// Hour at which the window closes (0-23); before `start_hour` wraps past midnight
+    pub end_hour: u8,
+}
+
+impl DownloadWindow {
+    /// Check that both hours are valid
+    pub fn validate(&self) -> Result<(), String> {
+        if self.start_hour > 23 || self.end_hour > 23 {
+            return Err(format!(
+                "Invalid download window {}-{}: hours must be between 0 and 23",
+                self.start_hour, self.end_hour
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether downloads may run during `hour`
+    ///
+    /// Equal start and end hours mean the window is always open.
+    pub fn contains(&self, hour: u8) -> bool {
+        if self.start_hour == self.end_hour {
+            true
+        } else if self.start_hour < self.end_hour {
+            hour >= self.start_hour && hour < self.end_hour
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+/// Wait until the window is open, returning early if the download is cancelled
+pub async fn wait_for_window(window: DownloadWindow, state: &DownloadState) -> Result<(), String> {
+    let mut logged = false;
+    loop {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Download cancelled".to_string());
+        }
+        let hour = chrono::Local::now().hour() as u8;
+        if window.contains(hour) {
+            return Ok(());
+        }
+        if !logged {
+            log_info!(
+                MODULE,
+                "Outside the download window ({}:00-{}:00), waiting",
+                window.start_hour,
+                window.end_hour
+            );
+            logged = true;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_contains() {
+        let day = DownloadWindow {
+            start_hour: 9,
+            end_hour: 17,
+        };
+        assert!(day.contains(9));
+        assert!(day.contains(16));
+        assert!(!day.contains(17));
+        assert!(!day.contains(3));
+
+        let night = DownloadWindow {
+            start_hour: 22,
+            end_hour: 6,
+        };
+        assert!(night.contains(23));
+        assert!(night.contains(0));
+        assert!(night.contains(5));
+        assert!(!night.contains(6));
+        assert!(!night.contains(12));
+
+        let always = DownloadWindow {
+            start_hour: 4,
+            end_hour: 4,
+        };
+        assert!((0..24).all(|h| always.contains(h)));
+    }
+
+    #[test]
+    fn test_window_validate() {
+        assert!(DownloadWindow {
+            start_hour: 0,
+            end_hour: 23
+        }
+        .validate()
+        .is_ok());
+        assert!(DownloadWindow {
+            start_hour: 24,
+            end_hour: 6
+        }
+        .validate()
+        .is_err());
+    }
+}
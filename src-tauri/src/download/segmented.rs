@@ -0,0 +1,543 @@
+//! Segmented multi-mirror downloads
+//!
+//! Resolves the mirrors behind the Armbian redirector from the Metalink
+//! document published next to the image, and fetches byte ranges from
+//! several of them concurrently into one preallocated file.
+//! Mirrors that fail repeatedly or fall far behind the fastest one are
+//! dropped mid-transfer and their unfinished ranges are handed to the rest.
+
+use std::collections::{HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{Client, StatusCode};
+
+use crate::config;
+use crate::{log_debug, log_info, log_warn};
+
+use super::{resume, DownloadState};
+
+const MODULE: &str = "download::segmented";
+
+/// Byte range `[start, end)` still to be downloaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    start: u64,
+    end: u64,
+}
+
+/// Per-mirror transfer statistics
+struct Mirror {
+    url: String,
+    /// Bytes received from this mirror
+    bytes: AtomicU64,
+    /// Milliseconds spent actively receiving from this mirror
+    busy_ms: AtomicU64,
+    failures: AtomicU32,
+    dropped: AtomicBool,
+}
+
+impl Mirror {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            bytes: AtomicU64::new(0),
+            busy_ms: AtomicU64::new(0),
+            failures: AtomicU32::new(0),
+            dropped: AtomicBool::new(false),
+        }
+    }
+
+    /// Average throughput in bytes per second (0 until measured)
+    fn speed(&self) -> f64 {
+        let ms = self.busy_ms.load(Ordering::SeqCst);
+        if ms == 0 {
+            return 0.0;
+        }
+        self.bytes.load(Ordering::SeqCst) as f64 * 1000.0 / ms as f64
+    }
+
+    fn drop_mirror(&self, reason: &str) {
+        if !self.dropped.swap(true, Ordering::SeqCst) {
+            log_warn!(MODULE, "Dropping mirror {}: {}", self.url, reason);
+        }
+    }
+}
+
+/// Shared state of one segmented transfer
+struct Transfer<'a> {
+    client: &'a Client,
+    temp_path: &'a Path,
+    state: &'a Arc<DownloadState>,
+    mirrors: Vec<Mirror>,
+    queue: Mutex<VecDeque<Segment>>,
+    aborted: AtomicBool,
+}
+
+/// Resolve the list of mirrors serving a redirector URL
+///
+/// Reads the Metalink (`.meta4`) document published next to the file.
+/// Returns an empty list when the URL is not backed by multiple mirrors.
+pub async fn resolve_mirrors(client: &Client, url: &str) -> Vec<String> {
+    let mut mirrors = dedup_mirrors(fetch_metalink(client, url).await);
+    mirrors.truncate(config::download::MAX_MIRRORS);
+    if !mirrors.is_empty() {
+        log_info!(MODULE, "Resolved {} mirrors for {}", mirrors.len(), url);
+        for mirror in &mirrors {
+            log_debug!(MODULE, "  - {}", mirror);
+        }
+    }
+    mirrors
+}
+
+/// Drop repeated mirrors, keeping the first occurrence of each
+fn dedup_mirrors(mirrors: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    mirrors
+        .into_iter()
+        .filter(|mirror| seen.insert(mirror.clone()))
+        .collect()
+}
+
+/// Fetch a Metalink 4 document published next to the file
+async fn fetch_metalink(client: &Client, url: &str) -> Vec<String> {
+    let metalink_url = format!("{}.meta4", url.split('?').next().unwrap_or(url));
+
+    let response = match client.get(&metalink_url).send().await {
+        Ok(r) if r.status().is_success() => r,
+        _ => return Vec::new(),
+    };
+
+    match response.text().await {
+        Ok(text) => parse_metalink(&text),
+        Err(e) => {
+            log_debug!(MODULE, "Failed to read metalink: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Parse the `<url>` entries of a Metalink 4 document, ordered by priority
+fn parse_metalink(text: &str) -> Vec<String> {
+    let root = match xmltree::Element::parse(text.as_bytes()) {
+        Ok(r) => r,
+        Err(e) => {
+            log_debug!(MODULE, "Invalid metalink document: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut urls: Vec<(u32, String)> = Vec::new();
+    for file in root.children.iter().filter_map(|n| n.as_element()) {
+        if file.name != "file" {
+            continue;
+        }
+        for url in file.children.iter().filter_map(|n| n.as_element()) {
+            if url.name != "url" {
+                continue;
+            }
+            let priority = url
+                .attributes
+                .get("priority")
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(u32::MAX);
+            if let Some(text) = url.get_text() {
+                let text = text.trim();
+                if text.starts_with("http://") || text.starts_with("https://") {
+                    urls.push((priority, text.to_string()));
+                }
+            }
+        }
+    }
+
+    urls.sort_by_key(|(priority, _)| *priority);
+    urls.into_iter().map(|(_, u)| u).collect()
+}
+
+/// Split `[0, total)` into fixed-size segments
+fn split_segments(total: u64, segment_size: u64) -> VecDeque<Segment> {
+    let mut segments = VecDeque::new();
+    let mut start = 0;
+    while start < total {
+        let end = std::cmp::min(start + segment_size, total);
+        segments.push_back(Segment { start, end });
+        start = end;
+    }
+    segments
+}
+
+/// Check that a mirror serves byte ranges and return the file size it reports
+async fn probe_mirror(client: &Client, url: &str) -> Option<u64> {
+    let response = client
+        .get(url)
+        .header(RANGE, "bytes=0-0")
+        .send()
+        .await
+        .ok()?;
+
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        log_debug!(
+            MODULE,
+            "Mirror does not support ranges ({}): {}",
+            response.status(),
+            url
+        );
+        return None;
+    }
+
+    response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(resume::parse_content_range)
+        .and_then(|(_, _, total)| total)
+}
+
+/// Download a file from several mirrors in parallel byte ranges
+///
+/// Probes the mirrors, keeps those that agree on the file size and support
+/// ranges, preallocates `temp_path` and fills it segment by segment.
+/// Progress is aggregated into `state.downloaded_bytes`.
+///
+/// Returns an error without touching `temp_path` if fewer than two usable
+/// mirrors are found, so the caller can fall back to a single stream.
+pub async fn download(
+    client: &Client,
+    mirror_urls: &[String],
+    temp_path: &Path,
+    state: &Arc<DownloadState>,
+) -> Result<(), String> {
+    let probes = futures_util::future::join_all(
+        mirror_urls
+            .iter()
+            .map(|url| async move { (url.clone(), probe_mirror(client, url).await) }),
+    )
+    .await;
+
+    // The first mirror answering decides the expected size
+    let total_size = probes
+        .iter()
+        .find_map(|(_, size)| *size)
+        .ok_or("No mirror supports range requests")?;
+    let mirrors: Vec<Mirror> = probes
+        .into_iter()
+        .filter(|(_, size)| *size == Some(total_size))
+        .map(|(url, _)| Mirror::new(url))
+        .collect();
+
+    if mirrors.len() < 2 {
+        return Err("Not enough mirrors for a segmented download".to_string());
+    }
+
+    log_info!(
+        MODULE,
+        "Segmented download of {} bytes from {} mirrors",
+        total_size,
+        mirrors.len()
+    );
+
+    let file = File::create(temp_path).map_err(|e| format!("Failed to create temp file: {}", e))?;
+    file.set_len(total_size)
+        .map_err(|e| format!("Failed to preallocate temp file: {}", e))?;
+    drop(file);
+
+    state.total_bytes.store(total_size, Ordering::SeqCst);
+    state.downloaded_bytes.store(0, Ordering::SeqCst);
+
+    let transfer = Transfer {
+        client,
+        temp_path,
+        state,
+        mirrors,
+        queue: Mutex::new(split_segments(total_size, config::download::SEGMENT_SIZE)),
+        aborted: AtomicBool::new(false),
+    };
+
+    // Workers exit when the queue is empty; a mirror dropped late may hand a
+    // range back after the others stopped, so keep going until it drains.
+    loop {
+        let remaining = transfer.queue.lock().map(|q| q.len()).unwrap_or(0);
+        if remaining == 0 {
+            break;
+        }
+
+        let active: Vec<&Mirror> = transfer
+            .mirrors
+            .iter()
+            .filter(|m| !m.dropped.load(Ordering::SeqCst))
+            .collect();
+        if active.is_empty() {
+            return Err("All mirrors failed".to_string());
+        }
+
+        let results =
+            futures_util::future::join_all(active.into_iter().map(|m| run_worker(&transfer, m)))
+                .await;
+
+        if let Some(err) = results.into_iter().find_map(|r| r.err()) {
+            return Err(err);
+        }
+    }
+
+    for mirror in &transfer.mirrors {
+        log_info!(
+            MODULE,
+            "Mirror {}: {:.1} MB at {:.1} MB/s{}",
+            mirror.url,
+            crate::utils::bytes_to_mb(mirror.bytes.load(Ordering::SeqCst)),
+            mirror.speed() / (1024.0 * 1024.0),
+            if mirror.dropped.load(Ordering::SeqCst) {
+                " (dropped)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// Take segments from the queue and download them from one mirror
+///
+/// Returns `Err` only for fatal conditions (cancellation, local I/O errors).
+/// Network problems are absorbed: the unfinished range is put back and the
+/// mirror is dropped after repeated failures.
+async fn run_worker(transfer: &Transfer<'_>, mirror: &Mirror) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(transfer.temp_path)
+        .map_err(|e| format!("Failed to open temp file: {}", e))?;
+
+    while !mirror.dropped.load(Ordering::SeqCst) {
+        if transfer.aborted.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        // Paused downloads hold no connection; segments resume by range
+        transfer.state.wait_while_paused().await;
+        let segment = match transfer.queue.lock().ok().and_then(|mut q| q.pop_front()) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        match fetch_segment(transfer, mirror, &mut file, segment).await {
+            Ok(()) => check_slow_mirror(transfer, mirror),
+            Err(SegmentError::Fatal(e)) => {
+                transfer.aborted.store(true, Ordering::SeqCst);
+                return Err(e);
+            }
+            Err(SegmentError::Paused(rest)) => {
+                if let Ok(mut q) = transfer.queue.lock() {
+                    q.push_front(rest);
+                }
+            }
+            Err(SegmentError::Network(rest, e)) => {
+                if let Ok(mut q) = transfer.queue.lock() {
+                    q.push_front(rest);
+                }
+                let failures = mirror.failures.fetch_add(1, Ordering::SeqCst) + 1;
+                log_warn!(
+                    MODULE,
+                    "Segment {}-{} failed on {} ({}/{}): {}",
+                    rest.start,
+                    rest.end,
+                    mirror.url,
+                    failures,
+                    config::download::MAX_MIRROR_FAILURES,
+                    e
+                );
+                if failures >= config::download::MAX_MIRROR_FAILURES {
+                    mirror.drop_mirror("too many failures");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Failure while fetching a segment
+enum SegmentError {
+    /// Network/mirror problem: the remaining range can be retried elsewhere
+    Network(Segment, String),
+    /// Local problem or cancellation: the whole download must stop
+    Fatal(String),
+    /// Download paused: the remaining range is fetched once unpaused
+    Paused(Segment),
+}
+
+/// Download one segment and write it at its offset
+async fn fetch_segment(
+    transfer: &Transfer<'_>,
+    mirror: &Mirror,
+    file: &mut File,
+    segment: Segment,
+) -> Result<(), SegmentError> {
+    let mut position = segment.start;
+    let remaining = |pos: u64| Segment {
+        start: pos,
+        end: segment.end,
+    };
+
+    let response = transfer
+        .client
+        .get(&mirror.url)
+        .header(
+            RANGE,
+            format!("bytes={}-{}", segment.start, segment.end - 1),
+        )
+        .send()
+        .await
+        .map_err(|e| SegmentError::Network(segment, e.to_string()))?;
+
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(SegmentError::Network(
+            segment,
+            format!("unexpected status {}", response.status()),
+        ));
+    }
+
+    file.seek(SeekFrom::Start(position))
+        .map_err(|e| SegmentError::Fatal(format!("Failed to seek temp file: {}", e)))?;
+
+    let idle_timeout = Duration::from_secs(config::download::SEGMENT_IDLE_TIMEOUT_SECS);
+    let mut stream = response.bytes_stream();
+
+    while position < segment.end {
+        if transfer.state.is_cancelled.load(Ordering::SeqCst) {
+            return Err(SegmentError::Fatal("Download cancelled".to_string()));
+        }
+        if transfer.aborted.load(Ordering::SeqCst) || mirror.dropped.load(Ordering::SeqCst) {
+            return Err(SegmentError::Network(
+                remaining(position),
+                "transfer interrupted".to_string(),
+            ));
+        }
+
+        let started = Instant::now();
+        let chunk = match tokio::time::timeout(idle_timeout, stream.next()).await {
+            Ok(Some(Ok(c))) => c,
+            Ok(Some(Err(e))) => {
+                return Err(SegmentError::Network(remaining(position), e.to_string()))
+            }
+            Ok(None) => {
+                return Err(SegmentError::Network(
+                    remaining(position),
+                    "connection closed early".to_string(),
+                ))
+            }
+            Err(_) => {
+                return Err(SegmentError::Network(
+                    remaining(position),
+                    "stalled".to_string(),
+                ))
+            }
+        };
+
+        // Never write past the segment, even if the mirror sends extra bytes
+        let len = std::cmp::min(chunk.len() as u64, segment.end - position) as usize;
+        file.write_all(&chunk[..len])
+            .map_err(|e| SegmentError::Fatal(format!("Failed to write chunk: {}", e)))?;
+
+        position += len as u64;
+        mirror.bytes.fetch_add(len as u64, Ordering::SeqCst);
+        mirror
+            .busy_ms
+            .fetch_add(started.elapsed().as_millis() as u64, Ordering::SeqCst);
+        transfer
+            .state
+            .downloaded_bytes
+            .fetch_add(len as u64, Ordering::SeqCst);
+
+        super::throttle::consume(len as u64).await;
+        if transfer.state.is_paused.load(Ordering::SeqCst) && position < segment.end {
+            return Err(SegmentError::Paused(remaining(position)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Drop a mirror that is much slower than the fastest active one
+fn check_slow_mirror(transfer: &Transfer<'_>, mirror: &Mirror) {
+    if mirror.bytes.load(Ordering::SeqCst) < config::download::SEGMENT_SIZE {
+        return;
+    }
+
+    let active: Vec<&Mirror> = transfer
+        .mirrors
+        .iter()
+        .filter(|m| !m.dropped.load(Ordering::SeqCst))
+        .collect();
+    if active.len() < 2 {
+        return;
+    }
+
+    let fastest = active.iter().map(|m| m.speed()).fold(0.0, f64::max);
+    if mirror.speed() < fastest * config::download::SLOW_MIRROR_RATIO {
+        mirror.drop_mirror(&format!(
+            "{:.1} MB/s vs {:.1} MB/s on the fastest mirror",
+            mirror.speed() / (1024.0 * 1024.0),
+            fastest / (1024.0 * 1024.0)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_segments() {
+        let segments = split_segments(25, 10);
+        assert_eq!(
+            segments,
+            VecDeque::from(vec![
+                Segment { start: 0, end: 10 },
+                Segment { start: 10, end: 20 },
+                Segment { start: 20, end: 25 },
+            ])
+        );
+        assert!(split_segments(0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_dedup_mirrors() {
+        let mirrors = [
+            "https://a.example/x",
+            "https://b.example/x",
+            "https://a.example/x",
+        ]
+        .map(String::from)
+        .to_vec();
+        assert_eq!(
+            dedup_mirrors(mirrors),
+            vec!["https://a.example/x", "https://b.example/x"]
+        );
+    }
+
+    #[test]
+    fn test_parse_metalink() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <file name="a.img.xz">
+    <size>1234</size>
+    <url priority="2">https://slow.example/a.img.xz</url>
+    <url priority="1">https://fast.example/a.img.xz</url>
+  </file>
+</metalink>"#;
+        assert_eq!(
+            parse_metalink(xml),
+            vec![
+                "https://fast.example/a.img.xz",
+                "https://slow.example/a.img.xz"
+            ]
+        );
+    }
+}
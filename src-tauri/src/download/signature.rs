@@ -0,0 +1,810 @@
+//! OpenPGP signature verification
+//!
+//! Verifies the detached `.asc` signature of a downloaded image against the
+//! Armbian signing keyring bundled with the app (`keys/` resource directory).
+//!
+//! Only the subset of OpenPGP (RFC 4880) needed for that is implemented:
+//! ASCII armor, v4 public keys and v4 binary signatures made with RSA keys
+//! over SHA-2 digests. Key expiry and revocation are not evaluated; the
+//! bundled keyring is trusted as shipped.
+
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use base64::Engine;
+use reqwest::Client;
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+use crate::config;
+use crate::http::retry;
+use crate::{log_debug, log_error, log_info, log_warn};
+
+const MODULE: &str = "download::signature";
+
+/// OpenPGP packet tags
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+
+/// Signature subpacket types
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+/// Outcome of checking an image signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureStatus {
+    /// Signed by a key in the bundled keyring
+    Valid,
+    /// Signed by a key that is not in the bundled keyring
+    UnknownKey,
+    /// Signature does not match the data, or is malformed
+    BadSignature,
+    /// No signature published for the image
+    Missing,
+}
+
+impl SignatureStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Valid => "valid",
+            Self::UnknownKey => "unknown key",
+            Self::BadSignature => "bad signature",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+/// RSA signing key (primary key or subkey)
+#[derive(Clone)]
+struct PublicKey {
+    fingerprint: [u8; 20],
+    key: RsaPublicKey,
+}
+
+impl PublicKey {
+    fn key_id(&self) -> &[u8] {
+        &self.fingerprint[12..]
+    }
+}
+
+/// Set of trusted signing keys
+#[derive(Default)]
+pub struct Keyring {
+    keys: Vec<PublicKey>,
+}
+
+impl std::fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.keys.iter().map(|k| hex::encode_upper(k.fingerprint)))
+            .finish()
+    }
+}
+
+impl PartialEq for Keyring {
+    fn eq(&self, other: &Self) -> bool {
+        self.keys
+            .iter()
+            .map(|k| k.fingerprint)
+            .eq(other.keys.iter().map(|k| k.fingerprint))
+    }
+}
+
+impl Eq for Keyring {}
+
+impl Keyring {
+    /// Parse armored or binary OpenPGP public key data
+    ///
+    /// Non-RSA keys are skipped.
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut keys = Vec::new();
+        for block in dearmor(data)? {
+            for packet in parse_packets(&block)? {
+                if packet.tag != TAG_PUBLIC_KEY && packet.tag != TAG_PUBLIC_SUBKEY {
+                    continue;
+                }
+                match parse_public_key(packet.body) {
+                    Ok(Some(key)) => keys.push(key),
+                    Ok(None) => log_debug!(MODULE, "Skipping unsupported key packet"),
+                    Err(e) => log_warn!(MODULE, "Skipping malformed key packet: {}", e),
+                }
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    /// Load every key file (`.asc`, `.gpg`, `.pgp`, `.key`) in a directory
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut keyring = Self::default();
+        let entries = match std::fs::read_dir(dir) {
+            Ok(e) => e,
+            Err(e) => {
+                log_warn!(
+                    MODULE,
+                    "Keyring directory unavailable {}: {}",
+                    dir.display(),
+                    e
+                );
+                return keyring;
+            }
+        };
+
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some("asc") {
+                continue;
+            }
+            match std::fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| Self::parse(&data))
+            {
+                Ok(parsed) => {
+                    log_debug!(
+                        MODULE,
+                        "Loaded {} key(s) from {}",
+                        parsed.keys.len(),
+                        path.display()
+                    );
+                    keyring.keys.extend(parsed.keys);
+                }
+                Err(e) => log_warn!(MODULE, "Failed to load keyring {}: {}", path.display(), e),
+            }
+        }
+        keyring
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Keyring with the keys of both
+    pub fn union(&self, other: &Keyring) -> Keyring {
+        let mut keys = self.keys.clone();
+        for key in &other.keys {
+            if !keys.iter().any(|k| k.fingerprint == key.fingerprint) {
+                keys.push(key.clone());
+            }
+        }
+        Keyring { keys }
+    }
+
+    /// Keys that may have made `sig`, matched on its issuer subpackets
+    fn candidates<'a>(&'a self, sig: &'a Signature) -> impl Iterator<Item = &'a PublicKey> + 'a {
+        self.keys
+            .iter()
+            .filter(|k| match (&sig.issuer_fingerprint, &sig.issuer_key_id) {
+                (Some(fp), _) => &k.fingerprint == fp,
+                (None, Some(id)) => k.key_id() == id,
+                (None, None) => true,
+            })
+    }
+}
+
+static KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+/// Load the bundled keyring (called once at startup)
+pub fn init_keyring(dir: &Path) {
+    let keyring = Keyring::load_dir(dir);
+    if keyring.is_empty() {
+        log_error!(
+            MODULE,
+            "No signing keys found in {}, image signatures cannot be verified",
+            dir.display()
+        );
+    } else {
+        log_info!(MODULE, "Loaded {} signing key(s)", keyring.len());
+    }
+    let _ = KEYRING.set(keyring);
+}
+
+pub(crate) fn bundled_keyring() -> &'static Keyring {
+    KEYRING.get_or_init(Keyring::default)
+}
+
+/// Parsed v4 signature packet
+struct Signature {
+    sig_type: u8,
+    pubkey_algo: u8,
+    hash_algo: u8,
+    /// Signed part of the packet (version through hashed subpackets)
+    hashed_area: Vec<u8>,
+    issuer_key_id: Option<[u8; 8]>,
+    issuer_fingerprint: Option<[u8; 20]>,
+    left16: [u8; 2],
+    value: Vec<u8>,
+}
+
+/// Incremental hash for the supported signature digests
+enum SigHasher {
+    Sha224(Sha224),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl SigHasher {
+    fn new(hash_algo: u8) -> Option<Self> {
+        match hash_algo {
+            8 => Some(Self::Sha256(Sha256::new())),
+            9 => Some(Self::Sha384(Sha384::new())),
+            10 => Some(Self::Sha512(Sha512::new())),
+            11 => Some(Self::Sha224(Sha224::new())),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha224(h) => h.update(data),
+            Self::Sha256(h) => h.update(data),
+            Self::Sha384(h) => h.update(data),
+            Self::Sha512(h) => h.update(data),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Self::Sha224(h) => Digest::reset(h),
+            Self::Sha256(h) => Digest::reset(h),
+            Self::Sha384(h) => Digest::reset(h),
+            Self::Sha512(h) => Digest::reset(h),
+        }
+    }
+
+    fn finish(self) -> (Vec<u8>, Pkcs1v15Sign) {
+        match self {
+            Self::Sha224(h) => (h.finalize().to_vec(), Pkcs1v15Sign::new::<Sha224>()),
+            Self::Sha256(h) => (h.finalize().to_vec(), Pkcs1v15Sign::new::<Sha256>()),
+            Self::Sha384(h) => (h.finalize().to_vec(), Pkcs1v15Sign::new::<Sha384>()),
+            Self::Sha512(h) => (h.finalize().to_vec(), Pkcs1v15Sign::new::<Sha512>()),
+        }
+    }
+}
+
+/// Signature check fed with the image data as it is downloaded
+pub struct PendingSignature {
+    signature: Signature,
+    hasher: SigHasher,
+    /// Keys of the catalog source, used instead of the bundled keyring
+    keyring: Option<Arc<Keyring>>,
+}
+
+impl PendingSignature {
+    /// Parse a detached signature and prepare its digest
+    fn new(data: &[u8]) -> Result<Self, String> {
+        let signature = dearmor(data)?
+            .iter()
+            .flat_map(|block| parse_packets(block).unwrap_or_default())
+            .find(|p| p.tag == TAG_SIGNATURE)
+            .ok_or_else(|| "No signature packet found".to_string())
+            .and_then(|p| parse_signature(p.body))?;
+
+        if signature.sig_type != 0x00 {
+            return Err(format!(
+                "Unsupported signature type 0x{:02x} (expected binary document)",
+                signature.sig_type
+            ));
+        }
+        if !matches!(signature.pubkey_algo, 1 | 3) {
+            return Err(format!(
+                "Unsupported public key algorithm {}",
+                signature.pubkey_algo
+            ));
+        }
+        let hasher = SigHasher::new(signature.hash_algo)
+            .ok_or_else(|| format!("Unsupported hash algorithm {}", signature.hash_algo))?;
+
+        Ok(Self {
+            signature,
+            hasher,
+            keyring: None,
+        })
+    }
+
+    /// Hash the next piece of signed data
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    /// Discard the data hashed so far, to start again from the first byte
+    pub fn reset(&mut self) {
+        self.hasher.reset();
+    }
+
+    /// Complete the check against the source keys, or the bundled keyring
+    pub fn finish(mut self) -> SignatureStatus {
+        match self.keyring.take() {
+            Some(keyring) => self.finish_with(&keyring),
+            None => self.finish_with(bundled_keyring()),
+        }
+    }
+
+    fn finish_with(mut self, keyring: &Keyring) -> SignatureStatus {
+        let sig = &self.signature;
+
+        // Trailer: hashed area, then 0x04 0xFF and its length
+        self.hasher.update(&sig.hashed_area);
+        self.hasher.update(&[0x04, 0xff]);
+        self.hasher
+            .update(&(sig.hashed_area.len() as u32).to_be_bytes());
+
+        let candidates: Vec<&PublicKey> = keyring.candidates(sig).collect();
+
+        if candidates.is_empty() {
+            log_warn!(
+                MODULE,
+                "Signature issuer {} is not a trusted key",
+                issuer_label(sig)
+            );
+            return SignatureStatus::UnknownKey;
+        }
+
+        let (digest, scheme) = self.hasher.finish();
+        if digest[..2] != sig.left16 {
+            log_warn!(MODULE, "Signature digest prefix mismatch");
+            return SignatureStatus::BadSignature;
+        }
+
+        for key in candidates {
+            // OpenPGP strips leading zeros from the MPI; RSA expects full length
+            let size = rsa::traits::PublicKeyParts::size(&key.key);
+            let mut value = vec![0u8; size.saturating_sub(sig.value.len())];
+            value.extend_from_slice(&sig.value);
+
+            if key.key.verify(scheme.clone(), &digest, &value).is_ok() {
+                log_info!(
+                    MODULE,
+                    "Good signature from key {}",
+                    hex::encode_upper(key.fingerprint)
+                );
+                return SignatureStatus::Valid;
+            }
+        }
+
+        log_warn!(
+            MODULE,
+            "Signature from {} does not match the data",
+            issuer_label(sig)
+        );
+        SignatureStatus::BadSignature
+    }
+}
+
+fn issuer_label(sig: &Signature) -> String {
+    match (&sig.issuer_fingerprint, &sig.issuer_key_id) {
+        (Some(fp), _) => hex::encode_upper(fp),
+        (None, Some(id)) => hex::encode_upper(id),
+        (None, None) => "<unknown>".to_string(),
+    }
+}
+
+/// Check the detached signature `asc` of a file against the bundled keyring
+pub fn check_file(asc: &[u8], path: &Path) -> Result<SignatureStatus, String> {
+    let mut pending = match PendingSignature::new(asc) {
+        Ok(pending) => pending,
+        Err(e) => {
+            log_warn!(MODULE, "Invalid signature file: {}", e);
+            return Ok(SignatureStatus::BadSignature);
+        }
+    };
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut buffer = vec![0u8; config::download::BUFFER_SIZE];
+    loop {
+        let n = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        pending.update(&buffer[..n]);
+    }
+    Ok(pending.finish())
+}
+
+/// Fetch the detached signature for an image
+///
+/// Returns the pending check to feed with the image data, or the final
+/// status when no check is possible (no signature published, malformed
+/// signature, or a key that is not in the keyring). The signature is
+/// checked against `keyring` (the catalog source keys) when given,
+/// otherwise against the bundled keyring.
+pub async fn fetch(
+    client: &Client,
+    asc_url: &str,
+    keyring: Option<Arc<Keyring>>,
+) -> Result<PendingSignature, SignatureStatus> {
+    log_debug!(MODULE, "Fetching signature from: {}", asc_url);
+
+    let response = match retry::send("Signature fetch", || client.get(asc_url)).await {
+        Ok(r) if r.status().is_success() => r,
+        Ok(r) => {
+            log_warn!(
+                MODULE,
+                "No signature available ({}): {}",
+                r.status(),
+                asc_url
+            );
+            return Err(SignatureStatus::Missing);
+        }
+        Err(e) => {
+            log_warn!(MODULE, "Failed to fetch signature: {}", e);
+            return Err(SignatureStatus::Missing);
+        }
+    };
+
+    let data = response.bytes().await.map_err(|e| {
+        log_warn!(MODULE, "Failed to read signature: {}", e);
+        SignatureStatus::Missing
+    })?;
+
+    let mut pending = PendingSignature::new(&data).map_err(|e| {
+        log_warn!(MODULE, "Invalid signature file: {}", e);
+        SignatureStatus::BadSignature
+    })?;
+
+    let trusted = match &keyring {
+        Some(keyring) => keyring,
+        None => bundled_keyring(),
+    };
+    if trusted.candidates(&pending.signature).next().is_none() {
+        log_warn!(
+            MODULE,
+            "Signature issuer {} is not a trusted key",
+            issuer_label(&pending.signature)
+        );
+        return Err(SignatureStatus::UnknownKey);
+    }
+
+    pending.keyring = keyring;
+    Ok(pending)
+}
+
+/// Decode ASCII armor into binary blocks (binary input is returned as is)
+fn dearmor(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let text = match std::str::from_utf8(data) {
+        Ok(t) if t.trim_start().starts_with("-----BEGIN PGP") => t,
+        _ => return Ok(vec![data.to_vec()]),
+    };
+
+    let mut blocks = Vec::new();
+    let mut lines = text.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        if !line.starts_with("-----BEGIN PGP") {
+            continue;
+        }
+
+        // Armor headers end at the first blank line
+        let mut body = String::new();
+        let mut in_headers = true;
+        for line in lines.by_ref() {
+            if line.starts_with("-----END PGP") {
+                break;
+            }
+            if in_headers {
+                if line.is_empty() {
+                    in_headers = false;
+                } else if !line.contains(": ") {
+                    // Tolerate armor without the blank separator line
+                    in_headers = false;
+                    body.push_str(line);
+                }
+                continue;
+            }
+            // CRC24 checksum line
+            if line.starts_with('=') {
+                continue;
+            }
+            body.push_str(line);
+        }
+
+        let block = base64::engine::general_purpose::STANDARD
+            .decode(body.as_bytes())
+            .map_err(|e| format!("Invalid armor encoding: {}", e))?;
+        blocks.push(block);
+    }
+
+    if blocks.is_empty() {
+        return Err("No armored block found".to_string());
+    }
+    Ok(blocks)
+}
+
+/// OpenPGP packet
+struct Packet<'a> {
+    tag: u8,
+    body: &'a [u8],
+}
+
+/// Split binary OpenPGP data into packets
+fn parse_packets(data: &[u8]) -> Result<Vec<Packet<'_>>, String> {
+    let mut packets = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let ctb = data[pos];
+        if ctb & 0x80 == 0 {
+            return Err(format!("Invalid packet header at offset {}", pos));
+        }
+        pos += 1;
+
+        let (tag, len) = if ctb & 0x40 != 0 {
+            // New format
+            let first = *data.get(pos).ok_or("Truncated packet length")? as usize;
+            let len = match first {
+                0..=191 => {
+                    pos += 1;
+                    first
+                }
+                192..=223 => {
+                    let second = *data.get(pos + 1).ok_or("Truncated packet length")? as usize;
+                    pos += 2;
+                    ((first - 192) << 8) + second + 192
+                }
+                255 => {
+                    let bytes = data
+                        .get(pos + 1..pos + 5)
+                        .ok_or("Truncated packet length")?;
+                    pos += 5;
+                    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+                }
+                _ => return Err("Partial body lengths are not supported".to_string()),
+            };
+            (ctb & 0x3f, len)
+        } else {
+            // Old format
+            let len = match ctb & 0x03 {
+                0 => {
+                    let b = *data.get(pos).ok_or("Truncated packet length")?;
+                    pos += 1;
+                    b as usize
+                }
+                1 => {
+                    let b = data.get(pos..pos + 2).ok_or("Truncated packet length")?;
+                    pos += 2;
+                    u16::from_be_bytes([b[0], b[1]]) as usize
+                }
+                2 => {
+                    let b = data.get(pos..pos + 4).ok_or("Truncated packet length")?;
+                    pos += 4;
+                    u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize
+                }
+                _ => data.len() - pos,
+            };
+            ((ctb >> 2) & 0x0f, len)
+        };
+
+        let body = data
+            .get(pos..pos + len)
+            .ok_or_else(|| format!("Truncated packet (tag {})", tag))?;
+        packets.push(Packet { tag, body });
+        pos += len;
+    }
+
+    Ok(packets)
+}
+
+/// Read a multiprecision integer, returning its bytes and the remaining data
+fn read_mpi(data: &[u8]) -> Result<(&[u8], &[u8]), String> {
+    let bits = data.get(..2).ok_or("Truncated MPI")?;
+    let len = (u16::from_be_bytes([bits[0], bits[1]]) as usize).div_ceil(8);
+    let value = data.get(2..2 + len).ok_or("Truncated MPI")?;
+    Ok((value, &data[2 + len..]))
+}
+
+/// Parse a v4 public key packet; `None` for unsupported key types
+fn parse_public_key(body: &[u8]) -> Result<Option<PublicKey>, String> {
+    if body.first() != Some(&4) {
+        return Ok(None);
+    }
+    let algo = *body.get(5).ok_or("Truncated key packet")?;
+    if !matches!(algo, 1 | 3) {
+        return Ok(None);
+    }
+
+    let (n, rest) = read_mpi(&body[6..])?;
+    let (e, _) = read_mpi(rest)?;
+    let key =
+        RsaPublicKey::new_with_max_size(BigUint::from_bytes_be(n), BigUint::from_bytes_be(e), 8192)
+            .map_err(|e| format!("Invalid RSA key: {}", e))?;
+
+    // v4 fingerprint: SHA-1 over 0x99, two-octet length and the packet body
+    let mut hasher = Sha1::new();
+    hasher.update([0x99]);
+    hasher.update((body.len() as u16).to_be_bytes());
+    hasher.update(body);
+
+    Ok(Some(PublicKey {
+        fingerprint: hasher.finalize().into(),
+        key,
+    }))
+}
+
+/// Parse a v4 signature packet
+fn parse_signature(body: &[u8]) -> Result<Signature, String> {
+    let version = *body.first().ok_or("Empty signature packet")?;
+    if version != 4 {
+        return Err(format!("Unsupported signature version {}", version));
+    }
+    if body.len() < 6 {
+        return Err("Truncated signature packet".to_string());
+    }
+
+    let hashed_len = u16::from_be_bytes([body[4], body[5]]) as usize;
+    let hashed_end = 6 + hashed_len;
+    let hashed = body.get(6..hashed_end).ok_or("Truncated hashed area")?;
+
+    let unhashed_len_bytes = body
+        .get(hashed_end..hashed_end + 2)
+        .ok_or("Truncated unhashed area")?;
+    let unhashed_len = u16::from_be_bytes([unhashed_len_bytes[0], unhashed_len_bytes[1]]) as usize;
+    let unhashed_end = hashed_end + 2 + unhashed_len;
+    let unhashed = body
+        .get(hashed_end + 2..unhashed_end)
+        .ok_or("Truncated unhashed area")?;
+
+    let left16 = body
+        .get(unhashed_end..unhashed_end + 2)
+        .ok_or("Truncated signature packet")?;
+    let (value, _) = read_mpi(&body[unhashed_end + 2..])?;
+
+    let mut signature = Signature {
+        sig_type: body[1],
+        pubkey_algo: body[2],
+        hash_algo: body[3],
+        hashed_area: body[..hashed_end].to_vec(),
+        issuer_key_id: None,
+        issuer_fingerprint: None,
+        left16: [left16[0], left16[1]],
+        value: value.to_vec(),
+    };
+
+    for area in [hashed, unhashed] {
+        for (kind, data) in parse_subpackets(area)? {
+            match kind {
+                SUBPACKET_ISSUER if data.len() == 8 => {
+                    signature.issuer_key_id = data.try_into().ok();
+                }
+                SUBPACKET_ISSUER_FINGERPRINT if data.len() == 21 && data[0] == 4 => {
+                    signature.issuer_fingerprint = data[1..].try_into().ok();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(signature)
+}
+
+/// Split a subpacket area into `(type, data)` pairs
+fn parse_subpackets(mut area: &[u8]) -> Result<Vec<(u8, &[u8])>, String> {
+    let mut subpackets = Vec::new();
+
+    while !area.is_empty() {
+        let first = area[0] as usize;
+        let (len, header) = match first {
+            0..=191 => (first, 1),
+            192..=254 => {
+                let second = *area.get(1).ok_or("Truncated subpacket")? as usize;
+                (((first - 192) << 8) + second + 192, 2)
+            }
+            _ => {
+                let b = area.get(1..5).ok_or("Truncated subpacket")?;
+                (u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize, 5)
+            }
+        };
+        if len == 0 {
+            return Err("Empty subpacket".to_string());
+        }
+
+        let sub = area
+            .get(header..header + len)
+            .ok_or("Truncated subpacket")?;
+        // Bit 7 of the type is the "critical" flag
+        subpackets.push((sub[0] & 0x7f, &sub[1..]));
+        area = &area[header + len..];
+    }
+
+    Ok(subpackets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = include_bytes!("testdata/test-key.asc");
+    const DATA: &[u8] = include_bytes!("testdata/signed.img.xz");
+    const SIG_SHA256: &[u8] = include_bytes!("testdata/signed.img.xz.asc");
+    const SIG_SHA512: &[u8] = include_bytes!("testdata/signed.img.xz.sha512.asc");
+    /// Armbian signing key, as checked by scripts/setup/fetch-signing-key.sh
+    const ARMBIAN_FINGERPRINT: &str = "DF00FAF1C577104B50BF1D0093D6889F9F0E78D5";
+
+    fn check(sig: &[u8], data: &[u8], keyring: &Keyring) -> SignatureStatus {
+        let mut pending = PendingSignature::new(sig).unwrap();
+        // Feed in pieces, as during a download
+        for chunk in data.chunks(5) {
+            pending.update(chunk);
+        }
+        pending.finish_with(keyring)
+    }
+
+    #[test]
+    fn test_keyring_parses_fingerprint() {
+        let keyring = Keyring::parse(KEY).unwrap();
+        assert_eq!(keyring.len(), 1);
+        assert_eq!(
+            hex::encode_upper(keyring.keys[0].fingerprint),
+            "67F91B629945D8C43DE4966DEDF072652AC85B34"
+        );
+    }
+
+    #[test]
+    fn test_valid_signatures() {
+        let keyring = Keyring::parse(KEY).unwrap();
+        assert_eq!(check(SIG_SHA256, DATA, &keyring), SignatureStatus::Valid);
+        assert_eq!(check(SIG_SHA512, DATA, &keyring), SignatureStatus::Valid);
+    }
+
+    #[test]
+    fn test_tampered_data_is_bad_signature() {
+        let keyring = Keyring::parse(KEY).unwrap();
+        let mut data = DATA.to_vec();
+        data[0] ^= 0x01;
+        assert_eq!(
+            check(SIG_SHA256, &data, &keyring),
+            SignatureStatus::BadSignature
+        );
+    }
+
+    #[test]
+    fn test_source_keyring_replaces_bundled_keyring() {
+        let mut pending = PendingSignature::new(SIG_SHA256).unwrap();
+        pending.keyring = Some(Arc::new(Keyring::parse(KEY).unwrap()));
+        pending.update(DATA);
+        assert_eq!(pending.finish(), SignatureStatus::Valid);
+    }
+
+    #[test]
+    fn test_bundled_keys_parse() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("keys");
+        for path in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = path.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("asc") {
+                let keyring = Keyring::parse(&std::fs::read(&path).unwrap()).unwrap();
+                assert!(!keyring.is_empty(), "{}", path.display());
+                if path.file_name().and_then(|n| n.to_str()) == Some("armbian.asc") {
+                    assert!(
+                        keyring
+                            .keys
+                            .iter()
+                            .any(|k| hex::encode_upper(k.fingerprint) == ARMBIAN_FINGERPRINT),
+                        "{} is not the Armbian signing key",
+                        path.display()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_key() {
+        assert_eq!(
+            check(SIG_SHA256, DATA, &Keyring::default()),
+            SignatureStatus::UnknownKey
+        );
+    }
+
+    #[test]
+    fn test_malformed_signature() {
+        assert!(PendingSignature::new(b"not a signature").is_err());
+        assert!(PendingSignature::new(KEY).is_err());
+    }
+}
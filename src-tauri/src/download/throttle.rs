@@ -0,0 +1,129 @@
+//! Download bandwidth limiting
+//!
+//! A single token bucket is shared by every transfer, so segmented, torrent
+//! and delta downloads together stay under the configured rate. The limit is
+//! read on every chunk and can be changed while a download is running.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::log_info;
+
+const MODULE: &str = "download::throttle";
+
+/// Longest single sleep, so a changed limit takes effect quickly
+const MAX_WAIT: Duration = Duration::from_millis(250);
+
+/// Configured limit in bytes per second (0 = unlimited)
+static RATE_LIMIT: AtomicU64 = AtomicU64::new(0);
+
+static BUCKET: Mutex<Option<Bucket>> = Mutex::new(None);
+
+/// Token bucket holding at most one second worth of bytes
+struct Bucket {
+    /// Available bytes; negative while transfers are ahead of the limit
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn new(now: Instant) -> Self {
+        Self {
+            tokens: 0.0,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant, rate: u64) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        self.last_refill = now;
+    }
+
+    /// Time until the bucket is no longer in debt
+    fn wait_time(&self, rate: u64) -> Duration {
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate as f64)
+        }
+    }
+}
+
+/// Set the download rate limit in bytes per second (0 = unlimited)
+pub fn set_rate_limit(bytes_per_sec: u64) {
+    let previous = RATE_LIMIT.swap(bytes_per_sec, Ordering::SeqCst);
+    if previous != bytes_per_sec {
+        log_info!(
+            MODULE,
+            "Download rate limit set to {} bytes/s",
+            bytes_per_sec
+        );
+        // Debt accumulated under the old limit does not carry over
+        if let Ok(mut bucket) = BUCKET.lock() {
+            *bucket = None;
+        }
+    }
+}
+
+/// Account for `bytes` received, sleeping while over the rate limit
+pub async fn consume(bytes: u64) {
+    if RATE_LIMIT.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+
+    let mut charged = false;
+    loop {
+        let rate = RATE_LIMIT.load(Ordering::SeqCst);
+        if rate == 0 {
+            return;
+        }
+
+        let wait = {
+            let Ok(mut guard) = BUCKET.lock() else {
+                return;
+            };
+            let now = Instant::now();
+            let bucket = guard.get_or_insert_with(|| Bucket::new(now));
+            bucket.refill(now, rate);
+            if !charged {
+                bucket.tokens -= bytes as f64;
+                charged = true;
+            }
+            bucket.wait_time(rate)
+        };
+
+        if wait.is_zero() {
+            return;
+        }
+        tokio::time::sleep(wait.min(MAX_WAIT)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_wait_matches_rate() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(start);
+        bucket.tokens -= 2000.0;
+        assert_eq!(bucket.wait_time(1000), Duration::from_secs(2));
+
+        // Half a second later, half a second worth of bytes is paid back
+        bucket.refill(start + Duration::from_millis(500), 1000);
+        assert_eq!(bucket.wait_time(1000), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_bucket_burst_is_capped() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(start);
+        bucket.refill(start + Duration::from_secs(60), 1000);
+        assert_eq!(bucket.tokens, 1000.0);
+    }
+}
@@ -0,0 +1,164 @@
+//! Minimal bencode decoder
+//!
+//! Decodes the subset of bencode used by `.torrent` files and tracker
+//! responses. Dictionaries keep their raw encoding so the info hash can be
+//! computed over the exact bytes that were received.
+
+use std::collections::BTreeMap;
+
+/// Maximum nesting depth accepted, to bound recursion on hostile input
+const MAX_DEPTH: usize = 32;
+
+/// Decoded bencode value borrowing from the input
+#[derive(Debug, PartialEq)]
+pub enum Value<'a> {
+    Int(i64),
+    Bytes(&'a [u8]),
+    List(Vec<Value<'a>>),
+    /// Entries and the raw encoding of the whole dictionary
+    Dict(BTreeMap<&'a [u8], Value<'a>>, &'a [u8]),
+}
+
+impl<'a> Value<'a> {
+    pub fn get(&self, key: &str) -> Option<&Value<'a>> {
+        match self {
+            Self::Dict(entries, _) => entries.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Self::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    pub fn as_list(&self) -> Option<&[Value<'a>]> {
+        match self {
+            Self::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Raw encoding of a dictionary
+    pub fn raw(&self) -> Option<&'a [u8]> {
+        match self {
+            Self::Dict(_, raw) => Some(raw),
+            _ => None,
+        }
+    }
+}
+
+/// Decode a complete bencoded document
+pub fn decode(data: &[u8]) -> Result<Value<'_>, String> {
+    let (value, used) = decode_at(data, 0, 0)?;
+    if used != data.len() {
+        return Err(format!("Trailing data after bencode value at {}", used));
+    }
+    Ok(value)
+}
+
+/// Decode the value starting at `pos`, returning it and the end offset
+fn decode_at(data: &[u8], pos: usize, depth: usize) -> Result<(Value<'_>, usize), String> {
+    if depth > MAX_DEPTH {
+        return Err("Bencode nesting too deep".to_string());
+    }
+
+    match data.get(pos) {
+        Some(b'i') => {
+            let end = find(data, pos + 1, b'e')?;
+            let text = std::str::from_utf8(&data[pos + 1..end])
+                .map_err(|_| format!("Invalid integer at {}", pos))?;
+            let value = text
+                .parse::<i64>()
+                .map_err(|_| format!("Invalid integer at {}", pos))?;
+            Ok((Value::Int(value), end + 1))
+        }
+        Some(b'l') => {
+            let mut items = Vec::new();
+            let mut cursor = pos + 1;
+            while data.get(cursor) != Some(&b'e') {
+                let (item, next) = decode_at(data, cursor, depth + 1)?;
+                items.push(item);
+                cursor = next;
+            }
+            Ok((Value::List(items), cursor + 1))
+        }
+        Some(b'd') => {
+            let mut entries = BTreeMap::new();
+            let mut cursor = pos + 1;
+            while data.get(cursor) != Some(&b'e') {
+                let (key, next) = decode_at(data, cursor, depth + 1)?;
+                let key = key
+                    .as_bytes()
+                    .ok_or_else(|| format!("Dictionary key is not a string at {}", cursor))?;
+                let (value, next) = decode_at(data, next, depth + 1)?;
+                entries.insert(key, value);
+                cursor = next;
+            }
+            Ok((Value::Dict(entries, &data[pos..cursor + 1]), cursor + 1))
+        }
+        Some(b'0'..=b'9') => {
+            let colon = find(data, pos, b':')?;
+            let len: usize = std::str::from_utf8(&data[pos..colon])
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| format!("Invalid string length at {}", pos))?;
+            let start = colon + 1;
+            let bytes = data
+                .get(start..start.saturating_add(len))
+                .ok_or_else(|| format!("Truncated string at {}", pos))?;
+            Ok((Value::Bytes(bytes), start + len))
+        }
+        Some(c) => Err(format!("Unexpected byte 0x{:02x} at {}", c, pos)),
+        None => Err("Unexpected end of bencode data".to_string()),
+    }
+}
+
+fn find(data: &[u8], from: usize, byte: u8) -> Result<usize, String> {
+    data.get(from..)
+        .and_then(|rest| rest.iter().position(|&b| b == byte))
+        .map(|i| from + i)
+        .ok_or_else(|| "Unexpected end of bencode data".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_nested() {
+        let data = b"d4:listli1ei-2ee4:name4:spam4:subdd1:ai0eee";
+        let value = decode(data).unwrap();
+
+        assert_eq!(value.get("name").and_then(|v| v.as_str()), Some("spam"));
+        let list = value.get("list").and_then(|v| v.as_list()).unwrap();
+        assert_eq!(list[1].as_int(), Some(-2));
+        assert_eq!(
+            value.get("subd").and_then(|v| v.raw()),
+            Some(&b"d1:ai0ee"[..])
+        );
+        assert_eq!(value.raw(), Some(&data[..]));
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        assert!(decode(b"i12").is_err());
+        assert!(decode(b"5:abc").is_err());
+        assert!(decode(b"di1ei2ee").is_err());
+        assert!(decode(b"i1ei2e").is_err());
+        assert!(decode(&[b'l'; 100]).is_err());
+    }
+}
@@ -0,0 +1,692 @@
+//! BitTorrent downloads
+//!
+//! Downloads an image through the `.torrent` file published next to it.
+//! Pieces come from the peers returned by the trackers and from web seeds
+//! (the torrent's `url-list` plus the HTTP mirrors), and every piece is
+//! checked against its SHA-1 before it is written to the preallocated file.
+//! Only downloading is implemented: no data is uploaded to other peers.
+
+mod bencode;
+mod peer;
+mod tracker;
+mod webseed;
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::Client;
+use sha1::{Digest, Sha1};
+
+use crate::config;
+use crate::http::retry;
+use crate::{log_debug, log_info, log_warn};
+
+use super::{throttle, DownloadState};
+use peer::Peer;
+
+const MODULE: &str = "download::torrent";
+
+/// Parsed single-file `.torrent` metainfo
+pub struct Metainfo {
+    pub name: String,
+    pub length: u64,
+    pub piece_length: u64,
+    pub info_hash: [u8; 20],
+    pieces: Vec<[u8; 20]>,
+    trackers: Vec<String>,
+    web_seeds: Vec<String>,
+}
+
+impl Metainfo {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let root = bencode::decode(data).map_err(|e| format!("Invalid torrent file: {}", e))?;
+        let info = root.get("info").ok_or("Torrent has no info dictionary")?;
+
+        if info.get("files").is_some() {
+            return Err("Multi-file torrents are not supported".to_string());
+        }
+        let name = info
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or("Torrent has no name")?
+            .to_string();
+        let length = info
+            .get("length")
+            .and_then(|v| v.as_int())
+            .and_then(|l| u64::try_from(l).ok())
+            .ok_or("Torrent has no length")?;
+        let piece_length = info
+            .get("piece length")
+            .and_then(|v| v.as_int())
+            .and_then(|l| u64::try_from(l).ok())
+            .filter(|l| *l > 0 && *l <= u32::MAX as u64)
+            .ok_or("Torrent has an invalid piece length")?;
+        let hashes = info
+            .get("pieces")
+            .and_then(|v| v.as_bytes())
+            .filter(|p| p.len() % 20 == 0)
+            .ok_or("Torrent has invalid piece hashes")?;
+        let pieces: Vec<[u8; 20]> = hashes
+            .chunks_exact(20)
+            .map(|c| c.try_into().unwrap_or([0u8; 20]))
+            .collect();
+
+        if pieces.len() as u64 != length.div_ceil(piece_length) {
+            return Err(format!(
+                "Torrent lists {} pieces for {} bytes",
+                pieces.len(),
+                length
+            ));
+        }
+
+        // Raw bytes of the info dictionary, as received
+        let info_raw = info.raw().ok_or("Torrent info is not a dictionary")?;
+        let info_hash: [u8; 20] = Sha1::digest(info_raw).into();
+
+        let mut trackers: Vec<String> = root
+            .get("announce")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .into_iter()
+            .collect();
+        // BEP 12: list of tiers, each a list of tracker URLs
+        for tier in root
+            .get("announce-list")
+            .and_then(|v| v.as_list())
+            .unwrap_or_default()
+        {
+            for tracker in tier.as_list().unwrap_or_default() {
+                if let Some(url) = tracker.as_str() {
+                    if !trackers.iter().any(|t| t == url) {
+                        trackers.push(url.to_string());
+                    }
+                }
+            }
+        }
+
+        // BEP 19: a single URL or a list of URLs
+        let web_seeds = match root.get("url-list") {
+            Some(bencode::Value::List(urls)) => urls
+                .iter()
+                .filter_map(|u| u.as_str())
+                .map(|u| u.to_string())
+                .collect(),
+            Some(value) => value
+                .as_str()
+                .map(|u| vec![u.to_string()])
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            name,
+            length,
+            piece_length,
+            info_hash,
+            pieces,
+            trackers,
+            web_seeds,
+        })
+    }
+
+    fn piece_count(&self) -> usize {
+        self.pieces.len()
+    }
+
+    fn piece_offset(&self, index: u32) -> u64 {
+        index as u64 * self.piece_length
+    }
+
+    /// Size of a piece (the last one may be shorter)
+    fn piece_size(&self, index: u32) -> u64 {
+        std::cmp::min(self.piece_length, self.length - self.piece_offset(index))
+    }
+}
+
+/// Random-looking peer ID in the Azureus style (`-AI0100-` + 12 characters)
+fn generate_peer_id() -> [u8; 20] {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let seed = Sha1::digest(format!("{}-{}", nanos, std::process::id()));
+
+    let mut id = [0u8; 20];
+    id[..8].copy_from_slice(b"-AI0100-");
+    id[8..].copy_from_slice(hex::encode(&seed[..6]).as_bytes());
+    id
+}
+
+/// Where pieces are downloaded from
+enum Source {
+    Peer(SocketAddr),
+    WebSeed(String),
+}
+
+/// Failure while storing a piece
+enum PieceError {
+    /// Data did not match the piece hash: the source is not trusted further
+    Corrupt,
+    /// Local I/O problem: the whole download must stop
+    Fatal(String),
+}
+
+/// Shared state of one torrent download
+struct Swarm<'a> {
+    client: &'a Client,
+    meta: &'a Metainfo,
+    temp_path: &'a Path,
+    state: &'a Arc<DownloadState>,
+    queue: Mutex<VecDeque<u32>>,
+    /// Pieces currently being fetched by a worker
+    in_flight: AtomicU32,
+    completed: AtomicU32,
+    aborted: AtomicBool,
+}
+
+impl Swarm<'_> {
+    /// Take the first queued piece accepted by `available`
+    fn take(&self, available: impl Fn(u32) -> bool) -> Option<u32> {
+        let mut queue = self.queue.lock().ok()?;
+        let position = queue.iter().position(|&i| available(i))?;
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        queue.remove(position)
+    }
+
+    /// Put a piece back after a failed attempt
+    fn give_back(&self, index: u32) {
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.push_front(index);
+        }
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn is_complete(&self) -> bool {
+        self.completed.load(Ordering::SeqCst) as usize == self.meta.piece_count()
+    }
+
+    /// Verify a piece against its hash and write it at its offset
+    fn store(&self, file: &mut File, index: u32, data: &[u8]) -> Result<(), PieceError> {
+        let hash: [u8; 20] = Sha1::digest(data).into();
+        if hash != self.meta.pieces[index as usize] {
+            self.give_back(index);
+            return Err(PieceError::Corrupt);
+        }
+
+        file.seek(SeekFrom::Start(self.meta.piece_offset(index)))
+            .and_then(|_| file.write_all(data))
+            .map_err(|e| PieceError::Fatal(format!("Failed to write piece: {}", e)))?;
+
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.completed.fetch_add(1, Ordering::SeqCst);
+        self.state
+            .downloaded_bytes
+            .fetch_add(data.len() as u64, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Download the image at `url` through its `.torrent` file into `temp_path`
+///
+/// `mirrors` are HTTP mirrors of the image, used as web seeds along with
+/// the torrent's own `url-list` and `url` itself.
+pub async fn download(
+    client: &Client,
+    url: &str,
+    mirrors: &[String],
+    temp_path: &Path,
+    state: &Arc<DownloadState>,
+) -> Result<(), String> {
+    let torrent_url = format!("{}.torrent", url);
+    log_info!(MODULE, "Fetching torrent: {}", torrent_url);
+
+    let response = retry::send("Torrent fetch", || client.get(&torrent_url))
+        .await
+        .map_err(|e| format!("Failed to fetch torrent: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Torrent not available ({})", response.status()));
+    }
+    let data = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read torrent: {}", e))?;
+    let meta = Metainfo::parse(&data)?;
+
+    log_info!(
+        MODULE,
+        "Torrent {}: {} bytes in {} pieces of {} bytes",
+        meta.name,
+        meta.length,
+        meta.piece_count(),
+        meta.piece_length
+    );
+
+    let file = File::create(temp_path).map_err(|e| format!("Failed to create temp file: {}", e))?;
+    file.set_len(meta.length)
+        .map_err(|e| format!("Failed to preallocate temp file: {}", e))?;
+    drop(file);
+
+    state.total_bytes.store(meta.length, Ordering::SeqCst);
+    state.downloaded_bytes.store(0, Ordering::SeqCst);
+
+    let mut web_seeds: Vec<String> = meta
+        .web_seeds
+        .iter()
+        .map(|seed| webseed::file_url(seed, &meta.name))
+        .chain(mirrors.iter().cloned())
+        .chain(std::iter::once(url.to_string()))
+        .collect();
+    web_seeds.dedup();
+
+    let peer_id = generate_peer_id();
+    let swarm = Swarm {
+        client,
+        meta: &meta,
+        temp_path,
+        state,
+        queue: Mutex::new((0..meta.piece_count() as u32).collect()),
+        in_flight: AtomicU32::new(0),
+        completed: AtomicU32::new(0),
+        aborted: AtomicBool::new(false),
+    };
+
+    for round in 0..config::download::TORRENT_ANNOUNCE_ROUNDS {
+        if swarm.is_complete() {
+            break;
+        }
+
+        let left = meta.length - state.downloaded_bytes.load(Ordering::SeqCst);
+        let mut peers = announce_all(client, &meta, &peer_id, left).await;
+        peers.truncate(config::download::TORRENT_MAX_PEERS);
+        log_info!(
+            MODULE,
+            "Round {}: {} peers, {} web seeds",
+            round + 1,
+            peers.len(),
+            web_seeds.len()
+        );
+
+        let sources = peers
+            .into_iter()
+            .map(Source::Peer)
+            .chain(web_seeds.iter().cloned().map(Source::WebSeed));
+        let results =
+            futures_util::future::join_all(sources.map(|source| run_worker(&swarm, source))).await;
+
+        if let Some(err) = results.into_iter().find_map(|r| r.err()) {
+            return Err(err);
+        }
+    }
+
+    if !swarm.is_complete() {
+        return Err(format!(
+            "Torrent incomplete: {} of {} pieces downloaded",
+            swarm.completed.load(Ordering::SeqCst),
+            meta.piece_count()
+        ));
+    }
+
+    log_info!(MODULE, "Torrent download complete: {}", meta.name);
+    Ok(())
+}
+
+/// Ask every tracker for peers, merging the answers
+async fn announce_all(
+    client: &Client,
+    meta: &Metainfo,
+    peer_id: &[u8; 20],
+    left: u64,
+) -> Vec<SocketAddr> {
+    let answers = futures_util::future::join_all(
+        meta.trackers
+            .iter()
+            .map(|t| tracker::announce(client, t, &meta.info_hash, peer_id, left)),
+    )
+    .await;
+
+    let mut peers = Vec::new();
+    for (tracker, answer) in meta.trackers.iter().zip(answers) {
+        match answer {
+            Ok(found) => {
+                log_debug!(MODULE, "Tracker {} returned {} peers", tracker, found.len());
+                for peer in found {
+                    if !peers.contains(&peer) {
+                        peers.push(peer);
+                    }
+                }
+            }
+            Err(e) => log_debug!(MODULE, "Tracker {} failed: {}", tracker, e),
+        }
+    }
+    peers
+}
+
+/// Take pieces from the queue and download them from one source
+///
+/// Returns `Err` only for fatal conditions (cancellation, local I/O errors).
+/// Sources that misbehave are abandoned and their piece is put back.
+async fn run_worker(swarm: &Swarm<'_>, source: Source) -> Result<(), String> {
+    let (mut peer, label, max_failures) = match &source {
+        Source::Peer(addr) => {
+            let connected = async {
+                let mut peer = Peer::connect(
+                    *addr,
+                    &swarm.meta.info_hash,
+                    &generate_peer_id(),
+                    swarm.meta.piece_count(),
+                )
+                .await?;
+                peer.wait_ready().await?;
+                Ok::<_, String>(peer)
+            };
+            match connected.await {
+                Ok(peer) => (Some(peer), addr.to_string(), 1),
+                Err(e) => {
+                    log_debug!(MODULE, "Peer {} unavailable: {}", addr, e);
+                    return Ok(());
+                }
+            }
+        }
+        Source::WebSeed(url) => (None, url.clone(), config::download::MAX_MIRROR_FAILURES),
+    };
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(swarm.temp_path)
+        .map_err(|e| format!("Failed to open temp file: {}", e))?;
+    let mut failures = 0;
+
+    loop {
+        if swarm.state.is_cancelled.load(Ordering::SeqCst) {
+            swarm.aborted.store(true, Ordering::SeqCst);
+            return Err("Download cancelled".to_string());
+        }
+        if swarm.aborted.load(Ordering::SeqCst) || swarm.is_complete() {
+            return Ok(());
+        }
+
+        let taken = swarm.take(|i| peer.as_ref().is_none_or(|p| p.has(i)));
+        let Some(index) = taken else {
+            // Nothing this source can serve; wait while other workers
+            // still hold pieces that may be handed back
+            if swarm.in_flight.load(Ordering::SeqCst) == 0 {
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            continue;
+        };
+
+        let size = swarm.meta.piece_size(index);
+        let fetched = match (&mut peer, &source) {
+            (Some(peer), _) => peer.download_piece(index, size as u32).await,
+            (None, Source::WebSeed(url)) => {
+                webseed::fetch_range(swarm.client, url, swarm.meta.piece_offset(index), size).await
+            }
+            (None, Source::Peer(_)) => Err("Peer not connected".to_string()),
+        };
+
+        let error = match fetched {
+            Ok(data) => {
+                throttle::consume(data.len() as u64).await;
+                swarm.state.wait_while_paused().await;
+                match swarm.store(&mut file, index, &data) {
+                    Ok(()) => continue,
+                    Err(PieceError::Fatal(e)) => {
+                        swarm.aborted.store(true, Ordering::SeqCst);
+                        return Err(e);
+                    }
+                    Err(PieceError::Corrupt) => format!("piece {} failed its hash check", index),
+                }
+            }
+            Err(e) => {
+                swarm.give_back(index);
+                e
+            }
+        };
+
+        failures += 1;
+        log_warn!(
+            MODULE,
+            "Source {} failed ({}/{}): {}",
+            label,
+            failures,
+            max_failures,
+            error
+        );
+        if failures >= max_failures {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const PIECE_LENGTH: usize = 32 * 1024;
+
+    fn test_image() -> Vec<u8> {
+        (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    fn info_dict(data: &[u8]) -> Vec<u8> {
+        let mut pieces = Vec::new();
+        for chunk in data.chunks(PIECE_LENGTH) {
+            pieces.extend_from_slice(&Sha1::digest(chunk));
+        }
+        let mut info = format!(
+            "d6:lengthi{}e4:name7:img.bin12:piece lengthi{}e6:pieces{}:",
+            data.len(),
+            PIECE_LENGTH,
+            pieces.len()
+        )
+        .into_bytes();
+        info.extend_from_slice(&pieces);
+        info.push(b'e');
+        info
+    }
+
+    fn torrent_file(announce: &str, info: &[u8]) -> Vec<u8> {
+        let mut torrent = format!("d8:announce{}:{}4:info", announce.len(), announce).into_bytes();
+        torrent.extend_from_slice(info);
+        torrent.push(b'e');
+        torrent
+    }
+
+    /// Minimal HTTP server with byte range support, serving fixed paths
+    fn serve_http(listener: TcpListener, routes: HashMap<&'static str, Vec<u8>>) {
+        let routes = Arc::new(routes);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let routes = routes.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request).to_string();
+                    let target = request.split_whitespace().nth(1).unwrap_or("/");
+                    let path = target.split('?').next().unwrap_or(target);
+                    let range = request.lines().find_map(|l| {
+                        let (start, end) = l.strip_prefix("range: bytes=")?.split_once('-')?;
+                        Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?))
+                    });
+
+                    let response = match (routes.get(path), range) {
+                        (Some(body), Some((start, end))) => {
+                            let mut r = format!(
+                                "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                                end + 1 - start,
+                                start,
+                                end,
+                                body.len()
+                            )
+                            .into_bytes();
+                            r.extend_from_slice(&body[start..=end]);
+                            r
+                        }
+                        (Some(body), None) => {
+                            let mut r = format!(
+                                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                                body.len()
+                            )
+                            .into_bytes();
+                            r.extend_from_slice(body);
+                            r
+                        }
+                        (None, _) => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                    };
+                    let _ = socket.write_all(&response).await;
+                });
+            }
+        });
+    }
+
+    /// Seeder holding the complete image
+    async fn serve_peer(data: Vec<u8>, info_hash: [u8; 20]) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let data = Arc::new(data);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let data = data.clone();
+                tokio::spawn(async move {
+                    let mut handshake = [0u8; 68];
+                    if socket.read_exact(&mut handshake).await.is_err() {
+                        return;
+                    }
+                    let mut reply = handshake;
+                    reply[28..48].copy_from_slice(&info_hash);
+                    reply[48..].copy_from_slice(b"-TS0001-seeder000000");
+                    let _ = socket.write_all(&reply).await;
+
+                    let pieces = data.len().div_ceil(PIECE_LENGTH);
+                    let mut bitfield = vec![0u8; pieces.div_ceil(8)];
+                    for i in 0..pieces {
+                        bitfield[i / 8] |= 0x80 >> (i % 8);
+                    }
+                    let mut hello = ((bitfield.len() + 1) as u32).to_be_bytes().to_vec();
+                    hello.push(5);
+                    hello.extend_from_slice(&bitfield);
+                    hello.extend_from_slice(&[0, 0, 0, 1, 1]);
+                    let _ = socket.write_all(&hello).await;
+
+                    loop {
+                        let mut len = [0u8; 4];
+                        if socket.read_exact(&mut len).await.is_err() {
+                            return;
+                        }
+                        let mut payload = vec![0u8; u32::from_be_bytes(len) as usize];
+                        if socket.read_exact(&mut payload).await.is_err() {
+                            return;
+                        }
+                        if payload.first() != Some(&6) {
+                            continue;
+                        }
+                        let field = |i: usize| {
+                            u32::from_be_bytes(payload[i..i + 4].try_into().unwrap()) as usize
+                        };
+                        let (index, begin, size) = (field(1), field(5), field(9));
+                        let start = index * PIECE_LENGTH + begin;
+
+                        let mut message = ((size + 9) as u32).to_be_bytes().to_vec();
+                        message.push(7);
+                        message.extend_from_slice(&payload[1..9]);
+                        message.extend_from_slice(&data[start..start + size]);
+                        if socket.write_all(&message).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    /// Serve the torrent (and optionally the image) over HTTP and download it
+    async fn run_download(data: &[u8], peers: Vec<u8>, serve_image: bool) -> Vec<u8> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let http = listener.local_addr().unwrap();
+        let url = format!("http://{}/img.bin", http);
+        let info = info_dict(data);
+
+        let mut routes = HashMap::from([
+            (
+                "/img.bin.torrent",
+                torrent_file(&format!("http://{}/announce", http), &info),
+            ),
+            ("/announce", peers),
+        ]);
+        if serve_image {
+            routes.insert("/img.bin", data.to_vec());
+        }
+        serve_http(listener, routes);
+
+        let client = Client::builder().no_proxy().build().unwrap();
+        let temp_path = std::env::temp_dir().join(format!(
+            "armbian-torrent-test-{}-{}",
+            std::process::id(),
+            serve_image
+        ));
+        let state = Arc::new(DownloadState::new());
+
+        download(&client, &url, &[], &temp_path, &state)
+            .await
+            .unwrap();
+        assert_eq!(
+            state.downloaded_bytes.load(Ordering::SeqCst),
+            data.len() as u64
+        );
+
+        let written = std::fs::read(&temp_path).unwrap();
+        let _ = std::fs::remove_file(&temp_path);
+        written
+    }
+
+    #[test]
+    fn test_metainfo_info_hash() {
+        let data = test_image();
+        let info = info_dict(&data);
+        let meta = Metainfo::parse(&torrent_file("http://t/announce", &info)).unwrap();
+
+        assert_eq!(meta.info_hash, <[u8; 20]>::from(Sha1::digest(&info)));
+        assert_eq!(meta.piece_count(), 4);
+        assert_eq!(meta.piece_size(3), 100_000 - 3 * PIECE_LENGTH as u64);
+        assert_eq!(meta.trackers, vec!["http://t/announce".to_string()]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_download_from_local_tracker_and_seeder() {
+        let data = test_image();
+        let info_hash: [u8; 20] = Sha1::digest(info_dict(&data)).into();
+        let seeder = serve_peer(data.clone(), info_hash).await;
+
+        let mut peers = b"d8:intervali60e5:peers6:".to_vec();
+        peers.extend_from_slice(&[127, 0, 0, 1]);
+        peers.extend_from_slice(&seeder.port().to_be_bytes());
+        peers.push(b'e');
+
+        // The image is not served over HTTP: every piece comes from the seeder
+        assert_eq!(run_download(&data, peers, false).await, data);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_download_from_web_seed() {
+        let data = test_image();
+        let peers = b"d8:intervali60e5:peers0:e".to_vec();
+        assert_eq!(run_download(&data, peers, true).await, data);
+    }
+}
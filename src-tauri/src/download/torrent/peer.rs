@@ -0,0 +1,248 @@
+//! BitTorrent peer wire protocol (BEP 3), download side only
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::config;
+
+const PROTOCOL: &[u8] = b"BitTorrent protocol";
+
+/// Largest message accepted from a peer (a block plus headroom for bitfields)
+const MAX_MESSAGE_LEN: usize = 2 * 1024 * 1024;
+
+const MSG_CHOKE: u8 = 0;
+const MSG_UNCHOKE: u8 = 1;
+const MSG_INTERESTED: u8 = 2;
+const MSG_HAVE: u8 = 4;
+const MSG_BITFIELD: u8 = 5;
+const MSG_REQUEST: u8 = 6;
+const MSG_PIECE: u8 = 7;
+
+/// Message received from a peer
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    KeepAlive,
+    Choke,
+    Unchoke,
+    Have(u32),
+    Bitfield(Vec<u8>),
+    Piece {
+        index: u32,
+        begin: u32,
+        data: Vec<u8>,
+    },
+    /// Messages a leecher does not act on (interested, request, cancel, ...)
+    Other(u8),
+}
+
+/// Parse a message payload (without its length prefix)
+pub fn parse_message(payload: &[u8]) -> Result<Message, String> {
+    let Some((&id, body)) = payload.split_first() else {
+        return Ok(Message::KeepAlive);
+    };
+    let u32_at = |offset: usize| -> Result<u32, String> {
+        body.get(offset..offset + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| format!("Truncated message {}", id))
+    };
+
+    Ok(match id {
+        MSG_CHOKE => Message::Choke,
+        MSG_UNCHOKE => Message::Unchoke,
+        MSG_HAVE => Message::Have(u32_at(0)?),
+        MSG_BITFIELD => Message::Bitfield(body.to_vec()),
+        MSG_PIECE => Message::Piece {
+            index: u32_at(0)?,
+            begin: u32_at(4)?,
+            data: body[8..].to_vec(),
+        },
+        other => Message::Other(other),
+    })
+}
+
+/// Connection to one peer
+pub struct Peer {
+    pub addr: SocketAddr,
+    stream: TcpStream,
+    bitfield: Vec<u8>,
+    choked: bool,
+}
+
+impl Peer {
+    /// Connect, exchange handshakes and declare interest
+    pub async fn connect(
+        addr: SocketAddr,
+        info_hash: &[u8; 20],
+        peer_id: &[u8; 20],
+        piece_count: usize,
+    ) -> Result<Self, String> {
+        let io_timeout = Duration::from_secs(config::download::TORRENT_PEER_TIMEOUT_SECS);
+        let mut stream = timeout(io_timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| "Connection timed out".to_string())?
+            .map_err(|e| format!("Connection failed: {}", e))?;
+
+        let mut handshake = Vec::with_capacity(68);
+        handshake.push(PROTOCOL.len() as u8);
+        handshake.extend_from_slice(PROTOCOL);
+        handshake.extend_from_slice(&[0u8; 8]);
+        handshake.extend_from_slice(info_hash);
+        handshake.extend_from_slice(peer_id);
+        stream
+            .write_all(&handshake)
+            .await
+            .map_err(|e| format!("Handshake failed: {}", e))?;
+
+        let mut reply = [0u8; 68];
+        timeout(io_timeout, stream.read_exact(&mut reply))
+            .await
+            .map_err(|_| "Handshake timed out".to_string())?
+            .map_err(|e| format!("Handshake failed: {}", e))?;
+        if reply[0] as usize != PROTOCOL.len() || &reply[1..20] != PROTOCOL {
+            return Err("Peer does not speak the BitTorrent protocol".to_string());
+        }
+        if &reply[28..48] != info_hash {
+            return Err("Peer serves a different torrent".to_string());
+        }
+
+        let mut peer = Self {
+            addr,
+            stream,
+            bitfield: vec![0u8; piece_count.div_ceil(8)],
+            choked: true,
+        };
+        peer.send(MSG_INTERESTED, &[]).await?;
+        Ok(peer)
+    }
+
+    /// Whether the peer announced it has `piece`
+    pub fn has(&self, piece: u32) -> bool {
+        let byte = piece as usize / 8;
+        let bit = 7 - (piece % 8);
+        self.bitfield.get(byte).is_some_and(|b| (b >> bit) & 1 == 1)
+    }
+
+    async fn send(&mut self, id: u8, payload: &[u8]) -> Result<(), String> {
+        let mut message = Vec::with_capacity(5 + payload.len());
+        message.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+        message.push(id);
+        message.extend_from_slice(payload);
+        self.stream
+            .write_all(&message)
+            .await
+            .map_err(|e| format!("Failed to send to peer: {}", e))
+    }
+
+    /// Read the next message and apply choke/have/bitfield updates
+    pub async fn receive(&mut self) -> Result<Message, String> {
+        let io_timeout = Duration::from_secs(config::download::TORRENT_PEER_TIMEOUT_SECS);
+        let mut len = [0u8; 4];
+        timeout(io_timeout, self.stream.read_exact(&mut len))
+            .await
+            .map_err(|_| "Peer timed out".to_string())?
+            .map_err(|e| format!("Peer connection lost: {}", e))?;
+
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_MESSAGE_LEN {
+            return Err(format!("Peer sent an oversized message ({} bytes)", len));
+        }
+        let mut payload = vec![0u8; len];
+        timeout(io_timeout, self.stream.read_exact(&mut payload))
+            .await
+            .map_err(|_| "Peer timed out".to_string())?
+            .map_err(|e| format!("Peer connection lost: {}", e))?;
+
+        let message = parse_message(&payload)?;
+        match &message {
+            Message::Choke => self.choked = true,
+            Message::Unchoke => self.choked = false,
+            Message::Have(piece) => {
+                if let Some(byte) = self.bitfield.get_mut(*piece as usize / 8) {
+                    *byte |= 0x80 >> (piece % 8);
+                }
+            }
+            Message::Bitfield(bits) => {
+                let len = self.bitfield.len();
+                self.bitfield = bits.clone();
+                self.bitfield.resize(len, 0);
+            }
+            _ => {}
+        }
+        Ok(message)
+    }
+
+    /// Process messages until the peer has at least one piece and unchokes us
+    pub async fn wait_ready(&mut self) -> Result<(), String> {
+        while self.choked || self.bitfield.iter().all(|b| *b == 0) {
+            self.receive().await?;
+        }
+        Ok(())
+    }
+
+    /// Download one piece, keeping several block requests in flight
+    pub async fn download_piece(&mut self, index: u32, length: u32) -> Result<Vec<u8>, String> {
+        let block_size = config::download::TORRENT_BLOCK_SIZE;
+        let mut data = vec![0u8; length as usize];
+        let mut next_offset = 0u32;
+        let mut received = 0u32;
+        let mut in_flight = 0usize;
+
+        while received < length {
+            if self.choked {
+                return Err("Peer choked us mid-piece".to_string());
+            }
+            while in_flight < config::download::TORRENT_PIPELINE_DEPTH && next_offset < length {
+                let size = std::cmp::min(block_size, length - next_offset);
+                let mut request = Vec::with_capacity(12);
+                request.extend_from_slice(&index.to_be_bytes());
+                request.extend_from_slice(&next_offset.to_be_bytes());
+                request.extend_from_slice(&size.to_be_bytes());
+                self.send(MSG_REQUEST, &request).await?;
+                next_offset += size;
+                in_flight += 1;
+            }
+
+            if let Message::Piece {
+                index: got,
+                begin,
+                data: block,
+            } = self.receive().await?
+            {
+                let end = begin as usize + block.len();
+                if got != index || end > data.len() {
+                    return Err("Peer sent an unexpected block".to_string());
+                }
+                data[begin as usize..end].copy_from_slice(&block);
+                received += block.len() as u32;
+                in_flight = in_flight.saturating_sub(1);
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_messages() {
+        assert_eq!(parse_message(&[]).unwrap(), Message::KeepAlive);
+        assert_eq!(parse_message(&[1]).unwrap(), Message::Unchoke);
+        assert_eq!(parse_message(&[4, 0, 0, 1, 2]).unwrap(), Message::Have(258));
+        assert_eq!(
+            parse_message(&[7, 0, 0, 0, 3, 0, 0, 0, 16, 0xaa]).unwrap(),
+            Message::Piece {
+                index: 3,
+                begin: 16,
+                data: vec![0xaa]
+            }
+        );
+        assert!(parse_message(&[7, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! HTTP tracker announces (BEP 3, compact peer lists from BEP 23)
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use reqwest::Client;
+
+use crate::config;
+use crate::log_debug;
+
+use super::bencode;
+
+const MODULE: &str = "download::torrent::tracker";
+
+/// Percent-encode raw bytes for a tracker query string
+fn url_encode(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Announce to an HTTP tracker and return the peers it knows about
+///
+/// UDP trackers are not supported and yield an error.
+pub async fn announce(
+    client: &Client,
+    tracker: &str,
+    info_hash: &[u8; 20],
+    peer_id: &[u8; 20],
+    left: u64,
+) -> Result<Vec<SocketAddr>, String> {
+    if !tracker.starts_with("http://") && !tracker.starts_with("https://") {
+        return Err(format!("Unsupported tracker protocol: {}", tracker));
+    }
+
+    let separator = if tracker.contains('?') { '&' } else { '?' };
+    let url = format!(
+        "{}{}info_hash={}&peer_id={}&port={}&uploaded=0&downloaded=0&left={}&compact=1&event=started",
+        tracker,
+        separator,
+        url_encode(info_hash),
+        url_encode(peer_id),
+        config::download::TORRENT_LISTEN_PORT,
+        left
+    );
+    log_debug!(MODULE, "Announcing to {}", tracker);
+
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Tracker request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Tracker returned status {}", response.status()));
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read tracker response: {}", e))?;
+
+    parse_response(&body)
+}
+
+/// Parse a tracker announce response
+fn parse_response(body: &[u8]) -> Result<Vec<SocketAddr>, String> {
+    let value = bencode::decode(body).map_err(|e| format!("Invalid tracker response: {}", e))?;
+    if let Some(reason) = value.get("failure reason").and_then(|v| v.as_str()) {
+        return Err(format!("Tracker error: {}", reason));
+    }
+
+    let mut peers = Vec::new();
+    match value.get("peers") {
+        // Compact form: 4-byte IPv4 address + 2-byte port per peer
+        Some(bencode::Value::Bytes(compact)) => {
+            for entry in compact.chunks_exact(6) {
+                let ip = Ipv4Addr::new(entry[0], entry[1], entry[2], entry[3]);
+                let port = u16::from_be_bytes([entry[4], entry[5]]);
+                peers.push(SocketAddr::new(ip.into(), port));
+            }
+        }
+        // Dictionary form: [{ "ip": "...", "port": N }, ...]
+        Some(bencode::Value::List(list)) => {
+            for entry in list {
+                let ip = entry
+                    .get("ip")
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| s.parse().ok());
+                let port = entry
+                    .get("port")
+                    .and_then(|v| v.as_int())
+                    .and_then(|p| u16::try_from(p).ok());
+                if let (Some(ip), Some(port)) = (ip, port) {
+                    peers.push(SocketAddr::new(ip, port));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    // Compact IPv6 peers (BEP 7): 16-byte address + 2-byte port
+    if let Some(compact) = value.get("peers6").and_then(|v| v.as_bytes()) {
+        for entry in compact.chunks_exact(18) {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&entry[..16]);
+            let port = u16::from_be_bytes([entry[16], entry[17]]);
+            peers.push(SocketAddr::new(Ipv6Addr::from(octets).into(), port));
+        }
+    }
+
+    peers.retain(|p| p.port() != 0);
+    Ok(peers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compact_and_dict_peers() {
+        let mut body = b"d8:intervali900e5:peers12:".to_vec();
+        body.extend_from_slice(&[127, 0, 0, 1, 0x1a, 0xe1, 10, 0, 0, 2, 0, 80]);
+        body.push(b'e');
+        let peers = parse_response(&body).unwrap();
+        assert_eq!(
+            peers,
+            vec![
+                "127.0.0.1:6881".parse().unwrap(),
+                "10.0.0.2:80".parse().unwrap()
+            ]
+        );
+
+        let body = b"d5:peersld2:ip9:127.0.0.14:porti7000eeee";
+        assert_eq!(
+            parse_response(body).unwrap(),
+            vec!["127.0.0.1:7000".parse().unwrap()]
+        );
+
+        assert!(parse_response(b"d14:failure reason4:nopee").is_err());
+    }
+
+    #[test]
+    fn test_url_encode() {
+        assert_eq!(url_encode(&[0x12, b'a', b' ', 0xff]), "%12a%20%FF");
+    }
+}
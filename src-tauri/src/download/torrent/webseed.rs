@@ -0,0 +1,60 @@
+//! HTTP web seeds (BEP 19)
+//!
+//! Web seeds serve torrent pieces as plain byte ranges of the file, so the
+//! regular HTTP mirrors can take part in a torrent download.
+
+use std::time::Duration;
+
+use reqwest::header::RANGE;
+use reqwest::{Client, StatusCode};
+
+use crate::config;
+
+/// URL of the file on a web seed
+///
+/// For single-file torrents a URL ending in `/` names a directory that
+/// contains the file; otherwise it is the file itself.
+pub fn file_url(seed: &str, name: &str) -> String {
+    if seed.ends_with('/') {
+        format!("{}{}", seed, name)
+    } else {
+        seed.to_string()
+    }
+}
+
+/// Fetch `length` bytes at `offset` from a web seed
+pub async fn fetch_range(
+    client: &Client,
+    url: &str,
+    offset: u64,
+    length: u64,
+) -> Result<Vec<u8>, String> {
+    let response = client
+        .get(url)
+        .header(RANGE, format!("bytes={}-{}", offset, offset + length - 1))
+        .send()
+        .await
+        .map_err(|e| format!("Web seed request failed: {}", e))?;
+
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(format!(
+            "Web seed does not serve ranges ({})",
+            response.status()
+        ));
+    }
+
+    let idle = Duration::from_secs(config::download::TORRENT_PEER_TIMEOUT_SECS);
+    let body = tokio::time::timeout(idle, response.bytes())
+        .await
+        .map_err(|_| "Web seed timed out".to_string())?
+        .map_err(|e| format!("Web seed transfer failed: {}", e))?;
+
+    if body.len() as u64 != length {
+        return Err(format!(
+            "Web seed returned {} bytes, expected {}",
+            body.len(),
+            length
+        ));
+    }
+    Ok(body.to_vec())
+}
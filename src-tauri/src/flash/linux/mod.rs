@@ -8,4 +8,4 @@ mod privileges;
 mod writer;
 
 pub use privileges::request_authorization;
-pub use writer::flash_image;
+pub use writer::{flash_image, flash_source};
@@ -4,14 +4,14 @@
 //! UDisks2 handles authentication via polkit, so the app can run as a normal user.
 
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Seek, SeekFrom, Write};
 use std::os::unix::io::{AsRawFd, FromRawFd};
-use std::path::PathBuf;
+use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::config;
-use crate::flash::{sync_device, unmount_device, FlashState};
+use crate::flash::{sync_device, unmount_device, FlashState, ImageSource};
 use crate::utils::{bytes_to_gb, ProgressTracker};
 use crate::{log_debug, log_error, log_info};
 
@@ -84,7 +84,18 @@ fn open_device_direct(device_path: &str) -> Result<File, String> {
 
 /// Flash an image to a block device
 pub async fn flash_image(
-    image_path: &PathBuf,
+    image_path: &Path,
+    device_path: &str,
+    state: Arc<FlashState>,
+    verify: bool,
+) -> Result<(), String> {
+    let source = ImageSource::open(image_path, &state)?;
+    flash_source(source, device_path, state, verify).await
+}
+
+/// Flash an image source (file or stream) to a block device
+pub async fn flash_source(
+    mut source: ImageSource,
     device_path: &str,
     state: Arc<FlashState>,
     verify: bool,
@@ -94,15 +105,11 @@ pub async fn flash_image(
     log_info!(
         MODULE,
         "Starting flash: {} -> {}",
-        image_path.display(),
+        source.describe(),
         device_path
     );
 
-    // Get image size
-    let image_size = std::fs::metadata(image_path)
-        .map_err(|e| format!("Failed to get image size: {}", e))?
-        .len();
-
+    let image_size = source.size();
     state.total_bytes.store(image_size, Ordering::SeqCst);
 
     log_info!(
@@ -137,10 +144,6 @@ pub async fn flash_image(
     // Quick erase - clear partition table area
     quick_erase(&mut device)?;
 
-    // Open image file
-    let mut image_file =
-        File::open(image_path).map_err(|e| format!("Failed to open image: {}", e))?;
-
     // Write image in chunks with progress
     let chunk_size = config::flash::CHUNK_SIZE;
     let mut buffer = vec![0u8; chunk_size];
@@ -165,9 +168,7 @@ pub async fn flash_image(
             return Err("Flash cancelled".to_string());
         }
 
-        let bytes_read = image_file
-            .read(&mut buffer)
-            .map_err(|e| format!("Failed to read image: {}", e))?;
+        let bytes_read = source.read_chunk(&mut buffer)?;
 
         if bytes_read == 0 {
             break;
@@ -196,6 +197,7 @@ pub async fn flash_image(
 
     // Log final summary
     tracker.finish();
+    state.written_bytes.store(written, Ordering::SeqCst);
     log_debug!(MODULE, "Syncing...");
 
     // Sync
@@ -214,7 +216,7 @@ pub async fn flash_image(
         // Invalidate page cache before verification to ensure we read from disk
         // This is critical - without this, we'd just be verifying cached data
         unsafe {
-            libc::posix_fadvise(device_fd, 0, image_size as i64, libc::POSIX_FADV_DONTNEED);
+            libc::posix_fadvise(device_fd, 0, written as i64, libc::POSIX_FADV_DONTNEED);
         }
 
         // Seek back to beginning
@@ -222,7 +224,7 @@ pub async fn flash_image(
             .seek(SeekFrom::Start(0))
             .map_err(|e| format!("Failed to seek device: {}", e))?;
 
-        verify_written_data(image_path, &mut device, state.clone())?;
+        source.verify(&mut device, state.clone())?;
     }
 
     log_info!(MODULE, "Flash complete!");
@@ -267,12 +269,3 @@ fn quick_erase(device: &mut File) -> Result<(), String> {
     log_debug!(MODULE, "Quick erase complete");
     Ok(())
 }
-
-/// Verify written data
-fn verify_written_data(
-    image_path: &PathBuf,
-    device: &mut File,
-    state: Arc<FlashState>,
-) -> Result<(), String> {
-    crate::flash::verify::verify_data(image_path, device, state)
-}
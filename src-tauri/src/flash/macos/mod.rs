@@ -9,4 +9,4 @@ mod writer;
 
 // Re-export public API
 pub use authorization::request_authorization;
-pub use writer::flash_image;
+pub use writer::{flash_image, flash_source};
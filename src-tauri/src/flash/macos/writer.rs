@@ -3,14 +3,14 @@
 //! Handles opening devices with authorization and writing data.
 
 use std::fs::File;
-use std::io::{BufReader, Read, Write};
+use std::io::{BufReader, Write};
 use std::os::unix::io::{AsRawFd, FromRawFd};
-use std::path::PathBuf;
+use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::config;
-use crate::flash::{sync_device, unmount_device, FlashState};
+use crate::flash::{sync_device, unmount_device, FlashState, ImageSource};
 use crate::utils::{bytes_to_gb, ProgressTracker};
 use crate::{log_debug, log_error, log_info};
 
@@ -241,18 +241,25 @@ pub fn quick_erase(device: &mut File, device_fd: i32) -> Result<(), String> {
 
 /// Flash an image to a block device on macOS
 pub async fn flash_image(
-    image_path: &PathBuf,
+    image_path: &Path,
     device_path: &str,
     state: Arc<FlashState>,
     verify: bool,
 ) -> Result<(), String> {
-    state.reset();
+    let source = ImageSource::open(image_path, &state)?;
+    flash_source(source, device_path, state, verify).await
+}
 
-    // Get image size
-    let image_size = std::fs::metadata(image_path)
-        .map_err(|e| format!("Failed to get image size: {}", e))?
-        .len();
+/// Flash an image source (file or stream) to a block device on macOS
+pub async fn flash_source(
+    source: ImageSource,
+    device_path: &str,
+    state: Arc<FlashState>,
+    verify: bool,
+) -> Result<(), String> {
+    state.reset();
 
+    let image_size = source.size();
     state.total_bytes.store(image_size, Ordering::SeqCst);
 
     // Use raw disk access for better performance
@@ -283,7 +290,7 @@ pub async fn flash_image(
 
     // Use inner function to do the actual work, then always free auth at the end
     let result = do_flash_work(
-        image_path,
+        source,
         device_path,
         &mut device,
         device_fd,
@@ -305,7 +312,7 @@ pub async fn flash_image(
 
 /// Inner function to do flash work
 async fn do_flash_work(
-    image_path: &PathBuf,
+    mut source: ImageSource,
     device_path: &str,
     device: &mut File,
     device_fd: i32,
@@ -316,10 +323,6 @@ async fn do_flash_work(
     // Quick erase first - clear partition tables and boot sectors
     quick_erase(device, device_fd)?;
 
-    // Open image file
-    let mut image_file =
-        File::open(image_path).map_err(|e| format!("Failed to open image: {}", e))?;
-
     // Write image in chunks with progress
     let chunk_size = config::flash::CHUNK_SIZE;
     let mut buffer = vec![0u8; chunk_size];
@@ -345,9 +348,9 @@ async fn do_flash_work(
             return Err("Flash cancelled".to_string());
         }
 
-        let bytes_read = image_file
-            .read(&mut buffer)
-            .map_err(|e| format!("Failed to read image: {}", e))?;
+        // Chunks are always full except the last one, so only the final
+        // write can need padding
+        let bytes_read = source.read_chunk(&mut buffer)?;
 
         if bytes_read == 0 {
             break;
@@ -405,7 +408,7 @@ async fn do_flash_work(
     // Verify if requested - reuse same fd (no additional auth needed)
     if verify {
         log_info!(MODULE, "Starting verification");
-        verify_written_data(image_path, device, device_fd, state.clone())?;
+        verify_written_data(&mut source, device, device_fd, state.clone())?;
     }
 
     log_info!(MODULE, "Flash complete!");
@@ -416,7 +419,7 @@ async fn do_flash_work(
 /// Wraps device in BufReader so all reads from the raw device (/dev/rdisk) are
 /// CHUNK_SIZE-aligned, even when verify_data requests a smaller final read.
 fn verify_written_data(
-    image_path: &PathBuf,
+    source: &mut ImageSource,
     device: &mut File,
     device_fd: i32,
     state: Arc<FlashState>,
@@ -429,5 +432,5 @@ fn verify_written_data(
     // BufReader ensures all underlying reads from the raw device are large and
     // sector-aligned, preventing EINVAL on the final partial-sector read.
     let mut buf_reader = BufReader::with_capacity(config::flash::CHUNK_SIZE, &*device);
-    crate::flash::verify::verify_data(image_path, &mut buf_reader, state)
+    source.verify(&mut buf_reader, state)
 }
@@ -5,6 +5,7 @@
 //! - Linux: Uses pkexec for privilege escalation
 //! - Windows: Requires running as Administrator
 
+mod source;
 mod verify;
 
 #[cfg(target_os = "linux")]
@@ -90,13 +91,15 @@ impl FlashState {
     }
 }
 
-// Re-export the platform-specific flash_image function
+pub use source::ImageSource;
+
+// Re-export the platform-specific flash functions
 #[cfg(target_os = "linux")]
-pub use linux::flash_image;
+pub use linux::{flash_image, flash_source};
 #[cfg(target_os = "macos")]
-pub use macos::flash_image;
+pub use macos::{flash_image, flash_source};
 #[cfg(target_os = "windows")]
-pub use windows::flash_image;
+pub use windows::{flash_image, flash_source};
 
 // Re-export authorization functions
 #[cfg(target_os = "linux")]
@@ -0,0 +1,302 @@
+//! Image sources for flashing
+//!
+//! A flash reads either from an image file on disk, or from a one-shot
+//! stream (e.g. an HTTP download or a compressed cached image being
+//! decompressed on the fly). Files are verified by re-reading them; streams
+//! cannot be replayed, so the written bytes are hashed on the way to the
+//! device and the read-back is compared against that hash instead.
+
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use super::FlashState;
+
+/// Where the image data comes from
+enum Origin {
+    File(PathBuf),
+    Stream {
+        /// Hash of every byte handed out by `read_chunk`
+        hasher: Sha256,
+        /// Number of bytes handed out by `read_chunk`
+        read: u64,
+        /// Compressed file the stream is decompressed from, if any
+        compressed: Option<PathBuf>,
+    },
+}
+
+/// File reader tracking its position, shared with the progress estimate
+struct Positioned {
+    file: File,
+    position: Arc<AtomicU64>,
+}
+
+impl Read for Positioned {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.file.read(buf)?;
+        self.position.fetch_add(n as u64, Ordering::SeqCst);
+        Ok(n)
+    }
+}
+
+impl Seek for Positioned {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = self.file.seek(pos)?;
+        self.position.store(position, Ordering::SeqCst);
+        Ok(position)
+    }
+}
+
+/// Decompressed data of a compressed file, with a size estimate for progress
+///
+/// The decompressed size is unknown until the end, so it is extrapolated
+/// from the compression ratio observed so far.
+struct Decompressing {
+    inner: Box<dyn Read + Send>,
+    produced: u64,
+    position: Arc<AtomicU64>,
+    compressed_size: u64,
+    state: Arc<FlashState>,
+}
+
+impl Read for Decompressing {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.produced += n as u64;
+
+        let position = self.position.load(Ordering::SeqCst);
+        let estimate = if n == 0 || position == 0 {
+            self.produced
+        } else {
+            let scaled = self.produced as u128 * self.compressed_size as u128 / position as u128;
+            std::cmp::max(scaled as u64, self.produced)
+        };
+        self.state.total_bytes.store(estimate, Ordering::SeqCst);
+        Ok(n)
+    }
+}
+
+/// Image data to be written to a device
+pub struct ImageSource {
+    reader: Box<dyn Read + Send>,
+    size: u64,
+    origin: Origin,
+}
+
+impl ImageSource {
+    /// Open an image file on disk
+    pub fn file(path: &Path) -> Result<Self, String> {
+        let size = std::fs::metadata(path)
+            .map_err(|e| format!("Failed to get image size: {}", e))?
+            .len();
+        let file = File::open(path).map_err(|e| format!("Failed to open image: {}", e))?;
+
+        Ok(Self {
+            reader: Box::new(file),
+            size,
+            origin: Origin::File(path.to_path_buf()),
+        })
+    }
+
+    /// Open an image file, decompressing it on the fly if it is compressed
+    ///
+    /// Progress of a compressed file is estimated into `state`.
+    pub fn open(path: &Path, state: &Arc<FlashState>) -> Result<Self, String> {
+        if !crate::decompress::needs_decompression(path) {
+            return Self::file(path);
+        }
+
+        let compressed_size = std::fs::metadata(path)
+            .map_err(|e| format!("Failed to get image size: {}", e))?
+            .len();
+        let position = Arc::new(AtomicU64::new(0));
+        let shared = Arc::clone(&position);
+        let inner = crate::decompress::file_decoder(path, move |file| Positioned {
+            file,
+            position: Arc::clone(&shared),
+        })?;
+
+        let mut source = Self::stream(
+            Decompressing {
+                inner,
+                produced: 0,
+                position,
+                compressed_size,
+                state: Arc::clone(state),
+            },
+            0,
+        );
+        if let Origin::Stream { compressed, .. } = &mut source.origin {
+            *compressed = Some(path.to_path_buf());
+        }
+        Ok(source)
+    }
+
+    /// Wrap a one-shot stream of raw image data
+    ///
+    /// `size_hint` is used for progress only (0 if unknown).
+    pub fn stream(reader: impl Read + Send + 'static, size_hint: u64) -> Self {
+        Self {
+            reader: Box::new(reader),
+            size: size_hint,
+            origin: Origin::Stream {
+                hasher: Sha256::new(),
+                read: 0,
+                compressed: None,
+            },
+        }
+    }
+
+    /// Image size in bytes (a hint for streams)
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Path of the image file, if the source is a file
+    pub fn path(&self) -> Option<&Path> {
+        match &self.origin {
+            Origin::File(path) => Some(path),
+            Origin::Stream { .. } => None,
+        }
+    }
+
+    /// Human-readable description for logs
+    pub fn describe(&self) -> String {
+        match &self.origin {
+            Origin::File(path) => path.display().to_string(),
+            Origin::Stream {
+                compressed: Some(path),
+                ..
+            } => format!("{} (decompressing)", path.display()),
+            Origin::Stream { .. } => "<stream>".to_string(),
+        }
+    }
+
+    /// Read the next chunk, filling `buf` completely unless the image ends
+    ///
+    /// Decompressors return short reads at arbitrary points; filling the
+    /// buffer keeps every write except the last one chunk-sized, which raw
+    /// devices with sector alignment requirements depend on.
+    /// Returns 0 at the end of the image.
+    pub fn read_chunk(&mut self, buf: &mut [u8]) -> Result<usize, String> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(format!("Failed to read image: {}", e)),
+            }
+        }
+
+        if let Origin::Stream { hasher, read, .. } = &mut self.origin {
+            hasher.update(&buf[..filled]);
+            *read += filled as u64;
+        }
+        Ok(filled)
+    }
+
+    /// Verify the device contents against the image
+    ///
+    /// `device_reader` must be positioned at the start of the device.
+    /// Files are compared byte by byte; streams are compared by SHA-256 of
+    /// the bytes that were written.
+    pub fn verify<R: Read>(
+        &mut self,
+        device_reader: &mut R,
+        state: Arc<FlashState>,
+    ) -> Result<(), String> {
+        match &mut self.origin {
+            Origin::File(path) => super::verify::verify_data(path, device_reader, state),
+            Origin::Stream { hasher, read, .. } => {
+                let expected: [u8; 32] = std::mem::take(hasher).finalize().into();
+                state.total_bytes.store(*read, Ordering::SeqCst);
+                super::verify::verify_hash(device_reader, *read, &expected, state)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader returning at most 3 bytes per call, like a decompressor
+    struct Trickle(std::io::Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = std::cmp::min(buf.len(), 3);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_stream_fills_chunks_and_verifies() {
+        let data: Vec<u8> = (0..20u8).collect();
+        let mut source = ImageSource::stream(Trickle(std::io::Cursor::new(data.clone())), 0);
+        assert!(source.path().is_none());
+
+        let mut buf = [0u8; 8];
+        assert_eq!(source.read_chunk(&mut buf).unwrap(), 8);
+        assert_eq!(source.read_chunk(&mut buf).unwrap(), 8);
+        assert_eq!(source.read_chunk(&mut buf).unwrap(), 4);
+        assert_eq!(source.read_chunk(&mut buf).unwrap(), 0);
+
+        // Device larger than the image: only the written bytes are compared
+        let mut device = data.clone();
+        device.extend_from_slice(&[0xff; 16]);
+        let state = Arc::new(FlashState::new());
+        assert!(source
+            .verify(&mut std::io::Cursor::new(device), state.clone())
+            .is_ok());
+        assert_eq!(state.verified_bytes.load(Ordering::SeqCst), 20);
+    }
+
+    #[test]
+    fn test_open_decompresses_compressed_file() {
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i % 7) as u8).collect();
+        let path = std::env::temp_dir().join(format!("source-test-{}.img.zst", std::process::id()));
+        std::fs::write(&path, zstd::encode_all(data.as_slice(), 3).unwrap()).unwrap();
+
+        let state = Arc::new(FlashState::new());
+        let mut source = ImageSource::open(&path, &state).unwrap();
+        assert!(source.path().is_none());
+
+        let mut written = Vec::new();
+        let mut buf = vec![0u8; 4096];
+        loop {
+            let n = source.read_chunk(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            written.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(written, data);
+        assert_eq!(state.total_bytes.load(Ordering::SeqCst), data.len() as u64);
+        assert!(source
+            .verify(&mut std::io::Cursor::new(data), state)
+            .is_ok());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_stream_verify_detects_mismatch() {
+        let data = vec![7u8; 32];
+        let mut source = ImageSource::stream(std::io::Cursor::new(data), 0);
+        let mut buf = [0u8; 64];
+        assert_eq!(source.read_chunk(&mut buf).unwrap(), 32);
+
+        let mut device = vec![7u8; 32];
+        device[10] = 0;
+        let state = Arc::new(FlashState::new());
+        assert!(source
+            .verify(&mut std::io::Cursor::new(device), state)
+            .is_err());
+    }
+}
@@ -8,6 +8,7 @@
 use crate::config;
 use crate::utils::{bytes_to_gb, ProgressTracker};
 use crate::{log_error, log_info};
+use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
@@ -126,6 +127,84 @@ pub fn verify_data<R: Read>(
     Ok(())
 }
 
+/// Verify written data by hashing the device contents
+///
+/// Used when the image was streamed and cannot be read a second time:
+/// reads `len` bytes back from the device and compares their SHA-256 with
+/// the hash of the bytes that were written.
+pub fn verify_hash<R: Read>(
+    device_reader: &mut R,
+    len: u64,
+    expected: &[u8; 32],
+    state: Arc<FlashState>,
+) -> Result<(), String> {
+    state.is_verifying.store(true, Ordering::SeqCst);
+    state.verified_bytes.store(0, Ordering::SeqCst);
+
+    let chunk_size = config::flash::CHUNK_SIZE;
+    let mut device_buffer = vec![0u8; chunk_size];
+    let mut hasher = Sha256::new();
+    let mut verified: u64 = 0;
+
+    let mut tracker = ProgressTracker::new(
+        "Verify",
+        MODULE,
+        len,
+        config::logging::WRITE_LOG_INTERVAL_MB,
+    );
+
+    log_info!(
+        MODULE,
+        "Starting hash verification of {} bytes ({:.2} GB)",
+        len,
+        bytes_to_gb(len)
+    );
+
+    while verified < len {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Verification cancelled".to_string());
+        }
+
+        let to_read = std::cmp::min(chunk_size as u64, len - verified) as usize;
+        let n = device_reader
+            .read(&mut device_buffer[..to_read])
+            .map_err(|e| format!("Failed to read device: {}", e))?;
+
+        if n == 0 {
+            log_error!(
+                MODULE,
+                "Verification failed: device ended at byte {} (expected {})",
+                verified,
+                len
+            );
+            return Err(format!(
+                "Verification failed: device ended at byte {} (expected {})",
+                verified, len
+            ));
+        }
+
+        hasher.update(&device_buffer[..n]);
+        verified += n as u64;
+        state.verified_bytes.store(verified, Ordering::SeqCst);
+        tracker.update(n as u64);
+    }
+
+    tracker.finish();
+
+    let actual: [u8; 32] = hasher.finalize().into();
+    if &actual != expected {
+        log_error!(
+            MODULE,
+            "Verification failed: device hash {} does not match written data {}",
+            hex::encode(actual),
+            hex::encode(expected)
+        );
+        return Err("Verification failed: device contents do not match written data".to_string());
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
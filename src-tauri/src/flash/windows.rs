@@ -2,12 +2,12 @@
 //!
 //! Requires Administrator privileges for raw disk access.
 
-use super::FlashState;
+use super::{FlashState, ImageSource};
 use crate::config;
 use crate::utils::{bytes_to_gb, ProgressTracker};
 use crate::{log_debug, log_error, log_info, log_warn};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
@@ -29,7 +29,20 @@ const FILE_FLAG_WRITE_THROUGH: u32 = 0x80000000;
 ///
 /// Requires Administrator privileges on Windows.
 pub async fn flash_image(
-    image_path: &PathBuf,
+    image_path: &Path,
+    device_path: &str,
+    state: Arc<FlashState>,
+    verify: bool,
+) -> Result<(), String> {
+    let source = ImageSource::open(image_path, &state)?;
+    flash_source(source, device_path, state, verify).await
+}
+
+/// Flashes an image source (file or stream) to a block device.
+///
+/// Requires Administrator privileges on Windows.
+pub async fn flash_source(
+    mut source: ImageSource,
     device_path: &str,
     state: Arc<FlashState>,
     verify: bool,
@@ -39,14 +52,11 @@ pub async fn flash_image(
     log_info!(
         MODULE,
         "Starting flash: {} -> {}",
-        image_path.display(),
+        source.describe(),
         device_path
     );
 
-    let image_size = std::fs::metadata(image_path)
-        .map_err(|e| format!("Failed to get image size: {}", e))?
-        .len();
-
+    let image_size = source.size();
     state.total_bytes.store(image_size, Ordering::SeqCst);
 
     log_info!(
@@ -64,9 +74,6 @@ pub async fn flash_image(
         config::flash::UNMOUNT_DELAY_MS,
     ));
 
-    let mut image_file =
-        std::fs::File::open(image_path).map_err(|e| format!("Failed to open image: {}", e))?;
-
     log_debug!(MODULE, "Opening device for writing...");
     let mut device = open_device_for_write(device_path)?;
 
@@ -90,9 +97,9 @@ pub async fn flash_image(
             return Err("Flash cancelled".to_string());
         }
 
-        let bytes_read = image_file.read(&mut buffer).map_err(|e| {
-            log_error!(MODULE, "Failed to read image: {}", e);
-            format!("Failed to read image: {}", e)
+        let bytes_read = source.read_chunk(&mut buffer).map_err(|e| {
+            log_error!(MODULE, "{}", e);
+            e
         })?;
 
         if bytes_read == 0 {
@@ -125,7 +132,15 @@ pub async fn flash_image(
             config::flash::UNMOUNT_DELAY_MS,
         ));
         let device = open_device_for_read(device_path)?;
-        verify_with_sector_alignment(image_path, device, state)?;
+        match source.path() {
+            Some(image_path) => verify_with_sector_alignment(image_path, device, state)?,
+            None => {
+                // Full-capacity reads keep every device read sector-aligned
+                let mut reader =
+                    std::io::BufReader::with_capacity(config::flash::CHUNK_SIZE, device);
+                source.verify(&mut reader, state)?
+            }
+        }
     }
 
     log_info!(MODULE, "Flash complete, releasing volume locks...");
@@ -390,7 +405,7 @@ fn flush_device_buffers(_device: &std::fs::File) -> Result<(), String> {
 /// Required when using `FILE_FLAG_NO_BUFFERING` which bypasses the OS cache.
 #[cfg(target_os = "windows")]
 fn verify_with_sector_alignment(
-    image_path: &PathBuf,
+    image_path: &Path,
     mut device: std::fs::File,
     state: Arc<FlashState>,
 ) -> Result<(), String> {
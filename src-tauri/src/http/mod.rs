@@ -0,0 +1,261 @@
+//! HTTP client factory
+//!
+//! Every request made by the app goes through a client built here, so the
+//! proxy, no-proxy list and extra CA certificates configured in the network
+//! settings apply everywhere. Without a configured proxy the standard
+//! `HTTP_PROXY` / `HTTPS_PROXY` / `ALL_PROXY` / `NO_PROXY` environment
+//! variables are honored, and `SSL_CERT_FILE` can supply the CA bundle.
+//!
+//! Clients are cached per timeout profile and rebuilt when the settings change.
+
+pub mod retry;
+
+use once_cell::sync::Lazy;
+use reqwest::{Certificate, Client, NoProxy, Proxy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use crate::config;
+use crate::log_info;
+
+const MODULE: &str = "http";
+
+/// Environment variable naming an extra PEM CA bundle
+const CA_BUNDLE_ENV: &str = "SSL_CERT_FILE";
+
+/// Network settings persisted in settings.json
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Proxy for all requests (http://, https://, socks5:// or socks5h://)
+    pub proxy: Option<String>,
+    /// Comma-separated hosts, domains or CIDR ranges that bypass `proxy`
+    pub no_proxy: Option<String>,
+    /// PEM file with extra CA certificates to trust
+    pub ca_bundle: Option<String>,
+    /// Attempts for network operations before giving up (default when unset)
+    pub retry_attempts: Option<u32>,
+}
+
+/// Timeout profile of a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Timeout {
+    /// Image transfers: no overall limit, only connect and read timeouts
+    Transfer,
+    /// API and upload requests
+    Request,
+    /// Small assets like board pictures
+    Short,
+    /// Connectivity probe
+    Connectivity,
+    /// Transfers from peers on the local network: never proxied, and a
+    /// short connect timeout so an unreachable peer is skipped quickly
+    Lan,
+}
+
+static SETTINGS: Lazy<RwLock<NetworkSettings>> = Lazy::new(Default::default);
+
+static CLIENTS: Lazy<Mutex<HashMap<Timeout, Client>>> = Lazy::new(Default::default);
+
+/// Apply new network settings to all clients created from now on
+pub fn configure(settings: NetworkSettings) {
+    log_info!(
+        MODULE,
+        "Network settings: proxy={}, no_proxy={}, ca_bundle={}, retry_attempts={}",
+        settings.proxy.as_deref().unwrap_or("(environment)"),
+        settings.no_proxy.as_deref().unwrap_or("(none)"),
+        settings.ca_bundle.as_deref().unwrap_or("(none)"),
+        settings
+            .retry_attempts
+            .unwrap_or(config::http::RETRY_ATTEMPTS)
+    );
+    if let Ok(mut current) = SETTINGS.write() {
+        *current = settings;
+    }
+    if let Ok(mut clients) = CLIENTS.lock() {
+        clients.clear();
+    }
+}
+
+/// Get a client for the given timeout profile
+pub fn client(timeout: Timeout) -> Result<Client, String> {
+    if let Some(client) = CLIENTS.lock().ok().and_then(|c| c.get(&timeout).cloned()) {
+        return Ok(client);
+    }
+
+    let settings = SETTINGS
+        .read()
+        .map(|s| s.clone())
+        .map_err(|_| "Network settings are unavailable".to_string())?;
+    let client = build_client(&settings, timeout)?;
+
+    if let Ok(mut clients) = CLIENTS.lock() {
+        clients.insert(timeout, client.clone());
+    }
+    Ok(client)
+}
+
+/// Check that settings produce a working client before they are saved
+pub fn validate(settings: &NetworkSettings) -> Result<(), String> {
+    if let Some(attempts) = settings.retry_attempts {
+        if !(1..=config::http::MAX_RETRY_ATTEMPTS).contains(&attempts) {
+            return Err(format!(
+                "Invalid retry attempts: {} (must be between 1 and {})",
+                attempts,
+                config::http::MAX_RETRY_ATTEMPTS
+            ));
+        }
+    }
+    build_client(settings, Timeout::Request).map(|_| ())
+}
+
+/// Configured number of attempts for network operations
+fn retry_attempts() -> u32 {
+    SETTINGS
+        .read()
+        .ok()
+        .and_then(|s| s.retry_attempts)
+        .unwrap_or(config::http::RETRY_ATTEMPTS)
+}
+
+/// Treat empty strings from the settings UI as unset
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn build_client(settings: &NetworkSettings, timeout: Timeout) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .user_agent(config::app::USER_AGENT)
+        .connect_timeout(Duration::from_secs(config::http::CONNECT_TIMEOUT_SECS));
+
+    builder = match timeout {
+        Timeout::Transfer => {
+            builder.read_timeout(Duration::from_secs(config::http::REQUEST_TIMEOUT_SECS))
+        }
+        Timeout::Request => {
+            builder.timeout(Duration::from_secs(config::http::REQUEST_TIMEOUT_SECS))
+        }
+        Timeout::Short => builder.timeout(Duration::from_secs(config::http::SHORT_TIMEOUT_SECS)),
+        Timeout::Connectivity => {
+            builder.timeout(Duration::from_secs(config::http::CONNECTIVITY_TIMEOUT_SECS))
+        }
+        Timeout::Lan => builder
+            .connect_timeout(Duration::from_secs(config::lan::CONNECT_TIMEOUT_SECS))
+            .read_timeout(Duration::from_secs(config::http::REQUEST_TIMEOUT_SECS))
+            .no_proxy(),
+    };
+
+    // Peers are reached directly, the proxy does not apply to them
+    let proxy_url = non_empty(&settings.proxy).filter(|_| timeout != Timeout::Lan);
+    if let Some(proxy_url) = proxy_url {
+        let no_proxy = match non_empty(&settings.no_proxy) {
+            Some(list) => NoProxy::from_string(list),
+            None => NoProxy::from_env(),
+        };
+        let proxy = Proxy::all(proxy_url)
+            .map_err(|e| format!("Invalid proxy URL {}: {}", proxy_url, e))?
+            .no_proxy(no_proxy);
+        builder = builder.proxy(proxy);
+    }
+
+    let ca_bundle = non_empty(&settings.ca_bundle)
+        .map(str::to_string)
+        .or_else(|| std::env::var(CA_BUNDLE_ENV).ok().filter(|v| !v.is_empty()));
+    if let Some(path) = ca_bundle {
+        for certificate in load_ca_bundle(&path)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Read the certificates of a PEM bundle
+fn load_ca_bundle(path: &str) -> Result<Vec<Certificate>, String> {
+    let pem =
+        std::fs::read(path).map_err(|e| format!("Failed to read CA bundle {}: {}", path, e))?;
+    let certificates = Certificate::from_pem_bundle(&pem)
+        .map_err(|e| format!("Invalid CA bundle {}: {}", path, e))?;
+    if certificates.is_empty() {
+        return Err(format!("CA bundle {} contains no certificates", path));
+    }
+    Ok(certificates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_invalid_settings_are_rejected() {
+        let bad_proxy = NetworkSettings {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(validate(&bad_proxy).is_err());
+
+        let path = std::env::temp_dir().join(format!("http-test-ca-{}.pem", std::process::id()));
+        std::fs::write(&path, "no certificates here").unwrap();
+        let bad_bundle = NetworkSettings {
+            ca_bundle: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        assert!(validate(&bad_bundle).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let socks = NetworkSettings {
+            proxy: Some("socks5h://127.0.0.1:1080".to_string()),
+            no_proxy: Some("localhost, .internal".to_string()),
+            ca_bundle: Some(String::new()),
+            retry_attempts: Some(3),
+        };
+        assert!(validate(&socks).is_ok());
+
+        let no_attempts = NetworkSettings {
+            retry_attempts: Some(0),
+            ..Default::default()
+        };
+        assert!(validate(&no_attempts).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            // A proxied request carries the absolute URL
+            let body: &[u8] = if buf[..n].starts_with(b"GET http://armbian.invalid/") {
+                b"proxied"
+            } else {
+                b"direct"
+            };
+            let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+            socket.write_all(header.as_bytes()).await.unwrap();
+            socket.write_all(body).await.unwrap();
+        });
+
+        let settings = NetworkSettings {
+            proxy: Some(format!("http://127.0.0.1:{}", port)),
+            ..Default::default()
+        };
+        let client = build_client(&settings, Timeout::Short).unwrap();
+        let body = client
+            .get("http://armbian.invalid/")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "proxied");
+    }
+}
@@ -0,0 +1,215 @@
+//! Retry policy for network operations
+//!
+//! Transient failures (timeouts, dropped connections, 5xx and 429 answers)
+//! are retried with exponential backoff and jitter; everything else fails
+//! immediately. The number of attempts comes from the network settings.
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::config;
+use crate::log_warn;
+
+const MODULE: &str = "http::retry";
+
+/// How often, and how patiently, a failed operation is retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Policy from the current network settings
+    pub fn current() -> Self {
+        Self {
+            attempts: super::retry_attempts(),
+            base_delay: Duration::from_millis(config::http::RETRY_BASE_DELAY_MS),
+            max_delay: Duration::from_secs(config::http::RETRY_MAX_DELAY_SECS),
+        }
+    }
+
+    /// Delay before retry number `retry` (starting at 1)
+    ///
+    /// Doubles with every retry up to `max_delay`, and is randomized between
+    /// half and the full value so clients that failed together do not retry
+    /// together.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(16);
+        let full = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+        full / 2 + full.mul_f64(jitter() / 2.0)
+    }
+}
+
+/// Random value in [0, 1)
+fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Whether a request answered with `status` may succeed if sent again:
+/// server errors and rate limiting
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Whether an I/O error is caused by the network rather than the local system
+pub fn is_retryable_io(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::UnexpectedEof
+    )
+}
+
+/// Whether a failed request or response body may succeed if tried again
+///
+/// Only connection failures, timeouts and network errors while reading the
+/// body are retried; anything else (invalid URL, redirect loop, undecodable
+/// body) fails the same way every time.
+pub fn is_retryable_error(error: &reqwest::Error) -> bool {
+    if let Some(status) = error.status() {
+        return is_retryable_status(status);
+    }
+    if error.is_connect() || error.is_timeout() {
+        return true;
+    }
+    if !error.is_body() && !error.is_decode() {
+        return false;
+    }
+
+    // Reading the body is retried only when the network failed underneath
+    // (a connection dropped mid-body is a decode error caused by an
+    // unexpected EOF); invalid JSON will not get better by fetching it again
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            return is_retryable_io(io);
+        }
+        source = cause.source();
+    }
+    false
+}
+
+/// Server-requested delay from a `Retry-After: <seconds>` header
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+/// Send a request, retrying transient failures
+///
+/// `make_request` builds a fresh request for every attempt. The last
+/// response is returned even if its status is an error, so callers keep
+/// their own status handling.
+pub async fn send<F>(what: &str, make_request: F) -> reqwest::Result<Response>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let policy = RetryPolicy::current();
+    let mut attempt = 1;
+    loop {
+        let (delay, reason) = match make_request().send().await {
+            Ok(response) if attempt < policy.attempts && is_retryable_status(response.status()) => {
+                let delay = policy.backoff(attempt);
+                let delay = retry_after(&response)
+                    .map(|d| d.clamp(delay, policy.max_delay))
+                    .unwrap_or(delay);
+                (delay, format!("status {}", response.status()))
+            }
+            Err(e) if attempt < policy.attempts && is_retryable_error(&e) => {
+                (policy.backoff(attempt), e.to_string())
+            }
+            result => return result,
+        };
+
+        log_warn!(
+            MODULE,
+            "{} failed ({}), retrying in {:.1}s ({}/{})",
+            what,
+            reason,
+            delay.as_secs_f64(),
+            attempt,
+            policy.attempts - 1
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+        for retry in 1..10 {
+            let full = Duration::from_millis(100 << (retry - 1)).min(policy.max_delay);
+            let delay = policy.backoff(retry);
+            assert!(delay >= full / 2 && delay <= full, "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn test_status_classification() {
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(StatusCode::FORBIDDEN));
+        assert!(!is_retryable_status(StatusCode::REQUEST_TIMEOUT));
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_transient_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicU32::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let reply: &[u8] = if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                } else {
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok"
+                };
+                let _ = socket.write_all(reply).await;
+            }
+        });
+
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let response = send("Test request", || client.get(&url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
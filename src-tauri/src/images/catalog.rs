@@ -0,0 +1,333 @@
+//! Catalog index
+//!
+//! The catalog sources parsed once per fetch into boards keyed by normalized
+//! slug, with each board's images and filter facets, so board and image
+//! queries do not walk the JSON tree again. Images keep the id of the
+//! source listing them, and download URLs map to the source's trust
+//! settings.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::download::signature::{bundled_keyring, Keyring};
+use crate::download::Trust;
+use crate::utils::normalize_slug;
+
+use super::filters::{extract_images, filter_images_for_board, get_unique_boards, to_image_info};
+use super::models::{ArmbianImage, BoardInfo, ImageInfo};
+use super::sources::Source;
+
+/// Distinct filter values among a board's images
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardFacets {
+    pub distros: Vec<String>,
+    pub branches: Vec<String>,
+    pub variants: Vec<String>,
+    /// Preinstalled applications ("" for plain images)
+    pub apps: Vec<String>,
+}
+
+impl BoardFacets {
+    fn from_images(images: &[ArmbianImage]) -> Self {
+        fn distinct(
+            images: &[ArmbianImage],
+            field: fn(&ArmbianImage) -> &Option<String>,
+        ) -> Vec<String> {
+            images
+                .iter()
+                .map(|img| field(img).clone().unwrap_or_default())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        }
+
+        let mut facets = Self {
+            distros: distinct(images, |img| &img.distro_release),
+            branches: distinct(images, |img| &img.kernel_branch),
+            variants: distinct(images, |img| &img.image_variant),
+            apps: distinct(images, |img| &img.preinstalled_application),
+        };
+        // Missing values only matter for applications (plain images)
+        facets.distros.retain(|v| !v.is_empty());
+        facets.branches.retain(|v| !v.is_empty());
+        facets.variants.retain(|v| !v.is_empty());
+        facets
+    }
+}
+
+/// One board with its images
+struct BoardEntry {
+    images: Vec<ArmbianImage>,
+    facets: BoardFacets,
+}
+
+/// Parsed and indexed catalog
+pub struct Catalog {
+    /// Board list in display order
+    boards: Vec<BoardInfo>,
+    /// Boards keyed by normalized slug
+    by_slug: HashMap<String, BoardEntry>,
+    /// Verification required for each download URL
+    trust: HashMap<String, Trust>,
+}
+
+/// Keys accepted for an image listed by two sources, None standing for
+/// the `bundled` keyring
+fn merge_keyrings(
+    a: &Option<Arc<Keyring>>,
+    b: &Option<Arc<Keyring>>,
+    bundled: &Keyring,
+) -> Option<Arc<Keyring>> {
+    match (a, b) {
+        (None, None) => None,
+        (Some(a), Some(b)) if a == b => Some(a.clone()),
+        _ => {
+            let a = a.as_deref().unwrap_or(bundled);
+            let b = b.as_deref().unwrap_or(bundled);
+            Some(Arc::new(a.union(b)))
+        }
+    }
+}
+
+impl Catalog {
+    /// Parse and index the built-in catalog alone
+    pub fn from_json(json: &serde_json::Value) -> Self {
+        let mut images = extract_images(json);
+        for image in &mut images {
+            image.source = config::images::BUILTIN_SOURCE_ID.to_string();
+        }
+        Self::index(images, HashMap::new())
+    }
+
+    /// Parse and index the catalogs of several sources
+    ///
+    /// An image listed by more than one source must pass the checks of
+    /// all of them; a signature by any of their keys (the bundled ones for
+    /// sources without their own) is accepted.
+    pub fn from_sources(catalogs: &[(Source, serde_json::Value)]) -> Self {
+        let mut images = Vec::new();
+        let mut trust: HashMap<String, Trust> = HashMap::new();
+        for (source, json) in catalogs {
+            let source_trust = source.trust();
+            for mut image in extract_images(json) {
+                image.source = source.id.clone();
+                if let Some(url) = &image.file_url {
+                    match trust.entry(url.clone()) {
+                        Entry::Vacant(entry) => {
+                            entry.insert(source_trust.clone());
+                        }
+                        Entry::Occupied(mut entry) => {
+                            let required = entry.get_mut();
+                            required.require_sha |= source_trust.require_sha;
+                            required.require_signature |= source_trust.require_signature;
+                            required.signing_key = merge_keyrings(
+                                &required.signing_key,
+                                &source_trust.signing_key,
+                                bundled_keyring(),
+                            );
+                        }
+                    }
+                }
+                images.push(image);
+            }
+        }
+        Self::index(images, trust)
+    }
+
+    fn index(images: Vec<ArmbianImage>, trust: HashMap<String, Trust>) -> Self {
+        let boards = get_unique_boards(&images);
+
+        let mut grouped: HashMap<String, Vec<ArmbianImage>> = HashMap::new();
+        for image in images {
+            if let Some(slug) = image.board_slug.as_deref() {
+                grouped.entry(normalize_slug(slug)).or_default().push(image);
+            }
+        }
+
+        let by_slug = grouped
+            .into_iter()
+            .map(|(slug, images)| {
+                let facets = BoardFacets::from_images(&images);
+                (slug, BoardEntry { images, facets })
+            })
+            .collect();
+
+        Self {
+            boards,
+            by_slug,
+            trust,
+        }
+    }
+
+    /// All boards in display order
+    pub fn boards(&self) -> &[BoardInfo] {
+        &self.boards
+    }
+
+    /// Look up a board by (any spelling of) its slug
+    pub fn board(&self, slug: &str) -> Option<&BoardInfo> {
+        let normalized = normalize_slug(slug);
+        self.boards.iter().find(|board| board.slug == normalized)
+    }
+
+    /// Raw images of a board
+    pub fn board_images(&self, slug: &str) -> &[ArmbianImage] {
+        self.by_slug
+            .get(&normalize_slug(slug))
+            .map(|entry| entry.images.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Filter values available for a board
+    pub fn facets(&self, slug: &str) -> Option<&BoardFacets> {
+        self.by_slug
+            .get(&normalize_slug(slug))
+            .map(|entry| &entry.facets)
+    }
+
+    /// Images of a board, filtered and sorted for display
+    pub fn images_for_board(
+        &self,
+        slug: &str,
+        preapp_filter: Option<&str>,
+        kernel_filter: Option<&str>,
+        variant_filter: Option<&str>,
+        stable_only: bool,
+    ) -> Vec<ImageInfo> {
+        filter_images_for_board(
+            self.board_images(slug),
+            slug,
+            preapp_filter,
+            kernel_filter,
+            variant_filter,
+            stable_only,
+        )
+    }
+
+    /// Total number of images
+    pub fn image_count(&self) -> usize {
+        self.by_slug.values().map(|entry| entry.images.len()).sum()
+    }
+
+    /// Find the image a cached file was downloaded from
+    pub fn image_for_cached_file(&self, filename: &str) -> Option<ImageInfo> {
+        self.by_slug
+            .values()
+            .flat_map(|entry| entry.images.iter())
+            .find(|img| {
+                img.file_url
+                    .as_deref()
+                    .and_then(|url| crate::download::cached_filenames(url).ok())
+                    .is_some_and(|names| names.contains(&filename))
+            })
+            .map(to_image_info)
+    }
+
+    /// Verification the sources listing an image require (none if unknown)
+    pub fn trust(&self, file_url: &str) -> Trust {
+        self.trust.get(file_url).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(board: &str, distro: &str, branch: &str, app: &str) -> serde_json::Value {
+        serde_json::json!({
+            "board_slug": board,
+            "distro": distro,
+            "branch": branch,
+            "variant": "minimal",
+            "file_application": app,
+            "file_url": format!("https://dl.armbian.com/{}/{}_{}_{}.img.xz", board, distro, branch, app),
+            "file_extension": "img.xz",
+        })
+    }
+
+    #[test]
+    fn test_catalog_index() {
+        let json = serde_json::json!({
+            "assets": [
+                image("Rock-5B", "bookworm", "current", ""),
+                image("rock-5b", "noble", "vendor", "openmediavault"),
+                image("orangepi-5", "noble", "current", ""),
+            ]
+        });
+        let catalog = Catalog::from_json(&json);
+
+        assert_eq!(catalog.boards().len(), 2);
+        assert_eq!(catalog.image_count(), 3);
+        assert_eq!(catalog.board_images("rock_5b").len(), 2);
+        assert!(catalog.board("ROCK-5B").is_some());
+
+        let facets = catalog.facets("rock-5b").unwrap();
+        assert_eq!(facets.distros, vec!["bookworm", "noble"]);
+        assert_eq!(facets.branches, vec!["current", "vendor"]);
+        assert_eq!(facets.apps, vec!["", "openmediavault"]);
+
+        let images = catalog.images_for_board("rock-5b", None, Some("vendor"), None, false);
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].distro_release, "noble");
+    }
+
+    #[test]
+    fn test_catalog_sources() {
+        let vendor = Source {
+            id: "vendor".to_string(),
+            label: "Vendor".to_string(),
+            location: "https://example.com/images.json".to_string(),
+            enabled: true,
+            require_sha: true,
+            require_signature: false,
+            signing_key: None,
+        };
+        let catalog = Catalog::from_sources(&[
+            (
+                Source::builtin(),
+                serde_json::json!([image("rock-5b", "bookworm", "current", "")]),
+            ),
+            (
+                vendor,
+                serde_json::json!([
+                    image("rock-5b", "noble", "vendor", ""),
+                    image("vendor-board", "noble", "vendor", ""),
+                ]),
+            ),
+        ]);
+
+        assert_eq!(
+            catalog.board("rock-5b").unwrap().sources,
+            vec!["armbian", "vendor"]
+        );
+        assert_eq!(
+            catalog.board("vendor-board").unwrap().sources,
+            vec!["vendor"]
+        );
+
+        let images = catalog.images_for_board("rock-5b", None, Some("vendor"), None, false);
+        assert_eq!(images[0].source, "vendor");
+        assert!(catalog.trust(&images[0].file_url).require_sha);
+        let images = catalog.images_for_board("rock-5b", None, Some("current"), None, false);
+        assert_eq!(catalog.trust(&images[0].file_url), Trust::default());
+    }
+
+    #[test]
+    fn test_merge_keyrings_keeps_bundled_keys() {
+        let bundled = Keyring::parse(include_bytes!("../download/testdata/test-key.asc")).unwrap();
+        let vendor = Some(Arc::new(Keyring::default()));
+
+        // Listed by the built-in source too: the bundled key still counts
+        let merged = merge_keyrings(&None, &vendor, &bundled).unwrap();
+        assert_eq!(*merged, bundled);
+        let merged = merge_keyrings(&vendor, &None, &bundled).unwrap();
+        assert_eq!(*merged, bundled);
+
+        assert_eq!(merge_keyrings(&None, &None, &bundled), None);
+        assert_eq!(merge_keyrings(&vendor, &vendor, &bundled), vendor);
+    }
+}
@@ -0,0 +1,239 @@
+//! Catalog change detection
+//!
+//! Compares a freshly fetched catalog with the previous snapshot: added and
+//! removed boards, new images per board and changed support levels. Each
+//! catalog source is compared with its own snapshot and the results merged.
+//! The latest non-empty diff is kept until the next change is detected.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::normalize_slug;
+
+use super::filters::extract_images;
+use super::models::ArmbianImage;
+
+/// Most recent non-empty diff (for the get command)
+static LATEST: Lazy<Mutex<Option<CatalogDiff>>> = Lazy::new(|| Mutex::new(None));
+
+/// Diff not yet announced to the frontend
+static PENDING: Lazy<Mutex<Option<CatalogDiff>>> = Lazy::new(|| Mutex::new(None));
+
+/// New images of one board
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewImages {
+    pub board_slug: String,
+    /// Armbian versions the board had no image for before
+    pub versions: Vec<String>,
+    /// Number of new image files
+    pub count: usize,
+}
+
+/// Support level change of one board ("conf", "csc", "eos", "tvb", "wip")
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupportChange {
+    pub board_slug: String,
+    pub previous: String,
+    pub current: String,
+}
+
+/// Differences between two catalog snapshots
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatalogDiff {
+    /// Unix timestamp (seconds) of the fetch that found the changes
+    pub detected_at: u64,
+    pub added_boards: Vec<String>,
+    pub removed_boards: Vec<String>,
+    pub new_images: Vec<NewImages>,
+    pub support_changes: Vec<SupportChange>,
+}
+
+impl CatalogDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_boards.is_empty()
+            && self.removed_boards.is_empty()
+            && self.new_images.is_empty()
+            && self.support_changes.is_empty()
+    }
+
+    /// Add the changes found in another catalog source
+    pub fn merge(&mut self, other: CatalogDiff) {
+        self.detected_at = self.detected_at.max(other.detected_at);
+        self.added_boards.extend(other.added_boards);
+        self.added_boards.sort();
+        self.added_boards.dedup();
+        self.removed_boards.extend(other.removed_boards);
+        self.removed_boards.sort();
+        self.removed_boards.dedup();
+        self.new_images.extend(other.new_images);
+        self.support_changes.extend(other.support_changes);
+    }
+}
+
+/// Images of one board in a snapshot
+#[derive(Default)]
+struct BoardSnapshot {
+    file_urls: BTreeSet<String>,
+    versions: BTreeSet<String>,
+    support: HashMap<String, usize>,
+}
+
+impl BoardSnapshot {
+    /// Support level shared by most of the board's images
+    fn support_level(&self) -> Option<&str> {
+        self.support
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map(|(level, _)| level.as_str())
+    }
+}
+
+fn snapshot(images: &[ArmbianImage]) -> BTreeMap<String, BoardSnapshot> {
+    let mut boards: BTreeMap<String, BoardSnapshot> = BTreeMap::new();
+    for image in images {
+        let slug = match image.board_slug.as_deref() {
+            Some(slug) if !slug.is_empty() => normalize_slug(slug),
+            _ => continue,
+        };
+        let board = boards.entry(slug).or_default();
+        if let Some(url) = &image.file_url {
+            board.file_urls.insert(url.clone());
+        }
+        if let Some(version) = &image.armbian_version {
+            board.versions.insert(version.clone());
+        }
+        if let Some(level) = image.board_support.as_deref().filter(|l| !l.is_empty()) {
+            *board.support.entry(level.to_string()).or_default() += 1;
+        }
+    }
+    boards
+}
+
+/// Compare two catalog snapshots
+pub fn compare(previous: &serde_json::Value, current: &serde_json::Value) -> CatalogDiff {
+    let old = snapshot(&extract_images(previous));
+    let new = snapshot(&extract_images(current));
+    let mut diff = CatalogDiff {
+        detected_at: super::status::now_secs(),
+        ..Default::default()
+    };
+
+    diff.removed_boards = old
+        .keys()
+        .filter(|slug| !new.contains_key(*slug))
+        .cloned()
+        .collect();
+
+    for (slug, board) in &new {
+        let before = match old.get(slug) {
+            Some(before) => before,
+            None => {
+                diff.added_boards.push(slug.clone());
+                continue;
+            }
+        };
+
+        let count = board.file_urls.difference(&before.file_urls).count();
+        if count > 0 {
+            diff.new_images.push(NewImages {
+                board_slug: slug.clone(),
+                versions: board
+                    .versions
+                    .difference(&before.versions)
+                    .cloned()
+                    .collect(),
+                count,
+            });
+        }
+
+        if let (Some(previous), Some(current)) = (before.support_level(), board.support_level()) {
+            if previous != current {
+                diff.support_changes.push(SupportChange {
+                    board_slug: slug.clone(),
+                    previous: previous.to_string(),
+                    current: current.to_string(),
+                });
+            }
+        }
+    }
+
+    diff
+}
+
+/// Keep a diff for the frontend, unless nothing changed
+pub(super) fn record(diff: CatalogDiff) {
+    if diff.is_empty() {
+        return;
+    }
+    if let Ok(mut latest) = LATEST.lock() {
+        *latest = Some(diff.clone());
+    }
+    if let Ok(mut pending) = PENDING.lock() {
+        *pending = Some(diff);
+    }
+}
+
+/// Most recent catalog changes, if any were detected this session
+pub fn latest() -> Option<CatalogDiff> {
+    LATEST.lock().ok().and_then(|latest| latest.clone())
+}
+
+/// Catalog changes not yet announced to the frontend
+pub fn take_pending() -> Option<CatalogDiff> {
+    PENDING.lock().ok().and_then(|mut pending| pending.take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(board: &str, version: &str, support: &str) -> serde_json::Value {
+        serde_json::json!({
+            "board_slug": board,
+            "armbian_version": version,
+            "board_support": support,
+            "file_url": format!("https://dl.armbian.com/{}/Armbian_{}_{}.img.xz", board, version, board),
+            "file_extension": "img.xz",
+        })
+    }
+
+    #[test]
+    fn test_compare_catalogs() {
+        let previous = serde_json::json!([
+            image("rock-5b", "25.02.0", "conf"),
+            image("orangepi-5", "25.02.0", "conf"),
+            image("nanopi-r2s", "25.02.0", "csc"),
+        ]);
+        let current = serde_json::json!([
+            image("rock-5b", "25.02.0", "conf"),
+            image("rock-5b", "25.05.0", "conf"),
+            image("orangepi-5", "25.02.0", "eos"),
+            image("odroid-m2", "25.05.0", "csc"),
+        ]);
+
+        let diff = compare(&previous, &current);
+        assert_eq!(diff.added_boards, vec!["odroid-m2"]);
+        assert_eq!(diff.removed_boards, vec!["nanopi-r2s"]);
+        assert_eq!(
+            diff.new_images,
+            vec![NewImages {
+                board_slug: "rock-5b".to_string(),
+                versions: vec!["25.05.0".to_string()],
+                count: 1,
+            }]
+        );
+        assert_eq!(
+            diff.support_changes,
+            vec![SupportChange {
+                board_slug: "orangepi-5".to_string(),
+                previous: "conf".to_string(),
+                current: "eos".to_string(),
+            }]
+        );
+
+        assert!(compare(&current, &current).is_empty());
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! Functions for parsing and filtering image data.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use crate::config;
 use crate::log_info;
@@ -194,6 +194,8 @@ struct BoardData {
     has_wip_support: bool,
     /// board_support: "conf" with platinum: "true" and valid date
     platinum_support_until: Option<String>,
+    /// Catalog sources listing the board
+    sources: BTreeSet<String>,
 }
 
 /// Get unique board list from images
@@ -216,8 +218,10 @@ pub fn get_unique_boards(images: &[ArmbianImage]) -> Vec<BoardInfo> {
                 has_tvb_support: false,
                 has_wip_support: false,
                 platinum_support_until: None,
+                sources: BTreeSet::new(),
             });
             entry.count += 1;
+            entry.sources.insert(img.source.clone());
 
             // Use board_support field to determine support level
             match img.board_support.as_deref() {
@@ -308,6 +312,7 @@ pub fn get_unique_boards(images: &[ArmbianImage]) -> Vec<BoardInfo> {
                 has_eos_support,
                 has_tvb_support,
                 has_wip_support,
+                sources: data.sources.into_iter().collect(),
             }
         })
         .collect();
@@ -347,6 +352,29 @@ pub fn get_unique_boards(images: &[ArmbianImage]) -> Vec<BoardInfo> {
     boards
 }
 
+/// Convert a raw API image to the UI representation
+pub(super) fn to_image_info(img: &ArmbianImage) -> ImageInfo {
+    ImageInfo {
+        armbian_version: img.armbian_version.clone().unwrap_or_default(),
+        distro_release: img.distro_release.clone().unwrap_or_default(),
+        kernel_branch: img.kernel_branch.clone().unwrap_or_default(),
+        kernel_version: img.kernel_version.clone().unwrap_or_default(),
+        image_variant: img.image_variant.clone().unwrap_or_default(),
+        preinstalled_application: img.preinstalled_application.clone().unwrap_or_default(),
+        promoted: img.promoted.as_deref() == Some("true"),
+        file_url: img.file_url.clone().unwrap_or_default(),
+        file_url_sha: img.file_url_sha.clone(),
+        file_size: img
+            .file_size
+            .as_ref()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0),
+        download_repository: img.download_repository.clone().unwrap_or_default(),
+        flash_method: determine_flash_method(img),
+        source: img.source.clone(),
+    }
+}
+
 /// Filter images for a specific board
 pub fn filter_images_for_board(
     images: &[ArmbianImage],
@@ -400,24 +428,7 @@ pub fn filter_images_for_board(
 
             true
         })
-        .map(|img| ImageInfo {
-            armbian_version: img.armbian_version.clone().unwrap_or_default(),
-            distro_release: img.distro_release.clone().unwrap_or_default(),
-            kernel_branch: img.kernel_branch.clone().unwrap_or_default(),
-            kernel_version: img.kernel_version.clone().unwrap_or_default(),
-            image_variant: img.image_variant.clone().unwrap_or_default(),
-            preinstalled_application: img.preinstalled_application.clone().unwrap_or_default(),
-            promoted: img.promoted.as_deref() == Some("true"),
-            file_url: img.file_url.clone().unwrap_or_default(),
-            file_url_sha: img.file_url_sha.clone(),
-            file_size: img
-                .file_size
-                .as_ref()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(0),
-            download_repository: img.download_repository.clone().unwrap_or_default(),
-            flash_method: determine_flash_method(img),
-        })
+        .map(to_image_info)
         .collect();
 
     filtered.sort_by(|a, b| match (a.promoted, b.promoted) {
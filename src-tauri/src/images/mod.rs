@@ -1,61 +1,271 @@
 //! Image management module
 //!
 //! Handles fetching, parsing, and filtering Armbian image data.
-//! Caches the API response on disk for offline use.
+//! Catalogs come from the built-in Armbian source and any configured
+//! third-party sources. Each is cached on disk for offline use, revalidated
+//! with conditional requests, and compared with its previous snapshot to
+//! detect changes.
 
+mod catalog;
+pub mod diff;
 mod filters;
 mod models;
+pub mod search;
+pub mod sources;
+pub mod status;
 
 // Re-export types and functions
-pub use filters::{extract_images, filter_images_for_board, get_unique_boards};
+pub use catalog::{BoardFacets, Catalog};
 pub use models::{BoardInfo, ImageInfo};
-// ArmbianImage is used internally by filters module
+// ArmbianImage is used internally by the catalog and filters modules
+pub use status::CatalogStatus;
 
-use crate::config;
-use crate::utils::get_cache_dir;
+use std::path::{Path, PathBuf};
+
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+
+use crate::cache::cache_root;
+use crate::http::retry;
 use crate::{log_error, log_info, log_warn};
 
-/// Path to the locally cached API response
-pub(crate) fn get_api_cache_path() -> std::path::PathBuf {
-    get_cache_dir(config::app::NAME)
-        .join("assets")
-        .join("api-images.json")
+use diff::CatalogDiff;
+use sources::Source;
+use status::CatalogSource;
+
+/// Path to the locally cached API response of the built-in catalog
+pub(crate) fn get_api_cache_path() -> PathBuf {
+    cache_root().join("assets").join("api-images.json")
+}
+
+/// When the saved built-in catalog was last confirmed by the server
+pub(crate) fn builtin_fetched_at() -> Option<u64> {
+    let path = get_api_cache_path();
+    status::load_meta(&path)
+        .map(|meta| meta.fetched_at)
+        .or_else(|| api_cache_mtime(&path))
 }
 
-/// Fetch the all-images.json from Armbian, with local disk cache fallback.
+/// Fetch every enabled catalog source and index the merged result
 ///
-/// On success: saves the response to disk for offline use.
-/// On failure: loads the last saved response from disk.
-/// If both fail: returns an error.
-pub async fn fetch_all_images() -> Result<serde_json::Value, String> {
+/// A source that cannot be fetched falls back to its saved copy, and is
+/// left out if it has none. Fails only when no source could be loaded.
+pub async fn fetch_catalog(sources: &[Source]) -> Result<Catalog, String> {
+    let mut catalogs = Vec::new();
+    let mut statuses = Vec::new();
+    let mut changes = CatalogDiff::default();
+    let mut last_error = None;
+
+    for source in sources.iter().filter(|s| s.enabled) {
+        match fetch_source(source).await {
+            Ok(loaded) => {
+                if let Some(diff) = loaded.changes {
+                    changes.merge(diff);
+                }
+                statuses.push(loaded.status);
+                catalogs.push((source.clone(), loaded.json));
+            }
+            Err(e) => {
+                log_warn!(
+                    "images",
+                    "Catalog source '{}' unavailable: {}",
+                    source.id,
+                    e
+                );
+                last_error = Some(e);
+            }
+        }
+    }
+
+    if catalogs.is_empty() {
+        return Err(last_error.unwrap_or_else(|| "No catalog source is enabled".to_string()));
+    }
+    if !changes.is_empty() {
+        log_info!(
+            "images",
+            "Catalog changed: {} boards added, {} removed, {} with new images, {} support changes",
+            changes.added_boards.len(),
+            changes.removed_boards.len(),
+            changes.new_images.len(),
+            changes.support_changes.len()
+        );
+    }
+    diff::record(changes);
+    status::set_current(statuses);
+    Ok(Catalog::from_sources(&catalogs))
+}
+
+/// One catalog source, loaded
+struct Loaded {
+    json: serde_json::Value,
+    status: CatalogStatus,
+    /// Changes since the saved copy (none on first load)
+    changes: Option<CatalogDiff>,
+}
+
+/// Fetch one catalog source, with local disk cache fallback.
+///
+/// For remote sources the request is conditional when a saved copy exists:
+/// on 304 the saved copy is used. On new data: compares it with the saved
+/// copy, then saves it to disk for offline use.
+/// On failure: uses a newer copy from a LAN peer for the built-in catalog,
+/// else the last saved copy from disk.
+/// If all fail: returns an error.
+async fn fetch_source(source: &Source) -> Result<Loaded, String> {
     log_info!(
         "images",
-        "Fetching all images from {}",
-        config::urls::ALL_IMAGES
+        "Fetching catalog '{}' from {}",
+        source.id,
+        source.location
     );
 
-    // Try fetching from the API
-    match fetch_from_api().await {
-        Ok(json) => {
+    let cache_path = source.cache_path();
+    let previous = load_api_cache(&cache_path).await;
+    let meta = previous
+        .as_ref()
+        .ok()
+        .and_then(|_| status::load_meta(&cache_path));
+
+    let fetched = if source.is_remote() {
+        fetch_from_api(&source.location, meta.as_ref()).await
+    } else {
+        read_local(Path::new(&source.location)).await
+    };
+
+    match fetched {
+        Ok(Fetched::Modified(json, validators)) => {
+            // Detect changes since the previous snapshot (none on first load)
+            let changes = previous
+                .ok()
+                .map(|previous| diff::compare(&previous, &json));
+            let kind = if source.is_remote() {
+                CatalogSource::Live
+            } else {
+                CatalogSource::Local
+            };
+            let status = CatalogStatus::new(source, kind, Some(validators.fetched_at));
+
             // Save to disk for offline use (non-blocking, best-effort)
-            save_api_cache(&json);
-            Ok(json)
+            save_api_cache(&cache_path, &json, validators);
+            Ok(Loaded {
+                json,
+                status,
+                changes,
+            })
+        }
+        Ok(Fetched::NotModified) => {
+            log_info!(
+                "images",
+                "Catalog '{}' not modified, using local cache",
+                source.id
+            );
+            let now = status::now_secs();
+            if let Some(meta) = meta {
+                status::save_meta(
+                    &cache_path,
+                    &status::CatalogMeta {
+                        fetched_at: now,
+                        ..meta
+                    },
+                );
+            }
+            Ok(Loaded {
+                json: previous?,
+                status: CatalogStatus::new(source, CatalogSource::Cached, Some(now)),
+                changes: None,
+            })
         }
         Err(e) => {
-            log_warn!("images", "API fetch failed, trying local cache: {}", e);
-            load_api_cache().await
+            log_warn!(
+                "images",
+                "Fetching catalog '{}' failed, trying LAN peers and local cache: {}",
+                source.id,
+                e
+            );
+            let fetched_at = meta
+                .map(|m| m.fetched_at)
+                .or_else(|| api_cache_mtime(&cache_path));
+            let saved_at = previous.as_ref().ok().and(fetched_at);
+            if source.is_builtin() {
+                if let Some((json, peer_fetched_at)) = crate::lan::fetch_catalog(saved_at).await {
+                    return Ok(Loaded {
+                        json,
+                        status: CatalogStatus::new(
+                            source,
+                            CatalogSource::Peer,
+                            Some(peer_fetched_at),
+                        ),
+                        changes: None,
+                    });
+                }
+            }
+            let json = previous?;
+            Ok(Loaded {
+                json,
+                status: CatalogStatus::new(source, CatalogSource::StaleOffline, fetched_at),
+                changes: None,
+            })
         }
     }
 }
 
-/// Fetch the all-images.json directly from the remote Armbian API
+/// Result of a catalog request
+enum Fetched {
+    /// New catalog data with its validators
+    Modified(serde_json::Value, status::CatalogMeta),
+    /// The saved copy is current (304)
+    NotModified,
+}
+
+/// Fetch a catalog directly from its remote URL
 ///
-/// Returns the parsed JSON on success, or an error string on network/parse failure.
-async fn fetch_from_api() -> Result<serde_json::Value, String> {
-    let response = reqwest::get(config::urls::ALL_IMAGES).await.map_err(|e| {
-        log_error!("images", "Failed to fetch images: {}", e);
-        format!("Failed to fetch images: {}", e)
-    })?;
+/// With the validators of a saved copy the request is conditional.
+/// Returns the parsed JSON with its validators, NotModified on 304, or an
+/// error string on network/parse failure.
+async fn fetch_from_api(url: &str, saved: Option<&status::CatalogMeta>) -> Result<Fetched, String> {
+    let client = crate::http::client(crate::http::Timeout::Request)?;
+    let make_request = || {
+        let mut request = client.get(url);
+        if let Some(saved) = saved {
+            if let Some(etag) = &saved.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &saved.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        request
+    };
+    let response = retry::send("Catalog fetch", make_request)
+        .await
+        .map_err(|e| {
+            log_error!("images", "Failed to fetch images: {}", e);
+            format!("Failed to fetch images: {}", e)
+        })?;
+
+    if response.status() == StatusCode::NOT_MODIFIED && saved.is_some() {
+        return Ok(Fetched::NotModified);
+    }
+    if !response.status().is_success() {
+        log_error!("images", "Catalog fetch returned {}", response.status());
+        return Err(format!(
+            "Failed to fetch images: HTTP {}",
+            response.status()
+        ));
+    }
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string())
+    };
+    let validators = status::CatalogMeta {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+        fetched_at: status::now_secs(),
+    };
 
     let json: serde_json::Value = response.json().await.map_err(|e| {
         log_error!("images", "Failed to parse JSON response: {}", e);
@@ -63,17 +273,42 @@ async fn fetch_from_api() -> Result<serde_json::Value, String> {
     })?;
 
     log_info!("images", "Successfully fetched images data from API");
-    Ok(json)
+    Ok(Fetched::Modified(json, validators))
+}
+
+/// Read a catalog from a local file
+async fn read_local(path: &Path) -> Result<Fetched, String> {
+    let data = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let json: serde_json::Value = serde_json::from_str(&data)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(Fetched::Modified(
+        json,
+        status::CatalogMeta {
+            fetched_at: status::now_secs(),
+            ..Default::default()
+        },
+    ))
 }
 
-/// Save API response to disk for offline use
+/// Modification time of a saved catalog as a Unix timestamp
+fn api_cache_mtime(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// Save a catalog to disk for offline use
 ///
 /// Serializes the JSON and writes it on a blocking thread to avoid
 /// stalling the Tokio async runtime with synchronous file I/O.
 /// Uses a temp file + rename pattern for atomic writes to prevent
 /// partial reads if the app crashes mid-write.
-fn save_api_cache(json: &serde_json::Value) {
-    let path = get_api_cache_path();
+fn save_api_cache(path: &Path, json: &serde_json::Value, meta: status::CatalogMeta) {
+    let path = path.to_path_buf();
     match serde_json::to_string(json) {
         Ok(data) => {
             std::thread::spawn(move || {
@@ -90,6 +325,8 @@ fn save_api_cache(json: &serde_json::Value) {
                     log_warn!("images", "Failed to rename API cache file: {}", e);
                     let _ = std::fs::remove_file(&tmp_path);
                 } else {
+                    // Validators only describe the copy they were sent with
+                    status::save_meta(&path, &meta);
                     log_info!("images", "Saved API cache to {}", path.display());
                 }
             });
@@ -100,16 +337,15 @@ fn save_api_cache(json: &serde_json::Value) {
     }
 }
 
-/// Load API response from disk cache
+/// Load a saved catalog from disk cache
 ///
 /// Uses async I/O to avoid blocking the Tokio runtime.
-async fn load_api_cache() -> Result<serde_json::Value, String> {
-    let path = get_api_cache_path();
+async fn load_api_cache(path: &Path) -> Result<serde_json::Value, String> {
     if !path.exists() {
         return Err("No cached API data available (first launch while offline)".to_string());
     }
 
-    let data = tokio::fs::read_to_string(&path).await.map_err(|e| {
+    let data = tokio::fs::read_to_string(path).await.map_err(|e| {
         log_error!("images", "Failed to read API cache: {}", e);
         format!("Failed to read cached data: {}", e)
     })?;
@@ -126,3 +362,42 @@ async fn load_api_cache() -> Result<serde_json::Value, String> {
     );
     Ok(json)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_conditional_fetch() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/all-images.json", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let reply: &[u8] = if request.contains("if-none-match: \"v1\"") {
+                    b"HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n"
+                } else {
+                    b"HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 2\r\nConnection: close\r\n\r\n[]"
+                };
+                let _ = socket.write_all(reply).await;
+            }
+        });
+
+        let validators = match fetch_from_api(&url, None).await.unwrap() {
+            Fetched::Modified(json, validators) => {
+                assert_eq!(json, serde_json::json!([]));
+                validators
+            }
+            Fetched::NotModified => panic!("unconditional request answered 304"),
+        };
+        assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+
+        let refetched = fetch_from_api(&url, Some(&validators)).await.unwrap();
+        assert!(matches!(refetched, Fetched::NotModified));
+    }
+}
@@ -45,6 +45,9 @@ pub struct ArmbianImage {
     pub board_support: Option<String>,
     /// Flash method: "block" (default SD/USB), "qdl" (Qualcomm EDL)
     pub flash_method: Option<String>,
+    /// Id of the catalog source listing the image (not an API field)
+    #[serde(skip)]
+    pub source: String,
 }
 
 /// Board information for display
@@ -62,6 +65,8 @@ pub struct BoardInfo {
     pub has_eos_support: bool,
     pub has_tvb_support: bool,
     pub has_wip_support: bool,
+    /// Ids of the catalog sources listing the board
+    pub sources: Vec<String>,
 }
 
 /// Processed image information for the UI
@@ -80,4 +85,6 @@ pub struct ImageInfo {
     pub download_repository: String,
     /// Flash method: "block" (default) or "qdl" (Qualcomm EDL)
     pub flash_method: String,
+    /// Id of the catalog source listing the image
+    pub source: String,
 }
@@ -0,0 +1,365 @@
+//! Catalog search
+//!
+//! Fuzzy search across all boards and images. Every word of the query must
+//! match a keyword of the image or its board: board name, slug, vendor, the
+//! image file name, distribution, branch, desktop, application and versions.
+//! The catalog has no SoC field, so SoC names such as "rk3588" match where
+//! the board name, slug or file name mentions them. Matches are exact,
+//! prefix, substring or within a small edit distance, and rank accordingly.
+//! Facet counts cover all matches, before the result limit.
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::catalog::Catalog;
+use super::filters::to_image_info;
+use super::models::{ArmbianImage, BoardInfo, ImageInfo};
+
+/// Exact facet values to narrow a search down to (case-insensitive)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchFilters {
+    pub vendor: Option<String>,
+    /// "platinum", "standard", "community", "eos", "tvb" or "wip"
+    pub support_level: Option<String>,
+    pub distro: Option<String>,
+    pub branch: Option<String>,
+    pub desktop: Option<String>,
+    pub kernel_version: Option<String>,
+}
+
+/// Number of matching images with a facet value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: usize,
+}
+
+/// Facet counts over all matching images
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchFacets {
+    pub vendors: Vec<FacetCount>,
+    pub support_levels: Vec<FacetCount>,
+    pub distros: Vec<FacetCount>,
+    pub branches: Vec<FacetCount>,
+    pub desktops: Vec<FacetCount>,
+    pub kernel_versions: Vec<FacetCount>,
+}
+
+/// A matching image with its board
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageHit {
+    pub board_slug: String,
+    pub board_name: String,
+    pub score: f64,
+    #[serde(flatten)]
+    pub image: ImageInfo,
+}
+
+/// Ranked search results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchResults {
+    /// Boards with matching images, best match first
+    pub boards: Vec<BoardInfo>,
+    /// Matching images, best match first
+    pub images: Vec<ImageHit>,
+    /// Number of matching images before the result limit
+    pub total_images: usize,
+    pub facets: SearchFacets,
+}
+
+/// Support level label of a board, as shown in the board list
+fn support_level(board: &BoardInfo) -> &'static str {
+    if board.has_platinum_support {
+        "platinum"
+    } else if board.has_standard_support {
+        "standard"
+    } else if board.has_community_support {
+        "community"
+    } else if board.has_eos_support {
+        "eos"
+    } else if board.has_tvb_support {
+        "tvb"
+    } else if board.has_wip_support {
+        "wip"
+    } else {
+        "unknown"
+    }
+}
+
+/// Split text into lowercase search words
+///
+/// Dots stay inside words so versions like "6.1" remain one word.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '.'))
+        .map(|w| w.trim_matches('.').to_lowercase())
+        .filter(|w| !w.is_empty())
+}
+
+/// Edit distance between two words
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// How well a query word matches a keyword (0 = no match)
+fn match_score(word: &str, keyword: &str) -> f64 {
+    if keyword == word {
+        1.0
+    } else if keyword.starts_with(word) {
+        0.8
+    } else if word.len() >= 3 && keyword.contains(word) {
+        0.6
+    } else if word.len() >= 4 {
+        let tolerance = if word.len() >= 8 { 2 } else { 1 };
+        if levenshtein(word, keyword) <= tolerance {
+            0.5
+        } else {
+            0.0
+        }
+    } else {
+        0.0
+    }
+}
+
+fn board_keywords(board: &BoardInfo) -> HashSet<String> {
+    let mut keywords: HashSet<String> = words(&board.name)
+        .chain(words(&board.slug))
+        .chain(words(&board.vendor))
+        .chain(words(&board.vendor_name))
+        .collect();
+    keywords.insert(board.slug.clone());
+    keywords
+}
+
+fn image_keywords(img: &ArmbianImage) -> HashSet<String> {
+    let file_name = img
+        .file_url
+        .as_deref()
+        .and_then(|url| url.rsplit('/').next())
+        .unwrap_or("");
+    [
+        img.distro_release.as_deref(),
+        img.kernel_branch.as_deref(),
+        img.image_variant.as_deref(),
+        img.preinstalled_application.as_deref(),
+        img.kernel_version.as_deref(),
+        img.armbian_version.as_deref(),
+        Some(file_name),
+    ]
+    .into_iter()
+    .flatten()
+    .flat_map(words)
+    .collect()
+}
+
+/// Score an image against the query words, None if a word does not match
+fn score(query: &[String], board: &HashSet<String>, image: &HashSet<String>) -> Option<f64> {
+    query.iter().try_fold(0.0, |total, word| {
+        let best = board
+            .iter()
+            .chain(image.iter())
+            .map(|keyword| match_score(word, keyword))
+            .fold(0.0, f64::max);
+        (best > 0.0).then_some(total + best)
+    })
+}
+
+fn matches_filter(filter: &Option<String>, value: &str) -> bool {
+    filter
+        .as_deref()
+        .is_none_or(|wanted| wanted.eq_ignore_ascii_case(value))
+}
+
+fn count_facet(counts: BTreeMap<String, usize>) -> Vec<FacetCount> {
+    let mut facet: Vec<FacetCount> = counts
+        .into_iter()
+        .filter(|(value, _)| !value.is_empty())
+        .map(|(value, count)| FacetCount { value, count })
+        .collect();
+    facet.sort_by(|a, b| b.count.cmp(&a.count).then(a.value.cmp(&b.value)));
+    facet
+}
+
+/// Search the catalog, returning at most `limit` boards and images
+pub fn search(
+    catalog: &Catalog,
+    query: &str,
+    filters: &SearchFilters,
+    limit: usize,
+) -> SearchResults {
+    let query: Vec<String> = words(query).collect();
+
+    let mut hits: Vec<(&BoardInfo, ImageHit)> = Vec::new();
+    for board in catalog.boards() {
+        let level = support_level(board);
+        if !matches_filter(&filters.vendor, &board.vendor)
+            || !matches_filter(&filters.support_level, level)
+        {
+            continue;
+        }
+
+        let board_words = board_keywords(board);
+        for img in catalog.board_images(&board.slug) {
+            let image = to_image_info(img);
+            if !matches_filter(&filters.distro, &image.distro_release)
+                || !matches_filter(&filters.branch, &image.kernel_branch)
+                || !matches_filter(&filters.desktop, &image.image_variant)
+                || !matches_filter(&filters.kernel_version, &image.kernel_version)
+            {
+                continue;
+            }
+
+            if let Some(score) = score(&query, &board_words, &image_keywords(img)) {
+                hits.push((
+                    board,
+                    ImageHit {
+                        board_slug: board.slug.clone(),
+                        board_name: board.name.clone(),
+                        score,
+                        image,
+                    },
+                ));
+            }
+        }
+    }
+
+    let mut vendors = BTreeMap::new();
+    let mut support_levels = BTreeMap::new();
+    let mut distros = BTreeMap::new();
+    let mut branches = BTreeMap::new();
+    let mut desktops = BTreeMap::new();
+    let mut kernel_versions = BTreeMap::new();
+    for (board, hit) in &hits {
+        let image = &hit.image;
+        *vendors.entry(board.vendor.clone()).or_default() += 1;
+        *support_levels
+            .entry(support_level(board).to_string())
+            .or_default() += 1;
+        *distros.entry(image.distro_release.clone()).or_default() += 1;
+        *branches.entry(image.kernel_branch.clone()).or_default() += 1;
+        *desktops.entry(image.image_variant.clone()).or_default() += 1;
+        *kernel_versions
+            .entry(image.kernel_version.clone())
+            .or_default() += 1;
+    }
+    let facets = SearchFacets {
+        vendors: count_facet(vendors),
+        support_levels: count_facet(support_levels),
+        distros: count_facet(distros),
+        branches: count_facet(branches),
+        desktops: count_facet(desktops),
+        kernel_versions: count_facet(kernel_versions),
+    };
+
+    // Best score first, then promoted images, then newest version
+    hits.sort_by(|(_, a), (_, b)| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.image.promoted.cmp(&a.image.promoted))
+            .then(b.image.armbian_version.cmp(&a.image.armbian_version))
+    });
+
+    let total_images = hits.len();
+    let mut seen = HashSet::new();
+    let boards = hits
+        .iter()
+        .filter(|(board, _)| seen.insert(board.slug.as_str()))
+        .map(|(board, _)| (*board).clone())
+        .take(limit)
+        .collect();
+    let images = hits.into_iter().map(|(_, hit)| hit).take(limit).collect();
+
+    SearchResults {
+        boards,
+        images,
+        total_images,
+        facets,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(board: &str, name: &str, distro: &str, variant: &str) -> serde_json::Value {
+        serde_json::json!({
+            "board_slug": board,
+            "board_name": name,
+            "board_vendor": "radxa",
+            "company_logo": "radxa.png",
+            "board_support": "conf",
+            "distro": distro,
+            "branch": "vendor",
+            "variant": variant,
+            "kernel_version": "6.1.115",
+            "file_url": format!("https://dl.armbian.com/{}/Armbian_25.8.1_{}_{}_vendor_6.1.115_{}.img.xz", board, name.replace(' ', "-"), distro, variant),
+            "file_extension": "img.xz",
+        })
+    }
+
+    fn catalog() -> Catalog {
+        Catalog::from_json(&serde_json::json!([
+            image("rock-5b", "Rock 5B RK3588", "bookworm", "gnome"),
+            image("rock-5b", "Rock 5B RK3588", "noble", "minimal"),
+            image("rock-4se", "Rock 4SE RK3399", "bookworm", "gnome"),
+        ]))
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("bookworm", "bookworm"), 0);
+        assert_eq!(levenshtein("bokworm", "bookworm"), 1);
+        assert_eq!(levenshtein("noble", "jammy"), 5);
+    }
+
+    #[test]
+    fn test_search_ranks_and_counts() {
+        let catalog = catalog();
+
+        let results = search(
+            &catalog,
+            "rk3588 bookworm gnome",
+            &SearchFilters::default(),
+            10,
+        );
+        assert_eq!(results.total_images, 1);
+        assert_eq!(results.boards[0].slug, "rock-5b");
+        assert_eq!(results.images[0].image.distro_release, "bookworm");
+
+        // A typo still matches, and facets count every match
+        let results = search(&catalog, "bokworm", &SearchFilters::default(), 10);
+        assert_eq!(results.total_images, 2);
+        assert_eq!(
+            results.facets.distros,
+            vec![FacetCount {
+                value: "bookworm".to_string(),
+                count: 2
+            }]
+        );
+
+        let filters = SearchFilters {
+            desktop: Some("minimal".to_string()),
+            ..Default::default()
+        };
+        let results = search(&catalog, "rock", &filters, 10);
+        assert_eq!(results.total_images, 1);
+        assert_eq!(results.images[0].image.distro_release, "noble");
+
+        assert_eq!(
+            search(&catalog, "odroid", &SearchFilters::default(), 10).total_images,
+            0
+        );
+    }
+}
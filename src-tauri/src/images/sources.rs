@@ -0,0 +1,200 @@
+//! Catalog sources
+//!
+//! The built-in Armbian catalog plus user-configured third-party catalogs,
+//! each read from a URL or a local file path. Every source is fetched and
+//! cached separately, and carries the verification its images must pass.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::cache_root;
+use crate::config;
+use crate::download::signature::Keyring;
+use crate::download::Trust;
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// One image catalog
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+    /// Short unique id: lowercase letters, digits, '-' and '_'
+    pub id: String,
+    /// Name shown on the board badge
+    pub label: String,
+    /// Catalog URL (http or https) or absolute path of a local file
+    pub location: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Refuse images that cannot be checked against a published SHA256
+    #[serde(default)]
+    pub require_sha: bool,
+    /// Refuse images without a valid signature
+    #[serde(default)]
+    pub require_signature: bool,
+    /// Armored OpenPGP public key of the source: signatures of its images
+    /// are checked against it instead of the bundled keyring
+    #[serde(default)]
+    pub signing_key: Option<String>,
+}
+
+impl Source {
+    /// The official Armbian catalog
+    pub fn builtin() -> Self {
+        Self {
+            id: config::images::BUILTIN_SOURCE_ID.to_string(),
+            label: config::images::BUILTIN_SOURCE_LABEL.to_string(),
+            location: config::urls::ALL_IMAGES.to_string(),
+            enabled: true,
+            require_sha: false,
+            require_signature: false,
+            signing_key: None,
+        }
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        self.id == config::images::BUILTIN_SOURCE_ID
+    }
+
+    /// Whether the catalog is fetched over HTTP rather than read from disk
+    pub fn is_remote(&self) -> bool {
+        self.location.starts_with("https://") || self.location.starts_with("http://")
+    }
+
+    pub fn trust(&self) -> Trust {
+        Trust {
+            require_sha: self.require_sha,
+            require_signature: self.require_signature,
+            signing_key: self.keyring().ok().flatten().map(Arc::new),
+        }
+    }
+
+    /// Parse the source's signing key, if it has one
+    fn keyring(&self) -> Result<Option<Keyring>, String> {
+        let Some(key) = &self.signing_key else {
+            return Ok(None);
+        };
+        let keyring = Keyring::parse(key.as_bytes())
+            .map_err(|e| format!("Invalid signing key for source '{}': {}", self.id, e))?;
+        if keyring.is_empty() {
+            return Err(format!(
+                "The signing key of source '{}' has no supported (RSA) key",
+                self.id
+            ));
+        }
+        Ok(Some(keyring))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty()
+            || !self
+                .id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "Invalid source id '{}': use lowercase letters, digits, '-' and '_'",
+                self.id
+            ));
+        }
+        if self.label.trim().is_empty() {
+            return Err(format!("Source '{}' needs a label", self.id));
+        }
+        self.keyring()?;
+        if self.is_builtin() {
+            if self.location != config::urls::ALL_IMAGES {
+                return Err("The location of the Armbian catalog cannot be changed".to_string());
+            }
+            if self.signing_key.is_some() {
+                return Err("The Armbian catalog is checked with the bundled keys".to_string());
+            }
+        } else if !self.is_remote() && !Path::new(&self.location).is_absolute() {
+            return Err(format!(
+                "Source '{}' must be an http(s) URL or an absolute file path",
+                self.id
+            ));
+        }
+        Ok(())
+    }
+
+    /// Path of the saved copy of the catalog
+    pub(super) fn cache_path(&self) -> PathBuf {
+        if self.is_builtin() {
+            super::get_api_cache_path()
+        } else {
+            cache_root()
+                .join("assets")
+                .join(config::images::SOURCES_DIR)
+                .join(format!("{}.json", self.id))
+        }
+    }
+}
+
+/// Validate a source list, adding the built-in catalog if it is missing
+pub fn normalize(mut sources: Vec<Source>) -> Result<Vec<Source>, String> {
+    let mut ids = HashSet::new();
+    for source in &sources {
+        source.validate()?;
+        if !ids.insert(source.id.as_str()) {
+            return Err(format!("Duplicate source id '{}'", source.id));
+        }
+    }
+    if !sources.iter().any(Source::is_builtin) {
+        sources.insert(0, Source::builtin());
+    }
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(id: &str, location: &str) -> Source {
+        Source {
+            id: id.to_string(),
+            label: id.to_string(),
+            location: location.to_string(),
+            enabled: true,
+            require_sha: true,
+            require_signature: false,
+            signing_key: None,
+        }
+    }
+
+    #[test]
+    fn test_normalize_sources() {
+        let sources = normalize(vec![source("vendor", "https://example.com/images.json")]).unwrap();
+        assert_eq!(sources.len(), 2);
+        assert!(sources[0].is_builtin());
+        assert!(sources[1].trust().require_sha);
+
+        assert!(normalize(vec![source("Vendor", "https://example.com/a.json")]).is_err());
+        assert!(normalize(vec![source("local", "images.json")]).is_err());
+        assert!(normalize(vec![source("armbian", "https://example.com/a.json")]).is_err());
+        assert!(normalize(vec![
+            source("vendor", "https://example.com/a.json"),
+            source("vendor", "https://example.com/b.json"),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_source_signing_key() {
+        let key = include_str!("../download/testdata/test-key.asc");
+        let mut vendor = source("vendor", "https://example.com/images.json");
+        vendor.signing_key = Some(key.to_string());
+        assert!(vendor.validate().is_ok());
+        assert_eq!(vendor.trust().signing_key.unwrap().len(), 1);
+
+        vendor.signing_key = Some("not a key".to_string());
+        assert!(vendor.validate().is_err());
+
+        let mut builtin = Source::builtin();
+        builtin.signing_key = Some(key.to_string());
+        assert!(builtin.validate().is_err());
+    }
+}
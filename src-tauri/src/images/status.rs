@@ -0,0 +1,122 @@
+//! Catalog freshness
+//!
+//! Keeps the HTTP validators (ETag, Last-Modified) of each saved catalog in
+//! a sidecar next to it, so the next fetch can be conditional, and tracks
+//! where the catalogs in use came from and how old they are.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::log_warn;
+
+/// Where the catalog in use came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CatalogSource {
+    /// Downloaded from the server
+    Live,
+    /// Saved copy, confirmed current by the server (304 Not Modified)
+    Cached,
+    /// Saved copy used because the server could not be reached
+    StaleOffline,
+    /// Copy of a LAN peer, newer than the saved one, used because the
+    /// server could not be reached
+    Peer,
+    /// Read from a local file
+    Local,
+}
+
+/// Origin and age of one catalog source for the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogStatus {
+    /// Id of the configured catalog source
+    pub source_id: String,
+    pub label: String,
+    pub source: CatalogSource,
+    /// Unix timestamp (seconds) the server last confirmed the catalog, if known
+    pub fetched_at: Option<u64>,
+    /// Seconds since `fetched_at`
+    pub age_secs: Option<u64>,
+}
+
+/// Validators of the saved catalog, persisted as a JSON sidecar
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct CatalogMeta {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Unix timestamp (seconds) the server last confirmed the saved catalog
+    pub fetched_at: u64,
+}
+
+/// Origin and confirmation time of each catalog in use
+static CURRENT: Lazy<Mutex<Vec<CatalogStatus>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Current time as a Unix timestamp (seconds)
+pub(super) fn now_secs() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+/// Path of the validators sidecar of a saved catalog
+fn meta_path(cache_path: &Path) -> PathBuf {
+    cache_path.with_extension("meta.json")
+}
+
+/// Load the validators of a saved catalog
+pub(super) fn load_meta(cache_path: &Path) -> Option<CatalogMeta> {
+    let data = std::fs::read_to_string(meta_path(cache_path)).ok()?;
+    match serde_json::from_str(&data) {
+        Ok(meta) => Some(meta),
+        Err(e) => {
+            log_warn!("images", "Ignoring corrupted catalog metadata: {}", e);
+            None
+        }
+    }
+}
+
+/// Save the validators of a saved catalog
+pub(super) fn save_meta(cache_path: &Path, meta: &CatalogMeta) {
+    match serde_json::to_string(meta) {
+        Ok(data) => {
+            if let Err(e) = std::fs::write(meta_path(cache_path), data) {
+                log_warn!("images", "Failed to write catalog metadata: {}", e);
+            }
+        }
+        Err(e) => log_warn!("images", "Failed to serialize catalog metadata: {}", e),
+    }
+}
+
+impl CatalogStatus {
+    pub(super) fn new(
+        source: &super::sources::Source,
+        kind: CatalogSource,
+        fetched_at: Option<u64>,
+    ) -> Self {
+        Self {
+            source_id: source.id.clone(),
+            label: source.label.clone(),
+            source: kind,
+            fetched_at,
+            age_secs: None,
+        }
+    }
+}
+
+/// Record where the catalogs in use came from
+pub(super) fn set_current(statuses: Vec<CatalogStatus>) {
+    if let Ok(mut current) = CURRENT.lock() {
+        *current = statuses;
+    }
+}
+
+/// Origin and age of each catalog in use, empty before the first fetch
+pub fn current() -> Vec<CatalogStatus> {
+    let now = now_secs();
+    let mut statuses = CURRENT.lock().map(|c| c.clone()).unwrap_or_default();
+    for status in &mut statuses {
+        status.age_secs = status.fetched_at.map(|t| now.saturating_sub(t));
+    }
+    statuses
+}
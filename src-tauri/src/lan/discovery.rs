@@ -0,0 +1,162 @@
+//! Peer discovery
+//!
+//! Announces the cache server over mDNS and keeps the list of other
+//! Armbian Imager instances on the local network that share theirs.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::{log_debug, log_info, log_warn};
+
+const MODULE: &str = "lan::discovery";
+
+/// A machine sharing its image cache
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Peer {
+    /// Instance name announced by the peer
+    pub name: String,
+    /// Base URL of its cache server
+    pub address: String,
+}
+
+/// The mDNS daemon, started on first use
+static DAEMON: Lazy<Mutex<Option<ServiceDaemon>>> = Lazy::new(|| Mutex::new(None));
+
+/// Discovered peers by mDNS full name
+static PEERS: Lazy<Mutex<HashMap<String, Peer>>> = Lazy::new(Default::default);
+
+/// Our own instance name, unique per process
+static INSTANCE: Lazy<String> = Lazy::new(|| {
+    let host = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_else(|_| config::app::NAME.to_string());
+    let host: String = host
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("{}-{}", host.to_lowercase(), std::process::id())
+});
+
+fn daemon() -> Result<ServiceDaemon, String> {
+    let mut daemon = DAEMON.lock().map_err(|e| e.to_string())?;
+    if let Some(daemon) = daemon.as_ref() {
+        return Ok(daemon.clone());
+    }
+    let started =
+        ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS discovery: {}", e))?;
+    *daemon = Some(started.clone());
+    Ok(started)
+}
+
+fn own_fullname() -> String {
+    format!("{}.{}", *INSTANCE, config::lan::SERVICE_TYPE)
+}
+
+/// Base URL of a resolved service, preferring IPv4
+fn service_address(info: &ServiceInfo) -> Option<String> {
+    let addresses = info.get_addresses();
+    let ip = addresses
+        .iter()
+        .find(|ip| ip.is_ipv4())
+        .or_else(|| addresses.iter().next())?;
+    Some(match ip {
+        IpAddr::V4(ip) => format!("http://{}:{}", ip, info.get_port()),
+        IpAddr::V6(ip) => format!("http://[{}]:{}", ip, info.get_port()),
+    })
+}
+
+/// Announce the cache server listening on `port`
+pub fn advertise(port: u16) -> Result<(), String> {
+    let hostname = format!("{}.local.", *INSTANCE);
+    let info = ServiceInfo::new(
+        config::lan::SERVICE_TYPE,
+        &INSTANCE,
+        &hostname,
+        "",
+        port,
+        [("version", env!("CARGO_PKG_VERSION"))].as_slice(),
+    )
+    .map_err(|e| format!("Invalid mDNS service: {}", e))?
+    .enable_addr_auto();
+    daemon()?
+        .register(info)
+        .map_err(|e| format!("Failed to announce the cache server: {}", e))?;
+    log_info!(MODULE, "Announced {} on port {}", *INSTANCE, port);
+    Ok(())
+}
+
+/// Stop announcing the cache server
+pub fn withdraw() {
+    if let Ok(daemon) = daemon() {
+        if let Err(e) = daemon.unregister(&own_fullname()) {
+            log_warn!(MODULE, "Failed to withdraw the announcement: {}", e);
+        }
+    }
+}
+
+/// Start looking for peers, updating the peer list in the background
+pub fn start_browsing() -> Result<(), String> {
+    let events = daemon()?
+        .browse(config::lan::SERVICE_TYPE)
+        .map_err(|e| format!("Failed to browse for peers: {}", e))?;
+    let own = own_fullname();
+
+    tauri::async_runtime::spawn(async move {
+        while let Ok(event) = events.recv_async().await {
+            match event {
+                ServiceEvent::ServiceResolved(info) if info.get_fullname() != own => {
+                    let Some(address) = service_address(&info) else {
+                        continue;
+                    };
+                    log_debug!(MODULE, "Found peer {} at {}", info.get_fullname(), address);
+                    let name = info
+                        .get_fullname()
+                        .trim_end_matches(config::lan::SERVICE_TYPE)
+                        .trim_end_matches('.')
+                        .to_string();
+                    if let Ok(mut peers) = PEERS.lock() {
+                        peers.insert(info.get_fullname().to_string(), Peer { name, address });
+                    }
+                }
+                ServiceEvent::ServiceRemoved(_, fullname) => {
+                    log_debug!(MODULE, "Peer {} left", fullname);
+                    if let Ok(mut peers) = PEERS.lock() {
+                        peers.remove(&fullname);
+                    }
+                }
+                ServiceEvent::SearchStopped(_) => break,
+                _ => {}
+            }
+        }
+    });
+    log_info!(MODULE, "Looking for peers on the local network");
+    Ok(())
+}
+
+/// Stop looking for peers and forget the ones found
+pub fn stop_browsing() {
+    if let Ok(daemon) = daemon() {
+        if let Err(e) = daemon.stop_browse(config::lan::SERVICE_TYPE) {
+            log_debug!(MODULE, "Failed to stop browsing: {}", e);
+        }
+    }
+    if let Ok(mut peers) = PEERS.lock() {
+        peers.clear();
+    }
+}
+
+/// Peers currently known, sorted by name
+pub fn peers() -> Vec<Peer> {
+    let mut peers: Vec<Peer> = PEERS
+        .lock()
+        .map(|peers| peers.values().cloned().collect())
+        .unwrap_or_default();
+    peers.sort_by(|a, b| a.name.cmp(&b.name));
+    peers
+}
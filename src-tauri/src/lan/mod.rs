@@ -0,0 +1,186 @@
+//! LAN cache sharing
+//!
+//! Machines on the same network can serve their image cache to each other,
+//! so an image is downloaded from the internet once and flashed everywhere.
+//! A small HTTP server exposes the cache and is announced over mDNS; peers
+//! found the same way are asked for an image before the internet mirrors,
+//! and for their catalog snapshot when the catalog server is unreachable.
+//!
+//! Peers are not trusted: an image fetched from one goes through the same
+//! SHA256 and signature checks as any other download, against files fetched
+//! from the catalog, so peers are only asked for images with a published
+//! SHA, and a peer's file that cannot be checked is discarded. Because the
+//! published SHA covers the compressed file, peers are only asked for the
+//! exact file name of the download, and a sharing machine keeps the images
+//! it caches compressed. A peer's catalog is only used for the session and
+//! never saved over the local copy.
+
+pub mod discovery;
+mod server;
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+use crate::config;
+use crate::http::{self, Timeout};
+use crate::{log_debug, log_info};
+
+use discovery::Peer;
+
+const MODULE: &str = "lan";
+
+/// The running cache server
+struct Server {
+    port: u16,
+    shutdown: watch::Sender<bool>,
+}
+
+static SERVER: Lazy<Mutex<Option<Server>>> = Lazy::new(|| Mutex::new(None));
+
+static BROWSING: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
+
+/// Sharing state shown in the settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanStatus {
+    /// Whether the cache is served to peers
+    pub sharing: bool,
+    /// Port of the cache server while sharing
+    pub port: Option<u16>,
+    /// Whether peers are looked for
+    pub browsing: bool,
+    pub peers: Vec<Peer>,
+}
+
+/// Start or stop serving the image cache to peers
+pub async fn set_sharing(enabled: bool) -> Result<(), String> {
+    let running = SERVER.lock().map_err(|e| e.to_string())?.take();
+    if let Some(server) = running {
+        discovery::withdraw();
+        let _ = server.shutdown.send(true);
+    }
+    if !enabled {
+        return Ok(());
+    }
+
+    let listener = TcpListener::bind(("0.0.0.0", 0))
+        .await
+        .map_err(|e| format!("Failed to start the cache server: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to start the cache server: {}", e))?
+        .port();
+    let (shutdown, stop) = watch::channel(false);
+    tauri::async_runtime::spawn(server::run(listener, stop));
+    log_info!(MODULE, "Sharing the image cache on port {}", port);
+
+    if let Err(e) = discovery::advertise(port) {
+        let _ = shutdown.send(true);
+        return Err(e);
+    }
+    *SERVER.lock().map_err(|e| e.to_string())? = Some(Server { port, shutdown });
+    Ok(())
+}
+
+/// Start or stop looking for peers
+pub fn set_browsing(enabled: bool) -> Result<(), String> {
+    let mut browsing = BROWSING.lock().map_err(|e| e.to_string())?;
+    if *browsing == enabled {
+        return Ok(());
+    }
+    if enabled {
+        discovery::start_browsing()?;
+    } else {
+        discovery::stop_browsing();
+    }
+    *browsing = enabled;
+    Ok(())
+}
+
+pub fn status() -> LanStatus {
+    let port = SERVER
+        .lock()
+        .ok()
+        .and_then(|server| server.as_ref().map(|s| s.port));
+    LanStatus {
+        sharing: port.is_some(),
+        port,
+        browsing: BROWSING.lock().map(|b| *b).unwrap_or(false),
+        peers: discovery::peers(),
+    }
+}
+
+/// Built-in catalog of a peer confirmed by the server after `newer_than`,
+/// with the time it was confirmed
+pub async fn fetch_catalog(newer_than: Option<u64>) -> Option<(serde_json::Value, u64)> {
+    let client = http::client(Timeout::Lan).ok()?;
+    for peer in discovery::peers() {
+        let response = match client.get(format!("{}/catalog", peer.address)).send().await {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                log_debug!(
+                    MODULE,
+                    "{} has no catalog ({})",
+                    peer.name,
+                    response.status()
+                );
+                continue;
+            }
+            Err(e) => {
+                log_debug!(MODULE, "{} is unreachable: {}", peer.name, e);
+                continue;
+            }
+        };
+        let fetched_at = response
+            .headers()
+            .get(config::lan::CATALOG_FETCHED_AT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        let fetched_at = match fetched_at {
+            Some(fetched_at) if newer_than.is_none_or(|ours| fetched_at > ours) => fetched_at,
+            _ => {
+                log_debug!(MODULE, "{} has no newer catalog", peer.name);
+                continue;
+            }
+        };
+        match response.json().await {
+            Ok(json) => {
+                log_info!(MODULE, "Using the catalog of {}", peer.name);
+                return Some((json, fetched_at));
+            }
+            Err(e) => log_debug!(MODULE, "Invalid catalog from {}: {}", peer.name, e),
+        }
+    }
+    None
+}
+
+/// URL of a peer holding `filename` in its cache
+pub async fn find_mirror(filename: &str) -> Option<String> {
+    let client = http::client(Timeout::Lan).ok()?;
+    for peer in discovery::peers() {
+        let url = format!("{}/images/{}", peer.address, filename);
+        let probe = client
+            .head(&url)
+            .timeout(Duration::from_secs(config::lan::PROBE_TIMEOUT_SECS))
+            .send()
+            .await;
+        match probe {
+            Ok(response) if response.status().is_success() => return Some(url),
+            Ok(response) => {
+                log_debug!(
+                    MODULE,
+                    "{} does not have {} ({})",
+                    peer.name,
+                    filename,
+                    response.status()
+                )
+            }
+            Err(e) => log_debug!(MODULE, "{} is unreachable: {}", peer.name, e),
+        }
+    }
+    None
+}
@@ -0,0 +1,285 @@
+//! Cache server
+//!
+//! Minimal HTTP/1.1 server for LAN peers. Serves the files of the image
+//! cache under `/images/<name>` (GET and HEAD, with single byte ranges so
+//! peers can resume) and the saved built-in catalog under `/catalog`, with
+//! the time it was last confirmed by the server. One request per connection.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, Semaphore};
+
+use crate::config;
+use crate::{log_debug, log_info, log_warn};
+
+const MODULE: &str = "lan::server";
+
+/// A parsed request
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    head_only: bool,
+    path: String,
+    range: Option<String>,
+}
+
+/// Decode %XX escapes of a request path
+fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = path.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Parse the request head, Err with the status to answer otherwise
+fn parse_request(head: &str) -> Result<Request, u16> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let head_only = match request_line.next() {
+        Some("GET") => false,
+        Some("HEAD") => true,
+        _ => return Err(405),
+    };
+    let target = request_line.next().ok_or(400u16)?;
+    let path = percent_decode(target.split('?').next().unwrap_or_default()).ok_or(400u16)?;
+
+    let range = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("range"))
+        .map(|(_, value)| value.trim().to_string());
+
+    Ok(Request {
+        head_only,
+        path,
+        range,
+    })
+}
+
+/// Resolve a single `bytes=` range against the file length
+///
+/// Returns the inclusive start and end, None if it cannot be satisfied.
+fn parse_range(range: &str, len: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (len.checked_sub(suffix.min(len))?, len.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, len.checked_sub(1)?),
+        (start, end) => (
+            start.parse().ok()?,
+            end.parse::<u64>().ok()?.min(len.saturating_sub(1)),
+        ),
+    };
+    (start <= end && end < len).then_some((start, end))
+}
+
+/// Map a request path to the file it names
+fn resolve(path: &str) -> Option<(PathBuf, &'static str)> {
+    if path == "/catalog" {
+        return Some((crate::images::get_api_cache_path(), "application/json"));
+    }
+    let name = path.strip_prefix("/images/")?;
+    if name.is_empty()
+        || name.contains(['/', '\\'])
+        || name.contains("..")
+        || crate::cache::is_partial_download(name)
+    {
+        return None;
+    }
+    Some((
+        crate::cache::get_images_cache_dir().join(name),
+        "application/octet-stream",
+    ))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        206 => "Partial Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        416 => "Range Not Satisfiable",
+        _ => "Internal Server Error",
+    }
+}
+
+async fn respond_status(stream: &mut TcpStream, status: u16) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status)
+    );
+    stream.write_all(head.as_bytes()).await
+}
+
+/// Read the request head, up to the blank line
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() > config::lan::MAX_REQUEST_HEAD {
+            return Ok(None);
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(Some(String::from_utf8_lossy(&head).into_owned()))
+}
+
+async fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let head = match read_head(&mut stream).await? {
+        Some(head) => head,
+        None => return respond_status(&mut stream, 400).await,
+    };
+    let request = match parse_request(&head) {
+        Ok(request) => request,
+        Err(status) => return respond_status(&mut stream, status).await,
+    };
+    let (path, content_type) = match resolve(&request.path) {
+        Some(found) => found,
+        None => return respond_status(&mut stream, 404).await,
+    };
+    let mut file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(_) => return respond_status(&mut stream, 404).await,
+    };
+    let len = file.metadata().await?.len();
+
+    let (status, start, end) = match request.range.as_deref() {
+        Some(range) => match parse_range(range, len) {
+            Some((start, end)) => (206, start, end),
+            None => {
+                let head = format!(
+                    "HTTP/1.1 416 {}\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    reason(416),
+                    len
+                );
+                return stream.write_all(head.as_bytes()).await;
+            }
+        },
+        None => (200, 0, len.saturating_sub(1)),
+    };
+    let body_len = if len == 0 { 0 } else { end - start + 1 };
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n",
+        status,
+        reason(status),
+        content_type,
+        body_len
+    );
+    if request.path == "/catalog" {
+        if let Some(fetched_at) = crate::images::builtin_fetched_at() {
+            head.push_str(&format!(
+                "{}: {}\r\n",
+                config::lan::CATALOG_FETCHED_AT_HEADER,
+                fetched_at
+            ));
+        }
+    }
+    if status == 206 {
+        head.push_str(&format!(
+            "Content-Range: bytes {}-{}/{}\r\n",
+            start, end, len
+        ));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+
+    if !request.head_only && body_len > 0 {
+        log_debug!(
+            MODULE,
+            "Serving {} ({} bytes from {})",
+            request.path,
+            body_len,
+            start
+        );
+        file.seek(std::io::SeekFrom::Start(start)).await?;
+        tokio::io::copy(&mut file.take(body_len), &mut stream).await?;
+    }
+    stream.shutdown().await
+}
+
+/// Serve the cache on `listener` until `shutdown` turns true
+pub async fn run(listener: TcpListener, mut shutdown: watch::Receiver<bool>) {
+    let slots = Arc::new(Semaphore::new(config::lan::MAX_CONNECTIONS));
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = shutdown.changed() => break,
+        };
+        let (stream, peer) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                log_warn!(MODULE, "Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let slot = match slots.clone().acquire_owned().await {
+            Ok(slot) => slot,
+            Err(_) => break,
+        };
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream).await {
+                log_debug!(MODULE, "Connection from {} ended: {}", peer, e);
+            }
+            drop(slot);
+        });
+    }
+    log_info!(MODULE, "Cache server stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = parse_request(
+            "GET /images/Armbian%20rock.img.xz HTTP/1.1\r\nHost: peer\r\nRange: bytes=10-\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request {
+                head_only: false,
+                path: "/images/Armbian rock.img.xz".to_string(),
+                range: Some("bytes=10-".to_string()),
+            }
+        );
+        assert_eq!(parse_request("POST / HTTP/1.1\r\n\r\n"), Err(405));
+        assert!(resolve("/images/../settings.json").is_none());
+        assert!(resolve("/images/a.img.xz.downloading").is_none());
+        assert_eq!(
+            resolve("/catalog"),
+            Some((crate::images::get_api_cache_path(), "application/json"))
+        );
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=900-", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=990-2000", 1000), Some((990, 999)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+}
@@ -6,6 +6,7 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod bundle;
 mod cache;
 mod commands;
 mod config;
@@ -13,19 +14,21 @@ mod decompress;
 mod devices;
 mod download;
 mod flash;
+mod http;
 mod images;
+mod lan;
 mod logging;
 mod paste;
 mod picture_cache;
+mod pinned;
 mod qdl;
 mod utils;
 
 use commands::AppState;
-#[allow(unused_imports)] // Used by get_webview_window in debug builds
 use tauri::Manager;
 use tauri_plugin_store::StoreExt;
 
-use crate::utils::get_cache_dir;
+use std::path::PathBuf;
 
 /// Manage cached download images based on cache settings
 ///
@@ -62,7 +65,8 @@ fn manage_download_cache(app: &tauri::App) {
             log_warn!("main", "Failed to clear cache: {}", e);
         }
     } else {
-        // Cache enabled - enforce size limit
+        // Cache enabled - enforce size limit, keeping pinned images
+        pinned::pin_cached(&commands::settings::get_pinned_boards(app.handle().clone()));
         log_info!(
             "main",
             "Image cache enabled with {} GB limit",
@@ -76,7 +80,7 @@ fn manage_download_cache(app: &tauri::App) {
 
 /// Clean up orphaned decompressed custom images from previous sessions
 fn cleanup_custom_decompress_cache() {
-    let custom_dir = get_cache_dir(config::app::NAME).join("custom-decompress");
+    let custom_dir = cache::cache_root().join(config::images::CUSTOM_DECOMPRESS_DIR);
 
     if custom_dir.exists() {
         if let Ok(entries) = std::fs::read_dir(&custom_dir) {
@@ -125,10 +129,6 @@ fn main() {
         config::urls::BOARD_IMAGES_BASE
     );
 
-    // Clean up orphaned custom decompressed images from previous sessions
-    // (Cache management is done in setup with access to settings)
-    cleanup_custom_decompress_cache();
-
     let mut builder = tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
@@ -160,8 +160,9 @@ fn main() {
             commands::operations::request_write_authorization,
             commands::operations::download_image,
             commands::operations::flash_image,
+            commands::operations::download_and_flash_image,
             commands::operations::delete_downloaded_image,
-            commands::operations::force_delete_cached_image,
+            commands::operations::record_flash_result,
             commands::operations::continue_download_without_sha,
             commands::operations::cleanup_failed_download,
             commands::progress::cancel_operation,
@@ -172,6 +173,9 @@ fn main() {
             commands::custom_image::select_custom_image,
             commands::custom_image::check_needs_decompression,
             commands::custom_image::decompress_custom_image,
+            commands::custom_image::import_custom_image,
+            commands::custom_image::get_import_progress,
+            commands::custom_image::cancel_import,
             commands::custom_image::delete_decompressed_custom_image,
             commands::custom_image::detect_board_from_filename,
             commands::custom_image::check_is_qdl_image,
@@ -204,12 +208,61 @@ fn main() {
             commands::settings::set_cache_enabled,
             commands::settings::get_cache_max_size,
             commands::settings::set_cache_max_size,
+            commands::settings::get_cache_compressed,
+            commands::settings::set_cache_compressed,
+            commands::settings::get_cache_eviction_policy,
+            commands::settings::set_cache_eviction_policy,
             commands::settings::get_cache_size,
             commands::settings::clear_cache,
             commands::settings::list_cached_images,
+            commands::bundle::export_offline_bundle,
+            commands::bundle::import_offline_bundle,
             commands::settings::delete_cached_image,
+            commands::settings::verify_cached_image,
+            commands::settings::set_cached_image_pinned,
+            commands::settings::set_cached_image_tags,
             commands::settings::get_armbian_board_detection,
             commands::settings::set_armbian_board_detection,
+            commands::settings::get_download_backend,
+            commands::settings::set_download_backend,
+            commands::settings::get_delta_download,
+            commands::settings::set_delta_download,
+            commands::settings::get_download_rate_limit,
+            commands::settings::set_download_rate_limit,
+            commands::settings::get_download_window,
+            commands::settings::set_download_window,
+            commands::settings::get_network_settings,
+            commands::settings::set_network_settings,
+            commands::settings::get_download_concurrency,
+            commands::settings::set_download_concurrency,
+            commands::queue::queue_download,
+            commands::queue::prefetch_image,
+            commands::queue::list_download_jobs,
+            commands::queue::get_download_job,
+            commands::queue::pause_download_job,
+            commands::queue::resume_download_job,
+            commands::queue::cancel_download_job,
+            commands::queue::clear_finished_download_jobs,
+            commands::scrub::start_cache_scrub,
+            commands::scrub::get_cache_scrub_progress,
+            commands::scrub::cancel_cache_scrub,
+            commands::scrub::redownload_cached_image,
+            commands::settings::get_pinned_boards,
+            commands::settings::set_pinned_boards,
+            commands::settings::get_catalog_sources,
+            commands::settings::set_catalog_sources,
+            commands::settings::get_lan_share_cache,
+            commands::settings::set_lan_share_cache,
+            commands::settings::get_lan_use_peers,
+            commands::settings::set_lan_use_peers,
+            commands::settings::get_lan_status,
+            commands::settings::get_cache_location,
+            commands::settings::set_cache_location,
+            commands::settings::get_cache_location_status,
+            commands::board_queries::get_catalog_changes,
+            commands::board_queries::get_catalog_status,
+            commands::board_queries::get_board_facets,
+            commands::board_queries::search_catalog,
         ])
         .setup(|app| {
             #[cfg(debug_assertions)]
@@ -243,9 +296,40 @@ fn main() {
                 }
             }
 
+            // Apply proxy and CA settings before any request is made
+            http::configure(commands::settings::get_network_settings(
+                app.handle().clone(),
+            ));
+
+            // Use the configured cache location, then clean up orphaned
+            // custom decompressed images from previous sessions
+            cache::location::set_configured_root(
+                commands::settings::get_cache_location(app.handle().clone()).map(PathBuf::from),
+            );
+            cleanup_custom_decompress_cache();
+
             // Manage download cache based on settings
+            cache::set_eviction_policy(commands::settings::get_cache_eviction_policy(
+                app.handle().clone(),
+            ));
             manage_download_cache(app);
 
+            // Apply the configured download queue concurrency
+            app.state::<AppState>().download_queue.set_concurrency(
+                commands::settings::get_download_concurrency(app.handle().clone()),
+            );
+
+            // Apply the configured download rate limit
+            download::throttle::set_rate_limit(commands::settings::get_download_rate_limit(
+                app.handle().clone(),
+            ));
+
+            // Load the bundled keyring used to verify image signatures
+            match app.path().resource_dir() {
+                Ok(dir) => download::signature::init_keyring(&dir.join("keys")),
+                Err(e) => log_warn!("main", "Failed to locate resource directory: {}", e),
+            }
+
             // Spawn background asset cache tasks:
             // 1. Refresh stale cached assets (conditional requests for >24h old entries)
             // 2. Pre-populate cache with all board images and vendor logos
@@ -254,6 +338,23 @@ fn main() {
                 picture_cache::prepopulate_assets().await;
             });
 
+            // Share the image cache and look for peers, if enabled
+            if commands::settings::get_lan_use_peers(app.handle().clone()) {
+                if let Err(e) = lan::set_browsing(true) {
+                    log_warn!("main", "Failed to look for LAN peers: {}", e);
+                }
+            }
+            if commands::settings::get_lan_share_cache(app.handle().clone()) {
+                tauri::async_runtime::spawn(async {
+                    if let Err(e) = lan::set_sharing(true).await {
+                        log_warn!("main", "Failed to share the image cache: {}", e);
+                    }
+                });
+            }
+
+            // Keep the newest images of pinned boards in the cache
+            tauri::async_runtime::spawn(pinned::run(app.handle().clone()));
+
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -138,10 +138,7 @@ pub async fn upload_logs() -> Result<UploadResult, String> {
     log_info!("paste", "Collected {} bytes of log data", content.len());
 
     // Create HTTP client
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let client = crate::http::client(crate::http::Timeout::Request)?;
 
     // Upload to paste service
     let url = format!("{}{}", PASTE_URL, PASTE_ENDPOINT);
@@ -13,8 +13,9 @@ use tokio::sync::Mutex;
 
 use base64::Engine;
 
+use crate::cache::cache_root;
 use crate::config;
-use crate::utils::get_cache_dir;
+use crate::http::retry;
 use crate::{log_debug, log_info, log_warn};
 
 const MODULE: &str = "picture_cache";
@@ -49,17 +50,9 @@ struct AssetsMeta {
 static META: once_cell::sync::Lazy<Mutex<Option<AssetsMeta>>> =
     once_cell::sync::Lazy::new(|| Mutex::new(None));
 
-/// Shared HTTP client for asset downloads (10s timeout)
-static HTTP_CLIENT: once_cell::sync::Lazy<reqwest::Client> = once_cell::sync::Lazy::new(|| {
-    reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .expect("Failed to build HTTP client")
-});
-
 /// Get the assets cache base directory
 fn get_assets_dir() -> PathBuf {
-    get_cache_dir(config::app::NAME).join("assets")
+    cache_root().join("assets")
 }
 
 /// Get the path to meta.json
@@ -121,6 +114,13 @@ async fn load_meta() -> AssetsMeta {
     init_meta_from_disk(&mut guard).clone()
 }
 
+/// Drop the in-memory metadata so it is read from disk again
+///
+/// Used after meta.json was replaced, e.g. by an offline bundle import.
+pub async fn reload_meta() {
+    *META.lock().await = None;
+}
+
 /// Atomically update a single entry in metadata and persist to disk
 ///
 /// Holds the mutex for the entire read-modify-write cycle to prevent
@@ -213,7 +213,15 @@ async fn download_asset(meta_key: &str, url: &str, file_path: &Path) -> Option<P
         file_path.display()
     );
 
-    let response = match HTTP_CLIENT.get(url).send().await {
+    let client = match crate::http::client(crate::http::Timeout::Short) {
+        Ok(c) => c,
+        Err(e) => {
+            log_warn!(MODULE, "Failed to build HTTP client for assets: {}", e);
+            return None;
+        }
+    };
+
+    let response = match retry::send("Asset download", || client.get(url)).await {
         Ok(r) if r.status().is_success() => r,
         Ok(r) => {
             log_debug!(MODULE, "Asset download returned {}: {}", r.status(), url);
@@ -292,17 +300,27 @@ async fn refresh_asset(
     etag: Option<&str>,
     last_modified: Option<&str>,
 ) {
-    let mut request = HTTP_CLIENT.get(url);
+    let client = match crate::http::client(crate::http::Timeout::Short) {
+        Ok(c) => c,
+        Err(e) => {
+            log_warn!(MODULE, "Failed to build HTTP client for assets: {}", e);
+            return;
+        }
+    };
+    let make_request = || {
+        let mut request = client.get(url);
 
-    // Add conditional headers
-    if let Some(etag) = etag {
-        request = request.header("If-None-Match", etag);
-    }
-    if let Some(last_modified) = last_modified {
-        request = request.header("If-Modified-Since", last_modified);
-    }
+        // Add conditional headers
+        if let Some(etag) = etag {
+            request = request.header("If-None-Match", etag);
+        }
+        if let Some(last_modified) = last_modified {
+            request = request.header("If-Modified-Since", last_modified);
+        }
+        request
+    };
 
-    match request.send().await {
+    match retry::send("Asset refresh", make_request).await {
         Ok(response) => {
             if response.status() == reqwest::StatusCode::NOT_MODIFIED {
                 // Asset unchanged -- just update last_checked
@@ -402,7 +420,7 @@ pub async fn read_as_data_uri(path: &Path) -> Option<String> {
     }
 }
 
-/// Read the cached API JSON from disk (same path used by `images::fetch_all_images`)
+/// Read the cached API JSON from disk (the built-in catalog saved by `images::fetch_catalog`)
 ///
 /// Returns `Some(json_text)` if the file exists and can be read, `None` otherwise.
 fn read_api_cache_from_disk() -> Option<String> {
@@ -421,12 +439,7 @@ fn read_api_cache_from_disk() -> Option<String> {
 
 /// Fetch the API JSON from the remote server (fallback for prepopulate)
 async fn fetch_api_for_prepopulate() -> Option<String> {
-    use std::time::Duration;
-
-    let client = match reqwest::Client::builder()
-        .timeout(Duration::from_secs(30))
-        .build()
-    {
+    let client = match crate::http::client(crate::http::Timeout::Request) {
         Ok(c) => c,
         Err(e) => {
             log_warn!(MODULE, "Failed to build HTTP client for prepopulate: {}", e);
@@ -434,7 +447,8 @@ async fn fetch_api_for_prepopulate() -> Option<String> {
         }
     };
 
-    let response = match client.get(config::urls::ALL_IMAGES).send().await {
+    let response = match retry::send("Catalog fetch", || client.get(config::urls::ALL_IMAGES)).await
+    {
         Ok(r) if r.status().is_success() => r,
         Ok(r) => {
             log_warn!(
@@ -462,7 +476,7 @@ async fn fetch_api_for_prepopulate() -> Option<String> {
 /// Pre-populate the asset cache by downloading all board images and vendor logos
 ///
 /// Reads the board list from the local API cache on disk (populated by
-/// `images::fetch_all_images`). Falls back to fetching from the remote API
+/// `images::fetch_catalog`). Falls back to fetching from the remote API
 /// if the local cache is missing. Uses a semaphore to limit concurrency.
 /// Intended to be called once at app startup in the background.
 pub async fn prepopulate_assets() {
@@ -0,0 +1,363 @@
+//! Pinned boards
+//!
+//! Users pin the boards they flash regularly, optionally narrowed down to a
+//! distribution, kernel branch and variant. On startup and periodically the
+//! newest stable image of every pinned board is prefetched into the image
+//! cache, and kept there: eviction skips the current pinned images. Until
+//! the catalog can be fetched, the newest cached build of each pin is kept.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::commands::settings;
+use crate::commands::AppState;
+use crate::config;
+use crate::download::queue::{JobId, JobKind};
+use crate::images::{fetch_catalog, Catalog, ImageInfo};
+use crate::utils::{parse_armbian_filename, version_parts};
+use crate::{cache, download, log_info, log_warn};
+
+const MODULE: &str = "pinned";
+
+/// A pinned board with optional image filters
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedBoard {
+    pub board_slug: String,
+    #[serde(default)]
+    pub distro_release: Option<String>,
+    #[serde(default)]
+    pub kernel_branch: Option<String>,
+    #[serde(default)]
+    pub image_variant: Option<String>,
+}
+
+impl PinnedBoard {
+    /// Check that the pin names a board
+    pub fn validate(&self) -> Result<(), String> {
+        if self.board_slug.trim().is_empty() {
+            return Err("Pinned board has no board slug".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Pick the image to keep cached for a pin
+///
+/// Picks the newest stable image, preferring a promoted one of the same
+/// version.
+pub fn select_image(catalog: &Catalog, pin: &PinnedBoard) -> Option<ImageInfo> {
+    catalog
+        .images_for_board(
+            &pin.board_slug,
+            None,
+            pin.kernel_branch.as_deref(),
+            pin.image_variant.as_deref(),
+            true,
+        )
+        .into_iter()
+        .filter(|image| {
+            !image.file_url.is_empty()
+                && pin
+                    .distro_release
+                    .as_deref()
+                    .is_none_or(|distro| image.distro_release == distro)
+        })
+        .max_by(|a, b| {
+            version_parts(&a.armbian_version)
+                .cmp(&version_parts(&b.armbian_version))
+                .then(a.promoted.cmp(&b.promoted))
+        })
+}
+
+/// Pick the cached images to keep for the pins without the catalog
+///
+/// The newest cached build matching each pin, in whichever forms it is
+/// cached, so it stays pinned until a newer one is cached.
+pub fn select_cached(filenames: &[String], pins: &[PinnedBoard]) -> HashSet<String> {
+    let matches =
+        |wanted: &Option<String>, found: &Option<String>| wanted.is_none() || wanted == found;
+    let mut selected = HashSet::new();
+    for pin in pins {
+        let builds: Vec<(&String, Vec<u64>)> = filenames
+            .iter()
+            .filter_map(|filename| {
+                let info = parse_armbian_filename(filename)?;
+                let matched = info.board_slug == pin.board_slug
+                    && matches(&pin.distro_release, &info.distro)
+                    && matches(&pin.kernel_branch, &info.branch)
+                    && matches(&pin.image_variant, &info.desktop);
+                matched.then(|| {
+                    (
+                        filename,
+                        version_parts(info.version.as_deref().unwrap_or("")),
+                    )
+                })
+            })
+            .collect();
+        if let Some(newest) = builds.iter().map(|(_, version)| version).max() {
+            selected.extend(
+                builds
+                    .iter()
+                    .filter(|(_, version)| version == newest)
+                    .map(|(filename, _)| (*filename).clone()),
+            );
+        }
+    }
+    selected
+}
+
+/// Pin the newest cached build of each pin, returning the pinned filenames
+pub fn pin_cached(pins: &[PinnedBoard]) -> HashSet<String> {
+    let cached: Vec<String> = cache::list_cached_images()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|image| image.filename)
+        .collect();
+    let pinned = select_cached(&cached, pins);
+    cache::set_pinned_images(pinned.clone());
+    pinned
+}
+
+/// Compare the catalog against the cache and prefetch missing pinned images
+///
+/// Returns the number of prefetch jobs queued.
+pub async fn check(app: &AppHandle) -> Result<usize, String> {
+    let pins = settings::get_pinned_boards(app.clone());
+    if pins.is_empty() {
+        cache::set_pinned_images(HashSet::new());
+        return Ok(0);
+    }
+    if !settings::get_cache_enabled(app.clone()) {
+        log_info!(
+            MODULE,
+            "Image cache disabled, not prefetching pinned boards"
+        );
+        return Ok(0);
+    }
+
+    log_info!(
+        MODULE,
+        "Checking {} pinned boards for new releases",
+        pins.len()
+    );
+    // Before going online, in case the catalog cannot be fetched
+    let cached_pins = pin_cached(&pins);
+
+    let sources = settings::get_catalog_sources(app.clone());
+    let catalog = Arc::new(fetch_catalog(&sources).await?);
+    let state = app.state::<AppState>();
+    *state.catalog.lock().await = Some(catalog.clone());
+    crate::commands::board_queries::announce_catalog_changes(app);
+
+    let mut selected = Vec::new();
+    for pin in &pins {
+        match select_image(&catalog, pin) {
+            Some(image) => selected.push(image),
+            None => log_warn!(MODULE, "No stable image matches pin {:?}", pin),
+        }
+    }
+
+    // The older build stays pinned until the new one is cached
+    let filenames: HashSet<String> = selected
+        .iter()
+        .filter_map(|image| download::cached_filenames(&image.file_url).ok())
+        .flatten()
+        .map(str::to_string)
+        .chain(cached_pins)
+        .collect();
+    cache::set_pinned_images(filenames);
+
+    let max_size = settings::get_cache_max_size(app.clone());
+    let mut queued = 0;
+    for image in selected {
+        let filename = match download::cached_filename(&image.file_url) {
+            Ok(name) => name,
+            Err(e) => {
+                log_warn!(MODULE, "Skipping {}: {}", image.file_url, e);
+                continue;
+            }
+        };
+        let cached = download::cached_filenames(&image.file_url)
+            .unwrap_or_default()
+            .into_iter()
+            .any(|name| cache::get_cached_image(name).is_some());
+        if cached {
+            continue;
+        }
+
+        let options =
+            crate::commands::operations::download_options(app, &image.file_url, None, true).await;
+        let mut options = match options {
+            Ok(options) => options,
+            Err(e) => {
+                log_warn!(MODULE, "Not prefetching {}: {}", filename, e);
+                continue;
+            }
+        };
+
+        // The catalog only knows the compressed size: assume the worst for
+        // a decompressed image. Room is made once the download starts (it
+        // may wait for the download window), and the limit is enforced
+        // again once the image is in the cache
+        let size = if options.keep_compressed || !image.file_url.ends_with(".xz") {
+            image.file_size
+        } else {
+            image
+                .file_size
+                .saturating_mul(config::cache::DECOMPRESSED_SIZE_RATIO)
+        };
+        if let Err(e) = cache::check_space(size, max_size) {
+            log_warn!(MODULE, "Not prefetching {}: {}", filename, e);
+            continue;
+        }
+        options.reserve = Some(download::Reservation { size, max_size });
+
+        log_info!(MODULE, "Prefetching new pinned image: {}", filename);
+        let id = state.download_queue.enqueue(
+            &image.file_url,
+            image.file_url_sha.as_deref(),
+            JobKind::Prefetch,
+            options,
+        );
+        tauri::async_runtime::spawn(enforce_limit_after(app.clone(), id));
+        queued += 1;
+    }
+
+    Ok(queued)
+}
+
+/// Evict unpinned images once a prefetch has finished
+async fn enforce_limit_after(app: AppHandle, id: JobId) {
+    let interval = Duration::from_secs(config::cache::PINNED_POLL_INTERVAL_SECS);
+    loop {
+        tokio::time::sleep(interval).await;
+        let finished = match app.state::<AppState>().download_queue.get(id) {
+            Ok(job) => job.status.is_finished(),
+            Err(_) => true,
+        };
+        if finished {
+            break;
+        }
+    }
+
+    if let Err(e) = cache::evict_to_size(settings::get_cache_max_size(app.clone())) {
+        log_warn!(MODULE, "Failed to enforce cache size limit: {}", e);
+    }
+}
+
+/// Check pinned boards on startup and then periodically
+pub async fn run(app: AppHandle) {
+    let interval = Duration::from_secs(config::cache::PINNED_CHECK_INTERVAL_SECS);
+    loop {
+        match check(&app).await {
+            Ok(0) => {}
+            Ok(queued) => log_info!(MODULE, "Queued {} pinned image prefetches", queued),
+            Err(e) => log_warn!(MODULE, "Pinned board check failed: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(version: &str, distro: &str, repo: &str, promoted: bool) -> serde_json::Value {
+        serde_json::json!({
+            "board_slug": "rock-5b",
+            "armbian_version": version,
+            "distro": distro,
+            "branch": "current",
+            "variant": "minimal",
+            "promoted": if promoted { "true" } else { "false" },
+            "file_url": format!("https://dl.armbian.com/Armbian_{}_Rock-5b_{}_current_minimal.img.xz", version, distro),
+            "file_extension": "img.xz",
+            "download_repository": repo,
+        })
+    }
+
+    #[test]
+    fn test_select_image() {
+        let json = serde_json::json!([
+            image("25.02.0", "bookworm", "archive", true),
+            image("25.05.0", "bookworm", "archive", false),
+            image("25.08.0", "bookworm", "beta", true),
+            image("25.05.0", "noble", "archive", true),
+        ]);
+        let catalog = Catalog::from_json(&json);
+
+        let mut pin = PinnedBoard {
+            board_slug: "rock-5b".to_string(),
+            distro_release: None,
+            kernel_branch: None,
+            image_variant: None,
+        };
+        // The newest stable image wins; beta images never qualify
+        let selected = select_image(&catalog, &pin).unwrap();
+        assert_eq!(selected.armbian_version, "25.05.0");
+        assert!(selected.promoted);
+
+        pin.distro_release = Some("noble".to_string());
+        let selected = select_image(&catalog, &pin).unwrap();
+        assert_eq!(selected.distro_release, "noble");
+
+        pin.board_slug = "orangepi-5".to_string();
+        assert!(select_image(&catalog, &pin).is_none());
+    }
+
+    #[test]
+    fn test_select_image_compares_versions_numerically() {
+        let json = serde_json::json!([
+            image("25.8.1", "bookworm", "archive", false),
+            image("25.11.1", "bookworm", "archive", false),
+            image("25.9.0", "bookworm", "archive", false),
+        ]);
+        let catalog = Catalog::from_json(&json);
+        let pin = PinnedBoard {
+            board_slug: "rock-5b".to_string(),
+            distro_release: None,
+            kernel_branch: None,
+            image_variant: None,
+        };
+        let selected = select_image(&catalog, &pin).unwrap();
+        assert_eq!(selected.armbian_version, "25.11.1");
+    }
+
+    #[test]
+    fn test_select_cached() {
+        let filenames: Vec<String> = [
+            "Armbian_25.8.1_Rock-5b_bookworm_current_6.1.0_minimal.img",
+            "Armbian_25.11.1_Rock-5b_bookworm_current_6.12.0_minimal.img",
+            "Armbian_25.11.1_Rock-5b_bookworm_current_6.12.0_minimal.img.xz",
+            "Armbian_25.11.1_Rock-5b_noble_current_6.12.0_gnome.img",
+            "Armbian_26.2.0_Orangepi5_bookworm_current_6.12.0_minimal.img",
+            "custom.img",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        let mut pin = PinnedBoard {
+            board_slug: "rock-5b".to_string(),
+            distro_release: Some("bookworm".to_string()),
+            kernel_branch: None,
+            image_variant: Some("minimal".to_string()),
+        };
+        let mut expected: Vec<&str> = vec![
+            "Armbian_25.11.1_Rock-5b_bookworm_current_6.12.0_minimal.img",
+            "Armbian_25.11.1_Rock-5b_bookworm_current_6.12.0_minimal.img.xz",
+        ];
+        let mut selected: Vec<String> = select_cached(&filenames, &[pin.clone()])
+            .into_iter()
+            .collect();
+        selected.sort();
+        expected.sort();
+        assert_eq!(selected, expected);
+
+        pin.kernel_branch = Some("edge".to_string());
+        assert!(select_cached(&filenames, &[pin]).is_empty());
+    }
+}
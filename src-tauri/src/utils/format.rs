@@ -40,7 +40,7 @@ pub fn format_size(bytes: u64) -> String {
 }
 
 /// Parsed metadata from an Armbian image filename
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct ArmbianFilenameInfo {
     /// Board slug extracted from filename (lowercase, e.g. "nanopi-m5")
     pub board_slug: String,
@@ -122,6 +122,16 @@ pub fn parse_armbian_filename(filename: &str) -> Option<ArmbianFilenameInfo> {
     })
 }
 
+/// Numeric parts of an Armbian version, for ordering releases
+///
+/// `25.11.1` sorts after `25.8.1`, which a string comparison gets wrong.
+pub fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
 /// Normalize a slug by replacing non-alphanumeric chars with hyphens
 /// and collapsing multiple hyphens into one
 pub fn normalize_slug(slug: &str) -> String {
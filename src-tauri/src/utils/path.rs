@@ -8,27 +8,43 @@ use crate::config;
 
 use super::get_cache_dir;
 
-/// Validates that a path is within the application cache directory
+/// Validates that a path is within the managed cache directories
 ///
-/// Canonicalizes both the target path and the cache directory to resolve
-/// symlinks and prevent path traversal attacks, then checks containment.
+/// Canonicalizes both the target path and the `images` and
+/// `custom-decompress` directories to resolve symlinks and prevent path
+/// traversal attacks, then checks containment. Those of both the cache
+/// location in use and the default one are accepted; anything else under
+/// the cache root (index, assets) is not.
 ///
 /// # Arguments
 /// * `path` - The path to validate
 ///
 /// # Returns
-/// The canonicalized path if it is within the cache directory
+/// The canonicalized path if it is within a managed cache directory
 ///
 /// # Errors
-/// Returns an error if the path cannot be resolved or is outside the cache directory
+/// Returns an error if the path cannot be resolved or is outside the managed cache directories
 pub fn validate_cache_path(path: &Path) -> Result<PathBuf, String> {
-    let cache_dir = get_cache_dir(config::app::NAME)
-        .canonicalize()
-        .map_err(|e| format!("Failed to resolve cache directory: {}", e))?;
+    let cache_dirs: Vec<PathBuf> = [crate::cache::cache_root(), get_cache_dir(config::app::NAME)]
+        .iter()
+        .flat_map(|root| {
+            [
+                root.join("images"),
+                root.join(config::images::CUSTOM_DECOMPRESS_DIR),
+            ]
+        })
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect();
+    if cache_dirs.is_empty() {
+        return Err("Failed to resolve cache directory".to_string());
+    }
     let canonical_path = path
         .canonicalize()
         .map_err(|e| format!("Failed to resolve path: {}", e))?;
-    if !canonical_path.starts_with(&cache_dir) {
+    if !cache_dirs
+        .iter()
+        .any(|dir| canonical_path.starts_with(dir) && canonical_path != *dir)
+    {
         return Err("Cannot operate on files outside cache directory".to_string());
     }
     Ok(canonical_path)
@@ -63,4 +79,13 @@ mod tests {
         assert_eq!(strip_compression_ext("image.img"), "image.img");
         assert_eq!(strip_compression_ext("no-extension"), "no-extension");
     }
+
+    #[test]
+    fn test_validate_cache_path() {
+        let images = crate::cache::get_images_cache_dir();
+        std::fs::create_dir_all(&images).unwrap();
+        assert!(validate_cache_path(&crate::cache::cache_root()).is_err());
+        assert!(validate_cache_path(&images).is_err());
+        assert!(validate_cache_path(&std::env::temp_dir()).is_err());
+    }
 }
@@ -4,7 +4,7 @@ import { Header, HomePage } from './components/layout';
 import { ManufacturerModal, BoardModal, ImageModal, DeviceModal, ArmbianBoardModal } from './components/modals';
 import { FlashProgress } from './components/flash';
 import { SettingsButton, CacheManagerModal } from './components/settings';
-import { selectCustomImage, detectBoardFromFilename, logInfo, logWarn, getArmbianRelease, getBoards, getSystemInfo, getCachedBoardImage, checkNeedsDecompression, decompressCustomImage, checkIsQdlImage } from './hooks/useTauri';
+import { selectCustomImage, detectBoardFromFilename, logInfo, logWarn, getArmbianRelease, getBoards, getSystemInfo, getCachedBoardImage, checkIsQdlImage } from './hooks/useTauri';
 import { useDeviceMonitor } from './hooks/useDeviceMonitor';
 import { useConnectivity } from './hooks/useConnectivity';
 import { ToastProvider, useToasts } from './hooks/useToasts';
@@ -203,19 +203,6 @@ function AppContent() {
         // Ignore detection errors
       }
 
-      // Check if image needs decompression
-      let imagePath = path;
-      try {
-        const needsDecompress = await checkNeedsDecompression(path);
-        if (needsDecompress) {
-          logInfo('app', `Cached image needs decompression: ${filename}`);
-          imagePath = await decompressCustomImage(path);
-        }
-      } catch (err) {
-        logWarn('app', `Failed to check/decompress cached image: ${err}`);
-        // Continue with original path
-      }
-
       // Create ImageInfo for the cached image (same pattern as handleCustomImage)
       const cachedImage: ImageInfo = {
         armbian_version: 'Cached',
@@ -230,8 +217,9 @@ function AppContent() {
         file_size: size,
         download_repository: 'cache',
         flash_method: 'block',
+        source: '',
         is_custom: true,
-        custom_path: imagePath,
+        custom_path: path,
       };
 
       // Reset downstream selections (board, image, device)
@@ -254,6 +242,7 @@ function AppContent() {
         has_eos_support: false,
         has_tvb_support: false,
         has_wip_support: false,
+        sources: [],
       };
 
       setSelectedManufacturer({
@@ -351,6 +340,7 @@ function AppContent() {
           file_size: result.size,
           download_repository: 'local',
           flash_method: flashMethod,
+          source: '',
           is_custom: true,
           custom_path: result.path,
         };
@@ -372,6 +362,7 @@ function AppContent() {
           has_eos_support: false,
           has_tvb_support: false,
           has_wip_support: false,
+          sources: [],
         };
 
         // Set manufacturer for display consistency (same pattern as cached image reuse)
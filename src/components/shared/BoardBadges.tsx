@@ -1,13 +1,17 @@
 /**
  * Board support badges component
  *
- * Displays support status badges for boards (Platinum, Standard, Community, EOS, TV Box, WIP)
+ * Displays support status badges for boards (Platinum, Standard, Community, EOS, TV Box, WIP),
+ * plus one badge per third-party catalog source listing the board
  * Consolidated from BoardModal and ArmbianBoardModal to eliminate duplication
  */
 
-import { Crown, Shield, Users, Clock, Tv, Wrench } from 'lucide-react';
+import { Crown, Shield, Users, Clock, Tv, Wrench, Package } from 'lucide-react';
 import type { BoardInfo } from '../../types';
 
+/** Id of the built-in Armbian catalog source (no badge) */
+const BUILTIN_SOURCE_ID = 'armbian';
+
 interface BoardBadgesProps {
   board: BoardInfo;
   className?: string;
@@ -52,6 +56,14 @@ export function BoardBadges({ board, className = '' }: BoardBadgesProps) {
           <span>WIP</span>
         </span>
       )}
+      {board.sources
+        .filter((source) => source !== BUILTIN_SOURCE_ID)
+        .map((source) => (
+          <span key={source} className="badge-source">
+            <Package size={10} />
+            <span>{source}</span>
+          </span>
+        ))}
     </div>
   );
 }
@@ -39,8 +39,6 @@ export const TIMING = {
 
 /** Cache configuration */
 export const CACHE = {
-  /** Maximum consecutive flash failures before auto-deleting cached image */
-  MAX_FLASH_FAILURES: 3,
   /** Default maximum cache size: 20 GB */
   DEFAULT_SIZE: 20 * 1024 * 1024 * 1024,
   /** Cache size options in bytes with display labels */
@@ -63,12 +61,6 @@ export const EVENTS = {
   CACHE_IMAGE_REUSE: 'armbian-cache-image-reuse',
 } as const;
 
-/** Storage key prefixes for sessionStorage/localStorage */
-export const STORAGE_KEYS = {
-  /** Prefix for flash failure count (appended with image URL) */
-  FLASH_FAILURE_PREFIX: 'flash_failure_count_',
-} as const;
-
 /** Settings store configuration */
 export const SETTINGS = {
   /** Settings file name */
@@ -83,7 +75,15 @@ export const SETTINGS = {
     SKIP_VERIFY: 'skip_verify',
     CACHE_ENABLED: 'cache_enabled',
     CACHE_MAX_SIZE: 'cache_max_size',
+    CACHE_COMPRESSED: 'cache_compressed',
     ARMBIAN_BOARD_DETECTION: 'armbian_board_detection',
+    DOWNLOAD_BACKEND: 'download_backend',
+    DELTA_DOWNLOAD: 'delta_download',
+    DOWNLOAD_RATE_LIMIT: 'download_rate_limit',
+    DOWNLOAD_WINDOW: 'download_window',
+    NETWORK: 'network',
+    DOWNLOAD_CONCURRENCY: 'download_concurrency',
+    PINNED_BOARDS: 'pinned_boards',
   },
   /** Default values for settings */
   DEFAULTS: {
@@ -94,7 +94,12 @@ export const SETTINGS = {
     DEVELOPER_MODE: false,
     SKIP_VERIFY: false,
     CACHE_ENABLED: true,
+    CACHE_COMPRESSED: false,
     ARMBIAN_BOARD_DETECTION: 'modal',
+    DOWNLOAD_BACKEND: 'http',
+    DELTA_DOWNLOAD: false,
+    DOWNLOAD_RATE_LIMIT: 0,
+    DOWNLOAD_CONCURRENCY: 2,
   },
   /** Armbian board detection modes */
   ARMBIAN_DETECTION_MODES: {
@@ -30,7 +30,6 @@ export {
   TIMING,
   CACHE,
   EVENTS,
-  STORAGE_KEYS,
   SETTINGS,
   COLORS,
   QR_CODE,
@@ -17,7 +17,7 @@ import {
   cancelOperation,
   deleteDownloadedImage,
   deleteDecompressedCustomImage,
-  forceDeleteCachedImage,
+  recordFlashResult,
   requestWriteAuthorization,
   checkNeedsDecompression,
   decompressCustomImage,
@@ -27,7 +27,7 @@ import {
   cleanupFailedDownload,
 } from './useTauri';
 import { getSkipVerify } from './useSettings';
-import { POLLING, CACHE, STORAGE_KEYS } from '../config';
+import { POLLING } from '../config';
 import { isDeviceConnected } from '../utils/deviceUtils';
 import { isShaUnavailableError, translateQdlError } from '../utils/errorUtils';
 
@@ -93,30 +93,6 @@ export function useFlashOperation({
   const deviceDisconnectedRef = useRef<boolean>(false);
   const skipVerifyRef = useRef<boolean>(false);
 
-  // Failure tracking via sessionStorage
-  const failureStorageKey = `${STORAGE_KEYS.FLASH_FAILURE_PREFIX}${image.file_url}`;
-
-  const getFlashFailureCount = (): number => {
-    try {
-      const stored = sessionStorage.getItem(failureStorageKey);
-      return stored ? parseInt(stored, 10) : 0;
-    } catch {
-      return 0;
-    }
-  };
-
-  const setFlashFailureCount = (count: number): void => {
-    try {
-      if (count === 0) {
-        sessionStorage.removeItem(failureStorageKey);
-      } else {
-        sessionStorage.setItem(failureStorageKey, count.toString());
-      }
-    } catch {
-      // Ignore storage errors
-    }
-  };
-
   /** Clear all active polling intervals */
   const clearIntervals = useCallback(() => {
     if (intervalRef.current) {
@@ -358,7 +334,9 @@ export function useFlashOperation({
       if (intervalRef.current) clearInterval(intervalRef.current);
       setStage('complete');
       setProgress(100);
-      setFlashFailureCount(0);
+      if (!image.is_custom && !isQdlMode) {
+        await recordFlashResult(path, true).catch(() => false);
+      }
       // QDL: backend handles temp dir cleanup; don't delete the source TAR
       if (!isQdlMode) {
         await cleanupImageSafely(path, image.is_custom);
@@ -369,20 +347,10 @@ export function useFlashOperation({
 
       if (!(await checkDeviceOrDisconnect())) return;
 
-      // Increment failure count for cached (non-custom) images
+      // Count the failure for cached (non-custom) images; the backend drops
+      // the image after too many failures (possibly corrupted)
       if (!image.is_custom && !isQdlMode) {
-        const currentCount = getFlashFailureCount() + 1;
-        setFlashFailureCount(currentCount);
-
-        // Auto-drop cached image after too many failures (possibly corrupted)
-        if (currentCount >= CACHE.MAX_FLASH_FAILURES) {
-          try {
-            await forceDeleteCachedImage(path);
-            setFlashFailureCount(0);
-          } catch {
-            // Ignore deletion errors
-          }
-        }
+        await recordFlashResult(path, false).catch(() => false);
       }
 
       if (!isQdlMode) {
@@ -418,7 +386,11 @@ export function useFlashOperation({
         }
       }
 
-      if (image.is_custom && image.custom_path) {
+      if (image.is_custom && image.custom_path && image.download_repository === 'cache') {
+        // Cached images are flashed as is, compressed ones decompressed on the fly
+        setImagePath(image.custom_path);
+        startFlash(image.custom_path);
+      } else if (image.is_custom && image.custom_path) {
         await handleCustomImage(image.custom_path);
       } else {
         startDownload();
@@ -5,6 +5,7 @@
  * All operations are wrapped in proper error handling to prevent silent failures.
  */
 
+import { invoke } from '@tauri-apps/api/core';
 import { load } from '@tauri-apps/plugin-store';
 import { CACHE, SETTINGS } from '../config';
 let storeInstance: Awaited<ReturnType<typeof load>> | null = null;
@@ -317,6 +318,115 @@ export async function setCacheMaxSize(size: number): Promise<void> {
   }
 }
 
+/**
+ * Get the compressed cache preference
+ *
+ * When enabled, new downloads are cached compressed and decompressed on the
+ * fly while flashing.
+ *
+ * @returns Promise resolving to true if images are cached compressed
+ * @throws Error if store access fails
+ */
+export async function getCacheCompressed(): Promise<boolean> {
+  try {
+    const store = await getStore();
+    const value = await store.get<boolean>(SETTINGS.KEYS.CACHE_COMPRESSED);
+    return value ?? SETTINGS.DEFAULTS.CACHE_COMPRESSED;
+  } catch (error) {
+    throw new Error(`Failed to get compressed cache preference: ${error}`);
+  }
+}
+
+/**
+ * Set the compressed cache preference
+ *
+ * Images already cached are left as they are.
+ *
+ * @param enabled - true to keep new downloads compressed in the cache
+ * @throws Error if store access or save fails
+ */
+export async function setCacheCompressed(enabled: boolean): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.CACHE_COMPRESSED, enabled);
+    await store.save();
+  } catch (error) {
+    throw new Error(`Failed to set compressed cache preference: ${error}`);
+  }
+}
+
+/** How cached images are chosen for eviction (pinned images never are) */
+export type EvictionPolicy =
+  | { policy: 'lru' }
+  | { policy: 'lfu' }
+  | { policy: 'oldest_release' }
+  | { policy: 'keep_latest'; count: number };
+
+/**
+ * Get the cache eviction policy
+ *
+ * @returns Promise resolving to the policy (LRU by default)
+ * @throws Error if the setting cannot be read
+ */
+export async function getCacheEvictionPolicy(): Promise<EvictionPolicy> {
+  try {
+    return await invoke<EvictionPolicy>('get_cache_eviction_policy');
+  } catch (error) {
+    throw new Error(`Failed to get cache eviction policy: ${error}`);
+  }
+}
+
+/**
+ * Set the cache eviction policy
+ *
+ * Applied immediately: the cache is trimmed under the new policy, and with
+ * `keep_latest` older releases beyond the latest `count` per board are removed.
+ *
+ * @param policy - Eviction policy to use
+ * @throws Error if the policy is invalid or the setting cannot be saved
+ */
+export async function setCacheEvictionPolicy(policy: EvictionPolicy): Promise<void> {
+  try {
+    await invoke('set_cache_eviction_policy', { policy });
+  } catch (error) {
+    throw new Error(`Failed to set cache eviction policy: ${error}`);
+  }
+}
+
+/**
+ * Get the configured cache location
+ *
+ * @returns Promise resolving to the directory, or null for the default one
+ * @throws Error if the setting cannot be read
+ */
+export async function getCacheLocation(): Promise<string | null> {
+  try {
+    return await invoke<string | null>('get_cache_location');
+  } catch (error) {
+    throw new Error(`Failed to get cache location: ${error}`);
+  }
+}
+
+/**
+ * Move the cache to another directory, e.g. on an external drive
+ *
+ * The cache lives in an armbian-imager directory of `path`. Cached images,
+ * their index and the asset cache are migrated first; on failure they stay
+ * where they were. Refused while downloads are queued, while downloading,
+ * flashing, scrubbing or importing, and when the target already holds
+ * cache files.
+ *
+ * @param path - Directory to use, or null for the default location
+ * @throws Error if the directory is unusable or the migration fails
+ */
+export async function setCacheLocation(path: string | null): Promise<void> {
+  try {
+    await invoke('set_cache_location', { path });
+  } catch (error) {
+    throw new Error(`Failed to set cache location: ${error}`);
+  }
+}
+
 // ============================================================================
 // Armbian Board Detection Settings
 // ============================================================================
@@ -370,3 +480,396 @@ export async function setArmbianBoardDetection(mode: string): Promise<void> {
     throw new Error(`Failed to set Armbian board detection preference: ${error}`);
   }
 }
+
+// ============================================================================
+// Download Backend Settings
+// ============================================================================
+
+/**
+ * Get the download backend
+ *
+ * - "http": Download from the HTTP mirrors
+ * - "torrent": Download over BitTorrent, using the mirrors as web seeds
+ *
+ * @returns Promise resolving to backend string
+ * @throws Error if store access fails
+ */
+export async function getDownloadBackend(): Promise<string> {
+  try {
+    const store = await getStore();
+    return (
+      (await store.get<string>(SETTINGS.KEYS.DOWNLOAD_BACKEND)) ||
+      SETTINGS.DEFAULTS.DOWNLOAD_BACKEND
+    );
+  } catch (error) {
+    throw new Error(`Failed to get download backend preference: ${error}`);
+  }
+}
+
+/**
+ * Set the download backend
+ *
+ * @param backend - Download backend ('http' or 'torrent')
+ * @throws Error if store access fails or backend is invalid
+ */
+export async function setDownloadBackend(backend: string): Promise<void> {
+  if (!['http', 'torrent'].includes(backend)) {
+    throw new Error(`Invalid download backend: ${backend}. Must be 'http' or 'torrent'`);
+  }
+
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.DOWNLOAD_BACKEND, backend);
+    await store.save();
+  } catch (error) {
+    throw new Error(`Failed to set download backend preference: ${error}`);
+  }
+}
+
+/**
+ * Get the delta download preference
+ *
+ * When enabled, a new build of a cached image is rebuilt from the cached
+ * build plus the changed ranges (requires a zsync control file on the server).
+ *
+ * @returns Promise resolving to true if delta downloads are enabled
+ * @throws Error if store access fails
+ */
+export async function getDeltaDownload(): Promise<boolean> {
+  try {
+    const store = await getStore();
+    const value = await store.get<boolean>(SETTINGS.KEYS.DELTA_DOWNLOAD);
+    return value ?? SETTINGS.DEFAULTS.DELTA_DOWNLOAD;
+  } catch (error) {
+    throw new Error(`Failed to get delta download preference: ${error}`);
+  }
+}
+
+/**
+ * Set the delta download preference
+ *
+ * @param enabled - true to rebuild new builds from cached ones when possible
+ * @throws Error if store access or save fails
+ */
+export async function setDeltaDownload(enabled: boolean): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.DELTA_DOWNLOAD, enabled);
+    await store.save();
+  } catch (error) {
+    throw new Error(`Failed to set delta download preference: ${error}`);
+  }
+}
+
+// ============================================================================
+// Bandwidth Settings
+// ============================================================================
+
+/** Hours (local time) during which queued downloads may run */
+export interface DownloadWindow {
+  start_hour: number;
+  end_hour: number;
+}
+
+/**
+ * Get the download rate limit
+ *
+ * @returns Promise resolving to the limit in bytes per second (0 = unlimited)
+ * @throws Error if store access fails
+ */
+export async function getDownloadRateLimit(): Promise<number> {
+  try {
+    const store = await getStore();
+    const value = await store.get<number>(SETTINGS.KEYS.DOWNLOAD_RATE_LIMIT);
+    return value ?? SETTINGS.DEFAULTS.DOWNLOAD_RATE_LIMIT;
+  } catch (error) {
+    throw new Error(`Failed to get download rate limit: ${error}`);
+  }
+}
+
+/**
+ * Set the download rate limit
+ *
+ * Goes through the backend so the new limit also applies to a download
+ * already in progress.
+ *
+ * @param limit - Limit in bytes per second (0 = unlimited)
+ * @throws Error if the setting cannot be saved
+ */
+export async function setDownloadRateLimit(limit: number): Promise<void> {
+  try {
+    await invoke('set_download_rate_limit', { limit });
+  } catch (error) {
+    throw new Error(`Failed to set download rate limit: ${error}`);
+  }
+}
+
+/**
+ * Get the download window for queued downloads
+ *
+ * @returns Promise resolving to the window, or null if downloads may run at any time
+ * @throws Error if store access fails
+ */
+export async function getDownloadWindow(): Promise<DownloadWindow | null> {
+  try {
+    const store = await getStore();
+    const value = await store.get<DownloadWindow>(SETTINGS.KEYS.DOWNLOAD_WINDOW);
+    return value ?? null;
+  } catch (error) {
+    throw new Error(`Failed to get download window: ${error}`);
+  }
+}
+
+/**
+ * Set the download window for queued downloads
+ *
+ * @param window - Start and end hour (0-23, wrapping past midnight when end < start), or null for no window
+ * @throws Error if the hours are invalid or the setting cannot be saved
+ */
+export async function setDownloadWindow(window: DownloadWindow | null): Promise<void> {
+  try {
+    await invoke('set_download_window', { window });
+  } catch (error) {
+    throw new Error(`Failed to set download window: ${error}`);
+  }
+}
+
+// ============================================================================
+// Network Settings
+// ============================================================================
+
+/** Proxy and CA bundle configuration; empty or null fields are unset */
+export interface NetworkSettings {
+  /** Proxy for all requests (http://, https://, socks5:// or socks5h://) */
+  proxy: string | null;
+  /** Comma-separated hosts, domains or CIDR ranges that bypass the proxy */
+  no_proxy: string | null;
+  /** Path to a PEM file with extra CA certificates */
+  ca_bundle: string | null;
+  /** Attempts for network operations before giving up (1-20, null for the default) */
+  retry_attempts: number | null;
+}
+
+/**
+ * Get the network settings
+ *
+ * @returns Promise resolving to the proxy and CA bundle settings
+ * @throws Error if store access fails
+ */
+export async function getNetworkSettings(): Promise<NetworkSettings> {
+  try {
+    const store = await getStore();
+    const value = await store.get<Partial<NetworkSettings>>(SETTINGS.KEYS.NETWORK);
+    return {
+      proxy: value?.proxy ?? null,
+      no_proxy: value?.no_proxy ?? null,
+      ca_bundle: value?.ca_bundle ?? null,
+      retry_attempts: value?.retry_attempts ?? null,
+    };
+  } catch (error) {
+    throw new Error(`Failed to get network settings: ${error}`);
+  }
+}
+
+/**
+ * Set the network settings
+ *
+ * Goes through the backend, which rejects an invalid proxy URL, CA bundle
+ * or retry count and rebuilds its HTTP clients with the new settings.
+ *
+ * @param settings - Proxy, no-proxy list, CA bundle path and retry attempts
+ * @throws Error if the settings are invalid or cannot be saved
+ */
+export async function setNetworkSettings(settings: NetworkSettings): Promise<void> {
+  try {
+    await invoke('set_network_settings', { settings });
+  } catch (error) {
+    throw new Error(`Failed to set network settings: ${error}`);
+  }
+}
+
+/**
+ * Get how many queued downloads may run at the same time
+ *
+ * @returns Promise resolving to the concurrency limit
+ * @throws Error if store access fails
+ */
+export async function getDownloadConcurrency(): Promise<number> {
+  try {
+    const store = await getStore();
+    const value = await store.get<number>(SETTINGS.KEYS.DOWNLOAD_CONCURRENCY);
+    return value ?? SETTINGS.DEFAULTS.DOWNLOAD_CONCURRENCY;
+  } catch (error) {
+    throw new Error(`Failed to get download concurrency: ${error}`);
+  }
+}
+
+/**
+ * Set how many queued downloads may run at the same time
+ *
+ * Goes through the backend so waiting jobs start right away when the
+ * limit is raised.
+ *
+ * @param limit - Concurrency limit (1-8)
+ * @throws Error if the limit is out of range or cannot be saved
+ */
+export async function setDownloadConcurrency(limit: number): Promise<void> {
+  try {
+    await invoke('set_download_concurrency', { limit });
+  } catch (error) {
+    throw new Error(`Failed to set download concurrency: ${error}`);
+  }
+}
+
+/**
+ * A pinned board whose newest stable image is kept in the cache
+ *
+ * Unset filters match any distribution, kernel branch or variant.
+ */
+export interface PinnedBoard {
+  board_slug: string;
+  distro_release?: string | null;
+  kernel_branch?: string | null;
+  image_variant?: string | null;
+}
+
+/**
+ * Get the pinned boards
+ *
+ * @returns Promise resolving to the pinned boards
+ * @throws Error if store access fails
+ */
+export async function getPinnedBoards(): Promise<PinnedBoard[]> {
+  try {
+    const store = await getStore();
+    const value = await store.get<PinnedBoard[]>(SETTINGS.KEYS.PINNED_BOARDS);
+    return value ?? [];
+  } catch (error) {
+    throw new Error(`Failed to get pinned boards: ${error}`);
+  }
+}
+
+/**
+ * Set the pinned boards
+ *
+ * Goes through the backend, which prefetches the newest stable image of
+ * newly pinned boards right away.
+ *
+ * @param pins - Pinned boards with optional filters
+ * @throws Error if a pin is invalid or the setting cannot be saved
+ */
+export async function setPinnedBoards(pins: PinnedBoard[]): Promise<void> {
+  try {
+    await invoke('set_pinned_boards', { pins });
+  } catch (error) {
+    throw new Error(`Failed to set pinned boards: ${error}`);
+  }
+}
+
+/**
+ * An image catalog: the built-in Armbian catalog or a third-party one
+ */
+export interface CatalogSourceSettings {
+  /** Short unique id: lowercase letters, digits, '-' and '_' */
+  id: string;
+  /** Name shown on the board badge */
+  label: string;
+  /** Catalog URL (http or https) or absolute path of a local file */
+  location: string;
+  enabled: boolean;
+  /** Refuse images that cannot be checked against a published SHA256 */
+  require_sha: boolean;
+  /** Refuse images without a valid signature */
+  require_signature: boolean;
+  /** Armored OpenPGP public key checked instead of the bundled keyring */
+  signing_key?: string | null;
+}
+
+/**
+ * Get the catalog sources, including the built-in Armbian catalog
+ *
+ * @returns Promise resolving to the catalog sources
+ * @throws Error if the sources cannot be read
+ */
+export async function getCatalogSources(): Promise<CatalogSourceSettings[]> {
+  try {
+    return await invoke<CatalogSourceSettings[]>('get_catalog_sources');
+  } catch (error) {
+    throw new Error(`Failed to get catalog sources: ${error}`);
+  }
+}
+
+/**
+ * Set the catalog sources
+ *
+ * The catalog is fetched again from the new sources on the next board
+ * list request.
+ *
+ * @param sources - Catalog sources; the Armbian catalog is kept if missing
+ * @throws Error if a source is invalid or the setting cannot be saved
+ */
+export async function setCatalogSources(sources: CatalogSourceSettings[]): Promise<void> {
+  try {
+    await invoke('set_catalog_sources', { sources });
+  } catch (error) {
+    throw new Error(`Failed to set catalog sources: ${error}`);
+  }
+}
+
+/**
+ * Get whether the image cache is shared with the local network
+ *
+ * @returns Promise resolving to true when sharing
+ * @throws Error if the setting cannot be read
+ */
+export async function getLanShareCache(): Promise<boolean> {
+  try {
+    return await invoke<boolean>('get_lan_share_cache');
+  } catch (error) {
+    throw new Error(`Failed to get LAN sharing preference: ${error}`);
+  }
+}
+
+/**
+ * Share the image cache with the local network, or stop sharing it
+ *
+ * @param enabled - Serve the cache and announce it over mDNS
+ * @throws Error if the cache server cannot start or the setting cannot be saved
+ */
+export async function setLanShareCache(enabled: boolean): Promise<void> {
+  try {
+    await invoke('set_lan_share_cache', { enabled });
+  } catch (error) {
+    throw new Error(`Failed to set LAN sharing preference: ${error}`);
+  }
+}
+
+/**
+ * Get whether images are fetched from LAN peers when available
+ *
+ * @returns Promise resolving to true when peers are used
+ * @throws Error if the setting cannot be read
+ */
+export async function getLanUsePeers(): Promise<boolean> {
+  try {
+    return await invoke<boolean>('get_lan_use_peers');
+  } catch (error) {
+    throw new Error(`Failed to get LAN peers preference: ${error}`);
+  }
+}
+
+/**
+ * Fetch images from LAN peers when available, or stop looking for peers
+ *
+ * Images from peers are verified like any other download.
+ *
+ * @param enabled - Look for peers and ask them first
+ * @throws Error if discovery cannot start or the setting cannot be saved
+ */
+export async function setLanUsePeers(enabled: boolean): Promise<void> {
+  try {
+    await invoke('set_lan_use_peers', { enabled });
+  } catch (error) {
+    throw new Error(`Failed to set LAN peers preference: ${error}`);
+  }
+}
@@ -1,10 +1,50 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { BoardInfo, ImageInfo, BlockDevice, DownloadProgress, FlashProgress, CustomImageInfo, ArmbianReleaseInfo, CachedImageInfo, QdlDevice } from '../types';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { BoardFacets, BoardInfo, ImageInfo, BlockDevice, CatalogDiff, CatalogStatus, DownloadProgress, JobInfo, FlashProgress, CustomImageInfo, ImportProgress, ArmbianReleaseInfo, BundleSummary, CacheLocationStatus, CachedImageInfo, LanStatus, QdlDevice, ScrubProgress, SearchFilters, SearchResults } from '../types';
 
 export async function getBoards(): Promise<BoardInfo[]> {
   return invoke('get_boards');
 }
 
+/**
+ * Search boards and images across the whole catalog
+ *
+ * Query words match fuzzily (e.g. "rk3588 bookworm gnome"); filters narrow
+ * results down to exact facet values.
+ */
+export async function searchCatalog(
+  query: string,
+  filters?: SearchFilters
+): Promise<SearchResults> {
+  return invoke('search_catalog', { query, filters });
+}
+
+/** Distributions, branches, variants and applications available for a board */
+export async function getBoardFacets(boardSlug: string): Promise<BoardFacets> {
+  return invoke('get_board_facets', { boardSlug });
+}
+
+/** Latest catalog changes found this session, or null if none */
+export async function getCatalogChanges(): Promise<CatalogDiff | null> {
+  return invoke('get_catalog_changes');
+}
+
+/** Origin and age of each catalog source in use, empty before loading */
+export async function getCatalogStatus(): Promise<CatalogStatus[]> {
+  return invoke('get_catalog_status');
+}
+
+/**
+ * Subscribe to catalog changes found when the catalog is fetched
+ *
+ * @returns Promise resolving to a function that removes the listener
+ */
+export async function onCatalogChanged(
+  handler: (changes: CatalogDiff) => void
+): Promise<UnlistenFn> {
+  return listen<CatalogDiff>('catalog-changed', (event) => handler(event.payload));
+}
+
 export async function getImagesForBoard(
   boardSlug: string,
   preappFilter?: string,
@@ -29,8 +69,18 @@ export async function requestWriteAuthorization(devicePath: string): Promise<boo
   return invoke('request_write_authorization', { devicePath });
 }
 
-export async function downloadImage(fileUrl: string, fileUrlSha?: string | null): Promise<string> {
-  return invoke('download_image', { fileUrl, fileUrlSha });
+/**
+ * Download an image into the cache
+ * @param backend - 'http' or 'torrent'; defaults to the download_backend setting
+ * @param scheduled - Wait for the configured download window before starting
+ */
+export async function downloadImage(
+  fileUrl: string,
+  fileUrlSha?: string | null,
+  backend?: 'http' | 'torrent',
+  scheduled: boolean = false
+): Promise<string> {
+  return invoke('download_image', { fileUrl, fileUrlSha, backend, scheduled });
 }
 
 export async function getDownloadProgress(): Promise<DownloadProgress> {
@@ -45,10 +95,74 @@ export async function flashImage(
   return invoke('flash_image', { imagePath, devicePath, verify });
 }
 
+/**
+ * Download an image and flash it in a single streaming pass
+ *
+ * Fails with [SHA_UNAVAILABLE] before writing anything when the image's SHA
+ * cannot be fetched; call again with allowUnverified once the user accepted it.
+ */
+export async function downloadAndFlashImage(
+  fileUrl: string,
+  fileUrlSha: string | null | undefined,
+  devicePath: string,
+  verify: boolean = true,
+  allowUnverified: boolean = false
+): Promise<void> {
+  return invoke('download_and_flash_image', {
+    fileUrl,
+    fileUrlSha,
+    devicePath,
+    verify,
+    allowUnverified,
+  });
+}
+
 export async function getFlashProgress(): Promise<FlashProgress> {
   return invoke('get_flash_progress');
 }
 
+export async function queueDownload(
+  fileUrl: string,
+  fileUrlSha?: string | null,
+  backend?: 'http' | 'torrent'
+): Promise<number> {
+  return invoke('queue_download', { fileUrl, fileUrlSha, backend });
+}
+
+/**
+ * Download an image into the cache in the background
+ *
+ * Prefetches run after queued downloads and respect the download window.
+ * Fails if the image cache is disabled.
+ */
+export async function prefetchImage(fileUrl: string, fileUrlSha?: string | null): Promise<number> {
+  return invoke('prefetch_image', { fileUrl, fileUrlSha });
+}
+
+export async function listDownloadJobs(): Promise<JobInfo[]> {
+  return invoke('list_download_jobs');
+}
+
+export async function getDownloadJob(jobId: number): Promise<JobInfo> {
+  return invoke('get_download_job', { jobId });
+}
+
+export async function pauseDownloadJob(jobId: number): Promise<void> {
+  return invoke('pause_download_job', { jobId });
+}
+
+export async function resumeDownloadJob(jobId: number): Promise<void> {
+  return invoke('resume_download_job', { jobId });
+}
+
+export async function cancelDownloadJob(jobId: number): Promise<void> {
+  return invoke('cancel_download_job', { jobId });
+}
+
+export async function clearFinishedDownloadJobs(): Promise<void> {
+  return invoke('clear_finished_download_jobs');
+}
+
 export async function cancelOperation(): Promise<void> {
   return invoke('cancel_operation');
 }
@@ -58,16 +172,16 @@ export async function deleteDownloadedImage(imagePath: string): Promise<void> {
 }
 
 /**
- * Force delete a cached image regardless of cache settings
+ * Record whether flashing an image succeeded
  *
- * Used when an image repeatedly fails to flash, suggesting the cached
- * file may be corrupted. Bypasses the cache_enabled check.
+ * Failures are counted per cached image in the backend, which deletes the
+ * image after too many consecutive failures (possibly corrupted).
  *
- * @param imagePath - Path to the cached image file
- * @throws Error if deletion fails or path is outside cache directory
+ * @param imagePath - Path of the flashed image; images outside the cache are ignored
+ * @returns true if the cached image was deleted
  */
-export async function forceDeleteCachedImage(imagePath: string): Promise<void> {
-  return invoke('force_delete_cached_image', { imagePath });
+export async function recordFlashResult(imagePath: string, success: boolean): Promise<boolean> {
+  return invoke('record_flash_result', { imagePath, success });
 }
 
 /**
@@ -119,6 +233,35 @@ export async function decompressCustomImage(imagePath: string): Promise<string>
   return invoke('decompress_custom_image', { imagePath });
 }
 
+/**
+ * Import a local image into the image cache
+ *
+ * The image is copied (decompressed custom images are moved), hashed and
+ * indexed with its label, so it is listed with the cached images and kept
+ * across restarts. Progress is reported through getImportProgress().
+ *
+ * @param imagePath - Path of the image to import
+ * @param label - Optional label shown in the cache manager
+ * @param moveFile - Move instead of copy (defaults to moving decompressed images only)
+ * @returns Promise resolving to the cached image
+ * @throws Error if the cache is disabled, full of pinned images, or already holds the name
+ */
+export async function importCustomImage(
+  imagePath: string,
+  label?: string,
+  moveFile?: boolean
+): Promise<CachedImageInfo> {
+  return invoke('import_custom_image', { imagePath, label, moveFile });
+}
+
+export async function getImportProgress(): Promise<ImportProgress> {
+  return invoke('get_import_progress');
+}
+
+export async function cancelImport(): Promise<void> {
+  return invoke('cancel_import');
+}
+
 export interface UploadResult {
   url: string;
   key: string;
@@ -267,6 +410,116 @@ export async function deleteCachedImage(filename: string): Promise<number> {
   return invoke('delete_cached_image', { filename });
 }
 
+/**
+ * Check a cached image against its recorded SHA256
+ *
+ * A corrupted image is deleted from the cache.
+ *
+ * @param filename - Name of the cached file to check
+ * @returns Promise resolving to true if the image is intact
+ * @throws Error if the file is not in the cache or cannot be read
+ */
+export async function verifyCachedImage(filename: string): Promise<boolean> {
+  return invoke('verify_cached_image', { filename });
+}
+
+/**
+ * Pin or unpin a cached image; pinned images are never evicted
+ *
+ * @param filename - Name of the cached file
+ * @param pinned - Whether to pin it
+ * @throws Error if the file is not in the cache
+ */
+export async function setCachedImagePinned(filename: string, pinned: boolean): Promise<void> {
+  return invoke('set_cached_image_pinned', { filename, pinned });
+}
+
+/**
+ * Replace the tags of a cached image
+ *
+ * @param filename - Name of the cached file
+ * @param tags - Tags to set (trimmed, empty and repeated ones dropped)
+ * @returns Promise resolving to the tags as stored
+ * @throws Error if the file is not in the cache
+ */
+export async function setCachedImageTags(filename: string, tags: string[]): Promise<string[]> {
+  return invoke('set_cached_image_tags', { filename, tags });
+}
+
+/**
+ * Start checking every cached image against its recorded or published SHA256
+ *
+ * Runs in the background; poll getCacheScrubProgress for progress and the report.
+ * @throws Error if a scrub is already running
+ */
+export async function startCacheScrub(): Promise<void> {
+  return invoke('start_cache_scrub');
+}
+
+export async function getCacheScrubProgress(): Promise<ScrubProgress> {
+  return invoke('get_cache_scrub_progress');
+}
+
+export async function cancelCacheScrub(): Promise<void> {
+  return invoke('cancel_cache_scrub');
+}
+
+/**
+ * Delete a cached image and queue its download again
+ *
+ * An abandoned partial download is resumed instead.
+ * @param filename - Name of the cached file, as in a scrub report
+ * @returns Promise resolving to the download job ID
+ * @throws Error if the source of the file is unknown
+ */
+export async function redownloadCachedImage(filename: string): Promise<number> {
+  return invoke('redownload_cached_image', { filename });
+}
+
+/**
+ * Get where the cache lives, whether the default location is used because
+ * the configured one is unavailable, and the progress of a migration
+ */
+export async function getCacheLocationStatus(): Promise<CacheLocationStatus> {
+  return invoke('get_cache_location_status');
+}
+
+/**
+ * Export an offline bundle for machines without network access
+ *
+ * Contains the saved catalogs, board photos, vendor logos and the given
+ * cached images with their published SHA and signature files.
+ *
+ * @param path - Destination file of the bundle
+ * @param images - File names of cached images to include
+ * @returns Promise resolving to what the bundle contains
+ * @throws Error if an image is not cached or the bundle cannot be written
+ */
+export async function exportOfflineBundle(path: string, images: string[]): Promise<BundleSummary> {
+  return invoke('export_offline_bundle', { path, images });
+}
+
+/**
+ * Import an offline bundle into the catalog, picture and image caches
+ *
+ * Nothing is installed unless every file matches the bundle manifest.
+ * Reload the board list afterwards.
+ *
+ * @param path - Bundle file
+ * @returns Promise resolving to what was imported
+ * @throws Error if the bundle is invalid or corrupted
+ */
+export async function importOfflineBundle(path: string): Promise<BundleSummary> {
+  return invoke('import_offline_bundle', { path });
+}
+
+/**
+ * Get the LAN cache sharing state and the peers found
+ */
+export async function getLanStatus(): Promise<LanStatus> {
+  return invoke('get_lan_status');
+}
+
 // ============================================================================
 // Connectivity
 // ============================================================================
@@ -11,6 +11,8 @@ export interface BoardInfo {
   has_eos_support: boolean;
   has_tvb_support: boolean;
   has_wip_support: boolean;
+  /** Ids of the catalog sources listing the board (for the source badge) */
+  sources: string[];
 }
 
 export interface ImageInfo {
@@ -27,6 +29,8 @@ export interface ImageInfo {
   download_repository: string;
   /** Flash method: "block" (default SD/USB) or "qdl" (Qualcomm EDL) */
   flash_method: string;
+  /** Id of the catalog source listing the image */
+  source: string;
   // Custom image fields
   is_custom?: boolean;
   custom_path?: string;
@@ -45,6 +49,9 @@ export interface BlockDevice {
   is_read_only?: boolean;
 }
 
+/** Result of verifying an image's detached OpenPGP signature */
+export type SignatureStatus = 'valid' | 'unknown_key' | 'bad_signature' | 'missing';
+
 export interface DownloadProgress {
   total_bytes: number;
   downloaded_bytes: number;
@@ -52,6 +59,117 @@ export interface DownloadProgress {
   is_decompressing: boolean;
   progress_percent: number;
   error: string | null;
+  /** SHA256 of the compressed download, computed while streaming */
+  computed_sha: string | null;
+  /** OpenPGP signature check result, once the download is complete */
+  signature_status: SignatureStatus | null;
+}
+
+/** Distinct filter values among a board's images */
+export interface BoardFacets {
+  distros: string[];
+  branches: string[];
+  variants: string[];
+  /** Preinstalled applications ('' for plain images) */
+  apps: string[];
+}
+
+/** Exact facet values to narrow a catalog search down to */
+export interface SearchFilters {
+  vendor?: string | null;
+  /** 'platinum', 'standard', 'community', 'eos', 'tvb' or 'wip' */
+  support_level?: string | null;
+  distro?: string | null;
+  branch?: string | null;
+  desktop?: string | null;
+  kernel_version?: string | null;
+}
+
+/** Number of matching images with a facet value */
+export interface FacetCount {
+  value: string;
+  count: number;
+}
+
+/** A matching image with its board */
+export interface ImageHit extends ImageInfo {
+  board_slug: string;
+  board_name: string;
+  score: number;
+}
+
+/** Ranked catalog search results */
+export interface SearchResults {
+  boards: BoardInfo[];
+  images: ImageHit[];
+  /** Number of matching images before the result limit */
+  total_images: number;
+  facets: {
+    vendors: FacetCount[];
+    support_levels: FacetCount[];
+    distros: FacetCount[];
+    branches: FacetCount[];
+    desktops: FacetCount[];
+    kernel_versions: FacetCount[];
+  };
+}
+
+/** Where a catalog in use came from */
+export type CatalogSource = 'live' | 'cached' | 'stale-offline' | 'peer' | 'local';
+
+/** Origin and age of one catalog source, to warn when showing old data */
+export interface CatalogStatus {
+  /** Id of the configured catalog source */
+  source_id: string;
+  label: string;
+  source: CatalogSource;
+  /** Unix timestamp (seconds) the server last confirmed the catalog, if known */
+  fetched_at: number | null;
+  /** Seconds since fetched_at */
+  age_secs: number | null;
+}
+
+/** New images of one board since the previous catalog snapshot */
+export interface NewImages {
+  board_slug: string;
+  /** Armbian versions the board had no image for before */
+  versions: string[];
+  count: number;
+}
+
+/** Support level change of one board ('conf', 'csc', 'eos', 'tvb', 'wip') */
+export interface SupportChange {
+  board_slug: string;
+  previous: string;
+  current: string;
+}
+
+/** Differences between the fetched catalog and the previous snapshot */
+export interface CatalogDiff {
+  /** Unix timestamp (seconds) of the fetch that found the changes */
+  detected_at: number;
+  added_boards: string[];
+  removed_boards: string[];
+  new_images: NewImages[];
+  support_changes: SupportChange[];
+}
+
+/** Lifecycle of a background download job */
+export type JobStatus = 'queued' | 'running' | 'paused' | 'completed' | 'failed' | 'cancelled';
+
+/** Background download job with its progress */
+export interface JobInfo {
+  id: number;
+  url: string;
+  /** 'prefetch' jobs only fill the cache and run after 'download' jobs */
+  kind: 'download' | 'prefetch';
+  status: JobStatus;
+  total_bytes: number;
+  downloaded_bytes: number;
+  progress_percent: number;
+  error: string | null;
+  /** Path of the downloaded image, once completed */
+  output_path: string | null;
 }
 
 export interface FlashProgress {
@@ -113,6 +231,84 @@ export interface CustomImageInfo {
   size: number;
 }
 
+/** What an offline bundle export or import covered */
+export interface BundleSummary {
+  /** Cached image file names */
+  images: string[];
+  /** Number of catalog and picture cache files */
+  assets: number;
+  total_bytes: number;
+}
+
+/** A machine on the local network sharing its image cache */
+export interface LanPeer {
+  name: string;
+  /** Base URL of its cache server */
+  address: string;
+}
+
+/** LAN cache sharing state */
+export interface LanStatus {
+  sharing: boolean;
+  /** Port of the cache server while sharing */
+  port: number | null;
+  browsing: boolean;
+  peers: LanPeer[];
+}
+
+/** What a cache scrub found wrong with a file */
+export interface ScrubIssue {
+  filename: string;
+  /** corrupt: checksum mismatch; unverifiable: no checksum to compare; orphaned: abandoned partial download */
+  kind: 'corrupt' | 'unverifiable' | 'orphaned';
+  size: number;
+  /** Where the file came from, set when it can be downloaded again */
+  source_url: string | null;
+}
+
+export interface ScrubReport {
+  checked: number;
+  intact: number;
+  /** Index entries dropped because their file is gone */
+  pruned_entries: number;
+  issues: ScrubIssue[];
+  /** Unix timestamp (seconds) */
+  finished_at: number;
+}
+
+/** Progress of an image import into the cache */
+export interface ImportProgress {
+  running: boolean;
+  total_bytes: number;
+  copied_bytes: number;
+  progress_percent: number;
+}
+
+/** Cache scrub progress, with the report once done */
+export interface ScrubProgress {
+  running: boolean;
+  total_bytes: number;
+  checked_bytes: number;
+  progress_percent: number;
+  current_file: string | null;
+  error: string | null;
+  report: ScrubReport | null;
+}
+
+/** Where the cache lives and the progress of a migration */
+export interface CacheLocationStatus {
+  /** Location in use */
+  path: string;
+  /** Directory chosen by the user (the cache is in its armbian-imager directory), null for the default one */
+  configured: string | null;
+  default_path: string;
+  /** The configured location is unavailable and the default one is used */
+  fallback: boolean;
+  migrating: boolean;
+  migrated_bytes: number;
+  migration_total_bytes: number;
+}
+
 /**
  * Cached image metadata from the backend cache directory
  */
@@ -126,6 +322,34 @@ export interface CachedImageInfo {
   board_slug: string | null;
   /** Human-readable board name derived from slug */
   board_name: string | null;
+  /** Release details parsed from an Armbian filename */
+  armbian: ArmbianFilenameInfo | null;
+  /** SHA256 of the file, once hashed */
+  sha256: string | null;
+  /** Whether the image matched its published checksum when downloaded */
+  verification: 'verified' | 'unverified';
+  /** Unix timestamp (seconds) of the last successful integrity check */
+  checked_at: number | null;
+  /** Consecutive failed flashes */
+  flash_failures: number;
+  /** Successful flashes */
+  use_count: number;
+  /** Pinned by the user (never evicted) */
+  pinned: boolean;
+  /** User tags, e.g. "production-v3" */
+  tags: string[];
+  /** Label given when the image was imported */
+  label: string | null;
+}
+
+/** Metadata parsed from an Armbian image filename */
+export interface ArmbianFilenameInfo {
+  board_slug: string;
+  version: string | null;
+  distro: string | null;
+  branch: string | null;
+  kernel: string | null;
+  desktop: string | null;
 }
 
 /**